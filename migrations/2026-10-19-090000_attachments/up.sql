@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE attachments (
+    id SERIAL NOT NULL PRIMARY KEY,
+    task_id INT NOT NULL REFERENCES todotasks (id) ON DELETE CASCADE,
+    file_name VARCHAR(255) NOT NULL,
+    content_type VARCHAR(255) NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    content_hash VARCHAR(64) NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX attachments_task_id_idx ON attachments (task_id);
+CREATE INDEX attachments_content_hash_idx ON attachments (content_hash);
@@ -0,0 +1,2 @@
+DROP TABLE task_watchers;
+DROP TABLE task_assignees;
\ No newline at end of file
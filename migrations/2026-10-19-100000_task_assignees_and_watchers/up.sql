@@ -0,0 +1,18 @@
+-- Your SQL goes here
+CREATE TABLE task_assignees (
+    task_id INT NOT NULL REFERENCES todotasks (id) ON DELETE CASCADE,
+    user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (task_id, user_id)
+);
+
+CREATE INDEX task_assignees_user_id_idx ON task_assignees (user_id);
+
+CREATE TABLE task_watchers (
+    task_id INT NOT NULL REFERENCES todotasks (id) ON DELETE CASCADE,
+    user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (task_id, user_id)
+);
+
+CREATE INDEX task_watchers_user_id_idx ON task_watchers (user_id);
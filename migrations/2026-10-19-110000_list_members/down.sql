@@ -0,0 +1,13 @@
+ALTER TABLE todolists ADD COLUMN shared_with TEXT;
+
+UPDATE todolists l
+SET shared_with = m.user_ids
+FROM (
+    SELECT list_id, string_agg(user_id::TEXT, ',' ORDER BY user_id) AS user_ids
+    FROM list_members
+    WHERE role <> 'owner' AND accepted_at IS NOT NULL
+    GROUP BY list_id
+) m
+WHERE m.list_id = l.id;
+
+DROP TABLE list_members;
@@ -0,0 +1,30 @@
+-- Your SQL goes here
+CREATE TABLE list_members (
+    list_id INT NOT NULL REFERENCES todolists (id) ON DELETE CASCADE,
+    user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    role VARCHAR(16) NOT NULL CHECK (role IN ('owner', 'editor', 'commenter', 'viewer')),
+    invited_by INT REFERENCES users (id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL,
+    accepted_at TIMESTAMP,
+    PRIMARY KEY (list_id, user_id)
+);
+
+CREATE INDEX list_members_user_id_idx ON list_members (user_id);
+
+INSERT INTO list_members (list_id, user_id, role, created_at, accepted_at)
+SELECT l.id, l.user_id, 'owner', l.created_at, l.created_at
+FROM todolists l
+JOIN users u ON u.id = l.user_id;
+
+-- shared_with never had a defined format; entries separated by commas,
+-- semicolons or whitespace are matched against user ids and emails, and
+-- every match keeps the edit access it implicitly had before
+INSERT INTO list_members (list_id, user_id, role, created_at, accepted_at)
+SELECT DISTINCT l.id, u.id, 'editor', l.modified_at, l.modified_at
+FROM todolists l
+CROSS JOIN LATERAL regexp_split_to_table(l.shared_with, '[,;[:space:]]+') AS entry
+JOIN users u ON u.id::TEXT = entry OR lower(u.email) = lower(entry)
+WHERE l.shared_with IS NOT NULL AND entry <> ''
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE todolists DROP COLUMN shared_with;
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE share_links (
+    id SERIAL NOT NULL PRIMARY KEY,
+    list_id INT NOT NULL REFERENCES todolists (id) ON DELETE CASCADE,
+    created_by INT REFERENCES users (id) ON DELETE SET NULL,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    password_hash TEXT,
+    expires_at TIMESTAMP,
+    revoked_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX share_links_list_id_idx ON share_links (list_id);
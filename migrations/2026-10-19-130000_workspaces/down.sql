@@ -0,0 +1,4 @@
+ALTER TABLE todolists DROP COLUMN workspace_id;
+ALTER TABLE users DROP COLUMN active_workspace_id;
+DROP TABLE workspace_members;
+DROP TABLE workspaces;
@@ -0,0 +1,70 @@
+-- Your SQL goes here
+CREATE TABLE workspaces (
+    id SERIAL NOT NULL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    modified_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE workspace_members (
+    workspace_id INT NOT NULL REFERENCES workspaces (id) ON DELETE CASCADE,
+    user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    role VARCHAR(16) NOT NULL CHECK (role IN ('admin', 'member')),
+    created_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (workspace_id, user_id)
+);
+
+CREATE INDEX workspace_members_user_id_idx ON workspace_members (user_id);
+
+ALTER TABLE users ADD COLUMN active_workspace_id INT REFERENCES workspaces (id) ON DELETE SET NULL;
+ALTER TABLE todolists
+    ADD COLUMN workspace_id INT REFERENCES workspaces (id) ON DELETE CASCADE;
+
+-- Nobody administered anything before, so nobody becomes admin of anyone
+-- else's lists: each user gets a personal workspace of their own, and each
+-- list moves into its creator's. Everyone the list is shared with joins that
+-- workspace as a plain member, so sharing keeps working.
+DO $$
+DECLARE
+    account RECORD;
+    personal INT;
+BEGIN
+    FOR account IN SELECT id FROM users ORDER BY id LOOP
+        INSERT INTO workspaces (name, created_at, modified_at)
+        VALUES ('Personal', now(), now())
+        RETURNING id INTO personal;
+        INSERT INTO workspace_members (workspace_id, user_id, role, created_at)
+        VALUES (personal, account.id, 'admin', now());
+        UPDATE users SET active_workspace_id = personal WHERE id = account.id;
+    END LOOP;
+END $$;
+
+UPDATE todolists l
+SET workspace_id = u.active_workspace_id
+FROM users u
+WHERE u.id = l.user_id;
+
+-- lists whose creator is gone go to whoever they are shared with
+UPDATE todolists l
+SET workspace_id = u.active_workspace_id
+FROM users u
+WHERE l.workspace_id IS NULL
+  AND u.id = (SELECT min(m.user_id) FROM list_members m WHERE m.list_id = l.id);
+
+-- and those nobody can reach any more are kept in a workspace of their own
+INSERT INTO workspaces (name, created_at, modified_at)
+SELECT 'Unclaimed', now(), now()
+WHERE EXISTS (SELECT 1 FROM todolists WHERE workspace_id IS NULL);
+
+UPDATE todolists
+SET workspace_id = (SELECT max(id) FROM workspaces WHERE name = 'Unclaimed')
+WHERE workspace_id IS NULL;
+
+INSERT INTO workspace_members (workspace_id, user_id, role, created_at)
+SELECT DISTINCT l.workspace_id, m.user_id, 'member', now()
+FROM list_members m
+JOIN todolists l ON l.id = m.list_id
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE todolists ALTER COLUMN workspace_id SET NOT NULL;
+CREATE INDEX todolists_workspace_id_idx ON todolists (workspace_id);
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+CREATE TABLE events (
+    id BIGSERIAL PRIMARY KEY,
+    kind VARCHAR(32) NOT NULL,
+    payload JSONB NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
+
+-- Who may see an event is decided when it is recorded, so that deletions
+-- still reach the members who lost access by them.
+CREATE TABLE event_recipients (
+    event_id BIGINT NOT NULL REFERENCES events (id) ON DELETE CASCADE,
+    user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    PRIMARY KEY (user_id, event_id)
+);
@@ -0,0 +1,4 @@
+DROP TABLE tombstones;
+ALTER TABLE list_members DROP COLUMN change_seq;
+ALTER TABLE todolists DROP COLUMN change_seq;
+ALTER TABLE todotasks DROP COLUMN change_seq;
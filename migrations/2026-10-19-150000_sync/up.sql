@@ -0,0 +1,21 @@
+-- Your SQL goes here
+-- Id of the event log entry that last touched the row; sync tokens are
+-- event ids, so these say which rows a client holding a token has missed.
+ALTER TABLE todotasks ADD COLUMN change_seq BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE todolists ADD COLUMN change_seq BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE list_members ADD COLUMN change_seq BIGINT NOT NULL DEFAULT 0;
+
+CREATE INDEX todotasks_change_seq_idx ON todotasks (change_seq);
+CREATE INDEX todolists_change_seq_idx ON todolists (change_seq);
+
+CREATE TABLE tombstones (
+    id SERIAL PRIMARY KEY,
+    entity VARCHAR(8) NOT NULL CHECK (entity IN ('task', 'list')),
+    entity_id INT NOT NULL,
+    list_id INT NOT NULL,
+    workspace_id INT NOT NULL,
+    change_seq BIGINT NOT NULL,
+    deleted_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX tombstones_change_seq_idx ON tombstones (change_seq);
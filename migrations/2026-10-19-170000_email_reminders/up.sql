@@ -0,0 +1,18 @@
+-- Your SQL goes here
+-- Users without a row get the defaults below.
+CREATE TABLE reminder_preferences (
+    user_id INT PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    email_enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    window_hours INT NOT NULL DEFAULT 24 CHECK (window_hours BETWEEN 1 AND 720),
+    digest BOOLEAN NOT NULL DEFAULT TRUE,
+    modified_at TIMESTAMP NOT NULL
+);
+
+-- One row per reminder ever sent; a new due date earns a new reminder.
+CREATE TABLE sent_reminders (
+    task_id INT NOT NULL REFERENCES todotasks (id) ON DELETE CASCADE,
+    user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    due_date DATE NOT NULL,
+    sent_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (task_id, user_id, due_date)
+);
@@ -0,0 +1,36 @@
+-- Your SQL goes here
+-- Reminders are private to the user who set them. A reminder fires either at
+-- `remind_at` or `minutes_before_due` ahead of its task's due date, and a
+-- snooze pushes it back to `snoozed_until`.
+CREATE TABLE task_reminders (
+    id SERIAL PRIMARY KEY,
+    task_id INT NOT NULL REFERENCES todotasks (id) ON DELETE CASCADE,
+    user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    remind_at TIMESTAMP,
+    minutes_before_due INT CHECK (minutes_before_due >= 0),
+    channel VARCHAR(16) NOT NULL DEFAULT 'in_app'
+        CHECK (channel IN ('in_app', 'email', 'webhook')),
+    snoozed_until TIMESTAMP,
+    sent_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL,
+    modified_at TIMESTAMP NOT NULL,
+    CHECK ((remind_at IS NULL) <> (minutes_before_due IS NULL))
+);
+
+CREATE INDEX task_reminders_task_id_user_id_idx ON task_reminders (task_id, user_id);
+CREATE INDEX task_reminders_pending_idx ON task_reminders (id) WHERE sent_at IS NULL;
+
+-- The in-app inbox. Notifications outlive the task or list they mention.
+CREATE TABLE notifications (
+    id BIGSERIAL PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    kind VARCHAR(32) NOT NULL,
+    task_id INT REFERENCES todotasks (id) ON DELETE SET NULL,
+    list_id INT REFERENCES todolists (id) ON DELETE SET NULL,
+    title TEXT NOT NULL,
+    body TEXT NOT NULL,
+    read_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX notifications_user_id_id_idx ON notifications (user_id, id DESC);
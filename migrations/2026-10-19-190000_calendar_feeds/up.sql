@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- One secret feed URL per user; rotating it replaces the row.
+CREATE TABLE calendar_feeds (
+    user_id INT PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+-- The resource name and UID a CalDAV client chose for a task it created.
+-- Rows deliberately outlive their task, so that sync-collection can still
+-- report the client's href as gone.
+CREATE TABLE dav_objects (
+    task_id INT PRIMARY KEY,
+    uid TEXT NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX dav_objects_name_idx ON dav_objects (name);
+CREATE INDEX dav_objects_uid_idx ON dav_objects (uid);
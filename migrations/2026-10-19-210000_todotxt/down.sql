@@ -0,0 +1,5 @@
+DROP TABLE task_tags;
+
+ALTER TABLE todotasks
+    DROP COLUMN completed_at,
+    DROP COLUMN priority;
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+-- Room for what todo.txt carries beyond a name and due date.
+ALTER TABLE todotasks
+    ADD COLUMN priority VARCHAR(1) CHECK (priority ~ '^[A-Z]$'),
+    ADD COLUMN completed_at TIMESTAMP;
+
+CREATE TABLE task_tags (
+    task_id INT NOT NULL REFERENCES todotasks (id) ON DELETE CASCADE,
+    tag VARCHAR(64) NOT NULL,
+    PRIMARY KEY (task_id, tag)
+);
+
+CREATE INDEX task_tags_tag_idx ON task_tags (tag);
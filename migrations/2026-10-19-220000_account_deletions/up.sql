@@ -0,0 +1,16 @@
+-- Your SQL goes here
+-- The row outlives the account so its receipt can still be fetched.
+CREATE TABLE account_deletions (
+    id SERIAL PRIMARY KEY,
+    user_id INT UNIQUE REFERENCES users (id) ON DELETE SET NULL,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    owned_lists VARCHAR(16) NOT NULL DEFAULT 'transfer'
+        CHECK (owned_lists IN ('transfer', 'delete')),
+    requested_at TIMESTAMP NOT NULL,
+    scheduled_for TIMESTAMP NOT NULL,
+    completed_at TIMESTAMP,
+    receipt JSONB
+);
+
+CREATE INDEX account_deletions_pending_idx ON account_deletions (scheduled_for)
+    WHERE completed_at IS NULL;
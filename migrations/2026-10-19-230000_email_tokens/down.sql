@@ -0,0 +1,7 @@
+DROP TABLE email_tokens;
+
+DROP INDEX users_email_lower_idx;
+
+ALTER TABLE users
+    DROP COLUMN password_hash,
+    DROP COLUMN email_verified_at;
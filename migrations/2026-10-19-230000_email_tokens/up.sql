@@ -0,0 +1,32 @@
+-- Your SQL goes here
+-- Accounts without a password can only get one through a reset.
+ALTER TABLE users
+    ADD COLUMN password_hash TEXT,
+    ADD COLUMN email_verified_at TIMESTAMP;
+
+-- Addresses now identify accounts, so no two may share one. Where they do,
+-- the oldest account keeps it and the others get an undeliverable address
+-- that still shows the original, until their owners change it.
+UPDATE users
+SET email = users.email || '.duplicate-' || users.id || '.invalid'
+WHERE EXISTS (
+    SELECT 1 FROM users AS older
+    WHERE lower(older.email) = lower(users.email) AND older.id < users.id
+);
+
+CREATE UNIQUE INDEX users_email_lower_idx ON users (lower(email));
+
+-- Single-use tokens mailed to `email`, which must still be the account's
+-- address when the token is redeemed.
+CREATE TABLE email_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    purpose VARCHAR(16) NOT NULL CHECK (purpose IN ('verify_email', 'reset_password')),
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    email TEXT NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX email_tokens_user_id_idx ON email_tokens (user_id, purpose);
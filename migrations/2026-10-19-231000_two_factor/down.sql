@@ -0,0 +1,5 @@
+ALTER TABLE workspaces DROP COLUMN require_two_factor;
+
+DROP TABLE login_challenges;
+DROP TABLE recovery_codes;
+DROP TABLE user_totp;
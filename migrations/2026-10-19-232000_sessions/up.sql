@@ -0,0 +1,32 @@
+-- Your SQL goes here
+-- One row per signed-in device. The access token is short-lived and rotated
+-- together with the refresh token, so only the current hash of each is kept.
+CREATE TABLE sessions (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    access_token_hash VARCHAR(64) NOT NULL UNIQUE,
+    access_expires_at TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    user_agent TEXT,
+    ip_address TEXT,
+    created_at TIMESTAMP NOT NULL,
+    last_seen_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP,
+    revoked_reason VARCHAR(32) CHECK (
+        revoked_reason IN ('logout', 'remote_logout', 'password_change', 'token_reuse')
+    )
+);
+
+CREATE INDEX sessions_active_user_id_idx ON sessions (user_id) WHERE revoked_at IS NULL;
+
+-- Every refresh token a session was ever given. Spent ones are kept so that
+-- presenting one again can be recognised as theft.
+CREATE TABLE refresh_tokens (
+    id SERIAL PRIMARY KEY,
+    session_id INT NOT NULL REFERENCES sessions (id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX refresh_tokens_session_id_idx ON refresh_tokens (session_id);
@@ -0,0 +1,27 @@
+-- Your SQL goes here
+-- One row per started sign-in, found again by the `state` the provider echoes
+-- back. The PKCE verifier and nonce are needed in plain to finish it.
+CREATE TABLE oidc_login_attempts (
+    id SERIAL PRIMARY KEY,
+    state_hash VARCHAR(64) NOT NULL UNIQUE,
+    code_verifier VARCHAR(128) NOT NULL,
+    nonce VARCHAR(64) NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL
+);
+
+-- Links an account at the identity provider to a user; `email` is what the
+-- provider last reported.
+CREATE TABLE oidc_identities (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    email TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    last_login_at TIMESTAMP NOT NULL,
+    UNIQUE (issuer, subject)
+);
+
+CREATE INDEX oidc_identities_user_id_idx ON oidc_identities (user_id);
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+-- Who caused the notification, so it can be found again when their account
+-- is deleted. Notifications written before this column have no actor.
+ALTER TABLE notifications
+    ADD COLUMN actor_id INT REFERENCES users (id) ON DELETE SET NULL;
+
+CREATE INDEX notifications_actor_id_idx ON notifications (actor_id)
+    WHERE actor_id IS NOT NULL;
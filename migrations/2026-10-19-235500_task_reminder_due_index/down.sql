@@ -0,0 +1 @@
+DROP INDEX task_reminders_pending_fires_at_idx;
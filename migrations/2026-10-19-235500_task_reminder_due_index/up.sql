@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- The reminder poll only loads reminders whose time has come; this covers
+-- the ones set to a time, snoozed or not.
+CREATE INDEX task_reminders_pending_fires_at_idx
+    ON task_reminders ((COALESCE(snoozed_until, remind_at)))
+    WHERE sent_at IS NULL;
@@ -0,0 +1,2 @@
+DROP INDEX events_created_at_idx;
+DROP TABLE event_log_state;
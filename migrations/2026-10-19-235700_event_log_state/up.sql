@@ -0,0 +1,14 @@
+-- Your SQL goes here
+-- Event ids come from the sequence without serializing writers, so they can
+-- commit out of order. `settled_id` is an id up to which the log has no gaps
+-- left to fill; `pruned_id` the newest id retention has removed, below which
+-- cursors and sync tokens no longer work.
+CREATE TABLE event_log_state (
+    singleton BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (singleton),
+    settled_id BIGINT NOT NULL,
+    pruned_id BIGINT NOT NULL DEFAULT 0
+);
+
+INSERT INTO event_log_state (settled_id) SELECT COALESCE(MAX(id), 0) FROM events;
+
+CREATE INDEX events_created_at_idx ON events (created_at);
@@ -1,22 +1,105 @@
 extern crate diesel;
 
 use actix_web::{web::Data, App, HttpServer};
+use std::sync::Arc;
 
 mod models {
+    pub mod account_archive;
+    pub mod account_deletion;
+    pub mod api_key;
+    pub mod attachment;
+    pub mod calendar_feed;
+    pub mod dav_object;
+    pub mod email_token;
+    pub mod event;
+    pub mod external_import;
+    pub mod list_member;
+    pub mod markdown;
+    pub mod notification;
+    pub mod oidc;
+    pub mod reminder_preference;
+    pub mod sent_reminder;
+    pub mod session;
+    pub mod share_link;
+    pub mod sync;
     pub mod tailored_response;
+    pub mod task_assignee;
+    pub mod task_csv;
+    pub mod task_reminder;
+    pub mod task_tag;
+    pub mod task_watcher;
     pub mod todo_list;
     pub mod todo_task;
+    pub mod todotxt;
+    pub mod tombstone;
+    pub mod two_factor;
     pub mod user;
+    pub mod webhook;
+    pub mod webhook_delivery;
+    pub mod workspace;
+    pub mod workspace_member;
 }
 mod routes {
+    pub mod account_archive;
+    pub mod account_deletion;
+    pub mod api_key;
+    pub mod attachment;
+    pub mod auth;
+    pub mod caldav;
+    pub mod calendar;
+    pub mod event_stream;
+    pub mod external_import;
+    pub mod list_member;
+    pub mod markdown;
+    pub mod notification;
+    pub mod oidc;
+    pub mod reminder_preference;
+    pub mod session;
+    pub mod share_link;
+    pub mod sync;
+    pub mod task_assignee;
+    pub mod task_csv;
+    pub mod task_feed;
+    pub mod task_reminder;
+    pub mod task_watcher;
+    pub mod todo_list;
     pub mod todo_task;
+    pub mod todotxt;
+    pub mod two_factor;
     pub mod user;
+    pub mod webhook;
+    pub mod workspace;
 }
 mod utils {
+    pub mod account_deletion;
+    pub mod api_keys;
+    pub mod auth;
     pub mod database {
+        pub mod access;
         pub mod connection;
     }
     pub mod config;
+    pub mod email_tokens;
+    pub mod events;
+    pub mod ical;
+    pub mod importers;
+    pub mod inbox;
+    pub mod mailer;
+    pub mod markdown;
+    pub mod notifier;
+    pub mod oidc;
+    pub mod password;
+    pub mod reminders;
+    pub mod sessions;
+    pub mod storage;
+    #[cfg(test)]
+    pub mod testing;
+    pub mod todotxt;
+    pub mod token;
+    pub mod totp;
+    pub mod two_factor;
+    pub mod webdav;
+    pub mod webhooks;
 }
 
 pub mod schema;
@@ -25,10 +108,25 @@ pub mod schema;
 async fn main() -> std::io::Result<()> {
     utils::config::init();
     let pool = utils::database::connection::get_connection_pool();
+    let storage: Arc<dyn utils::storage::AttachmentStorage> =
+        Arc::new(utils::storage::get_attachment_storage());
+    let event_hub = utils::events::start_event_listener();
+    utils::events::start_event_log_maintenance(pool.clone());
+    utils::webhooks::start_delivery_worker(pool.clone(), event_hub.clone());
+    let mailer = utils::mailer::get_mailer().map(Arc::new);
+    let oidc_provider = utils::oidc::get_oidc_provider().map(Arc::new);
+    let notifiers = Arc::new(utils::notifier::Notifiers::new(mailer.clone()));
+    utils::reminders::start_reminder_scheduler(pool.clone(), mailer.clone());
+    utils::reminders::start_task_reminder_scheduler(pool.clone(), notifiers);
+    utils::account_deletion::start_account_deletion_scheduler(pool.clone(), storage.clone());
 
     HttpServer::new(move || {
         App::new()
             .app_data(Data::new(pool.clone()))
+            .app_data(Data::from(storage.clone()))
+            .app_data(Data::new(event_hub.clone()))
+            .app_data(Data::new(mailer.clone()))
+            .app_data(Data::new(oidc_provider.clone()))
             .service(routes::user::get_users)
             .service(routes::user::get_user_by_id)
             .service(routes::user::add_user)
@@ -36,6 +134,69 @@ async fn main() -> std::io::Result<()> {
             .service(routes::user::patch_user_email)
             .service(routes::user::patch_user_first_name)
             .service(routes::user::patch_user_last_name)
+            .service(routes::auth::login)
+            .service(routes::auth::login_two_factor)
+            .service(routes::auth::verify_email)
+            .service(routes::auth::resend_verify_email)
+            .service(routes::auth::forgot_password)
+            .service(routes::auth::reset_password)
+            .service(routes::auth::change_password)
+            .service(routes::oidc::oidc_authorize)
+            .service(routes::oidc::oidc_callback)
+            .service(routes::oidc::oidc_link)
+            .service(routes::session::refresh_session)
+            .service(routes::session::logout)
+            .service(routes::session::get_sessions)
+            .service(routes::session::delete_session)
+            .service(routes::api_key::get_api_keys)
+            .service(routes::api_key::create_api_key)
+            .service(routes::api_key::delete_api_key)
+            .service(routes::two_factor::get_totp)
+            .service(routes::two_factor::enroll_totp)
+            .service(routes::two_factor::confirm_totp)
+            .service(routes::two_factor::delete_totp)
+            .service(routes::two_factor::replace_recovery_codes)
+            .service(routes::account_archive::export_account)
+            .service(routes::account_archive::import_account)
+            .service(routes::account_deletion::request_deletion)
+            .service(routes::account_deletion::get_deletion)
+            .service(routes::account_deletion::cancel_deletion)
+            .service(routes::account_deletion::get_deletion_receipt)
+            .service(routes::reminder_preference::get_reminder_preferences)
+            .service(routes::reminder_preference::patch_reminder_preferences)
+            .service(routes::workspace::get_workspaces)
+            .service(routes::workspace::get_workspace_by_id)
+            .service(routes::workspace::add_workspace)
+            .service(routes::workspace::patch_workspace_name)
+            .service(routes::workspace::patch_workspace_two_factor)
+            .service(routes::workspace::activate_workspace)
+            .service(routes::workspace::get_workspace_members)
+            .service(routes::workspace::add_workspace_member)
+            .service(routes::workspace::patch_workspace_member_role)
+            .service(routes::workspace::delete_workspace_member)
+            .service(routes::todo_list::get_lists)
+            .service(routes::todo_list::get_list_by_id)
+            .service(routes::todo_list::add_list)
+            .service(routes::todo_list::delete_list)
+            .service(routes::todo_list::patch_list_name)
+            .service(routes::todo_list::patch_list_description)
+            .service(routes::list_member::get_members)
+            .service(routes::list_member::invite_member)
+            .service(routes::list_member::accept_invite)
+            .service(routes::list_member::patch_member_role)
+            .service(routes::list_member::revoke_member)
+            .service(routes::share_link::create_share_link)
+            .service(routes::share_link::get_share_links)
+            .service(routes::share_link::delete_share_link)
+            .service(routes::share_link::get_shared_list)
+            .service(routes::task_csv::export_list_csv)
+            .service(routes::task_csv::import_list_csv)
+            .service(routes::todotxt::export_todotxt)
+            .service(routes::todotxt::import_todotxt)
+            .service(routes::markdown::export_list_markdown)
+            .service(routes::markdown::import_markdown)
+            .service(routes::external_import::import_todoist)
+            .service(routes::external_import::import_trello)
             .service(routes::todo_task::get_tasks)
             .service(routes::todo_task::get_task_by_id)
             .service(routes::todo_task::add_task)
@@ -45,6 +206,45 @@ async fn main() -> std::io::Result<()> {
             .service(routes::todo_task::patch_task_due_date)
             .service(routes::todo_task::patch_task_todolist_id)
             .service(routes::todo_task::patch_task_parent_task_id)
+            .service(routes::attachment::upload_attachment)
+            .service(routes::attachment::get_attachments)
+            .service(routes::attachment::download_attachment)
+            .service(routes::attachment::delete_attachment)
+            .service(routes::task_assignee::get_assignees)
+            .service(routes::task_assignee::assign_task)
+            .service(routes::task_assignee::unassign_task)
+            .service(routes::task_assignee::get_user_assigned_tasks)
+            .service(routes::task_watcher::get_watchers)
+            .service(routes::task_watcher::watch_task)
+            .service(routes::task_watcher::unwatch_task)
+            .service(routes::task_reminder::get_reminders)
+            .service(routes::task_reminder::add_reminder)
+            .service(routes::task_reminder::patch_reminder)
+            .service(routes::task_reminder::snooze_reminder)
+            .service(routes::task_reminder::delete_reminder)
+            .service(routes::notification::get_notifications)
+            .service(routes::notification::mark_read)
+            .service(routes::notification::mark_all_read)
+            .service(routes::calendar::create_calendar_feed)
+            .service(routes::calendar::delete_calendar_feed)
+            .service(routes::calendar::get_calendar_feed)
+            .service(routes::caldav::well_known)
+            .service(routes::caldav::options)
+            .service(routes::caldav::propfind)
+            .service(routes::caldav::calendar_report)
+            .service(routes::caldav::get_object)
+            .service(routes::caldav::put_object)
+            .service(routes::caldav::delete_object)
+            .service(routes::task_feed::task_feed)
+            .service(routes::event_stream::event_stream)
+            .service(routes::sync::get_sync)
+            .service(routes::sync::post_sync)
+            .service(routes::webhook::get_webhooks)
+            .service(routes::webhook::get_webhook_by_id)
+            .service(routes::webhook::add_webhook)
+            .service(routes::webhook::patch_webhook)
+            .service(routes::webhook::delete_webhook)
+            .service(routes::webhook::get_deliveries)
     })
     .bind("127.0.0.1:8080")?
     .run()
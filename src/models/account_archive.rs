@@ -0,0 +1,108 @@
+use crate::models::task_reminder::ReminderChannel;
+use crate::models::workspace_member::WorkspaceRole;
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const ARCHIVE_FORMAT: &str = "todoer.account";
+/// Bumped whenever a field changes meaning or goes away; importers reject
+/// versions they don't know.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// Everything an account holds, with the ids it had on the exporting server.
+/// Attachments are listed but their files are not included.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountArchive {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: NaiveDateTime,
+    pub user: ArchivedUser,
+    #[serde(default)]
+    pub reminder_preferences: Option<ArchivedReminderPreference>,
+    #[serde(default)]
+    pub workspaces: Vec<ArchivedWorkspace>,
+    #[serde(default)]
+    pub lists: Vec<ArchivedList>,
+    #[serde(default)]
+    pub tasks: Vec<ArchivedTask>,
+    #[serde(default)]
+    pub reminders: Vec<ArchivedReminder>,
+    #[serde(default)]
+    pub attachments: Vec<ArchivedAttachment>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedUser {
+    pub id: i32,
+    pub first_name: String,
+    pub last_name: String,
+    pub email: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedReminderPreference {
+    pub email_enabled: bool,
+    pub window_hours: i32,
+    pub digest: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedWorkspace {
+    pub id: i32,
+    pub name: String,
+    pub role: WorkspaceRole,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedList {
+    pub id: i32,
+    pub name: String,
+    pub description: String,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedTask {
+    pub id: i32,
+    pub list_id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub parent_task_id: Option<i32>,
+    pub due_date: Option<NaiveDate>,
+    pub priority: Option<String>,
+    pub completed_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedReminder {
+    pub task_id: i32,
+    pub remind_at: Option<NaiveDateTime>,
+    pub minutes_before_due: Option<i32>,
+    pub channel: ReminderChannel,
+    pub snoozed_until: Option<NaiveDateTime>,
+    pub sent_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedAttachment {
+    pub task_id: i32,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub created_at: NaiveDateTime,
+}
+
+/// Maps the archive's ids to the ones the import created.
+#[derive(Debug, Serialize)]
+pub struct AccountImportSummary {
+    pub list_ids: BTreeMap<i32, i32>,
+    pub task_ids: BTreeMap<i32, i32>,
+    pub reminders: usize,
+    pub skipped_attachments: usize,
+}
@@ -0,0 +1,119 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Text;
+use diesel::{
+    prelude::{AsChangeset, Insertable},
+    Queryable, Selectable,
+};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+/// What happens to lists the user is the only owner of.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow,
+)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "lowercase")]
+pub enum OwnedListPolicy {
+    /// Hand each list to its most privileged remaining member, deleting it
+    /// only when nobody else is on it.
+    #[default]
+    Transfer,
+    Delete,
+}
+
+impl OwnedListPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OwnedListPolicy::Transfer => "transfer",
+            OwnedListPolicy::Delete => "delete",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for OwnedListPolicy {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for OwnedListPolicy {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"transfer" => Ok(OwnedListPolicy::Transfer),
+            b"delete" => Ok(OwnedListPolicy::Delete),
+            other => Err(format!(
+                "Unknown owned list policy: {}",
+                String::from_utf8_lossy(other)
+            )
+            .into()),
+        }
+    }
+}
+
+/// `user_id` is cleared once the account is gone; the receipt is only set
+/// then.
+#[derive(Debug, Serialize, Deserialize, Queryable, Selectable)]
+#[diesel(table_name = account_deletions)]
+pub struct AccountDeletion {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    pub owned_lists: OwnedListPolicy,
+    pub requested_at: NaiveDateTime,
+    pub scheduled_for: NaiveDateTime,
+    pub completed_at: Option<NaiveDateTime>,
+    pub receipt: Option<serde_json::Value>,
+}
+
+/// Requesting again while pending replaces the request and its token.
+#[derive(Serialize, Deserialize, Insertable, AsChangeset, Debug)]
+#[diesel(table_name = account_deletions)]
+pub struct NewAccountDeletion {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub owned_lists: OwnedListPolicy,
+    pub requested_at: NaiveDateTime,
+    pub scheduled_for: NaiveDateTime,
+}
+
+/// `confirm_email` must repeat the account's address.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InputAccountDeletion {
+    pub confirm_email: String,
+    #[serde(default)]
+    pub owned_lists: OwnedListPolicy,
+}
+
+/// Returned once on request; the token is what fetches the receipt after the
+/// account, and with it any way to authenticate, is gone.
+#[derive(Debug, Serialize)]
+pub struct CreatedAccountDeletion {
+    #[serde(flatten)]
+    pub deletion: AccountDeletion,
+    pub receipt_token: String,
+    pub receipt_path: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransferredList {
+    pub list_id: i32,
+    pub new_owner_id: i32,
+}
+
+/// What the purge did, by id and count only: nothing in it identifies the
+/// person who was deleted.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeletionReceipt {
+    pub completed_at: NaiveDateTime,
+    pub lists_transferred: Vec<TransferredList>,
+    pub lists_deleted: Vec<i32>,
+    pub memberships_removed: usize,
+    pub tasks_reassigned: usize,
+    pub events_anonymized: usize,
+    pub notifications_anonymized: usize,
+}
@@ -0,0 +1,140 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Text;
+use diesel::{prelude::Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+/// Keys start with this so they can be told apart from session access tokens
+/// and spotted by secret scanners.
+pub const API_KEY_PREFIX: &str = "tdk_";
+
+pub const MAX_API_KEY_NAME_CHARS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ApiScope {
+    #[serde(rename = "tasks:read")]
+    TasksRead,
+    #[serde(rename = "tasks:write")]
+    TasksWrite,
+    /// Creating, renaming and deleting whole lists.
+    #[serde(rename = "lists:admin")]
+    ListsAdmin,
+    #[serde(rename = "users:read")]
+    UsersRead,
+    #[serde(rename = "users:write")]
+    UsersWrite,
+}
+
+impl ApiScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::TasksRead => "tasks:read",
+            ApiScope::TasksWrite => "tasks:write",
+            ApiScope::ListsAdmin => "lists:admin",
+            ApiScope::UsersRead => "users:read",
+            ApiScope::UsersWrite => "users:write",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "tasks:read" => Some(ApiScope::TasksRead),
+            "tasks:write" => Some(ApiScope::TasksWrite),
+            "lists:admin" => Some(ApiScope::ListsAdmin),
+            "users:read" => Some(ApiScope::UsersRead),
+            "users:write" => Some(ApiScope::UsersWrite),
+            _ => None,
+        }
+    }
+}
+
+/// Stored as one space separated column, the way OAuth writes scopes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+#[serde(transparent)]
+pub struct ApiScopes(pub Vec<ApiScope>);
+
+impl ApiScopes {
+    pub fn contains(&self, scope: ApiScope) -> bool {
+        self.0.contains(&scope)
+    }
+}
+
+impl ToSql<Text, Pg> for ApiScopes {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        let joined = self
+            .0
+            .iter()
+            .map(ApiScope::as_str)
+            .collect::<Vec<&str>>()
+            .join(" ");
+        out.write_all(joined.as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for ApiScopes {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        let text = std::str::from_utf8(bytes.as_bytes())?;
+        text.split_whitespace()
+            .map(|scope| {
+                ApiScope::parse(scope).ok_or_else(|| format!("Unknown API scope: {}", scope).into())
+            })
+            .collect::<deserialize::Result<Vec<ApiScope>>>()
+            .map(ApiScopes)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Selectable)]
+#[diesel(table_name = api_keys)]
+pub struct ApiKey {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: ApiScopes,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = api_keys)]
+pub struct NewApiKey {
+    pub user_id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub key_hash: String,
+    pub scopes: ApiScopes,
+    pub expires_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InputApiKey {
+    pub name: String,
+    pub scopes: ApiScopes,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+impl InputApiKey {
+    pub fn is_valid(&self, now: NaiveDateTime) -> bool {
+        let name_chars = self.name.trim().chars().count();
+        (1..=MAX_API_KEY_NAME_CHARS).contains(&name_chars)
+            && !self.scopes.0.is_empty()
+            && self.expires_at.is_none_or(|expiry| expiry > now)
+    }
+}
+
+/// Returned once on creation; only the key's hash is kept.
+#[derive(Debug, Serialize)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
+}
@@ -0,0 +1,26 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::{prelude::Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Queryable)]
+pub struct Attachment {
+    pub id: i32,
+    pub task_id: i32,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub content_hash: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = attachments)]
+pub struct NewAttachment {
+    pub task_id: i32,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub content_hash: String,
+    pub created_at: NaiveDateTime,
+}
@@ -0,0 +1,39 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::{
+    prelude::{AsChangeset, Insertable},
+    Queryable, Selectable,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Selectable)]
+#[diesel(table_name = calendar_feeds)]
+pub struct CalendarFeed {
+    pub user_id: i32,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, AsChangeset, Debug)]
+#[diesel(table_name = calendar_feeds)]
+pub struct NewCalendarFeed {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// Returned once on creation; the plain token can't be recovered afterwards.
+#[derive(Debug, Serialize)]
+pub struct CreatedCalendarFeed {
+    #[serde(flatten)]
+    pub feed: CalendarFeed,
+    pub token: String,
+    pub path: String,
+}
+
+/// `lists` is a comma-separated list of list ids; `component` picks `vevent`
+/// (the default, which every calendar app shows) or `vtodo`.
+#[derive(Debug, Deserialize)]
+pub struct CalendarFeedQuery {
+    pub lists: Option<String>,
+    pub component: Option<String>,
+}
@@ -0,0 +1,21 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::{prelude::Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Queryable)]
+pub struct DavObject {
+    pub task_id: i32,
+    pub uid: String,
+    pub name: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = dav_objects)]
+pub struct NewDavObject<'a> {
+    pub task_id: i32,
+    pub uid: &'a str,
+    pub name: &'a str,
+    pub created_at: NaiveDateTime,
+}
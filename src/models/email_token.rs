@@ -0,0 +1,102 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Text;
+use diesel::{prelude::Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+pub const MIN_PASSWORD_CHARS: usize = 8;
+pub const MAX_PASSWORD_CHARS: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+}
+
+impl TokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenPurpose::VerifyEmail => "verify_email",
+            TokenPurpose::ResetPassword => "reset_password",
+        }
+    }
+
+    /// Reset links grant the account, so they are kept short-lived.
+    pub fn lifetime(&self) -> chrono::Duration {
+        match self {
+            TokenPurpose::VerifyEmail => chrono::Duration::hours(48),
+            TokenPurpose::ResetPassword => chrono::Duration::hours(1),
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for TokenPurpose {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for TokenPurpose {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"verify_email" => Ok(TokenPurpose::VerifyEmail),
+            b"reset_password" => Ok(TokenPurpose::ResetPassword),
+            other => {
+                Err(format!("Unknown token purpose: {}", String::from_utf8_lossy(other)).into())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Selectable)]
+#[diesel(table_name = email_tokens)]
+pub struct EmailToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub purpose: TokenPurpose,
+    pub email: String,
+    pub expires_at: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = email_tokens)]
+pub struct NewEmailToken {
+    pub user_id: i32,
+    pub purpose: TokenPurpose,
+    pub token_hash: String,
+    pub email: String,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyEmail {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ForgotPassword {
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResetPassword {
+    pub token: String,
+    pub password: String,
+}
+
+impl ResetPassword {
+    pub fn is_valid(&self) -> bool {
+        (MIN_PASSWORD_CHARS..=MAX_PASSWORD_CHARS).contains(&self.password.chars().count())
+    }
+}
@@ -0,0 +1,28 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::{prelude::Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Queryable)]
+pub struct Event {
+    pub id: i64,
+    pub kind: String,
+    pub payload: serde_json::Value,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = events)]
+pub struct NewEvent<'a> {
+    pub id: i64,
+    pub kind: &'a str,
+    pub payload: serde_json::Value,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = event_recipients)]
+pub struct NewEventRecipient {
+    pub event_id: i64,
+    pub user_id: i32,
+}
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Deserialize)]
+pub struct ExternalImportQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// What an import did, or would do on a dry run. Ids are the source
+/// service's, mapped to the ones created here.
+#[derive(Debug, Serialize)]
+pub struct ExternalImportReport {
+    pub source: &'static str,
+    pub dry_run: bool,
+    pub lists: usize,
+    pub tasks: usize,
+    pub subtasks: usize,
+    pub tags: usize,
+    /// Things left out or changed to fit, such as archived cards.
+    pub warnings: Vec<String>,
+    /// Problems that stopped the import; nothing is written if there are any.
+    pub errors: Vec<String>,
+    pub list_ids: BTreeMap<String, i32>,
+    pub task_ids: BTreeMap<String, i32>,
+}
@@ -0,0 +1,97 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Text;
+use diesel::{prelude::Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+/// Roles are ordered by privilege, so `role >= ListRole::Editor` reads as
+/// "may edit".
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    AsExpression,
+    FromSqlRow,
+)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "lowercase")]
+pub enum ListRole {
+    Viewer,
+    Commenter,
+    Editor,
+    Owner,
+}
+
+impl ListRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ListRole::Viewer => "viewer",
+            ListRole::Commenter => "commenter",
+            ListRole::Editor => "editor",
+            ListRole::Owner => "owner",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for ListRole {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for ListRole {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"viewer" => Ok(ListRole::Viewer),
+            b"commenter" => Ok(ListRole::Commenter),
+            b"editor" => Ok(ListRole::Editor),
+            b"owner" => Ok(ListRole::Owner),
+            other => Err(format!("Unknown list role: {}", String::from_utf8_lossy(other)).into()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable)]
+pub struct ListMember {
+    pub list_id: i32,
+    pub user_id: i32,
+    pub role: ListRole,
+    pub invited_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub accepted_at: Option<NaiveDateTime>,
+    pub change_seq: i64,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = list_members)]
+pub struct NewListMember {
+    pub list_id: i32,
+    pub user_id: i32,
+    pub role: ListRole,
+    pub invited_by: Option<i32>,
+    pub created_at: NaiveDateTime,
+    pub accepted_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InputListMember {
+    pub user_id: i32,
+    pub role: ListRole,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateListMemberRole {
+    pub role: ListRole,
+}
@@ -0,0 +1,26 @@
+use crate::models::todo_list::TodoList;
+use crate::models::todo_task::TodoTask;
+use serde::{Deserialize, Serialize};
+
+/// `name` overrides the document's `#` heading as the new list's name.
+#[derive(Debug, Deserialize)]
+pub struct MarkdownImportQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MarkdownLineError {
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MarkdownImportReport {
+    pub dry_run: bool,
+    pub items: usize,
+    pub errors: Vec<MarkdownLineError>,
+    pub list: Option<TodoList>,
+    pub tasks: Vec<TodoTask>,
+}
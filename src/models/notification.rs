@@ -0,0 +1,50 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::{prelude::Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Queryable)]
+pub struct Notification {
+    pub id: i64,
+    pub user_id: i32,
+    pub kind: String,
+    pub task_id: Option<i32>,
+    pub list_id: Option<i32>,
+    pub title: String,
+    pub body: String,
+    pub read_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    /// Who caused it; `None` for reminders and once the actor's account is
+    /// deleted.
+    pub actor_id: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = notifications)]
+pub struct NewNotification<'a> {
+    pub user_id: i32,
+    pub kind: &'a str,
+    pub task_id: Option<i32>,
+    pub list_id: Option<i32>,
+    pub title: &'a str,
+    pub body: &'a str,
+    pub created_at: NaiveDateTime,
+    pub actor_id: Option<i32>,
+}
+
+/// Pages run newest first; pass the previous page's `next_before` as `before`
+/// to get the next one.
+#[derive(Debug, Deserialize)]
+pub struct NotificationQuery {
+    #[serde(default)]
+    pub unread: bool,
+    pub before: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NotificationPage {
+    pub notifications: Vec<Notification>,
+    pub unread_count: i64,
+    pub next_before: Option<i64>,
+}
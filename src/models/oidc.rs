@@ -0,0 +1,80 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::{prelude::Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+
+/// How long the user has to get through the provider's sign-in page.
+pub const OIDC_LOGIN_MINUTES: i64 = 10;
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Selectable)]
+#[diesel(table_name = oidc_login_attempts)]
+pub struct OidcLoginAttempt {
+    pub id: i32,
+    pub code_verifier: String,
+    pub nonce: String,
+    pub expires_at: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = oidc_login_attempts)]
+pub struct NewOidcLoginAttempt {
+    pub state_hash: String,
+    pub code_verifier: String,
+    pub nonce: String,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Selectable)]
+#[diesel(table_name = oidc_identities)]
+pub struct OidcIdentity {
+    pub id: i32,
+    pub user_id: i32,
+    pub issuer: String,
+    pub subject: String,
+    pub email: String,
+    pub created_at: NaiveDateTime,
+    pub last_login_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = oidc_identities)]
+pub struct NewOidcIdentity {
+    pub user_id: i32,
+    pub issuer: String,
+    pub subject: String,
+    pub email: String,
+    pub created_at: NaiveDateTime,
+    pub last_login_at: NaiveDateTime,
+}
+
+/// Where to send the browser to sign in. `state` comes back on the redirect
+/// and has to be posted to the callback together with the code.
+#[derive(Debug, Serialize)]
+pub struct OidcAuthorization {
+    pub authorization_url: String,
+    pub state: String,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OidcCallback {
+    pub code: String,
+    pub state: String,
+}
+
+/// The ID token claims used to find or create the user.
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+    pub name: Option<String>,
+}
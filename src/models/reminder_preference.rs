@@ -0,0 +1,41 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::{
+    prelude::{AsChangeset, Insertable},
+    Queryable,
+};
+use serde::{Deserialize, Serialize};
+
+/// Bounds of `window_hours`, matching the table's check constraint.
+pub const MIN_WINDOW_HOURS: i32 = 1;
+pub const MAX_WINDOW_HOURS: i32 = 720;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable, Insertable, AsChangeset)]
+#[diesel(table_name = reminder_preferences)]
+pub struct ReminderPreference {
+    pub user_id: i32,
+    pub email_enabled: bool,
+    pub window_hours: i32,
+    pub digest: bool,
+    pub modified_at: NaiveDateTime,
+}
+
+impl ReminderPreference {
+    /// What users who never changed their preferences get.
+    pub fn default_for(user_id: i32) -> Self {
+        ReminderPreference {
+            user_id,
+            email_enabled: true,
+            window_hours: 24,
+            digest: true,
+            modified_at: chrono::Local::now().naive_local(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateReminderPreference {
+    pub email_enabled: Option<bool>,
+    pub window_hours: Option<i32>,
+    pub digest: Option<bool>,
+}
@@ -0,0 +1,21 @@
+use crate::schema::*;
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::{prelude::Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Queryable)]
+pub struct SentReminder {
+    pub task_id: i32,
+    pub user_id: i32,
+    pub due_date: NaiveDate,
+    pub sent_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = sent_reminders)]
+pub struct NewSentReminder {
+    pub task_id: i32,
+    pub user_id: i32,
+    pub due_date: NaiveDate,
+    pub sent_at: NaiveDateTime,
+}
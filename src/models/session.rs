@@ -0,0 +1,118 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Text;
+use diesel::{prelude::Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub enum RevokedReason {
+    /// Signed out from the device itself.
+    Logout,
+    /// Signed out from another of the user's sessions.
+    RemoteLogout,
+    PasswordChange,
+    /// A refresh token was presented after it had already been rotated, so
+    /// someone else may hold a copy of it.
+    TokenReuse,
+}
+
+impl RevokedReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RevokedReason::Logout => "logout",
+            RevokedReason::RemoteLogout => "remote_logout",
+            RevokedReason::PasswordChange => "password_change",
+            RevokedReason::TokenReuse => "token_reuse",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for RevokedReason {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for RevokedReason {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"logout" => Ok(RevokedReason::Logout),
+            b"remote_logout" => Ok(RevokedReason::RemoteLogout),
+            b"password_change" => Ok(RevokedReason::PasswordChange),
+            b"token_reuse" => Ok(RevokedReason::TokenReuse),
+            other => Err(format!(
+                "Unknown session revocation reason: {}",
+                String::from_utf8_lossy(other)
+            )
+            .into()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Selectable)]
+#[diesel(table_name = sessions)]
+pub struct Session {
+    pub id: i32,
+    pub user_id: i32,
+    pub access_expires_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_seen_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub revoked_reason: Option<RevokedReason>,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = sessions)]
+pub struct NewSession {
+    pub user_id: i32,
+    pub access_token_hash: String,
+    pub access_expires_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_seen_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = refresh_tokens)]
+pub struct NewRefreshToken {
+    pub session_id: i32,
+    pub token_hash: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// Handed to the client on sign-in and on every refresh; neither token is
+/// stored in plain.
+#[derive(Debug, Serialize)]
+pub struct SessionTokens {
+    pub session_id: i32,
+    pub access_token: String,
+    pub access_expires_at: NaiveDateTime,
+    pub refresh_token: String,
+    pub expires_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ActiveSession {
+    #[serde(flatten)]
+    pub session: Session,
+    /// Whether this is the session the request was made with.
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshSession {
+    pub refresh_token: String,
+}
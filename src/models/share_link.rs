@@ -0,0 +1,55 @@
+use crate::models::{todo_list::TodoList, todo_task::TodoTask};
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::{prelude::Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Selectable)]
+#[diesel(table_name = share_links)]
+pub struct ShareLink {
+    pub id: i32,
+    pub list_id: i32,
+    pub created_by: Option<i32>,
+    #[serde(skip_serializing)]
+    pub password_hash: Option<String>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl ShareLink {
+    pub fn is_active(&self, now: NaiveDateTime) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|expiry| expiry > now)
+    }
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = share_links)]
+pub struct NewShareLink {
+    pub list_id: i32,
+    pub created_by: Option<i32>,
+    pub token_hash: String,
+    pub password_hash: Option<String>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InputShareLink {
+    pub expires_at: Option<NaiveDateTime>,
+    pub password: Option<String>,
+}
+
+/// Returned once on creation; the plain token can't be recovered afterwards.
+#[derive(Debug, Serialize)]
+pub struct CreatedShareLink {
+    #[serde(flatten)]
+    pub link: ShareLink,
+    pub token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SharedList {
+    pub list: TodoList,
+    pub tasks: Vec<TodoTask>,
+}
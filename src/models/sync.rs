@@ -0,0 +1,83 @@
+use crate::models::todo_list::{InputTodoList, TodoList, UpdateTodoList};
+use crate::models::todo_task::{InputTodoTask, TodoTask, UpdateTodoTask};
+use crate::models::tombstone::Tombstone;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct SyncQuery {
+    pub since: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncChanges {
+    pub token: i64,
+    pub lists: Vec<TodoList>,
+    pub tasks: Vec<TodoTask>,
+    pub tombstones: Vec<Tombstone>,
+}
+
+/// Updates and deletions carry the `modified_at` the client last saw; if the
+/// server's copy has moved on since, the mutation is reported as a conflict
+/// instead of overwriting someone else's edit.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum SyncMutation {
+    CreateTask {
+        client_id: Option<String>,
+        task: InputTodoTask,
+    },
+    UpdateTask {
+        id: i32,
+        base_modified_at: NaiveDateTime,
+        changes: UpdateTodoTask,
+    },
+    DeleteTask {
+        id: i32,
+        base_modified_at: NaiveDateTime,
+    },
+    CreateList {
+        client_id: Option<String>,
+        list: InputTodoList,
+    },
+    UpdateList {
+        id: i32,
+        base_modified_at: NaiveDateTime,
+        changes: UpdateTodoList,
+    },
+    DeleteList {
+        id: i32,
+        base_modified_at: NaiveDateTime,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SyncRequest {
+    pub mutations: Vec<SyncMutation>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncStatus {
+    Applied,
+    Conflict,
+    Invalid,
+    NotFound,
+    Forbidden,
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncResult {
+    pub index: usize,
+    pub status: SyncStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// The row as it is on the server: after the mutation when it was
+    /// applied, or the version that won when it conflicted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current: Option<serde_json::Value>,
+    /// Why an invalid mutation was turned down.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'static str>,
+}
@@ -1,3 +1,4 @@
+use crate::utils::database::access::AccessError;
 use actix_web::{http::header::ContentType, HttpResponse};
 use serde_json::Value;
 
@@ -11,6 +12,63 @@ pub fn throw_response_ok(response_body: Value) -> HttpResponse {
     .json(response_body)
 }
 
+pub fn throw_response_unprocessable_entity(response_body: Value) -> HttpResponse {
+    HttpResponse::UnprocessableEntity()
+        .content_type(ContentType::json())
+        .json(response_body)
+}
+
+pub fn throw_response_accepted() -> HttpResponse {
+    HttpResponse::Accepted().finish()
+}
+
 pub fn throw_response_error() -> HttpResponse {
     HttpResponse::InternalServerError().finish()
-}
\ No newline at end of file
+}
+
+pub fn throw_response_bad_request() -> HttpResponse {
+    HttpResponse::BadRequest().finish()
+}
+
+pub fn throw_response_not_found() -> HttpResponse {
+    HttpResponse::NotFound().finish()
+}
+
+pub fn throw_response_payload_too_large() -> HttpResponse {
+    HttpResponse::PayloadTooLarge().finish()
+}
+
+pub fn throw_response_unsupported_media_type() -> HttpResponse {
+    HttpResponse::UnsupportedMediaType().finish()
+}
+
+pub fn throw_response_unauthorized() -> HttpResponse {
+    HttpResponse::Unauthorized().finish()
+}
+
+pub fn throw_response_forbidden() -> HttpResponse {
+    HttpResponse::Forbidden().finish()
+}
+
+pub fn throw_response_conflict() -> HttpResponse {
+    HttpResponse::Conflict().finish()
+}
+
+pub fn throw_response_gone() -> HttpResponse {
+    HttpResponse::Gone().finish()
+}
+
+pub fn throw_response_bad_gateway() -> HttpResponse {
+    HttpResponse::BadGateway().finish()
+}
+
+pub fn throw_response_access_error(error: AccessError) -> HttpResponse {
+    match error {
+        AccessError::Forbidden => throw_response_forbidden(),
+        AccessError::Database(diesel::result::Error::NotFound) => throw_response_not_found(),
+        AccessError::Database(e) => {
+            eprintln!("Database error: {}", e);
+            throw_response_error()
+        }
+    }
+}
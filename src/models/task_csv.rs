@@ -0,0 +1,44 @@
+use crate::models::todo_task::TodoTask;
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+/// One exported task. Imports read the same headers by default, so an export
+/// can be loaded back as is.
+#[derive(Debug, Serialize)]
+pub struct TaskCsvRecord<'a> {
+    pub id: i32,
+    pub name: String,
+    pub description: String,
+    pub parent_task_id: Option<i32>,
+    pub due_date: Option<NaiveDate>,
+    pub created_at: &'a NaiveDateTime,
+    pub modified_at: &'a NaiveDateTime,
+}
+
+/// Maps task fields to CSV headers; a field left out is read from the column
+/// named after it, if there is one.
+#[derive(Debug, Deserialize)]
+pub struct TaskCsvImportQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub due_date: Option<String>,
+    pub parent_task_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CsvRowError {
+    /// The line in the file, counting the header as line 1.
+    pub line: u64,
+    pub field: Option<&'static str>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaskCsvImportReport {
+    pub dry_run: bool,
+    pub rows: usize,
+    pub errors: Vec<CsvRowError>,
+    pub tasks: Vec<TodoTask>,
+}
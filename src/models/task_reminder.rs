@@ -0,0 +1,138 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Text;
+use diesel::{
+    prelude::{AsChangeset, Insertable},
+    Queryable,
+};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, AsExpression, FromSqlRow,
+)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "snake_case")]
+pub enum ReminderChannel {
+    #[default]
+    InApp,
+    Email,
+    Webhook,
+}
+
+impl ReminderChannel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReminderChannel::InApp => "in_app",
+            ReminderChannel::Email => "email",
+            ReminderChannel::Webhook => "webhook",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for ReminderChannel {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for ReminderChannel {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"in_app" => Ok(ReminderChannel::InApp),
+            b"email" => Ok(ReminderChannel::Email),
+            b"webhook" => Ok(ReminderChannel::Webhook),
+            other => Err(format!(
+                "Unknown reminder channel: {}",
+                String::from_utf8_lossy(other)
+            )
+            .into()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable)]
+pub struct TaskReminder {
+    pub id: i32,
+    pub task_id: i32,
+    pub user_id: i32,
+    pub remind_at: Option<NaiveDateTime>,
+    pub minutes_before_due: Option<i32>,
+    pub channel: ReminderChannel,
+    pub snoozed_until: Option<NaiveDateTime>,
+    pub sent_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = task_reminders)]
+pub struct NewTaskReminder {
+    pub task_id: i32,
+    pub user_id: i32,
+    pub remind_at: Option<NaiveDateTime>,
+    pub minutes_before_due: Option<i32>,
+    pub channel: ReminderChannel,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+}
+
+/// Exactly one of `remind_at` and `minutes_before_due` must be given.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InputTaskReminder {
+    pub remind_at: Option<NaiveDateTime>,
+    pub minutes_before_due: Option<i32>,
+    #[serde(default)]
+    pub channel: ReminderChannel,
+}
+
+impl InputTaskReminder {
+    pub fn is_valid(&self) -> bool {
+        match (self.remind_at, self.minutes_before_due) {
+            (Some(_), None) => true,
+            (None, Some(minutes)) => minutes >= 0,
+            _ => false,
+        }
+    }
+}
+
+/// Giving either trigger field replaces the trigger; giving both is invalid.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateTaskReminder {
+    pub remind_at: Option<NaiveDateTime>,
+    pub minutes_before_due: Option<i32>,
+    pub channel: Option<ReminderChannel>,
+}
+
+impl UpdateTaskReminder {
+    pub fn is_valid(&self) -> bool {
+        match (self.remind_at, self.minutes_before_due) {
+            (Some(_), Some(_)) => false,
+            (None, Some(minutes)) => minutes >= 0,
+            _ => true,
+        }
+    }
+}
+
+/// Replacing the trigger re-arms the reminder, so the snooze and the record of
+/// it having been sent are cleared as well.
+#[derive(Debug, AsChangeset)]
+#[diesel(table_name = task_reminders, treat_none_as_null = true)]
+pub struct TaskReminderTrigger {
+    pub remind_at: Option<NaiveDateTime>,
+    pub minutes_before_due: Option<i32>,
+    pub channel: ReminderChannel,
+    pub snoozed_until: Option<NaiveDateTime>,
+    pub sent_at: Option<NaiveDateTime>,
+    pub modified_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnoozeTaskReminder {
+    pub minutes: i64,
+}
@@ -0,0 +1,13 @@
+use crate::schema::*;
+use diesel::{prelude::Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+
+/// The tag column is a `VARCHAR(64)`.
+pub const MAX_TAG_CHARS: usize = 64;
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Insertable)]
+#[diesel(table_name = task_tags)]
+pub struct TaskTag {
+    pub task_id: i32,
+    pub tag: String,
+}
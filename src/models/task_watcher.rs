@@ -0,0 +1,24 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::{prelude::Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Queryable)]
+pub struct TaskWatcher {
+    pub task_id: i32,
+    pub user_id: i32,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = task_watchers)]
+pub struct NewTaskWatcher {
+    pub task_id: i32,
+    pub user_id: i32,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InputTaskWatcher {
+    pub user_id: i32,
+}
@@ -1,53 +1,56 @@
 use crate::schema::*;
 use chrono::NaiveDateTime;
-use diesel::{prelude::Insertable, Queryable};
+use diesel::{
+    prelude::{AsChangeset, Insertable},
+    Queryable,
+};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Queryable)]
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
 pub struct TodoList {
     pub id: i32,
     pub user_id: i32,
-    pub shared_with: Option<String>,
     pub name: String,
     pub description: String,
     pub created_at: NaiveDateTime,
     pub modified_at: NaiveDateTime,
+    pub workspace_id: i32,
+    pub change_seq: i64,
 }
 
 #[derive(Serialize, Deserialize, Insertable, Debug)]
 #[diesel(table_name = todolists)]
 pub struct NewTodoList {
     pub user_id: i32,
-    pub shared_with: Option<String>,
     pub name: String,
-    pub description: Option<String>,
+    pub description: String,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+    pub workspace_id: i32,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InputTodoList {
-    pub user_id: i32,
-    pub shared_with: Option<String>,
     pub name: String,
     pub description: Option<String>,
 }
 
+/// A partial update: absent fields are left untouched.
+#[derive(Debug, Default, Serialize, Deserialize, AsChangeset)]
+#[diesel(table_name = todolists)]
+pub struct UpdateTodoList {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateTodoListName {
-    pub user_id: i32,
     pub name: String,
-    pub modified_at: NaiveDateTime,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateTodoListDescription {
-    pub user_id: i32,
-    pub description: Option<String>,
-    pub modified_at: NaiveDateTime,
+    pub description: String,
 }
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UpdateTodoListSharedWith {
-    pub user_id: i32,
-    pub shared_with: String,
-    pub modified_at: NaiveDateTime,
-}
\ No newline at end of file
@@ -1,9 +1,12 @@
 use crate::schema::*;
 use chrono::{NaiveDate, NaiveDateTime};
-use diesel::{prelude::Insertable, Queryable};
-use serde::{Deserialize, Serialize};
+use diesel::{
+    prelude::{AsChangeset, Insertable},
+    Queryable,
+};
+use serde::{Deserialize, Deserializer, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Queryable)]
+#[derive(Debug, Clone, Serialize, Deserialize, Queryable)]
 pub struct TodoTask {
     pub id: i32,
     pub user_id: i32,
@@ -14,6 +17,9 @@ pub struct TodoTask {
     pub due_date: Option<NaiveDate>,
     pub created_at: NaiveDateTime,
     pub modified_at: NaiveDateTime,
+    pub change_seq: i64,
+    pub priority: Option<String>,
+    pub completed_at: Option<NaiveDateTime>,
 }
 
 #[derive(Serialize, Deserialize, Insertable, Debug)]
@@ -27,16 +33,95 @@ pub struct NewTodoTask {
     pub due_date: Option<NaiveDate>,
     pub created_at: NaiveDateTime,
     pub modified_at: NaiveDateTime,
+    pub priority: Option<String>,
+    pub completed_at: Option<NaiveDateTime>,
 }
 
+/// The task name column is a `VARCHAR(255)`.
+pub const MAX_TASK_NAME_CHARS: usize = 255;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InputTodoTask {
-    pub user_id: i32,
     pub todolist_id: i32,
     pub name: String,
     pub description: Option<String>,
     pub parent_task_id: Option<i32>,
     pub due_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub priority: Option<String>,
+    #[serde(default)]
+    pub completed_at: Option<NaiveDateTime>,
+}
+
+/// Priorities are single letters, `A` being the most urgent.
+pub fn is_valid_priority(priority: &str) -> bool {
+    priority.len() == 1 && priority.chars().all(|c| c.is_ascii_uppercase())
+}
+
+impl InputTodoTask {
+    /// Names the first field breaking the rules, with the reason.
+    pub fn validate(&self) -> Result<(), (&'static str, &'static str)> {
+        if self.name.trim().is_empty() {
+            return Err(("name", "name is required"));
+        }
+        if self.name.chars().count() > MAX_TASK_NAME_CHARS {
+            return Err(("name", "name is longer than 255 characters"));
+        }
+        if !self.priority.as_deref().is_none_or(is_valid_priority) {
+            return Err(("priority", "priority must be a letter from A to Z"));
+        }
+        Ok(())
+    }
+}
+
+impl UpdateTodoTask {
+    /// The same rules as [`InputTodoTask::validate`], for the fields present.
+    pub fn validate(&self) -> Result<(), (&'static str, &'static str)> {
+        if let Some(name) = &self.name {
+            if name.trim().is_empty() {
+                return Err(("name", "name is required"));
+            }
+            if name.chars().count() > MAX_TASK_NAME_CHARS {
+                return Err(("name", "name is longer than 255 characters"));
+            }
+        }
+        if let Some(Some(priority)) = &self.priority {
+            if !is_valid_priority(priority) {
+                return Err(("priority", "priority must be a letter from A to Z"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Tells an explicit `null` apart from an absent field, so that a partial
+/// update can clear a nullable column.
+fn deserialize_some<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// A partial update: absent fields are left untouched.
+#[derive(Debug, Default, Serialize, Deserialize, AsChangeset)]
+#[diesel(table_name = todotasks)]
+pub struct UpdateTodoTask {
+    #[serde(default)]
+    pub todolist_id: Option<i32>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub description: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub parent_task_id: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub due_date: Option<Option<NaiveDate>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub priority: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub completed_at: Option<Option<NaiveDateTime>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
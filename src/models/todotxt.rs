@@ -0,0 +1,27 @@
+use crate::models::todo_task::TodoTask;
+use serde::{Deserialize, Serialize};
+
+/// Lines without a `+project` go to `list_id`, or to an "Inbox" list when
+/// none is given.
+#[derive(Debug, Deserialize)]
+pub struct TodoTxtImportQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+    pub list_id: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TodoTxtLineError {
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TodoTxtImportReport {
+    pub dry_run: bool,
+    pub lines: usize,
+    pub errors: Vec<TodoTxtLineError>,
+    /// Projects with no matching list, which get one created.
+    pub created_lists: Vec<String>,
+    pub tasks: Vec<TodoTask>,
+}
@@ -0,0 +1,27 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::prelude::{Insertable, Queryable, Selectable};
+use serde::{Deserialize, Serialize};
+
+/// Left behind when a task or list is deleted, or a task moves out of a list,
+/// so that delta sync can tell clients to drop their copy.
+#[derive(Debug, Serialize, Deserialize, Queryable, Selectable)]
+#[diesel(table_name = tombstones)]
+pub struct Tombstone {
+    pub entity: String,
+    #[serde(rename = "id")]
+    pub entity_id: i32,
+    pub list_id: i32,
+    pub deleted_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = tombstones)]
+pub struct NewTombstone<'a> {
+    pub entity: &'a str,
+    pub entity_id: i32,
+    pub list_id: i32,
+    pub workspace_id: i32,
+    pub change_seq: i64,
+    pub deleted_at: NaiveDateTime,
+}
@@ -0,0 +1,124 @@
+use crate::models::session::SessionTokens;
+use crate::models::user::User;
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::{
+    prelude::{AsChangeset, Insertable},
+    Queryable, Selectable,
+};
+use serde::{Deserialize, Serialize};
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Wrong codes allowed against one login challenge before it is burned.
+pub const MAX_CHALLENGE_ATTEMPTS: i32 = 5;
+
+pub const LOGIN_CHALLENGE_MINUTES: i64 = 5;
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Selectable)]
+#[diesel(table_name = user_totp)]
+pub struct UserTotp {
+    pub user_id: i32,
+    #[serde(skip)]
+    pub secret: String,
+    pub confirmed_at: Option<NaiveDateTime>,
+    #[serde(skip)]
+    pub last_step: Option<i64>,
+    pub created_at: NaiveDateTime,
+}
+
+/// Starting over replaces an enrollment that was never confirmed.
+#[derive(Serialize, Deserialize, Insertable, AsChangeset, Debug)]
+#[diesel(table_name = user_totp, treat_none_as_null = true)]
+pub struct NewUserTotp {
+    pub user_id: i32,
+    pub secret: String,
+    pub confirmed_at: Option<NaiveDateTime>,
+    pub last_step: Option<i64>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = recovery_codes)]
+pub struct NewRecoveryCode {
+    pub user_id: i32,
+    pub code_hash: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable, Selectable)]
+#[diesel(table_name = login_challenges)]
+pub struct LoginChallenge {
+    pub id: i32,
+    pub user_id: i32,
+    pub attempts: i32,
+    pub expires_at: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = login_challenges)]
+pub struct NewLoginChallenge {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+/// Returned once when enrollment starts; the secret is what the QR code
+/// encodes.
+#[derive(Debug, Serialize)]
+pub struct TotpEnrollment {
+    #[serde(flatten)]
+    pub totp: UserTotp,
+    pub secret: String,
+    pub provisioning_uri: String,
+}
+
+/// Shown once; only their hashes are kept.
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TotpCode {
+    pub code: String,
+}
+
+/// Either a current authenticator code or an unused recovery code.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecondFactor {
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginCredentials {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginSecondStep {
+    pub challenge: String,
+    #[serde(flatten)]
+    pub factor: SecondFactor,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum LoginOutcome {
+    /// `two_factor_setup_required` is set when one of the user's workspaces
+    /// requires two-factor and they haven't enrolled yet.
+    SignedIn {
+        user: User,
+        session: SessionTokens,
+        two_factor_setup_required: bool,
+    },
+    TwoFactorRequired {
+        challenge: String,
+        expires_at: NaiveDateTime,
+    },
+}
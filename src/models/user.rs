@@ -1,7 +1,8 @@
+use crate::models::email_token::{MAX_PASSWORD_CHARS, MIN_PASSWORD_CHARS};
 use crate::schema::*;
 use chrono::NaiveDateTime;
 use diesel::{prelude::Insertable, Queryable};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 
 #[derive(Debug, Serialize, Deserialize, Queryable)]
 pub struct User {
@@ -11,6 +12,23 @@ pub struct User {
     pub email: String,
     pub created_at: NaiveDateTime,
     pub modified_at: NaiveDateTime,
+    pub active_workspace_id: Option<i32>,
+    /// Only whether one is set is ever shown.
+    #[serde(
+        rename(serialize = "has_password"),
+        serialize_with = "serialize_is_set",
+        skip_deserializing
+    )]
+    pub password_hash: Option<String>,
+    #[serde(default)]
+    pub email_verified_at: Option<NaiveDateTime>,
+}
+
+fn serialize_is_set<S: Serializer>(
+    value: &Option<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_bool(value.is_some())
 }
 
 #[derive(Serialize, Deserialize, Insertable, Debug)]
@@ -44,9 +62,24 @@ pub struct UpdateUserLastName {
     pub modified_at: NaiveDateTime,
 }
 
+/// `current_password` has to match before the address changes. Accounts
+/// without a password set one through the reset mail sent to the old address
+/// first.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateUserEmail {
     pub id: i32,
     pub email: String,
+    pub current_password: String,
     pub modified_at: NaiveDateTime,
-}
\ No newline at end of file
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangePassword {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+impl ChangePassword {
+    pub fn is_valid(&self) -> bool {
+        (MIN_PASSWORD_CHARS..=MAX_PASSWORD_CHARS).contains(&self.new_password.chars().count())
+    }
+}
@@ -0,0 +1,78 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::{
+    prelude::{AsChangeset, Insertable},
+    Queryable,
+};
+use serde::{Deserialize, Serialize};
+
+/// Fires for events visible to `user_id`; with a `list_id`, only for events
+/// touching that list. `event_filter` is a comma-separated list of event
+/// kinds, where `*` matches everything and `task.*` a whole family.
+#[derive(Debug, Serialize, Deserialize, Queryable)]
+pub struct Webhook {
+    pub id: i32,
+    pub user_id: i32,
+    pub list_id: Option<i32>,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub event_filter: String,
+    pub enabled: bool,
+    pub consecutive_failures: i32,
+    pub disabled_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+}
+
+impl Webhook {
+    pub fn matches(&self, kind: &str) -> bool {
+        self.event_filter
+            .split(',')
+            .map(str::trim)
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => kind.starts_with(prefix),
+                None => pattern == kind,
+            })
+    }
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = webhooks)]
+pub struct NewWebhook {
+    pub user_id: i32,
+    pub list_id: Option<i32>,
+    pub url: String,
+    pub secret: String,
+    pub event_filter: String,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InputWebhook {
+    pub url: String,
+    pub list_id: Option<i32>,
+    pub event_filter: Option<String>,
+}
+
+/// Re-enabling a webhook also clears its failure count.
+#[derive(Debug, Default, Serialize, Deserialize, AsChangeset)]
+#[diesel(table_name = webhooks)]
+pub struct UpdateWebhook {
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub event_filter: Option<String>,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+/// Returned once on creation; receivers need the secret to verify
+/// signatures, and it isn't shown again.
+#[derive(Debug, Serialize)]
+pub struct CreatedWebhook {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    pub secret: String,
+}
@@ -0,0 +1,38 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::{prelude::Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Queryable)]
+pub struct Workspace {
+    pub id: i32,
+    pub name: String,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+    pub require_two_factor: bool,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = workspaces)]
+pub struct NewWorkspace {
+    pub name: String,
+    pub created_at: NaiveDateTime,
+    pub modified_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InputWorkspace {
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateWorkspaceName {
+    pub name: String,
+}
+
+/// Members without two-factor enabled lose access to the workspace's lists
+/// until they enroll.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateWorkspaceTwoFactor {
+    pub require_two_factor: bool,
+}
@@ -0,0 +1,86 @@
+use crate::schema::*;
+use chrono::NaiveDateTime;
+use diesel::deserialize::{self, FromSql, FromSqlRow};
+use diesel::expression::AsExpression;
+use diesel::pg::{Pg, PgValue};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::Text;
+use diesel::{prelude::Insertable, Queryable};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    AsExpression,
+    FromSqlRow,
+)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "lowercase")]
+pub enum WorkspaceRole {
+    Member,
+    Admin,
+}
+
+impl WorkspaceRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WorkspaceRole::Member => "member",
+            WorkspaceRole::Admin => "admin",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for WorkspaceRole {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for WorkspaceRole {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match bytes.as_bytes() {
+            b"member" => Ok(WorkspaceRole::Member),
+            b"admin" => Ok(WorkspaceRole::Admin),
+            other => {
+                Err(format!("Unknown workspace role: {}", String::from_utf8_lossy(other)).into())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Queryable)]
+pub struct WorkspaceMember {
+    pub workspace_id: i32,
+    pub user_id: i32,
+    pub role: WorkspaceRole,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Insertable, Debug)]
+#[diesel(table_name = workspace_members)]
+pub struct NewWorkspaceMember {
+    pub workspace_id: i32,
+    pub user_id: i32,
+    pub role: WorkspaceRole,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InputWorkspaceMember {
+    pub user_id: i32,
+    pub role: WorkspaceRole,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateWorkspaceMemberRole {
+    pub role: WorkspaceRole,
+}
@@ -0,0 +1,432 @@
+use crate::models::account_archive::*;
+use crate::models::attachment::Attachment;
+use crate::models::list_member::ListRole;
+use crate::models::reminder_preference::{ReminderPreference, MAX_WINDOW_HOURS, MIN_WINDOW_HOURS};
+use crate::models::tailored_response::*;
+use crate::models::task_reminder::{NewTaskReminder, TaskReminder};
+use crate::models::task_tag::{TaskTag, MAX_TAG_CHARS};
+use crate::models::todo_list::{InputTodoList, TodoList};
+use crate::models::todo_task::{InputTodoTask, TodoTask};
+use crate::models::user::User;
+use crate::models::workspace_member::WorkspaceRole;
+use crate::routes::todo_list::db_insert_list;
+use crate::routes::todo_task::db_insert_task;
+use crate::schema::{
+    attachments, list_members, reminder_preferences, task_reminders, task_tags, todolists,
+    todotasks, users, workspace_members, workspaces,
+};
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::access::{db_get_active_workspace, AccessError};
+use crate::utils::database::connection::Pool;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web::{self};
+use actix_web::{get, post, HttpResponse};
+use diesel::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+enum ArchiveError {
+    Access(AccessError),
+    Invalid(Vec<String>),
+}
+
+impl From<AccessError> for ArchiveError {
+    fn from(e: AccessError) -> Self {
+        ArchiveError::Access(e)
+    }
+}
+
+/// The lists the user owns, with every task in them, whoever created it.
+fn db_export_account(
+    pool: web::Data<Pool>,
+    user_id: i32,
+) -> Result<AccountArchive, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    let user = users::table.find(user_id).first::<User>(&mut conn)?;
+    let preference = reminder_preferences::table
+        .find(user_id)
+        .first::<ReminderPreference>(&mut conn)
+        .optional()?;
+    let memberships = workspace_members::table
+        .inner_join(workspaces::table)
+        .filter(workspace_members::user_id.eq(user_id))
+        .order(workspaces::id.asc())
+        .select((workspaces::id, workspaces::name, workspace_members::role))
+        .load::<(i32, String, WorkspaceRole)>(&mut conn)?;
+
+    let owned: Vec<i32> = list_members::table
+        .filter(list_members::user_id.eq(user_id))
+        .filter(list_members::role.eq(ListRole::Owner.as_str()))
+        .select(list_members::list_id)
+        .load(&mut conn)?;
+    let lists = todolists::table
+        .filter(todolists::id.eq_any(&owned))
+        .order(todolists::id.asc())
+        .load::<TodoList>(&mut conn)?;
+    let tasks = todotasks::table
+        .filter(todotasks::todolist_id.eq_any(&owned))
+        .order(todotasks::id.asc())
+        .load::<TodoTask>(&mut conn)?;
+    let task_ids: Vec<i32> = tasks.iter().map(|task| task.id).collect();
+    let mut tags: HashMap<i32, Vec<String>> = HashMap::new();
+    for tag in task_tags::table
+        .filter(task_tags::task_id.eq_any(&task_ids))
+        .order(task_tags::tag.asc())
+        .load::<TaskTag>(&mut conn)?
+    {
+        tags.entry(tag.task_id).or_default().push(tag.tag);
+    }
+    let reminders = task_reminders::table
+        .filter(task_reminders::user_id.eq(user_id))
+        .filter(task_reminders::task_id.eq_any(&task_ids))
+        .order(task_reminders::id.asc())
+        .load::<TaskReminder>(&mut conn)?;
+    let files = attachments::table
+        .filter(attachments::task_id.eq_any(&task_ids))
+        .order(attachments::id.asc())
+        .load::<Attachment>(&mut conn)?;
+
+    Ok(AccountArchive {
+        format: String::from(ARCHIVE_FORMAT),
+        version: ARCHIVE_VERSION,
+        exported_at: chrono::Local::now().naive_local(),
+        user: ArchivedUser {
+            id: user.id,
+            first_name: user.first_name,
+            last_name: user.last_name,
+            email: user.email,
+            created_at: user.created_at,
+        },
+        reminder_preferences: preference.map(|preference| ArchivedReminderPreference {
+            email_enabled: preference.email_enabled,
+            window_hours: preference.window_hours,
+            digest: preference.digest,
+        }),
+        workspaces: memberships
+            .into_iter()
+            .map(|(id, name, role)| ArchivedWorkspace { id, name, role })
+            .collect(),
+        lists: lists
+            .into_iter()
+            .map(|list| ArchivedList {
+                id: list.id,
+                name: list.name,
+                description: list.description,
+                created_at: list.created_at,
+                modified_at: list.modified_at,
+            })
+            .collect(),
+        tasks: tasks
+            .into_iter()
+            .map(|task| ArchivedTask {
+                tags: tags.remove(&task.id).unwrap_or_default(),
+                id: task.id,
+                list_id: task.todolist_id,
+                name: task.name,
+                description: task.description,
+                parent_task_id: task.parent_task_id,
+                due_date: task.due_date,
+                priority: task.priority,
+                completed_at: task.completed_at,
+                created_at: task.created_at,
+                modified_at: task.modified_at,
+            })
+            .collect(),
+        reminders: reminders
+            .into_iter()
+            .map(|reminder| ArchivedReminder {
+                task_id: reminder.task_id,
+                remind_at: reminder.remind_at,
+                minutes_before_due: reminder.minutes_before_due,
+                channel: reminder.channel,
+                snoozed_until: reminder.snoozed_until,
+                sent_at: reminder.sent_at,
+            })
+            .collect(),
+        attachments: files
+            .into_iter()
+            .map(|file| ArchivedAttachment {
+                task_id: file.task_id,
+                file_name: file.file_name,
+                content_type: file.content_type,
+                size_bytes: file.size_bytes,
+                created_at: file.created_at,
+            })
+            .collect(),
+    })
+}
+
+/// Everything wrong with the archive, so it can be fixed in one go.
+fn archive_problems(archive: &AccountArchive) -> Vec<String> {
+    let mut problems = Vec::new();
+    if archive.format != ARCHIVE_FORMAT || archive.version != ARCHIVE_VERSION {
+        problems.push(format!(
+            "expected a {} archive of version {}",
+            ARCHIVE_FORMAT, ARCHIVE_VERSION
+        ));
+        return problems;
+    }
+    if let Some(preference) = &archive.reminder_preferences {
+        if !(MIN_WINDOW_HOURS..=MAX_WINDOW_HOURS).contains(&preference.window_hours) {
+            problems.push(String::from("reminder window_hours is out of range"));
+        }
+    }
+    let mut list_ids = HashSet::new();
+    for list in &archive.lists {
+        if !list_ids.insert(list.id) {
+            problems.push(format!("list {} appears twice", list.id));
+        }
+        if list.name.trim().is_empty() || list.name.chars().count() > 255 {
+            problems.push(format!("list {} has an invalid name", list.id));
+        }
+    }
+    let mut task_ids = HashSet::new();
+    for task in &archive.tasks {
+        if !task_ids.insert(task.id) {
+            problems.push(format!("task {} appears twice", task.id));
+        }
+        if !list_ids.contains(&task.list_id) {
+            problems.push(format!(
+                "task {} is in unknown list {}",
+                task.id, task.list_id
+            ));
+        }
+        if let Err((_, message)) = as_input(task, 0, None).validate() {
+            problems.push(format!("task {}: {}", task.id, message));
+        }
+        if task
+            .tags
+            .iter()
+            .any(|tag| tag.chars().count() > MAX_TAG_CHARS)
+        {
+            problems.push(format!(
+                "task {} has a tag longer than 64 characters",
+                task.id
+            ));
+        }
+    }
+    for reminder in &archive.reminders {
+        if !task_ids.contains(&reminder.task_id) {
+            problems.push(format!("reminder on unknown task {}", reminder.task_id));
+        }
+        let valid_trigger = match (reminder.remind_at, reminder.minutes_before_due) {
+            (Some(_), None) => true,
+            (None, Some(minutes)) => minutes >= 0,
+            _ => false,
+        };
+        if !valid_trigger {
+            problems.push(format!(
+                "reminder on task {} needs exactly one trigger",
+                reminder.task_id
+            ));
+        }
+    }
+    problems
+}
+
+fn as_input(task: &ArchivedTask, list_id: i32, parent_task_id: Option<i32>) -> InputTodoTask {
+    InputTodoTask {
+        todolist_id: list_id,
+        name: task.name.clone(),
+        description: task.description.clone(),
+        parent_task_id,
+        due_date: task.due_date,
+        priority: task.priority.clone(),
+        completed_at: task.completed_at,
+    }
+}
+
+/// Orders tasks so parents come before their subtasks. Parents missing from
+/// the archive, in another list, or caught in a cycle, are dropped.
+fn parents_first(tasks: &[ArchivedTask]) -> Vec<(&ArchivedTask, Option<i32>)> {
+    let by_id: HashMap<i32, &ArchivedTask> = tasks.iter().map(|task| (task.id, task)).collect();
+    let mut ordered = Vec::with_capacity(tasks.len());
+    let mut placed: HashSet<i32> = HashSet::new();
+    for task in tasks {
+        let mut chain = vec![task];
+        let mut seen: HashSet<i32> = HashSet::from([task.id]);
+        while let Some(parent) = chain
+            .last()
+            .and_then(|task| task.parent_task_id)
+            .filter(|parent| !placed.contains(parent))
+            .and_then(|parent| by_id.get(&parent))
+            .filter(|parent| parent.list_id == task.list_id)
+        {
+            if !seen.insert(parent.id) {
+                break;
+            }
+            chain.push(parent);
+        }
+        for task in chain.into_iter().rev() {
+            if !placed.insert(task.id) {
+                continue;
+            }
+            let parent = task
+                .parent_task_id
+                .filter(|parent| placed.contains(parent) && by_id[parent].list_id == task.list_id);
+            ordered.push((task, parent));
+        }
+    }
+    ordered
+}
+
+/// Restores an archive into the caller's account, normally a fresh one on
+/// another deployment. Lists land in the active workspace and get new ids;
+/// the summary says which.
+fn db_import_account(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    archive: AccountArchive,
+) -> Result<AccountImportSummary, ArchiveError> {
+    let problems = archive_problems(&archive);
+    if !problems.is_empty() {
+        return Err(ArchiveError::Invalid(problems));
+    }
+    let mut conn = pool.get().unwrap();
+    db_get_active_workspace(&mut conn, caller.id)?;
+    let summary = conn.transaction(|conn| {
+        let now = chrono::Local::now().naive_local();
+        if let Some(preference) = &archive.reminder_preferences {
+            let restored = ReminderPreference {
+                user_id: caller.id,
+                email_enabled: preference.email_enabled,
+                window_hours: preference.window_hours,
+                digest: preference.digest,
+                modified_at: now,
+            };
+            diesel::insert_into(reminder_preferences::table)
+                .values(&restored)
+                .on_conflict(reminder_preferences::user_id)
+                .do_update()
+                .set(&restored)
+                .execute(conn)?;
+        }
+
+        let mut list_ids = BTreeMap::new();
+        for list in &archive.lists {
+            let input = InputTodoList {
+                name: list.name.clone(),
+                description: Some(list.description.clone()),
+            };
+            let created = db_insert_list(conn, caller, &input)?;
+            diesel::update(todolists::table.find(created.id))
+                .set(todolists::created_at.eq(list.created_at))
+                .execute(conn)?;
+            list_ids.insert(list.id, created.id);
+        }
+
+        let mut task_ids = BTreeMap::new();
+        for (task, parent) in parents_first(&archive.tasks) {
+            let input = as_input(
+                task,
+                list_ids[&task.list_id],
+                parent.map(|parent| task_ids[&parent]),
+            );
+            let created = db_insert_task(conn, caller, &input)?;
+            diesel::update(todotasks::table.find(created.id))
+                .set(todotasks::created_at.eq(task.created_at))
+                .execute(conn)?;
+            let tags: Vec<TaskTag> = task
+                .tags
+                .iter()
+                .map(|tag| TaskTag {
+                    task_id: created.id,
+                    tag: tag.clone(),
+                })
+                .collect();
+            diesel::insert_into(task_tags::table)
+                .values(&tags)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+            task_ids.insert(task.id, created.id);
+        }
+
+        for reminder in &archive.reminders {
+            let new_reminder = NewTaskReminder {
+                task_id: task_ids[&reminder.task_id],
+                user_id: caller.id,
+                remind_at: reminder.remind_at,
+                minutes_before_due: reminder.minutes_before_due,
+                channel: reminder.channel,
+                created_at: now,
+                modified_at: now,
+            };
+            let reminder_id: i32 = diesel::insert_into(task_reminders::table)
+                .values(&new_reminder)
+                .returning(task_reminders::id)
+                .get_result(conn)?;
+            // restored as they were, so fired reminders don't fire again
+            diesel::update(task_reminders::table.find(reminder_id))
+                .set((
+                    task_reminders::snoozed_until.eq(reminder.snoozed_until),
+                    task_reminders::sent_at.eq(reminder.sent_at),
+                ))
+                .execute(conn)?;
+        }
+
+        Ok::<_, AccessError>(AccountImportSummary {
+            list_ids,
+            task_ids,
+            reminders: archive.reminders.len(),
+            skipped_attachments: archive.attachments.len(),
+        })
+    })?;
+    Ok(summary)
+}
+
+#[get("/users/{id}/export")]
+pub async fn export_account(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    user_id: web::Path<i32>,
+) -> HttpResponse {
+    let user_id = user_id.into_inner();
+    if user_id != caller.id {
+        return throw_response_forbidden();
+    }
+    match web::block(move || db_export_account(db, user_id)).await {
+        Ok(Ok(archive)) => match serde_json::to_value(archive) {
+            Ok(response_body) => HttpResponse::Ok()
+                .insert_header(ContentDisposition {
+                    disposition: DispositionType::Attachment,
+                    parameters: vec![DispositionParam::Filename(format!(
+                        "todoer-account-{}.json",
+                        user_id
+                    ))],
+                })
+                .json(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize account archive: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e.into()),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/users/{id}/import")]
+pub async fn import_account(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    user_id: web::Path<i32>,
+    archive: web::Json<AccountArchive>,
+) -> HttpResponse {
+    if user_id.into_inner() != caller.id {
+        return throw_response_forbidden();
+    }
+    let archive = archive.into_inner();
+    match web::block(move || db_import_account(db, caller, archive)).await {
+        Ok(Ok(summary)) => match serde_json::to_value(summary) {
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize account import summary: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(ArchiveError::Invalid(problems))) => {
+            throw_response_unprocessable_entity(serde_json::json!({ "errors": problems }))
+        }
+        Ok(Err(ArchiveError::Access(e))) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
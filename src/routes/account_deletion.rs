@@ -0,0 +1,187 @@
+use crate::models::account_deletion::*;
+use crate::models::tailored_response::*;
+use crate::schema::account_deletions::dsl::*;
+use crate::schema::users;
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::config;
+use crate::utils::database::access::AccessError;
+use crate::utils::database::connection::Pool;
+use crate::utils::token;
+use actix_web::web::{self};
+use actix_web::{delete, get, post, HttpResponse};
+use diesel::prelude::*;
+use diesel::{delete, insert_into};
+
+/// `None` when `confirm_email` doesn't match the account's address.
+pub(crate) fn request_account_deletion(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: i32,
+    item: InputAccountDeletion,
+) -> Result<Option<CreatedAccountDeletion>, AccessError> {
+    if caller.id != target_user_id {
+        return Err(AccessError::Forbidden);
+    }
+    let mut conn = pool.get().unwrap();
+    let address = users::table
+        .find(target_user_id)
+        .select(users::email)
+        .first::<String>(&mut conn)?;
+    if !address
+        .trim()
+        .eq_ignore_ascii_case(item.confirm_email.trim())
+    {
+        return Ok(None);
+    }
+    let plain_token = token::generate_token();
+    let now = chrono::Local::now().naive_local();
+    let new_deletion = NewAccountDeletion {
+        user_id: target_user_id,
+        token_hash: token::hash_token(&plain_token),
+        owned_lists: item.owned_lists,
+        requested_at: now,
+        scheduled_for: now + chrono::Days::new(config::get_account_deletion_grace_days() as u64),
+    };
+    let deletion = insert_into(account_deletions)
+        .values(&new_deletion)
+        .on_conflict(user_id)
+        .do_update()
+        .set(&new_deletion)
+        .returning(AccountDeletion::as_returning())
+        .get_result(&mut conn)?;
+    Ok(Some(CreatedAccountDeletion {
+        deletion,
+        receipt_path: format!("/account-deletions/{}", plain_token),
+        receipt_token: plain_token,
+    }))
+}
+
+fn db_get_account_deletion(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: i32,
+) -> Result<AccountDeletion, AccessError> {
+    if caller.id != target_user_id {
+        return Err(AccessError::Forbidden);
+    }
+    let mut conn = pool.get().unwrap();
+    let deletion = account_deletions
+        .filter(user_id.eq(target_user_id))
+        .select(AccountDeletion::as_select())
+        .first(&mut conn)?;
+    Ok(deletion)
+}
+
+fn cancel_account_deletion(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: i32,
+) -> Result<usize, AccessError> {
+    if caller.id != target_user_id {
+        return Err(AccessError::Forbidden);
+    }
+    let mut conn = pool.get().unwrap();
+    let deletion = delete(account_deletions.filter(user_id.eq(target_user_id)))
+        .filter(completed_at.is_null())
+        .execute(&mut conn)?;
+    Ok(deletion)
+}
+
+/// Unknown tokens get `NotFound`.
+fn db_get_deletion_receipt(
+    pool: web::Data<Pool>,
+    plain_token: String,
+) -> Result<AccountDeletion, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    account_deletions
+        .filter(token_hash.eq(token::hash_token(&plain_token)))
+        .select(AccountDeletion::as_select())
+        .first(&mut conn)
+}
+
+/// Schedules the caller's account for deletion after the grace period.
+#[post("/users/{id}/deletion")]
+pub async fn request_deletion(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: web::Path<i32>,
+    item: web::Json<InputAccountDeletion>,
+) -> HttpResponse {
+    let item = item.into_inner();
+    match web::block(move || {
+        request_account_deletion(db, caller, target_user_id.into_inner(), item)
+    })
+    .await
+    {
+        Ok(Ok(Some(created))) => match serde_json::to_value(created) {
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to request account deletion: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Ok(None)) => throw_response_bad_request(),
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[get("/users/{id}/deletion")]
+pub async fn get_deletion(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || db_get_account_deletion(db, caller, target_user_id.into_inner())).await
+    {
+        Ok(Ok(deletion)) => match serde_json::to_value(deletion) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize account deletion: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[delete("/users/{id}/deletion")]
+pub async fn cancel_deletion(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || cancel_account_deletion(db, caller, target_user_id.into_inner())).await
+    {
+        Ok(Ok(deletion)) => match serde_json::to_value(deletion) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to cancel account deletion: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+/// Needs no authentication: once the account is gone, the receipt token is
+/// the only thing that can fetch it.
+#[get("/account-deletions/{token}")]
+pub async fn get_deletion_receipt(
+    db: web::Data<Pool>,
+    plain_token: web::Path<String>,
+) -> HttpResponse {
+    match web::block(move || db_get_deletion_receipt(db, plain_token.into_inner())).await {
+        Ok(Ok(deletion)) => match serde_json::to_value(deletion) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize deletion receipt: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e.into()),
+        Err(_) => throw_response_error(),
+    }
+}
@@ -0,0 +1,146 @@
+use crate::models::api_key::*;
+use crate::models::tailored_response::*;
+use crate::schema::api_keys;
+use crate::utils::api_keys::generate_api_key;
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::access::AccessError;
+use crate::utils::database::connection::Pool;
+use crate::utils::token;
+use actix_web::web::{self};
+use actix_web::{delete, get, post, HttpResponse};
+use diesel::prelude::*;
+
+/// Keys are managed with a session only, so a leaked key can't mint itself
+/// broader ones.
+fn get_user_api_keys(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: i32,
+) -> Result<Vec<ApiKey>, AccessError> {
+    if caller.id != target_user_id {
+        return Err(AccessError::Forbidden);
+    }
+    let mut conn = pool.get().unwrap();
+    let keys = api_keys::table
+        .filter(api_keys::user_id.eq(target_user_id))
+        .order(api_keys::id.asc())
+        .select(ApiKey::as_select())
+        .load(&mut conn)?;
+    Ok(keys)
+}
+
+fn add_user_api_key(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: i32,
+    item: InputApiKey,
+) -> Result<CreatedApiKey, AccessError> {
+    if caller.id != target_user_id {
+        return Err(AccessError::Forbidden);
+    }
+    let mut conn = pool.get().unwrap();
+    let (key, prefix) = generate_api_key();
+    let mut scopes: Vec<ApiScope> = Vec::new();
+    for scope in item.scopes.0 {
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    let api_key = diesel::insert_into(api_keys::table)
+        .values(NewApiKey {
+            user_id: target_user_id,
+            name: item.name.trim().to_string(),
+            prefix,
+            key_hash: token::hash_token(&key),
+            scopes: ApiScopes(scopes),
+            expires_at: item.expires_at,
+            created_at: chrono::Local::now().naive_local(),
+        })
+        .returning(ApiKey::as_returning())
+        .get_result(&mut conn)?;
+    Ok(CreatedApiKey { api_key, key })
+}
+
+fn delete_user_api_key(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: i32,
+    key_id: i32,
+) -> Result<usize, AccessError> {
+    if caller.id != target_user_id {
+        return Err(AccessError::Forbidden);
+    }
+    let mut conn = pool.get().unwrap();
+    let deletion = diesel::delete(
+        api_keys::table
+            .filter(api_keys::id.eq(key_id))
+            .filter(api_keys::user_id.eq(target_user_id)),
+    )
+    .execute(&mut conn)?;
+    Ok(deletion)
+}
+
+#[get("/users/{id}/api-keys")]
+pub async fn get_api_keys(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || get_user_api_keys(db, caller, target_user_id.into_inner())).await {
+        Ok(Ok(keys)) => match serde_json::to_value(keys) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to list API keys: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/users/{id}/api-keys")]
+pub async fn create_api_key(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: web::Path<i32>,
+    item: web::Json<InputApiKey>,
+) -> HttpResponse {
+    let item = item.into_inner();
+    if !item.is_valid(chrono::Local::now().naive_local()) {
+        return throw_response_bad_request();
+    }
+    match web::block(move || add_user_api_key(db, caller, target_user_id.into_inner(), item)).await
+    {
+        Ok(Ok(created)) => match serde_json::to_value(created) {
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to create API key: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[delete("/users/{id}/api-keys/{key_id}")]
+pub async fn delete_api_key(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    path: web::Path<(i32, i32)>,
+) -> HttpResponse {
+    let (target_user_id, key_id) = path.into_inner();
+    match web::block(move || delete_user_api_key(db, caller, target_user_id, key_id)).await {
+        Ok(Ok(0)) => throw_response_not_found(),
+        Ok(Ok(deletion)) => match serde_json::to_value(deletion) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to delete API key: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
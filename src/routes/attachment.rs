@@ -0,0 +1,405 @@
+use crate::models::attachment::*;
+use crate::models::list_member::ListRole;
+use crate::models::tailored_response::*;
+use crate::schema::attachments::dsl::*;
+use crate::schema::todotasks;
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::access::{db_require_task_role, AccessError};
+use crate::utils::{config, database::connection::Pool, storage::AttachmentStorage};
+use actix_multipart::Multipart;
+use actix_web::http::header::{
+    Charset, ContentDisposition, DispositionParam, DispositionType, ExtendedValue,
+};
+use actix_web::web::{self};
+use actix_web::{delete, get, post, HttpResponse};
+use derive_more::{Display, From};
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use diesel::{delete, insert_into};
+use futures_util::TryStreamExt;
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Display, From)]
+enum AttachmentError {
+    Access(AccessError),
+    Storage(std::io::Error),
+}
+
+impl From<diesel::result::Error> for AttachmentError {
+    fn from(e: diesel::result::Error) -> Self {
+        AttachmentError::Access(e.into())
+    }
+}
+
+enum UploadError {
+    MissingFile,
+    TooLarge,
+    UnsupportedType,
+    Multipart(actix_multipart::MultipartError),
+}
+
+struct Upload {
+    file_name: String,
+    content_type: String,
+    bytes: Vec<u8>,
+}
+
+/// Detects the real type of the well-known upload formats from their magic
+/// bytes, so a client cannot smuggle arbitrary content past the MIME filter by
+/// lying in the part's `Content-Type`.
+fn sniff_content_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if bytes.starts_with(b"%PDF-") {
+        Some("application/pdf")
+    } else {
+        None
+    }
+}
+
+fn sanitize_file_name(raw: &str) -> String {
+    let base = raw.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    let cleaned: String = base.chars().filter(|c| !c.is_control()).take(255).collect();
+    if cleaned.is_empty() {
+        String::from("attachment")
+    } else {
+        cleaned
+    }
+}
+
+async fn read_upload(payload: &mut Multipart) -> Result<Upload, UploadError> {
+    let max_bytes = config::get_attachments_max_bytes();
+    while let Some(mut field) = payload.try_next().await.map_err(UploadError::Multipart)? {
+        if field.name() != Some("file") {
+            continue;
+        }
+        let raw_name = field
+            .content_disposition()
+            .and_then(|disposition| disposition.get_filename())
+            .unwrap_or_default()
+            .to_owned();
+        let declared_type = field
+            .content_type()
+            .map(|mime| mime.essence_str().to_lowercase())
+            .unwrap_or_default();
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.try_next().await.map_err(UploadError::Multipart)? {
+            if bytes.len() + chunk.len() > max_bytes {
+                return Err(UploadError::TooLarge);
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        let effective_type = sniff_content_type(&bytes)
+            .map(String::from)
+            .unwrap_or(declared_type);
+        if !config::get_attachments_allowed_types().contains(&effective_type) {
+            return Err(UploadError::UnsupportedType);
+        }
+
+        return Ok(Upload {
+            file_name: sanitize_file_name(&raw_name),
+            content_type: effective_type,
+            bytes,
+        });
+    }
+    Err(UploadError::MissingFile)
+}
+
+/// Serializes writers and deleters of the same blob for the rest of the
+/// transaction, so a delete can't remove a blob an upload just reused.
+fn lock_content_hash(conn: &mut PgConnection, hash: &str) -> Result<(), diesel::result::Error> {
+    diesel::sql_query("SELECT pg_advisory_xact_lock(hashtext($1))")
+        .bind::<Text, _>(hash)
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Content hashes of the attachments on `task_ids`, taken before the tasks
+/// are deleted so that their blobs can be released once the rows are gone.
+pub(crate) fn db_task_attachment_hashes(
+    conn: &mut PgConnection,
+    task_ids: &[i32],
+) -> Result<Vec<String>, diesel::result::Error> {
+    attachments
+        .filter(task_id.eq_any(task_ids))
+        .select(content_hash)
+        .distinct()
+        .load(conn)
+}
+
+pub(crate) fn db_list_attachment_hashes(
+    conn: &mut PgConnection,
+    list_id: i32,
+) -> Result<Vec<String>, diesel::result::Error> {
+    let list_tasks: Vec<i32> = todotasks::table
+        .filter(todotasks::todolist_id.eq(list_id))
+        .select(todotasks::id)
+        .load(conn)?;
+    db_task_attachment_hashes(conn, &list_tasks)
+}
+
+/// Deletes the blobs among `hashes` that no attachment refers to any more.
+/// Call it after the deleting transaction has committed; failures are only
+/// logged, since the rows are gone either way and a leftover blob is
+/// harmless.
+pub(crate) fn db_release_attachment_blobs(
+    conn: &mut PgConnection,
+    storage: &dyn AttachmentStorage,
+    hashes: &[String],
+) {
+    for hash in hashes {
+        let released = conn.transaction(|conn| {
+            lock_content_hash(conn, hash)?;
+            let remaining: i64 = attachments
+                .filter(content_hash.eq(hash))
+                .count()
+                .get_result(conn)?;
+            if remaining == 0 {
+                storage.delete(hash)?;
+            }
+            Ok::<_, AttachmentError>(())
+        });
+        if let Err(e) = released {
+            eprintln!("Failed to release attachment blob {}: {}", hash, e);
+        }
+    }
+}
+
+fn check_task_role(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: i32,
+    minimum: ListRole,
+) -> Result<(), AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_task_role(&mut conn, target_task_id, caller.id, minimum)?;
+    Ok(())
+}
+
+fn add_single_attachment(
+    pool: web::Data<Pool>,
+    storage: web::Data<dyn AttachmentStorage>,
+    target_task_id: i32,
+    upload: Upload,
+) -> Result<Attachment, AttachmentError> {
+    let mut conn = pool.get().unwrap();
+    let hash = hex::encode(Sha256::digest(&upload.bytes));
+    conn.transaction(|conn| {
+        lock_content_hash(conn, &hash)?;
+        if !storage.exists(&hash)? {
+            storage.put(&hash, &upload.bytes)?;
+        }
+        let new_attachment = NewAttachment {
+            task_id: target_task_id,
+            file_name: upload.file_name,
+            content_type: upload.content_type,
+            size_bytes: upload.bytes.len() as i64,
+            content_hash: hash.clone(),
+            created_at: chrono::Local::now().naive_local(),
+        };
+        let res = insert_into(attachments)
+            .values(&new_attachment)
+            .get_result(conn)?;
+        Ok(res)
+    })
+}
+
+fn get_task_attachments(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: i32,
+) -> Result<Vec<Attachment>, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_task_role(&mut conn, target_task_id, caller.id, ListRole::Viewer)?;
+    let items = attachments
+        .filter(task_id.eq(target_task_id))
+        .order(created_at.asc())
+        .load::<Attachment>(&mut conn)?;
+    Ok(items)
+}
+
+fn db_get_attachment(
+    pool: web::Data<Pool>,
+    storage: web::Data<dyn AttachmentStorage>,
+    caller: AuthenticatedUser,
+    target_task_id: i32,
+    attachment_id: i32,
+) -> Result<(Attachment, Vec<u8>), AttachmentError> {
+    let mut conn = pool.get().unwrap();
+    db_require_task_role(&mut conn, target_task_id, caller.id, ListRole::Viewer)?;
+    let attachment = attachments
+        .filter(task_id.eq(target_task_id))
+        .find(attachment_id)
+        .get_result::<Attachment>(&mut conn)?;
+    let bytes = storage.get(&attachment.content_hash)?;
+    Ok((attachment, bytes))
+}
+
+fn delete_single_attachment(
+    pool: web::Data<Pool>,
+    storage: web::Data<dyn AttachmentStorage>,
+    caller: AuthenticatedUser,
+    target_task_id: i32,
+    attachment_id: i32,
+) -> Result<usize, AttachmentError> {
+    let mut conn = pool.get().unwrap();
+    db_require_task_role(&mut conn, target_task_id, caller.id, ListRole::Editor)?;
+    conn.transaction(|conn| {
+        let attachment = attachments
+            .filter(task_id.eq(target_task_id))
+            .find(attachment_id)
+            .get_result::<Attachment>(conn)?;
+        lock_content_hash(conn, &attachment.content_hash)?;
+        let deletion = delete(attachments.find(attachment.id)).execute(conn)?;
+        let remaining: i64 = attachments
+            .filter(content_hash.eq(&attachment.content_hash))
+            .count()
+            .get_result(conn)?;
+        if remaining == 0 {
+            storage.delete(&attachment.content_hash)?;
+        }
+        Ok(deletion)
+    })
+}
+
+fn content_disposition_for(raw_name: &str) -> ContentDisposition {
+    let ascii_name: String = raw_name
+        .chars()
+        .map(|c| if c.is_ascii() && c != '"' { c } else { '_' })
+        .collect();
+    ContentDisposition {
+        disposition: DispositionType::Attachment,
+        parameters: vec![
+            DispositionParam::Filename(ascii_name),
+            DispositionParam::FilenameExt(ExtendedValue {
+                charset: Charset::Ext(String::from("UTF-8")),
+                language_tag: None,
+                value: raw_name.as_bytes().to_vec(),
+            }),
+        ],
+    }
+}
+
+#[post("/tasks/{id}/attachments")]
+pub async fn upload_attachment(
+    db: web::Data<Pool>,
+    storage: web::Data<dyn AttachmentStorage>,
+    caller: AuthenticatedUser,
+    target_task_id: web::Path<i32>,
+    mut payload: Multipart,
+) -> HttpResponse {
+    let target_task_id = target_task_id.into_inner();
+    let check_db = db.clone();
+    match web::block(move || check_task_role(check_db, caller, target_task_id, ListRole::Editor))
+        .await
+    {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => return throw_response_access_error(e),
+        Err(_) => return throw_response_error(),
+    }
+    let upload = match read_upload(&mut payload).await {
+        Ok(upload) => upload,
+        Err(UploadError::MissingFile) => return throw_response_bad_request(),
+        Err(UploadError::TooLarge) => return throw_response_payload_too_large(),
+        Err(UploadError::UnsupportedType) => return throw_response_unsupported_media_type(),
+        Err(UploadError::Multipart(e)) => {
+            eprintln!("Failed to read attachment upload: {}", e);
+            return throw_response_bad_request();
+        }
+    };
+    match web::block(move || add_single_attachment(db, storage, target_task_id, upload)).await {
+        Ok(Ok(attachment)) => match serde_json::to_value(attachment) {
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize attachment: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(AttachmentError::Access(e))) => throw_response_access_error(e),
+        Ok(Err(e)) => {
+            eprintln!("Failed to store attachment: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[get("/tasks/{id}/attachments")]
+pub async fn get_attachments(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || get_task_attachments(db, caller, target_task_id.into_inner())).await {
+        Ok(Ok(items)) => match serde_json::to_value(items) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize attachments: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[get("/tasks/{id}/attachments/{attachment_id}")]
+pub async fn download_attachment(
+    db: web::Data<Pool>,
+    storage: web::Data<dyn AttachmentStorage>,
+    caller: AuthenticatedUser,
+    path: web::Path<(i32, i32)>,
+) -> HttpResponse {
+    let (target_task_id, attachment_id) = path.into_inner();
+    match web::block(move || db_get_attachment(db, storage, caller, target_task_id, attachment_id))
+        .await
+    {
+        Ok(Ok((attachment, bytes))) => HttpResponse::Ok()
+            .content_type(attachment.content_type.as_str())
+            .insert_header(content_disposition_for(&attachment.file_name))
+            .body(bytes),
+        Ok(Err(AttachmentError::Access(e))) => throw_response_access_error(e),
+        Ok(Err(e)) => {
+            eprintln!("Failed to read attachment: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[delete("/tasks/{id}/attachments/{attachment_id}")]
+pub async fn delete_attachment(
+    db: web::Data<Pool>,
+    storage: web::Data<dyn AttachmentStorage>,
+    caller: AuthenticatedUser,
+    path: web::Path<(i32, i32)>,
+) -> HttpResponse {
+    let (target_task_id, attachment_id) = path.into_inner();
+    match web::block(move || {
+        delete_single_attachment(db, storage, caller, target_task_id, attachment_id)
+    })
+    .await
+    {
+        Ok(Ok(deletion)) => match serde_json::to_value(deletion) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to delete attachment: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(AttachmentError::Access(e))) => throw_response_access_error(e),
+        Ok(Err(e)) => {
+            eprintln!("Failed to delete attachment: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
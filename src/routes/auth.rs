@@ -0,0 +1,456 @@
+use crate::models::email_token::*;
+use crate::models::session::{RevokedReason, SessionTokens};
+use crate::models::tailored_response::*;
+use crate::models::two_factor::*;
+use crate::models::user::{ChangePassword, User};
+use crate::schema::{login_challenges, users};
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::access::AccessError;
+use crate::utils::database::connection::Pool;
+use crate::utils::email_tokens::{db_issue_email_token, db_redeem_email_token, send_email_token};
+use crate::utils::mailer::Mailer;
+use crate::utils::sessions::{db_revoke_sessions, db_start_session, ClientInfo};
+use crate::utils::two_factor::{
+    db_check_second_factor, db_get_confirmed_totp, db_two_factor_required,
+};
+use crate::utils::{password, token};
+use actix_web::web::{self};
+use actix_web::{post, HttpRequest, HttpResponse};
+use diesel::prelude::*;
+use std::sync::Arc;
+
+diesel::sql_function!(fn lower(x: diesel::sql_types::Text) -> diesel::sql_types::Text);
+
+fn verify_user_email(
+    pool: web::Data<Pool>,
+    plain_token: String,
+) -> Result<User, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    conn.transaction(|conn| {
+        let redeemed = db_redeem_email_token(conn, &plain_token, TokenPurpose::VerifyEmail)?;
+        diesel::update(users::table.find(redeemed.user_id))
+            .set(users::email_verified_at.eq(redeemed.used_at))
+            .get_result(conn)
+    })
+}
+
+/// `None` when the address is already verified.
+fn resend_verification(
+    pool: web::Data<Pool>,
+    mailer: web::Data<Option<Arc<Mailer>>>,
+    caller: AuthenticatedUser,
+) -> Result<Option<EmailToken>, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    let (email, verified_at) = users::table
+        .find(caller.id)
+        .select((users::email, users::email_verified_at))
+        .first::<(String, Option<chrono::NaiveDateTime>)>(&mut conn)?;
+    if verified_at.is_some() {
+        return Ok(None);
+    }
+    let issued = db_issue_email_token(&mut conn, caller.id, TokenPurpose::VerifyEmail, &email)?;
+    send_email_token(mailer.as_deref(), &issued);
+    Ok(Some(issued.record))
+}
+
+/// Unknown addresses are silently ignored, so the response never reveals
+/// whether an account exists.
+fn request_password_reset(
+    pool: web::Data<Pool>,
+    mailer: web::Data<Option<Arc<Mailer>>>,
+    email: String,
+) -> Result<(), diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    let Some((target_user_id, address)) = users::table
+        .filter(lower(users::email).eq(email.trim().to_lowercase()))
+        .select((users::id, users::email))
+        .first::<(i32, String)>(&mut conn)
+        .optional()?
+    else {
+        return Ok(());
+    };
+    let issued = db_issue_email_token(
+        &mut conn,
+        target_user_id,
+        TokenPurpose::ResetPassword,
+        &address,
+    )?;
+    send_email_token(mailer.as_deref(), &issued);
+    Ok(())
+}
+
+/// The reset mail proves the user reads the address, so it also counts as
+/// verifying it. Whoever knew the old password is signed out everywhere.
+fn db_reset_password(
+    conn: &mut PgConnection,
+    plain_token: &str,
+    hashed_password: String,
+) -> Result<User, diesel::result::Error> {
+    conn.transaction(|conn| {
+        let redeemed = db_redeem_email_token(conn, plain_token, TokenPurpose::ResetPassword)?;
+        let verified_at = users::table
+            .find(redeemed.user_id)
+            .select(users::email_verified_at)
+            .first::<Option<chrono::NaiveDateTime>>(conn)?;
+        db_revoke_sessions(conn, redeemed.user_id, None, RevokedReason::PasswordChange)?;
+        diesel::update(users::table.find(redeemed.user_id))
+            .set((
+                users::password_hash.eq(hashed_password),
+                users::email_verified_at.eq(verified_at.or(redeemed.used_at)),
+                users::modified_at.eq(chrono::Local::now().naive_local()),
+            ))
+            .get_result(conn)
+    })
+}
+
+fn reset_user_password(
+    pool: web::Data<Pool>,
+    item: ResetPassword,
+) -> Result<User, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    let hashed_password = password::hash_password(&item.password).expect("Failed to hash password");
+    db_reset_password(&mut conn, &item.token, hashed_password)
+}
+
+/// Every session, including the caller's, is revoked; the caller gets a fresh
+/// one in return so it stays signed in.
+fn change_user_password(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    client: ClientInfo,
+    item: ChangePassword,
+) -> Result<SessionTokens, AccessError> {
+    let mut conn = pool.get().unwrap();
+    let current_hash = users::table
+        .find(caller.id)
+        .select(users::password_hash)
+        .first::<Option<String>>(&mut conn)?;
+    let password_matches = current_hash
+        .as_deref()
+        .is_some_and(|expected| password::verify_password(&item.current_password, expected));
+    if !password_matches {
+        return Err(AccessError::Forbidden);
+    }
+    let hashed_password =
+        password::hash_password(&item.new_password).expect("Failed to hash password");
+    let tokens = conn.transaction(|conn| {
+        diesel::update(users::table.find(caller.id))
+            .set((
+                users::password_hash.eq(hashed_password),
+                users::modified_at.eq(chrono::Local::now().naive_local()),
+            ))
+            .execute(conn)?;
+        db_revoke_sessions(conn, caller.id, None, RevokedReason::PasswordChange)?;
+        db_start_session(conn, caller.id, &client)
+    })?;
+    Ok(tokens)
+}
+
+/// `None` for unknown addresses, accounts without a password and wrong
+/// passwords alike.
+fn login_user(
+    pool: web::Data<Pool>,
+    client: ClientInfo,
+    credentials: LoginCredentials,
+) -> Result<Option<LoginOutcome>, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    let Some(user) = users::table
+        .filter(lower(users::email).eq(credentials.email.trim().to_lowercase()))
+        .first::<User>(&mut conn)
+        .optional()?
+    else {
+        return Ok(None);
+    };
+    let password_matches = user
+        .password_hash
+        .as_deref()
+        .is_some_and(|expected| password::verify_password(&credentials.password, expected));
+    if !password_matches {
+        return Ok(None);
+    }
+    db_begin_sign_in(&mut conn, user, &client).map(Some)
+}
+
+/// Signs in a user who proved their first factor, or asks for their second
+/// one if they have two-factor enabled.
+pub(crate) fn db_begin_sign_in(
+    conn: &mut PgConnection,
+    user: User,
+    client: &ClientInfo,
+) -> Result<LoginOutcome, diesel::result::Error> {
+    if db_get_confirmed_totp(conn, user.id)?.is_none() {
+        let two_factor_setup_required = db_two_factor_required(conn, user.id)?;
+        let session = db_start_session(conn, user.id, client)?;
+        return Ok(LoginOutcome::SignedIn {
+            user,
+            session,
+            two_factor_setup_required,
+        });
+    }
+    let plain_challenge = token::generate_token();
+    let now = chrono::Local::now().naive_local();
+    let new_challenge = NewLoginChallenge {
+        user_id: user.id,
+        token_hash: token::hash_token(&plain_challenge),
+        expires_at: now + chrono::Duration::minutes(LOGIN_CHALLENGE_MINUTES),
+        created_at: now,
+    };
+    diesel::insert_into(login_challenges::table)
+        .values(&new_challenge)
+        .execute(conn)?;
+    Ok(LoginOutcome::TwoFactorRequired {
+        challenge: plain_challenge,
+        expires_at: new_challenge.expires_at,
+    })
+}
+
+/// Wrong codes count against the challenge, which is burned after
+/// `MAX_CHALLENGE_ATTEMPTS` of them. `None` when the login is refused.
+fn complete_login(
+    pool: web::Data<Pool>,
+    client: ClientInfo,
+    item: LoginSecondStep,
+) -> Result<Option<LoginOutcome>, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    let now = chrono::Local::now().naive_local();
+    conn.transaction(|conn| {
+        let Some(challenge) = login_challenges::table
+            .filter(login_challenges::token_hash.eq(token::hash_token(&item.challenge)))
+            .filter(login_challenges::used_at.is_null())
+            .filter(login_challenges::expires_at.gt(now))
+            .filter(login_challenges::attempts.lt(MAX_CHALLENGE_ATTEMPTS))
+            .for_update()
+            .select(LoginChallenge::as_select())
+            .first(conn)
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        if !db_check_second_factor(conn, challenge.user_id, &item.factor)? {
+            let attempts = challenge.attempts + 1;
+            diesel::update(login_challenges::table.find(challenge.id))
+                .set((
+                    login_challenges::attempts.eq(attempts),
+                    login_challenges::used_at
+                        .eq(Some(now).filter(|_| attempts >= MAX_CHALLENGE_ATTEMPTS)),
+                ))
+                .execute(conn)?;
+            return Ok(None);
+        }
+        diesel::update(login_challenges::table.find(challenge.id))
+            .set(login_challenges::used_at.eq(now))
+            .execute(conn)?;
+        let user = users::table.find(challenge.user_id).first::<User>(conn)?;
+        let session = db_start_session(conn, user.id, &client)?;
+        Ok(Some(LoginOutcome::SignedIn {
+            user,
+            session,
+            two_factor_setup_required: false,
+        }))
+    })
+}
+
+#[post("/auth/login")]
+pub async fn login(
+    db: web::Data<Pool>,
+    req: HttpRequest,
+    item: web::Json<LoginCredentials>,
+) -> HttpResponse {
+    let client = ClientInfo::from_request(&req);
+    let credentials = item.into_inner();
+    match web::block(move || login_user(db, client, credentials)).await {
+        Ok(Ok(Some(outcome))) => match serde_json::to_value(outcome) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to log in: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Ok(None)) => throw_response_unauthorized(),
+        Ok(Err(e)) => {
+            eprintln!("Failed to log in: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/auth/login/two-factor")]
+pub async fn login_two_factor(
+    db: web::Data<Pool>,
+    req: HttpRequest,
+    item: web::Json<LoginSecondStep>,
+) -> HttpResponse {
+    let client = ClientInfo::from_request(&req);
+    let item = item.into_inner();
+    match web::block(move || complete_login(db, client, item)).await {
+        Ok(Ok(Some(outcome))) => match serde_json::to_value(outcome) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to log in: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Ok(None)) => throw_response_unauthorized(),
+        Ok(Err(e)) => {
+            eprintln!("Failed to log in: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/auth/verify-email")]
+pub async fn verify_email(db: web::Data<Pool>, item: web::Json<VerifyEmail>) -> HttpResponse {
+    let plain_token = item.into_inner().token;
+    match web::block(move || verify_user_email(db, plain_token)).await {
+        Ok(Ok(user)) => match serde_json::to_value(user) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to verify email: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(diesel::result::Error::NotFound)) => throw_response_bad_request(),
+        Ok(Err(e)) => {
+            eprintln!("Failed to verify email: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/auth/verify-email/resend")]
+pub async fn resend_verify_email(
+    db: web::Data<Pool>,
+    mailer: web::Data<Option<Arc<Mailer>>>,
+    caller: AuthenticatedUser,
+) -> HttpResponse {
+    match web::block(move || resend_verification(db, mailer, caller)).await {
+        Ok(Ok(Some(token))) => match serde_json::to_value(token) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to resend verification email: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Ok(None)) => throw_response_bad_request(),
+        Ok(Err(e)) => throw_response_access_error(e.into()),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/auth/forgot-password")]
+pub async fn forgot_password(
+    db: web::Data<Pool>,
+    mailer: web::Data<Option<Arc<Mailer>>>,
+    item: web::Json<ForgotPassword>,
+) -> HttpResponse {
+    let email = item.into_inner().email;
+    match web::block(move || request_password_reset(db, mailer, email)).await {
+        Ok(Ok(())) => throw_response_accepted(),
+        Ok(Err(e)) => {
+            eprintln!("Failed to start password reset: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/auth/reset-password")]
+pub async fn reset_password(db: web::Data<Pool>, item: web::Json<ResetPassword>) -> HttpResponse {
+    let item = item.into_inner();
+    if !item.is_valid() {
+        return throw_response_bad_request();
+    }
+    match web::block(move || reset_user_password(db, item)).await {
+        Ok(Ok(user)) => match serde_json::to_value(user) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to reset password: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(diesel::result::Error::NotFound)) => throw_response_bad_request(),
+        Ok(Err(e)) => {
+            eprintln!("Failed to reset password: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/auth/change-password")]
+pub async fn change_password(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    req: HttpRequest,
+    item: web::Json<ChangePassword>,
+) -> HttpResponse {
+    let item = item.into_inner();
+    if !item.is_valid() {
+        return throw_response_bad_request();
+    }
+    let client = ClientInfo::from_request(&req);
+    match web::block(move || change_user_password(db, caller, client, item)).await {
+        Ok(Ok(tokens)) => match serde_json::to_value(tokens) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to change password: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::sessions::db_authenticate_access_token;
+    use crate::utils::testing::{db_insert_user, test_connection};
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn password_reset_signs_out_every_session() {
+        let mut conn = test_connection();
+        let user = db_insert_user(&mut conn, false);
+        let client = ClientInfo::default();
+        let first = db_start_session(&mut conn, user.id, &client).unwrap();
+        let second = db_start_session(&mut conn, user.id, &client).unwrap();
+        let issued =
+            db_issue_email_token(&mut conn, user.id, TokenPurpose::ResetPassword, &user.email)
+                .unwrap();
+
+        let hashed_password = password::hash_password("a new password").unwrap();
+        let updated = db_reset_password(&mut conn, &issued.token, hashed_password).unwrap();
+
+        assert!(password::verify_password(
+            "a new password",
+            updated.password_hash.as_deref().unwrap()
+        ));
+        assert!(updated.email_verified_at.is_some());
+        for session in [first, second] {
+            let authenticated =
+                db_authenticate_access_token(&mut conn, &session.access_token, &client).unwrap();
+            assert!(authenticated.is_none());
+        }
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn password_reset_tokens_work_once() {
+        let mut conn = test_connection();
+        let user = db_insert_user(&mut conn, false);
+        let issued =
+            db_issue_email_token(&mut conn, user.id, TokenPurpose::ResetPassword, &user.email)
+                .unwrap();
+        let hashed_password = password::hash_password("a new password").unwrap();
+        assert!(db_reset_password(&mut conn, &issued.token, hashed_password.clone()).is_ok());
+        assert!(matches!(
+            db_reset_password(&mut conn, &issued.token, hashed_password),
+            Err(diesel::result::Error::NotFound)
+        ));
+    }
+}
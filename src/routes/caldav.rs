@@ -0,0 +1,957 @@
+use crate::models::api_key::ApiScope;
+use crate::models::dav_object::*;
+use crate::models::list_member::ListRole;
+use crate::models::tailored_response::*;
+use crate::models::todo_list::TodoList;
+use crate::models::todo_task::{InputTodoTask, TodoTask, UpdateTodoTask};
+use crate::routes::attachment::{db_release_attachment_blobs, db_task_attachment_hashes};
+use crate::routes::todo_task::{db_delete_task, db_insert_task, db_update_task};
+use crate::schema::{dav_objects, todolists, todotasks, tombstones};
+use crate::utils::auth::{AuthenticatedUser, DavUser};
+use crate::utils::database::access::{
+    db_get_active_workspace, db_require_list_role, db_require_task_role, visible_list_ids,
+    AccessError,
+};
+use crate::utils::database::connection::Pool;
+use crate::utils::events::{db_pruned_event_id, db_settled_event_id};
+use crate::utils::ical::{
+    parse_task_uid, parse_vtodo, render_calendar, task_uid, CalendarEntry, Component,
+};
+use crate::utils::storage::AttachmentStorage;
+use crate::utils::webdav::*;
+use actix_web::http::{header, StatusCode};
+use actix_web::web::{self};
+use actix_web::{get, route, HttpRequest, HttpResponse};
+use diesel::prelude::*;
+use std::collections::HashMap;
+
+const SYNC_TOKEN_PREFIX: &str = "http://todoer/ns/sync/";
+const ALLOWED_METHODS: &str = "OPTIONS, GET, PUT, DELETE, PROPFIND, REPORT";
+
+/// The resources under `/dav`: each list the user can see is a calendar
+/// collection and each of its tasks a VTODO resource in it.
+enum DavPath {
+    Root,
+    Principal(i32),
+    Home(i32),
+    Calendar(i32, i32),
+    Object(i32, i32, String),
+}
+
+impl DavPath {
+    /// `path` is whatever follows `/dav`.
+    fn parse(path: &str) -> Option<DavPath> {
+        if !path.is_empty() && !path.starts_with('/') {
+            return None;
+        }
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        match segments.as_slice() {
+            [] => Some(DavPath::Root),
+            ["principals", user] => Some(DavPath::Principal(user.parse().ok()?)),
+            ["calendars", user] => Some(DavPath::Home(user.parse().ok()?)),
+            ["calendars", user, list] => {
+                Some(DavPath::Calendar(user.parse().ok()?, list.parse().ok()?))
+            }
+            ["calendars", user, list, name] if name.ends_with(".ics") => Some(DavPath::Object(
+                user.parse().ok()?,
+                list.parse().ok()?,
+                name.to_string(),
+            )),
+            _ => None,
+        }
+    }
+
+    fn owner(&self) -> Option<i32> {
+        match self {
+            DavPath::Root => None,
+            DavPath::Principal(user)
+            | DavPath::Home(user)
+            | DavPath::Calendar(user, _)
+            | DavPath::Object(user, _, _) => Some(*user),
+        }
+    }
+}
+
+fn principal_href(user: i32) -> String {
+    format!("/dav/principals/{}/", user)
+}
+
+fn home_href(user: i32) -> String {
+    format!("/dav/calendars/{}/", user)
+}
+
+fn calendar_href(user: i32, list_id: i32) -> String {
+    format!("/dav/calendars/{}/{}/", user, list_id)
+}
+
+fn etag(task: &TodoTask) -> String {
+    format!("\"{}\"", task.modified_at.and_utc().timestamp_micros())
+}
+
+fn href_value(href: &str) -> String {
+    format!("<d:href>{}</d:href>", escape_xml(href))
+}
+
+/// A task together with the resource name it is served under.
+struct DavTask {
+    entry: CalendarEntry,
+    name: String,
+}
+
+fn db_dav_objects(
+    conn: &mut PgConnection,
+    task_ids: &[i32],
+) -> Result<HashMap<i32, DavObject>, diesel::result::Error> {
+    Ok(dav_objects::table
+        .filter(dav_objects::task_id.eq_any(task_ids))
+        .load::<DavObject>(conn)?
+        .into_iter()
+        .map(|object| (object.task_id, object))
+        .collect())
+}
+
+fn db_dav_tasks(
+    conn: &mut PgConnection,
+    rows: Vec<(TodoTask, String)>,
+) -> Result<Vec<DavTask>, diesel::result::Error> {
+    let mut ids: Vec<i32> = rows.iter().map(|(task, _)| task.id).collect();
+    ids.extend(rows.iter().filter_map(|(task, _)| task.parent_task_id));
+    let objects = db_dav_objects(conn, &ids)?;
+    let uid_of = |task_id: i32| {
+        objects
+            .get(&task_id)
+            .map_or_else(|| task_uid(task_id), |object| object.uid.clone())
+    };
+    Ok(rows
+        .into_iter()
+        .map(|(task, list_name)| DavTask {
+            name: objects
+                .get(&task.id)
+                .map_or_else(|| format!("{}.ics", task.id), |object| object.name.clone()),
+            entry: CalendarEntry {
+                uid: uid_of(task.id),
+                parent_uid: task.parent_task_id.map(uid_of),
+                task,
+                list_name,
+            },
+        })
+        .collect())
+}
+
+/// Calendar entries for `(task, list name)` rows, honouring the UIDs CalDAV
+/// clients chose.
+pub(crate) fn db_calendar_entries(
+    conn: &mut PgConnection,
+    rows: Vec<(TodoTask, String)>,
+) -> Result<Vec<CalendarEntry>, diesel::result::Error> {
+    Ok(db_dav_tasks(conn, rows)?
+        .into_iter()
+        .map(|dav_task| dav_task.entry)
+        .collect())
+}
+
+/// The newest change to the list, its tasks, or tasks that left it, held back
+/// to the settled event id so that it doubles as the collection's sync token
+/// and ctag; changes past it are reported again on the next sync. Lists left
+/// alone since before the pruned id report that instead, so their token
+/// stays valid.
+fn db_list_version(conn: &mut PgConnection, list: &TodoList) -> Result<i64, diesel::result::Error> {
+    let tasks = todotasks::table
+        .filter(todotasks::todolist_id.eq(list.id))
+        .select(diesel::dsl::max(todotasks::change_seq))
+        .first::<Option<i64>>(conn)?;
+    let gone = tombstones::table
+        .filter(tombstones::entity.eq("task"))
+        .filter(tombstones::list_id.eq(list.id))
+        .select(diesel::dsl::max(tombstones::change_seq))
+        .first::<Option<i64>>(conn)?;
+    let newest = [Some(list.change_seq), tasks, gone]
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or(0);
+    let pruned = db_pruned_event_id(conn)?;
+    Ok(newest.max(pruned).min(db_settled_event_id(conn)?))
+}
+
+fn db_list_tasks(
+    conn: &mut PgConnection,
+    list: &TodoList,
+    changed_since: Option<i64>,
+) -> Result<Vec<DavTask>, diesel::result::Error> {
+    let mut query = todotasks::table
+        .filter(todotasks::todolist_id.eq(list.id))
+        .order(todotasks::id.asc())
+        .into_boxed();
+    if let Some(since) = changed_since {
+        query = query.filter(todotasks::change_seq.gt(since));
+    }
+    let rows = query
+        .load::<TodoTask>(conn)?
+        .into_iter()
+        .map(|task| (task, list.name.clone()))
+        .collect();
+    db_dav_tasks(conn, rows)
+}
+
+/// Looks a resource name up among the list's tasks: first the names clients
+/// chose, then the `{id}.ics` names this server hands out.
+fn db_find_object(
+    conn: &mut PgConnection,
+    list: &TodoList,
+    name: &str,
+) -> Result<Option<DavTask>, diesel::result::Error> {
+    let chosen = dav_objects::table
+        .inner_join(todotasks::table.on(todotasks::id.eq(dav_objects::task_id)))
+        .filter(dav_objects::name.eq(name))
+        .filter(todotasks::todolist_id.eq(list.id))
+        .select(todotasks::all_columns)
+        .first::<TodoTask>(conn)
+        .optional()?;
+    let task = match chosen {
+        Some(task) => Some(task),
+        None => match name
+            .strip_suffix(".ics")
+            .and_then(|id| id.parse::<i32>().ok())
+        {
+            Some(task_id) => todotasks::table
+                .find(task_id)
+                .filter(todotasks::todolist_id.eq(list.id))
+                .first::<TodoTask>(conn)
+                .optional()?,
+            None => None,
+        },
+    };
+    let Some(task) = task else {
+        return Ok(None);
+    };
+    let mut found = db_dav_tasks(conn, vec![(task, list.name.clone())])?;
+    Ok(found.pop().filter(|dav_task| dav_task.name == name))
+}
+
+enum Resource<'a> {
+    Root,
+    Principal,
+    Home,
+    Calendar {
+        list: &'a TodoList,
+        version: i64,
+        role: ListRole,
+    },
+    Object(&'a DavTask),
+}
+
+fn prop(ns: &str, name: &str) -> PropName {
+    PropName {
+        ns: ns.to_string(),
+        name: name.to_string(),
+    }
+}
+
+/// Every property of `resource`, rendered. `calendar-data` is left out of
+/// `allprop`, as RFC 4791 asks.
+fn resource_props(resource: &Resource, user: i32, with_data: bool) -> Vec<(PropName, String)> {
+    let mut props = vec![(
+        prop(DAV_NS, "current-user-principal"),
+        href_value(&principal_href(user)),
+    )];
+    match resource {
+        Resource::Root => {
+            props.push((prop(DAV_NS, "resourcetype"), "<d:collection/>".into()));
+        }
+        Resource::Principal => {
+            props.push((
+                prop(DAV_NS, "resourcetype"),
+                "<d:collection/><d:principal/>".into(),
+            ));
+            props.push((
+                prop(DAV_NS, "principal-URL"),
+                href_value(&principal_href(user)),
+            ));
+            props.push((
+                prop(CALDAV_NS, "calendar-home-set"),
+                href_value(&home_href(user)),
+            ));
+        }
+        Resource::Home => {
+            props.push((prop(DAV_NS, "resourcetype"), "<d:collection/>".into()));
+            props.push((prop(DAV_NS, "owner"), href_value(&principal_href(user))));
+        }
+        Resource::Calendar {
+            list,
+            version,
+            role,
+        } => {
+            props.push((
+                prop(DAV_NS, "resourcetype"),
+                "<d:collection/><c:calendar/>".into(),
+            ));
+            props.push((prop(DAV_NS, "displayname"), escape_xml(&list.name)));
+            props.push((
+                prop(CALDAV_NS, "calendar-description"),
+                escape_xml(&list.description),
+            ));
+            props.push((
+                prop(CALDAV_NS, "supported-calendar-component-set"),
+                "<c:comp name=\"VTODO\"/>".into(),
+            ));
+            props.push((
+                prop(DAV_NS, "supported-report-set"),
+                [
+                    "c:calendar-query",
+                    "c:calendar-multiget",
+                    "d:sync-collection",
+                ]
+                .iter()
+                .map(|report| {
+                    format!(
+                        "<d:supported-report><d:report><{}/></d:report></d:supported-report>",
+                        report
+                    )
+                })
+                .collect(),
+            ));
+            let privileges = if *role >= ListRole::Editor {
+                "<d:privilege><d:read/></d:privilege><d:privilege><d:write/></d:privilege>"
+            } else {
+                "<d:privilege><d:read/></d:privilege>"
+            };
+            props.push((
+                prop(DAV_NS, "current-user-privilege-set"),
+                privileges.into(),
+            ));
+            props.push((prop(CALSERVER_NS, "getctag"), version.to_string()));
+            props.push((
+                prop(DAV_NS, "sync-token"),
+                format!("{}{}", SYNC_TOKEN_PREFIX, version),
+            ));
+        }
+        Resource::Object(dav_task) => {
+            let task = &dav_task.entry.task;
+            props.push((prop(DAV_NS, "resourcetype"), String::new()));
+            props.push((prop(DAV_NS, "getetag"), escape_xml(&etag(task))));
+            props.push((
+                prop(DAV_NS, "getcontenttype"),
+                "text/calendar; charset=utf-8; component=VTODO".into(),
+            ));
+            props.push((
+                prop(DAV_NS, "getlastmodified"),
+                task.modified_at
+                    .and_utc()
+                    .format("%a, %d %b %Y %H:%M:%S GMT")
+                    .to_string(),
+            ));
+            if with_data {
+                props.push((
+                    prop(CALDAV_NS, "calendar-data"),
+                    escape_xml(&render_object(dav_task)),
+                ));
+            }
+        }
+    }
+    props
+}
+
+fn render_object(dav_task: &DavTask) -> String {
+    render_calendar(
+        &dav_task.entry.list_name,
+        std::slice::from_ref(&dav_task.entry),
+        Component::Todo,
+    )
+}
+
+fn push_resource(
+    out: &mut MultiStatus,
+    href: &str,
+    resource: &Resource,
+    user: i32,
+    request: &PropRequest,
+) {
+    match request {
+        PropRequest::AllProp => out.push_props(href, &resource_props(resource, user, false), &[]),
+        PropRequest::PropName => {
+            let names: Vec<(PropName, String)> = resource_props(resource, user, true)
+                .into_iter()
+                .map(|(name, _)| (name, String::new()))
+                .collect();
+            out.push_props(href, &names, &[]);
+        }
+        PropRequest::Props(wanted) => {
+            let with_data = wanted
+                .iter()
+                .any(|name| name.is(CALDAV_NS, "calendar-data"));
+            let available = resource_props(resource, user, with_data);
+            let mut found = Vec::new();
+            let mut missing = Vec::new();
+            for name in wanted {
+                match available.iter().find(|(have, _)| have == name) {
+                    Some(pair) => found.push(pair.clone()),
+                    None => missing.push(name.clone()),
+                }
+            }
+            out.push_props(href, &found, &missing);
+        }
+    }
+}
+
+/// What a DAV handler decided, built off the async executor.
+enum DavReply {
+    MultiStatus(String),
+    Calendar { body: String, etag: String },
+    Stored { created: bool, etag: String },
+    Deleted,
+    NotFound,
+    Forbidden,
+    BadRequest,
+    PreconditionFailed,
+    InvalidSyncToken,
+    InvalidCalendarData,
+}
+
+impl DavReply {
+    fn into_response(self) -> HttpResponse {
+        match self {
+            DavReply::MultiStatus(body) => HttpResponse::build(StatusCode::MULTI_STATUS)
+                .content_type("application/xml; charset=utf-8")
+                .body(body),
+            DavReply::Calendar { body, etag } => HttpResponse::Ok()
+                .content_type("text/calendar; charset=utf-8")
+                .insert_header((header::ETAG, etag))
+                .body(body),
+            DavReply::Stored { created, etag } => {
+                let mut response = match created {
+                    true => HttpResponse::Created(),
+                    false => HttpResponse::NoContent(),
+                };
+                response.insert_header((header::ETAG, etag)).finish()
+            }
+            DavReply::Deleted => HttpResponse::NoContent().finish(),
+            DavReply::NotFound => throw_response_not_found(),
+            DavReply::Forbidden => throw_response_forbidden(),
+            DavReply::BadRequest => throw_response_bad_request(),
+            DavReply::PreconditionFailed => HttpResponse::PreconditionFailed().finish(),
+            DavReply::InvalidSyncToken => HttpResponse::Forbidden()
+                .content_type("application/xml; charset=utf-8")
+                .body(format!(
+                    "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                     <d:error xmlns:d=\"{}\"><d:valid-sync-token/></d:error>",
+                    DAV_NS
+                )),
+            DavReply::InvalidCalendarData => HttpResponse::Forbidden()
+                .content_type("application/xml; charset=utf-8")
+                .body(format!(
+                    "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                     <d:error xmlns:d=\"{}\" xmlns:c=\"{}\"><c:valid-calendar-data/></d:error>",
+                    DAV_NS, CALDAV_NS
+                )),
+        }
+    }
+}
+
+fn reply(
+    result: Result<Result<DavReply, AccessError>, actix_web::error::BlockingError>,
+) -> HttpResponse {
+    match result {
+        Ok(Ok(reply)) => reply.into_response(),
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+fn header_value(req: &HttpRequest, name: header::HeaderName) -> Option<String> {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_string())
+}
+
+fn dav_propfind(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    path: DavPath,
+    depth: Depth,
+    request: PropRequest,
+) -> Result<DavReply, AccessError> {
+    let mut conn = pool.get().unwrap();
+    let user = caller.id;
+    let mut out = MultiStatus::default();
+    match path {
+        DavPath::Root => push_resource(&mut out, "/dav/", &Resource::Root, user, &request),
+        DavPath::Principal(_) => push_resource(
+            &mut out,
+            &principal_href(user),
+            &Resource::Principal,
+            user,
+            &request,
+        ),
+        DavPath::Home(_) => {
+            push_resource(&mut out, &home_href(user), &Resource::Home, user, &request);
+            if depth == Depth::One {
+                let workspace_id = db_get_active_workspace(&mut conn, user)?;
+                let lists = todolists::table
+                    .filter(todolists::id.eq_any(visible_list_ids(user, workspace_id)))
+                    .order(todolists::id.asc())
+                    .load::<TodoList>(&mut conn)?;
+                for list in &lists {
+                    let role = db_require_list_role(&mut conn, list.id, user, ListRole::Viewer)?;
+                    let version = db_list_version(&mut conn, list)?;
+                    let resource = Resource::Calendar {
+                        list,
+                        version,
+                        role,
+                    };
+                    push_resource(
+                        &mut out,
+                        &calendar_href(user, list.id),
+                        &resource,
+                        user,
+                        &request,
+                    );
+                }
+            }
+        }
+        DavPath::Calendar(_, list_id) => {
+            let role = db_require_list_role(&mut conn, list_id, user, ListRole::Viewer)?;
+            let list = todolists::table
+                .find(list_id)
+                .first::<TodoList>(&mut conn)?;
+            let version = db_list_version(&mut conn, &list)?;
+            let href = calendar_href(user, list_id);
+            let resource = Resource::Calendar {
+                list: &list,
+                version,
+                role,
+            };
+            push_resource(&mut out, &href, &resource, user, &request);
+            if depth == Depth::One {
+                for dav_task in db_list_tasks(&mut conn, &list, None)? {
+                    let object_href = format!("{}{}", href, dav_task.name);
+                    push_resource(
+                        &mut out,
+                        &object_href,
+                        &Resource::Object(&dav_task),
+                        user,
+                        &request,
+                    );
+                }
+            }
+        }
+        DavPath::Object(_, list_id, name) => {
+            db_require_list_role(&mut conn, list_id, user, ListRole::Viewer)?;
+            let list = todolists::table
+                .find(list_id)
+                .first::<TodoList>(&mut conn)?;
+            let Some(dav_task) = db_find_object(&mut conn, &list, &name)? else {
+                return Ok(DavReply::NotFound);
+            };
+            let href = format!("{}{}", calendar_href(user, list_id), name);
+            push_resource(
+                &mut out,
+                &href,
+                &Resource::Object(&dav_task),
+                user,
+                &request,
+            );
+        }
+    }
+    Ok(DavReply::MultiStatus(out.finish()))
+}
+
+fn dav_report(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    list_id: i32,
+    report: ReportRequest,
+) -> Result<DavReply, AccessError> {
+    let mut conn = pool.get().unwrap();
+    let user = caller.id;
+    db_require_list_role(&mut conn, list_id, user, ListRole::Viewer)?;
+    let list = todolists::table
+        .find(list_id)
+        .first::<TodoList>(&mut conn)?;
+    let href = calendar_href(user, list_id);
+    let mut out = MultiStatus::default();
+    match report {
+        ReportRequest::CalendarQuery(request, components) => {
+            // only VTODOs live here, so a query for anything else is empty
+            if components.iter().all(|component| component == "VTODO") {
+                for dav_task in db_list_tasks(&mut conn, &list, None)? {
+                    let object_href = format!("{}{}", href, dav_task.name);
+                    push_resource(
+                        &mut out,
+                        &object_href,
+                        &Resource::Object(&dav_task),
+                        user,
+                        &request,
+                    );
+                }
+            }
+        }
+        ReportRequest::CalendarMultiget(request, hrefs) => {
+            for object_href in hrefs {
+                let name = object_href
+                    .strip_prefix(&href)
+                    .filter(|name| !name.contains('/'));
+                let found = match name {
+                    Some(name) => db_find_object(&mut conn, &list, name)?,
+                    None => None,
+                };
+                match found {
+                    Some(dav_task) => push_resource(
+                        &mut out,
+                        &object_href,
+                        &Resource::Object(&dav_task),
+                        user,
+                        &request,
+                    ),
+                    None => out.push_status(&object_href, "404 Not Found"),
+                }
+            }
+        }
+        ReportRequest::SyncCollection(request, token) => {
+            let since = match token {
+                None => None,
+                Some(token) => match token
+                    .strip_prefix(SYNC_TOKEN_PREFIX)
+                    .and_then(|seq| seq.parse::<i64>().ok())
+                {
+                    Some(since) if since >= db_pruned_event_id(&mut conn)? => Some(since),
+                    _ => return Ok(DavReply::InvalidSyncToken),
+                },
+            };
+            let version = db_list_version(&mut conn, &list)?;
+            let changed = db_list_tasks(&mut conn, &list, since)?;
+            for dav_task in &changed {
+                let object_href = format!("{}{}", href, dav_task.name);
+                push_resource(
+                    &mut out,
+                    &object_href,
+                    &Resource::Object(dav_task),
+                    user,
+                    &request,
+                );
+            }
+            if let Some(since) = since {
+                let gone: Vec<i32> = tombstones::table
+                    .filter(tombstones::entity.eq("task"))
+                    .filter(tombstones::list_id.eq(list_id))
+                    .filter(tombstones::change_seq.gt(since))
+                    .select(tombstones::entity_id)
+                    .load(&mut conn)?;
+                let live: Vec<i32> = todotasks::table
+                    .filter(todotasks::id.eq_any(&gone))
+                    .filter(todotasks::todolist_id.eq(list_id))
+                    .select(todotasks::id)
+                    .load(&mut conn)?;
+                let objects = db_dav_objects(&mut conn, &gone)?;
+                let mut gone: Vec<i32> = gone.into_iter().filter(|id| !live.contains(id)).collect();
+                gone.sort_unstable();
+                gone.dedup();
+                for task_id in gone {
+                    let name = objects
+                        .get(&task_id)
+                        .map_or_else(|| format!("{}.ics", task_id), |object| object.name.clone());
+                    out.push_status(&format!("{}{}", href, name), "404 Not Found");
+                }
+            }
+            out.push_sync_token(&format!("{}{}", SYNC_TOKEN_PREFIX, version));
+        }
+    }
+    Ok(DavReply::MultiStatus(out.finish()))
+}
+
+fn dav_get(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    list_id: i32,
+    name: String,
+) -> Result<DavReply, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_list_role(&mut conn, list_id, caller.id, ListRole::Viewer)?;
+    let list = todolists::table
+        .find(list_id)
+        .first::<TodoList>(&mut conn)?;
+    Ok(match db_find_object(&mut conn, &list, &name)? {
+        Some(dav_task) => DavReply::Calendar {
+            body: render_object(&dav_task),
+            etag: etag(&dav_task.entry.task),
+        },
+        None => DavReply::NotFound,
+    })
+}
+
+/// `If-Match` and `If-None-Match: *` guard writes against lost updates.
+fn preconditions_hold(
+    existing: Option<&DavTask>,
+    if_match: Option<&str>,
+    if_none_match: Option<&str>,
+) -> bool {
+    let current = existing.map(|dav_task| etag(&dav_task.entry.task));
+    let matches = match (if_match, &current) {
+        (None, _) => true,
+        (Some("*"), Some(_)) => true,
+        (Some(expected), Some(current)) => expected == current,
+        (Some(_), None) => false,
+    };
+    let none_matches = match (if_none_match, &current) {
+        (Some("*"), Some(_)) => false,
+        (Some(expected), Some(current)) => expected != current,
+        _ => true,
+    };
+    matches && none_matches
+}
+
+/// Parents are matched by UID and must be tasks of the same list the caller
+/// can see; anything else is dropped rather than rejected.
+fn db_resolve_parent(
+    conn: &mut PgConnection,
+    caller: AuthenticatedUser,
+    list_id: i32,
+    parent_uid: Option<&str>,
+) -> Result<Option<i32>, diesel::result::Error> {
+    let Some(parent_uid) = parent_uid else {
+        return Ok(None);
+    };
+    let chosen = dav_objects::table
+        .filter(dav_objects::uid.eq(parent_uid))
+        .select(dav_objects::task_id)
+        .first::<i32>(conn)
+        .optional()?;
+    let Some(parent_id) = chosen.or_else(|| parse_task_uid(parent_uid)) else {
+        return Ok(None);
+    };
+    match db_require_task_role(conn, parent_id, caller.id, ListRole::Viewer) {
+        Ok(parent) if parent.todolist_id == list_id => Ok(Some(parent_id)),
+        Ok(_) => Ok(None),
+        Err(AccessError::Database(diesel::result::Error::NotFound))
+        | Err(AccessError::Forbidden) => Ok(None),
+        Err(AccessError::Database(e)) => Err(e),
+    }
+}
+
+/// Tasks the usual rules reject, such as an overlong summary, are refused
+/// with the `valid-calendar-data` precondition.
+fn dav_put(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    list_id: i32,
+    name: String,
+    if_match: Option<String>,
+    if_none_match: Option<String>,
+    body: String,
+) -> Result<DavReply, AccessError> {
+    let Some(todo) = parse_vtodo(&body) else {
+        return Ok(DavReply::BadRequest);
+    };
+    let Some(summary) = todo.summary.clone().filter(|summary| !summary.is_empty()) else {
+        return Ok(DavReply::BadRequest);
+    };
+    let mut conn = pool.get().unwrap();
+    db_require_list_role(&mut conn, list_id, caller.id, ListRole::Editor)?;
+    let list = todolists::table
+        .find(list_id)
+        .first::<TodoList>(&mut conn)?;
+    let existing = db_find_object(&mut conn, &list, &name)?;
+    if !preconditions_hold(
+        existing.as_ref(),
+        if_match.as_deref(),
+        if_none_match.as_deref(),
+    ) {
+        return Ok(DavReply::PreconditionFailed);
+    }
+    let parent_task_id = db_resolve_parent(&mut conn, caller, list_id, todo.parent_uid.as_deref())?;
+    let now = chrono::Local::now().naive_local();
+    if let Some(existing) = existing {
+        let previous = &existing.entry.task;
+        let changes = UpdateTodoTask {
+            name: Some(summary),
+            priority: Some(todo.priority(previous.priority.as_deref())),
+            completed_at: Some(todo.completed_at(previous.completed_at, now)),
+            description: Some(todo.description),
+            due_date: Some(todo.due_date),
+            parent_task_id: Some(parent_task_id),
+            ..Default::default()
+        };
+        if changes.validate().is_err() {
+            return Ok(DavReply::InvalidCalendarData);
+        }
+        let task = db_update_task(&mut conn, caller, existing.entry.task.id, &changes)?;
+        return Ok(DavReply::Stored {
+            created: false,
+            etag: etag(&task),
+        });
+    }
+    let item = InputTodoTask {
+        todolist_id: list_id,
+        name: summary,
+        priority: todo.priority(None),
+        completed_at: todo.completed_at(None, now),
+        description: todo.description,
+        parent_task_id,
+        due_date: todo.due_date,
+    };
+    if item.validate().is_err() {
+        return Ok(DavReply::InvalidCalendarData);
+    }
+    let task = conn.transaction(|conn| {
+        let task = db_insert_task(conn, caller, &item)?;
+        let uid = todo.uid.unwrap_or_else(|| task_uid(task.id));
+        diesel::insert_into(dav_objects::table)
+            .values(&NewDavObject {
+                task_id: task.id,
+                uid: &uid,
+                name: &name,
+                created_at: now,
+            })
+            .execute(conn)?;
+        Ok::<_, AccessError>(task)
+    })?;
+    Ok(DavReply::Stored {
+        created: true,
+        etag: etag(&task),
+    })
+}
+
+fn dav_delete(
+    pool: web::Data<Pool>,
+    storage: web::Data<dyn AttachmentStorage>,
+    caller: AuthenticatedUser,
+    list_id: i32,
+    name: String,
+    if_match: Option<String>,
+) -> Result<DavReply, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_list_role(&mut conn, list_id, caller.id, ListRole::Editor)?;
+    let list = todolists::table
+        .find(list_id)
+        .first::<TodoList>(&mut conn)?;
+    let Some(existing) = db_find_object(&mut conn, &list, &name)? else {
+        return Ok(DavReply::NotFound);
+    };
+    if !preconditions_hold(Some(&existing), if_match.as_deref(), None) {
+        return Ok(DavReply::PreconditionFailed);
+    }
+    let hashes = db_task_attachment_hashes(&mut conn, &[existing.entry.task.id])?;
+    db_delete_task(&mut conn, caller, existing.entry.task.id)?;
+    db_release_attachment_blobs(&mut conn, storage.get_ref(), &hashes);
+    Ok(DavReply::Deleted)
+}
+
+/// Parses the path and checks it belongs to the caller; other users' trees
+/// are forbidden outright, as are API keys without `scope`.
+fn resolve_path(
+    caller: DavUser,
+    scope: ApiScope,
+    raw: &str,
+) -> Result<(AuthenticatedUser, DavPath), DavReply> {
+    let caller = caller.0.require(scope).map_err(|_| DavReply::Forbidden)?;
+    let path = DavPath::parse(raw).ok_or(DavReply::NotFound)?;
+    match path.owner() {
+        Some(owner) if owner != caller.id => Err(DavReply::Forbidden),
+        _ => Ok((caller, path)),
+    }
+}
+
+#[get("/.well-known/caldav")]
+pub async fn well_known() -> HttpResponse {
+    HttpResponse::MovedPermanently()
+        .insert_header((header::LOCATION, "/dav/"))
+        .finish()
+}
+
+#[route("/dav{path:.*}", method = "OPTIONS")]
+pub async fn options() -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(("DAV", "1, 3, calendar-access"))
+        .insert_header((header::ALLOW, ALLOWED_METHODS))
+        .finish()
+}
+
+#[route("/dav{path:.*}", method = "PROPFIND")]
+pub async fn propfind(
+    db: web::Data<Pool>,
+    caller: DavUser,
+    path: web::Path<String>,
+    req: HttpRequest,
+    body: String,
+) -> HttpResponse {
+    let (caller, path) = match resolve_path(caller, ApiScope::TasksRead, &path) {
+        Ok(resolved) => resolved,
+        Err(reply) => return reply.into_response(),
+    };
+    let Some(request) = parse_propfind(&body) else {
+        return throw_response_bad_request();
+    };
+    let depth =
+        Depth::from_header(header_value(&req, header::HeaderName::from_static("depth")).as_deref());
+    reply(web::block(move || dav_propfind(db, caller, path, depth, request)).await)
+}
+
+#[route("/dav{path:.*}", method = "REPORT")]
+pub async fn calendar_report(
+    db: web::Data<Pool>,
+    caller: DavUser,
+    path: web::Path<String>,
+    body: String,
+) -> HttpResponse {
+    let (caller, list_id) = match resolve_path(caller, ApiScope::TasksRead, &path) {
+        Ok((caller, DavPath::Calendar(_, list_id))) => (caller, list_id),
+        Ok(_) => return throw_response_forbidden(),
+        Err(reply) => return reply.into_response(),
+    };
+    let Some(report) = parse_report(&body) else {
+        return throw_response_bad_request();
+    };
+    reply(web::block(move || dav_report(db, caller, list_id, report)).await)
+}
+
+#[route("/dav{path:.*}", method = "GET")]
+pub async fn get_object(
+    db: web::Data<Pool>,
+    caller: DavUser,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let (caller, list_id, name) = match resolve_path(caller, ApiScope::TasksRead, &path) {
+        Ok((caller, DavPath::Object(_, list_id, name))) => (caller, list_id, name),
+        Ok(_) => return HttpResponse::MethodNotAllowed().finish(),
+        Err(reply) => return reply.into_response(),
+    };
+    reply(web::block(move || dav_get(db, caller, list_id, name)).await)
+}
+
+#[route("/dav{path:.*}", method = "PUT")]
+pub async fn put_object(
+    db: web::Data<Pool>,
+    caller: DavUser,
+    path: web::Path<String>,
+    req: HttpRequest,
+    body: String,
+) -> HttpResponse {
+    let (caller, list_id, name) = match resolve_path(caller, ApiScope::TasksWrite, &path) {
+        Ok((caller, DavPath::Object(_, list_id, name))) => (caller, list_id, name),
+        Ok(_) => return HttpResponse::MethodNotAllowed().finish(),
+        Err(reply) => return reply.into_response(),
+    };
+    let if_match = header_value(&req, header::IF_MATCH);
+    let if_none_match = header_value(&req, header::IF_NONE_MATCH);
+    reply(
+        web::block(move || dav_put(db, caller, list_id, name, if_match, if_none_match, body)).await,
+    )
+}
+
+#[route("/dav{path:.*}", method = "DELETE")]
+pub async fn delete_object(
+    db: web::Data<Pool>,
+    storage: web::Data<dyn AttachmentStorage>,
+    caller: DavUser,
+    path: web::Path<String>,
+    req: HttpRequest,
+) -> HttpResponse {
+    let (caller, list_id, name) = match resolve_path(caller, ApiScope::TasksWrite, &path) {
+        Ok((caller, DavPath::Object(_, list_id, name))) => (caller, list_id, name),
+        Ok(_) => return HttpResponse::MethodNotAllowed().finish(),
+        Err(reply) => return reply.into_response(),
+    };
+    let if_match = header_value(&req, header::IF_MATCH);
+    reply(web::block(move || dav_delete(db, storage, caller, list_id, name, if_match)).await)
+}
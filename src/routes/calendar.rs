@@ -0,0 +1,153 @@
+use crate::models::calendar_feed::*;
+use crate::models::tailored_response::*;
+use crate::models::todo_task::TodoTask;
+use crate::routes::caldav::db_calendar_entries;
+use crate::schema::calendar_feeds::dsl::*;
+use crate::schema::{todolists, todotasks};
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::access::{db_get_active_workspace, visible_list_ids, AccessError};
+use crate::utils::database::connection::Pool;
+use crate::utils::ical::{render_calendar, CalendarEntry, Component};
+use crate::utils::token;
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::web::{self};
+use actix_web::{delete, get, post, HttpResponse};
+use diesel::prelude::*;
+use diesel::{delete, insert_into};
+
+/// Issuing a new feed URL invalidates the previous one.
+fn rotate_calendar_feed(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: i32,
+) -> Result<CreatedCalendarFeed, AccessError> {
+    if caller.id != target_user_id {
+        return Err(AccessError::Forbidden);
+    }
+    let mut conn = pool.get().unwrap();
+    let plain_token = token::generate_token();
+    let new_feed = NewCalendarFeed {
+        user_id: target_user_id,
+        token_hash: token::hash_token(&plain_token),
+        created_at: chrono::Local::now().naive_local(),
+    };
+    let feed = insert_into(calendar_feeds)
+        .values(&new_feed)
+        .on_conflict(user_id)
+        .do_update()
+        .set(&new_feed)
+        .returning(CalendarFeed::as_returning())
+        .get_result(&mut conn)?;
+    Ok(CreatedCalendarFeed {
+        feed,
+        path: format!("/calendar/{}.ics", plain_token),
+        token: plain_token,
+    })
+}
+
+fn revoke_calendar_feed(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: i32,
+) -> Result<usize, AccessError> {
+    if caller.id != target_user_id {
+        return Err(AccessError::Forbidden);
+    }
+    let mut conn = pool.get().unwrap();
+    let deletion = delete(calendar_feeds.find(target_user_id)).execute(&mut conn)?;
+    Ok(deletion)
+}
+
+/// Tasks with a due date on the lists the feed's owner can currently see,
+/// optionally narrowed to `list_filter`. Unknown tokens get `NotFound`.
+fn db_get_feed_tasks(
+    pool: web::Data<Pool>,
+    plain_token: String,
+    list_filter: Option<Vec<i32>>,
+) -> Result<Vec<CalendarEntry>, AccessError> {
+    let mut conn = pool.get().unwrap();
+    let owner_id = calendar_feeds
+        .filter(token_hash.eq(token::hash_token(&plain_token)))
+        .select(user_id)
+        .first::<i32>(&mut conn)?;
+    let workspace_id = db_get_active_workspace(&mut conn, owner_id)?;
+    let mut query = todotasks::table
+        .inner_join(todolists::table.on(todolists::id.eq(todotasks::todolist_id)))
+        .filter(todotasks::todolist_id.eq_any(visible_list_ids(owner_id, workspace_id)))
+        .filter(todotasks::due_date.is_not_null())
+        .select((todotasks::all_columns, todolists::name))
+        .order((todotasks::due_date.asc(), todotasks::id.asc()))
+        .into_boxed();
+    if let Some(list_ids) = list_filter {
+        query = query.filter(todotasks::todolist_id.eq_any(list_ids));
+    }
+    let rows = query.load::<(TodoTask, String)>(&mut conn)?;
+    Ok(db_calendar_entries(&mut conn, rows)?)
+}
+
+fn parse_list_filter(raw: Option<&str>) -> Result<Option<Vec<i32>>, std::num::ParseIntError> {
+    raw.map(|lists| lists.split(',').map(|part| part.trim().parse()).collect())
+        .transpose()
+}
+
+#[post("/users/{id}/calendar-feed")]
+pub async fn create_calendar_feed(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || rotate_calendar_feed(db, caller, target_user_id.into_inner())).await {
+        Ok(Ok(feed)) => match serde_json::to_value(feed) {
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to create calendar feed: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[delete("/users/{id}/calendar-feed")]
+pub async fn delete_calendar_feed(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || revoke_calendar_feed(db, caller, target_user_id.into_inner())).await {
+        Ok(Ok(deletion)) => match serde_json::to_value(deletion) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to delete calendar feed: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+/// The token in the URL is the only credential, as calendar apps can't send
+/// custom headers.
+#[get("/calendar/{token}.ics")]
+pub async fn get_calendar_feed(
+    db: web::Data<Pool>,
+    plain_token: web::Path<String>,
+    query: web::Query<CalendarFeedQuery>,
+) -> HttpResponse {
+    let Some(component) = Component::from_query(query.component.as_deref()) else {
+        return throw_response_bad_request();
+    };
+    let Ok(list_filter) = parse_list_filter(query.lists.as_deref()) else {
+        return throw_response_bad_request();
+    };
+    match web::block(move || db_get_feed_tasks(db, plain_token.into_inner(), list_filter)).await {
+        Ok(Ok(entries)) => HttpResponse::Ok()
+            .content_type("text/calendar; charset=utf-8")
+            .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+            .body(render_calendar("Todoer", &entries, component)),
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
@@ -0,0 +1,150 @@
+use crate::models::event::Event;
+use crate::models::tailored_response::{throw_response_error, throw_response_gone};
+use crate::schema::{event_recipients, events};
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::connection::Pool;
+use crate::utils::events::{db_pruned_event_id, db_settled_event_id, EventHub};
+use actix_web::http::header::{CacheControl, CacheDirective, ContentType};
+use actix_web::rt::time::{interval_at, Instant, Interval};
+use actix_web::web::{self, Bytes};
+use actix_web::{get, Error, HttpRequest, HttpResponse};
+use diesel::prelude::*;
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::sync::watch;
+
+pub const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+const BATCH_SIZE: i64 = 100;
+
+struct StreamState {
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    cursor: i64,
+    pending: VecDeque<Event>,
+    log: watch::Receiver<i64>,
+    keep_alive: Interval,
+}
+
+/// Where a stream starts: the newest settled event without `Last-Event-ID`,
+/// and `None` when the events after it have already been pruned.
+fn db_get_start_cursor(
+    pool: web::Data<Pool>,
+    last_event_id: Option<i64>,
+) -> Result<Option<i64>, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    match last_event_id {
+        Some(cursor) if cursor < db_pruned_event_id(&mut conn)? => Ok(None),
+        Some(cursor) => Ok(Some(cursor)),
+        None => Ok(Some(db_settled_event_id(&mut conn)?)),
+    }
+}
+
+/// Stops at the settled id, so an event committing late is never stepped
+/// over.
+fn get_events_after(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    cursor: i64,
+) -> Result<Vec<Event>, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    let settled = db_settled_event_id(&mut conn)?;
+    events::table
+        .filter(events::id.gt(cursor))
+        .filter(events::id.le(settled))
+        .filter(
+            events::id.eq_any(
+                event_recipients::table
+                    .filter(event_recipients::user_id.eq(caller.id))
+                    .filter(event_recipients::event_id.gt(cursor))
+                    .select(event_recipients::event_id),
+            ),
+        )
+        .order(events::id.asc())
+        .limit(BATCH_SIZE)
+        .load::<Event>(&mut conn)
+}
+
+fn format_event(event: &Event) -> Bytes {
+    Bytes::from(format!(
+        "id: {}\nevent: {}\ndata: {}\n\n",
+        event.id, event.kind, event.payload
+    ))
+}
+
+/// Yields the next chunk of the stream: a logged event once one visible to
+/// the caller exists past the cursor, or a keep-alive comment so proxies
+/// don't time out idle connections.
+async fn next_chunk(mut state: StreamState) -> Option<(Result<Bytes, Error>, StreamState)> {
+    loop {
+        if let Some(event) = state.pending.pop_front() {
+            state.cursor = event.id;
+            return Some((Ok(format_event(&event)), state));
+        }
+        let (db, caller, cursor) = (state.db.clone(), state.caller, state.cursor);
+        match web::block(move || get_events_after(db, caller, cursor)).await {
+            Ok(Ok(batch)) if !batch.is_empty() => {
+                state.pending.extend(batch);
+                continue;
+            }
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => {
+                eprintln!("Failed to read event log: {}", e);
+                return None;
+            }
+            Err(_) => return None,
+        }
+        tokio::select! {
+            changed = state.log.changed() => {
+                if changed.is_err() {
+                    return None;
+                }
+            }
+            _ = state.keep_alive.tick() => {
+                return Some((Ok(Bytes::from_static(b": keep-alive\n\n")), state));
+            }
+        }
+    }
+}
+
+/// Streams task, list and user events as Server-Sent Events. Clients that
+/// reconnect with `Last-Event-ID` are replayed everything they missed;
+/// without it the stream starts at the newest event. An id older than the
+/// retained log gets 410 Gone, and the client has to sync from scratch.
+#[get("/events")]
+pub async fn event_stream(
+    req: HttpRequest,
+    db: web::Data<Pool>,
+    hub: web::Data<EventHub>,
+    caller: AuthenticatedUser,
+) -> HttpResponse {
+    let log = hub.subscribe_log();
+    let last_event_id = req
+        .headers()
+        .get(LAST_EVENT_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<i64>().ok());
+    let pool = db.clone();
+    let cursor = match web::block(move || db_get_start_cursor(pool, last_event_id)).await {
+        Ok(Ok(Some(cursor))) => cursor,
+        Ok(Ok(None)) => return throw_response_gone(),
+        Ok(Err(e)) => {
+            eprintln!("Failed to read event log: {}", e);
+            return throw_response_error();
+        }
+        Err(_) => return throw_response_error(),
+    };
+    let state = StreamState {
+        db,
+        caller,
+        cursor,
+        pending: VecDeque::new(),
+        log,
+        keep_alive: interval_at(Instant::now() + KEEP_ALIVE_INTERVAL, KEEP_ALIVE_INTERVAL),
+    };
+    HttpResponse::Ok()
+        .insert_header(ContentType(actix_web::mime::TEXT_EVENT_STREAM))
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .streaming(futures_util::stream::unfold(state, next_chunk))
+}
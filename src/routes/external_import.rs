@@ -0,0 +1,267 @@
+use crate::models::external_import::*;
+use crate::models::tailored_response::*;
+use crate::models::task_tag::{TaskTag, MAX_TAG_CHARS};
+use crate::models::todo_list::InputTodoList;
+use crate::models::todo_task::{InputTodoTask, MAX_TASK_NAME_CHARS};
+use crate::routes::todo_list::db_insert_list;
+use crate::routes::todo_task::db_insert_task;
+use crate::schema::task_tags;
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::config;
+use crate::utils::database::access::{db_get_active_workspace, AccessError};
+use crate::utils::database::connection::Pool;
+use crate::utils::importers::{parse_todoist, parse_trello, ImportPlan, PlannedList, PlannedTask};
+use actix_web::web::{self, Bytes};
+use actix_web::{post, HttpResponse};
+use diesel::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+const MAX_IMPORT_TASKS: usize = 10_000;
+
+fn truncate(value: &str, max_chars: usize) -> String {
+    value.chars().take(max_chars).collect()
+}
+
+/// Orders a list's tasks so parents come first. Parents outside the list or
+/// in a cycle are dropped, with a warning.
+fn parents_first<'a>(
+    list: &'a PlannedList,
+    warnings: &mut Vec<String>,
+) -> Vec<(&'a PlannedTask, Option<&'a str>)> {
+    let by_id: HashMap<&str, &PlannedTask> = list
+        .tasks
+        .iter()
+        .map(|task| (task.source_id.as_str(), task))
+        .collect();
+    let mut ordered = Vec::with_capacity(list.tasks.len());
+    let mut placed: HashSet<&str> = HashSet::new();
+    for task in &list.tasks {
+        let mut chain = vec![task];
+        let mut seen: HashSet<&str> = HashSet::from([task.source_id.as_str()]);
+        while let Some(parent) = chain
+            .last()
+            .and_then(|task| task.parent.as_deref())
+            .filter(|parent| !placed.contains(parent))
+            .and_then(|parent| by_id.get(parent))
+        {
+            if !seen.insert(&parent.source_id) {
+                break;
+            }
+            chain.push(parent);
+        }
+        for task in chain.into_iter().rev() {
+            if !placed.insert(&task.source_id) {
+                continue;
+            }
+            let parent = task.parent.as_deref();
+            let kept = parent.filter(|parent| placed.contains(parent) && *parent != task.source_id);
+            if parent.is_some() && kept.is_none() {
+                warnings.push(format!(
+                    "task {} lost its parent, which is not in the same list",
+                    task.source_id
+                ));
+            }
+            ordered.push((task, kept));
+        }
+    }
+    ordered
+}
+
+/// Fits names and tags to their columns and rejects what can't be fixed.
+/// Nothing is written when there are errors or on a dry run.
+fn db_apply_plan(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    source: &'static str,
+    plan: ImportPlan,
+    dry_run: bool,
+) -> Result<ExternalImportReport, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_get_active_workspace(&mut conn, caller.id)?;
+    let mut report = ExternalImportReport {
+        source,
+        dry_run,
+        lists: plan.lists.len(),
+        tasks: 0,
+        subtasks: 0,
+        tags: 0,
+        warnings: plan.warnings,
+        errors: Vec::new(),
+        list_ids: BTreeMap::new(),
+        task_ids: BTreeMap::new(),
+    };
+    let total: usize = plan.lists.iter().map(|list| list.tasks.len()).sum();
+    if total > MAX_IMPORT_TASKS {
+        report.errors.push(format!(
+            "the export holds {} tasks, more than the {} allowed at once",
+            total, MAX_IMPORT_TASKS
+        ));
+        return Ok(report);
+    }
+
+    let mut planned = Vec::with_capacity(plan.lists.len());
+    let mut tag_names: HashSet<String> = HashSet::new();
+    for list in &plan.lists {
+        let mut list_name = list.name.trim().to_string();
+        if list_name.is_empty() {
+            report
+                .errors
+                .push(format!("list {} has no name", list.source_id));
+        } else if list_name.chars().count() > MAX_TASK_NAME_CHARS {
+            list_name = truncate(&list_name, MAX_TASK_NAME_CHARS);
+            report
+                .warnings
+                .push(format!("the name of list {} was shortened", list.source_id));
+        }
+        let mut tasks = Vec::with_capacity(list.tasks.len());
+        for (task, parent) in parents_first(list, &mut report.warnings) {
+            let mut name = task.name.trim().to_string();
+            if name.chars().count() > MAX_TASK_NAME_CHARS {
+                name = truncate(&name, MAX_TASK_NAME_CHARS);
+                report
+                    .warnings
+                    .push(format!("the name of task {} was shortened", task.source_id));
+            }
+            let input = InputTodoTask {
+                todolist_id: 0,
+                name,
+                description: task.description.clone(),
+                parent_task_id: None,
+                due_date: task.due_date,
+                priority: task.priority.clone(),
+                completed_at: task.completed.then(|| chrono::Local::now().naive_local()),
+            };
+            if let Err((_, message)) = input.validate() {
+                report
+                    .errors
+                    .push(format!("task {}: {}", task.source_id, message));
+            }
+            let tags: Vec<String> = task
+                .tags
+                .iter()
+                .map(|tag| truncate(tag, MAX_TAG_CHARS))
+                .collect();
+            tag_names.extend(tags.iter().cloned());
+            match parent {
+                Some(_) => report.subtasks += 1,
+                None => report.tasks += 1,
+            }
+            tasks.push((task, parent, input, tags));
+        }
+        planned.push((list, list_name, tasks));
+    }
+    report.tags = tag_names.len();
+    if !report.errors.is_empty() || dry_run {
+        return Ok(report);
+    }
+
+    let (list_ids, task_ids) = conn.transaction(|conn| {
+        let mut list_ids = BTreeMap::new();
+        let mut task_ids: BTreeMap<String, i32> = BTreeMap::new();
+        for (list, list_name, tasks) in planned {
+            let input = InputTodoList {
+                name: list_name,
+                description: Some(list.description.clone()),
+            };
+            let created = db_insert_list(conn, caller, &input)?;
+            list_ids.insert(list.source_id.clone(), created.id);
+            for (task, parent, mut input, tags) in tasks {
+                input.todolist_id = created.id;
+                input.parent_task_id = parent.map(|parent| task_ids[parent]);
+                let inserted = db_insert_task(conn, caller, &input)?;
+                let rows: Vec<TaskTag> = tags
+                    .into_iter()
+                    .map(|tag| TaskTag {
+                        task_id: inserted.id,
+                        tag,
+                    })
+                    .collect();
+                diesel::insert_into(task_tags::table)
+                    .values(&rows)
+                    .on_conflict_do_nothing()
+                    .execute(conn)?;
+                task_ids.insert(task.source_id.clone(), inserted.id);
+            }
+        }
+        Ok::<_, AccessError>((list_ids, task_ids))
+    })?;
+    report.list_ids = list_ids;
+    report.task_ids = task_ids;
+    Ok(report)
+}
+
+async fn run_import(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    query: ExternalImportQuery,
+    payload: web::Payload,
+    source: &'static str,
+    parse: fn(&[u8]) -> Result<ImportPlan, serde_json::Error>,
+) -> HttpResponse {
+    let body: Bytes = match payload
+        .to_bytes_limited(config::get_import_max_bytes())
+        .await
+    {
+        Ok(Ok(body)) => body,
+        Ok(Err(_)) => return throw_response_bad_request(),
+        Err(_) => return throw_response_payload_too_large(),
+    };
+    let plan = match parse(&body) {
+        Ok(plan) => plan,
+        Err(e) => {
+            let response_body = serde_json::json!({ "errors": [e.to_string()] });
+            return throw_response_unprocessable_entity(response_body);
+        }
+    };
+    match web::block(move || db_apply_plan(db, caller, source, plan, query.dry_run)).await {
+        Ok(Ok(report)) => match serde_json::to_value(&report) {
+            Ok(response_body) if !report.errors.is_empty() => {
+                throw_response_unprocessable_entity(response_body)
+            }
+            Ok(response_body) if report.dry_run => throw_response_ok(response_body),
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize {} import report: {}", source, e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/import/todoist")]
+pub async fn import_todoist(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    query: web::Query<ExternalImportQuery>,
+    payload: web::Payload,
+) -> HttpResponse {
+    run_import(
+        db,
+        caller,
+        query.into_inner(),
+        payload,
+        "todoist",
+        parse_todoist,
+    )
+    .await
+}
+
+#[post("/import/trello")]
+pub async fn import_trello(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    query: web::Query<ExternalImportQuery>,
+    payload: web::Payload,
+) -> HttpResponse {
+    run_import(
+        db,
+        caller,
+        query.into_inner(),
+        payload,
+        "trello",
+        parse_trello,
+    )
+    .await
+}
@@ -0,0 +1,275 @@
+use crate::models::list_member::*;
+use crate::models::tailored_response::*;
+use crate::schema::list_members::dsl::*;
+use crate::schema::{task_assignees, task_watchers, todolists, todotasks};
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::access::{db_get_workspace_role, db_require_list_role, AccessError};
+use crate::utils::database::connection::Pool;
+use crate::utils::events::{publish_access_change, publish_member_joined};
+use crate::utils::inbox::db_notify_list_invited;
+use actix_web::web::{self};
+use actix_web::{delete, get, patch, post, HttpResponse};
+use diesel::prelude::*;
+use diesel::{delete, insert_into};
+
+/// A list must always keep at least one accepted owner. The owner rows are
+/// locked so that two owners demoting or removing each other at once can't
+/// both pass the check.
+fn db_check_not_last_owner(
+    conn: &mut PgConnection,
+    target_list_id: i32,
+    target_user_id: i32,
+) -> Result<(), AccessError> {
+    let owners: Vec<i32> = list_members
+        .filter(list_id.eq(target_list_id))
+        .filter(role.eq(ListRole::Owner))
+        .filter(accepted_at.is_not_null())
+        .select(user_id)
+        .for_update()
+        .load(conn)?;
+    if owners == [target_user_id] {
+        return Err(AccessError::Forbidden);
+    }
+    Ok(())
+}
+
+/// Someone who left a list is no longer assigned to or watching its tasks.
+fn db_drop_task_participation(
+    conn: &mut PgConnection,
+    target_list_id: i32,
+    target_user_id: i32,
+) -> Result<(), diesel::result::Error> {
+    let list_tasks = todotasks::table
+        .filter(todotasks::todolist_id.eq(target_list_id))
+        .select(todotasks::id);
+    delete(
+        task_assignees::table
+            .filter(task_assignees::user_id.eq(target_user_id))
+            .filter(task_assignees::task_id.eq_any(list_tasks)),
+    )
+    .execute(conn)?;
+    delete(
+        task_watchers::table
+            .filter(task_watchers::user_id.eq(target_user_id))
+            .filter(task_watchers::task_id.eq_any(list_tasks)),
+    )
+    .execute(conn)?;
+    Ok(())
+}
+
+fn get_list_members(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_list_id: i32,
+) -> Result<Vec<ListMember>, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_list_role(&mut conn, target_list_id, caller.id, ListRole::Viewer)?;
+    let members = list_members
+        .filter(list_id.eq(target_list_id))
+        .order(created_at.asc())
+        .load::<ListMember>(&mut conn)?;
+    Ok(members)
+}
+
+fn invite_list_member(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_list_id: i32,
+    item: web::Json<InputListMember>,
+) -> Result<ListMember, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_list_role(&mut conn, target_list_id, caller.id, ListRole::Owner)?;
+    let list_workspace_id = todolists::table
+        .find(target_list_id)
+        .select(todolists::workspace_id)
+        .first::<i32>(&mut conn)?;
+    if db_get_workspace_role(&mut conn, list_workspace_id, item.user_id)?.is_none() {
+        return Err(AccessError::Forbidden);
+    }
+    let new_member = NewListMember {
+        list_id: target_list_id,
+        user_id: item.user_id,
+        role: item.role,
+        invited_by: Some(caller.id),
+        created_at: chrono::Local::now().naive_local(),
+        accepted_at: None,
+    };
+    let member = conn.transaction(|conn| {
+        let inserted = insert_into(list_members)
+            .values(&new_member)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+        if inserted > 0 {
+            db_notify_list_invited(conn, caller.id, target_list_id, item.user_id, item.role)?;
+        }
+        list_members
+            .find((target_list_id, item.user_id))
+            .get_result(conn)
+    })?;
+    Ok(member)
+}
+
+fn accept_list_invite(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_list_id: i32,
+) -> Result<ListMember, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    conn.transaction(|conn| {
+        let member: ListMember = diesel::update(list_members.find((target_list_id, caller.id)))
+            .filter(accepted_at.is_null())
+            .set(accepted_at.eq(chrono::Local::now().naive_local()))
+            .get_result(conn)?;
+        publish_member_joined(conn, member)
+    })
+}
+
+fn update_list_member_role(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_list_id: i32,
+    target_user_id: i32,
+    item: web::Json<UpdateListMemberRole>,
+) -> Result<ListMember, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_list_role(&mut conn, target_list_id, caller.id, ListRole::Owner)?;
+    conn.transaction(|conn| {
+        if item.role != ListRole::Owner {
+            db_check_not_last_owner(conn, target_list_id, target_user_id)?;
+        }
+        let member = diesel::update(list_members.find((target_list_id, target_user_id)))
+            .set(role.eq(item.role))
+            .get_result(conn)?;
+        Ok(member)
+    })
+}
+
+/// Owners may remove anyone; every other member may only remove themselves,
+/// which covers both leaving a list and declining an invitation.
+fn revoke_list_member(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_list_id: i32,
+    target_user_id: i32,
+) -> Result<usize, AccessError> {
+    let mut conn = pool.get().unwrap();
+    if target_user_id != caller.id {
+        db_require_list_role(&mut conn, target_list_id, caller.id, ListRole::Owner)?;
+    }
+    conn.transaction(|conn| {
+        db_check_not_last_owner(conn, target_list_id, target_user_id)?;
+        let deletion = delete(list_members.find((target_list_id, target_user_id))).execute(conn)?;
+        db_drop_task_participation(conn, target_list_id, target_user_id)?;
+        publish_access_change(conn, &[target_user_id])?;
+        Ok(deletion)
+    })
+}
+
+#[get("/lists/{id}/members")]
+pub async fn get_members(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_list_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || get_list_members(db, caller, target_list_id.into_inner())).await {
+        Ok(Ok(members)) => match serde_json::to_value(members) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize list members: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/lists/{id}/members")]
+pub async fn invite_member(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_list_id: web::Path<i32>,
+    item: web::Json<InputListMember>,
+) -> HttpResponse {
+    match web::block(move || invite_list_member(db, caller, target_list_id.into_inner(), item))
+        .await
+    {
+        Ok(Ok(member)) => match serde_json::to_value(member) {
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to invite list member: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/lists/{id}/members/accept")]
+pub async fn accept_invite(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_list_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || accept_list_invite(db, caller, target_list_id.into_inner())).await {
+        Ok(Ok(member)) => match serde_json::to_value(member) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to accept list invite: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(diesel::result::Error::NotFound)) => throw_response_not_found(),
+        Ok(Err(e)) => {
+            eprintln!("Failed to accept list invite: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[patch("/lists/{id}/members/{user_id}")]
+pub async fn patch_member_role(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    path: web::Path<(i32, i32)>,
+    item: web::Json<UpdateListMemberRole>,
+) -> HttpResponse {
+    let (target_list_id, target_user_id) = path.into_inner();
+    match web::block(move || {
+        update_list_member_role(db, caller, target_list_id, target_user_id, item)
+    })
+    .await
+    {
+        Ok(Ok(member)) => match serde_json::to_value(member) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to patch list member role: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[delete("/lists/{id}/members/{user_id}")]
+pub async fn revoke_member(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    path: web::Path<(i32, i32)>,
+) -> HttpResponse {
+    let (target_list_id, target_user_id) = path.into_inner();
+    match web::block(move || revoke_list_member(db, caller, target_list_id, target_user_id)).await {
+        Ok(Ok(deletion)) => match serde_json::to_value(deletion) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to revoke list member: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
@@ -0,0 +1,162 @@
+use crate::models::list_member::ListRole;
+use crate::models::markdown::*;
+use crate::models::tailored_response::*;
+use crate::models::todo_list::{InputTodoList, TodoList};
+use crate::models::todo_task::{InputTodoTask, TodoTask};
+use crate::routes::todo_list::db_insert_list;
+use crate::routes::todo_task::db_insert_task;
+use crate::schema::{todolists, todotasks};
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::access::{db_get_active_workspace, db_require_list_role, AccessError};
+use crate::utils::database::connection::Pool;
+use crate::utils::markdown::{parse_checklist, render_checklist, ChecklistItem};
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web::{self};
+use actix_web::{get, post, HttpResponse};
+use diesel::prelude::*;
+
+const MAX_IMPORT_ITEMS: usize = 2000;
+const DEFAULT_LIST_NAME: &str = "Imported checklist";
+
+fn db_export_markdown(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    list_id: i32,
+) -> Result<String, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_list_role(&mut conn, list_id, caller.id, ListRole::Viewer)?;
+    let list = todolists::table
+        .find(list_id)
+        .first::<TodoList>(&mut conn)?;
+    let tasks = todotasks::table
+        .filter(todotasks::todolist_id.eq(list_id))
+        .order(todotasks::id.asc())
+        .load::<TodoTask>(&mut conn)?;
+    Ok(render_checklist(&list, &tasks))
+}
+
+/// Creates the list and its tasks in one transaction, parents before their
+/// subtasks, once every item has passed validation.
+fn db_import_markdown(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    query: MarkdownImportQuery,
+    body: String,
+) -> Result<Option<MarkdownImportReport>, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_get_active_workspace(&mut conn, caller.id)?;
+    let checklist = parse_checklist(&body);
+    if checklist.items.len() > MAX_IMPORT_ITEMS {
+        return Ok(None);
+    }
+    let list_input = InputTodoList {
+        name: query
+            .name
+            .or(checklist.title)
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or_else(|| String::from(DEFAULT_LIST_NAME)),
+        description: checklist.description,
+    };
+
+    let now = chrono::Local::now().naive_local();
+    let to_input = |item: &ChecklistItem| InputTodoTask {
+        todolist_id: 0,
+        name: item.name.clone(),
+        description: item.description.clone(),
+        parent_task_id: None,
+        due_date: None,
+        priority: None,
+        completed_at: item.checked.then_some(now),
+    };
+    let mut errors = Vec::new();
+    if list_input.name.chars().count() > 255 {
+        errors.push(MarkdownLineError {
+            line: 1,
+            message: String::from("list name is longer than 255 characters"),
+        });
+    }
+    for item in &checklist.items {
+        if let Err((_, message)) = to_input(item).validate() {
+            errors.push(MarkdownLineError {
+                line: item.line,
+                message: message.to_string(),
+            });
+        }
+    }
+    if !errors.is_empty() || query.dry_run {
+        return Ok(Some(MarkdownImportReport {
+            dry_run: query.dry_run,
+            items: checklist.items.len(),
+            errors,
+            list: None,
+            tasks: Vec::new(),
+        }));
+    }
+
+    let (list, tasks) = conn.transaction(|conn| {
+        let list = db_insert_list(conn, caller, &list_input)?;
+        let mut tasks: Vec<TodoTask> = Vec::with_capacity(checklist.items.len());
+        for item in &checklist.items {
+            let input = InputTodoTask {
+                todolist_id: list.id,
+                parent_task_id: item.parent.map(|parent| tasks[parent].id),
+                ..to_input(item)
+            };
+            tasks.push(db_insert_task(conn, caller, &input)?);
+        }
+        Ok::<_, AccessError>((list, tasks))
+    })?;
+    Ok(Some(MarkdownImportReport {
+        dry_run: false,
+        items: tasks.len(),
+        errors,
+        list: Some(list),
+        tasks,
+    }))
+}
+
+#[get("/lists/{id}/export.md")]
+pub async fn export_list_markdown(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    list_id: web::Path<i32>,
+) -> HttpResponse {
+    let list_id = list_id.into_inner();
+    match web::block(move || db_export_markdown(db, caller, list_id)).await {
+        Ok(Ok(body)) => HttpResponse::Ok()
+            .content_type("text/markdown; charset=utf-8")
+            .insert_header(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(format!("list-{}.md", list_id))],
+            })
+            .body(body),
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/import/markdown")]
+pub async fn import_markdown(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    query: web::Query<MarkdownImportQuery>,
+    body: String,
+) -> HttpResponse {
+    let query = query.into_inner();
+    match web::block(move || db_import_markdown(db, caller, query, body)).await {
+        Ok(Ok(Some(report))) => match serde_json::to_value(&report) {
+            Ok(response_body) if !report.errors.is_empty() => {
+                throw_response_unprocessable_entity(response_body)
+            }
+            Ok(response_body) if report.dry_run => throw_response_ok(response_body),
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize Markdown import report: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Ok(None)) => throw_response_payload_too_large(),
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
@@ -0,0 +1,149 @@
+use crate::models::notification::*;
+use crate::models::tailored_response::*;
+use crate::schema::notifications::dsl::*;
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::connection::Pool;
+use actix_web::web::{self};
+use actix_web::{get, post, HttpResponse};
+use diesel::prelude::*;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+fn get_notification_page(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    query: web::Query<NotificationQuery>,
+) -> Result<NotificationPage, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    let page_size = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let mut items = notifications
+        .filter(user_id.eq(caller.id))
+        .order(id.desc())
+        .limit(page_size + 1)
+        .into_boxed();
+    if query.unread {
+        items = items.filter(read_at.is_null());
+    }
+    if let Some(cursor) = query.before {
+        items = items.filter(id.lt(cursor));
+    }
+    let mut page = items.load::<Notification>(&mut conn)?;
+    let next_before = if page.len() as i64 > page_size {
+        page.truncate(page_size as usize);
+        page.last().map(|notification| notification.id)
+    } else {
+        None
+    };
+    let unread_count = notifications
+        .filter(user_id.eq(caller.id))
+        .filter(read_at.is_null())
+        .count()
+        .get_result(&mut conn)?;
+    Ok(NotificationPage {
+        notifications: page,
+        unread_count,
+        next_before,
+    })
+}
+
+/// Other users' notifications are reported as missing. Marking an already
+/// read notification keeps its original `read_at`.
+fn mark_notification_read(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    notification_id: i64,
+) -> Result<Notification, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    let now = chrono::Local::now().naive_local();
+    conn.transaction(|conn| {
+        diesel::update(notifications.find(notification_id))
+            .filter(user_id.eq(caller.id))
+            .filter(read_at.is_null())
+            .set(read_at.eq(now))
+            .execute(conn)?;
+        notifications
+            .find(notification_id)
+            .filter(user_id.eq(caller.id))
+            .get_result(conn)
+    })
+}
+
+fn mark_all_notifications_read(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    diesel::update(notifications)
+        .filter(user_id.eq(caller.id))
+        .filter(read_at.is_null())
+        .set(read_at.eq(chrono::Local::now().naive_local()))
+        .execute(&mut conn)
+}
+
+#[get("/notifications")]
+pub async fn get_notifications(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    query: web::Query<NotificationQuery>,
+) -> HttpResponse {
+    match web::block(move || get_notification_page(db, caller, query)).await {
+        Ok(Ok(page)) => match serde_json::to_value(page) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize notifications: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => {
+            eprintln!("Failed to get notifications: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/notifications/{id}/read")]
+pub async fn mark_read(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    notification_id: web::Path<i64>,
+) -> HttpResponse {
+    match web::block(move || mark_notification_read(db, caller, notification_id.into_inner())).await
+    {
+        Ok(Ok(notification)) => match serde_json::to_value(notification) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to mark notification read: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(diesel::result::Error::NotFound)) => throw_response_not_found(),
+        Ok(Err(e)) => {
+            eprintln!("Failed to mark notification read: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/notifications/read-all")]
+pub async fn mark_all_read(db: web::Data<Pool>, caller: AuthenticatedUser) -> HttpResponse {
+    match web::block(move || mark_all_notifications_read(db, caller)).await {
+        Ok(Ok(updated)) => match serde_json::to_value(updated) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to mark notifications read: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => {
+            eprintln!("Failed to mark notifications read: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
@@ -0,0 +1,497 @@
+use crate::models::oidc::*;
+use crate::models::tailored_response::*;
+use crate::models::two_factor::LoginOutcome;
+use crate::models::user::{NewUser, User};
+use crate::routes::auth::{db_begin_sign_in, lower};
+use crate::routes::workspace::{db_create_workspace, PERSONAL_WORKSPACE_NAME};
+use crate::schema::{oidc_identities, oidc_login_attempts, users};
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::connection::Pool;
+use crate::utils::events::record_event;
+use crate::utils::oidc::{generate_code_verifier, OidcError, OidcProvider};
+use crate::utils::sessions::ClientInfo;
+use crate::utils::token;
+use actix_web::web::{self};
+use actix_web::{post, HttpRequest, HttpResponse};
+use diesel::prelude::*;
+use std::sync::Arc;
+
+/// Stores what is needed to finish the sign-in, clearing out attempts that
+/// were never finished.
+fn start_login_attempt(
+    pool: web::Data<Pool>,
+    new_attempt: NewOidcLoginAttempt,
+) -> Result<(), diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    diesel::delete(
+        oidc_login_attempts::table
+            .filter(oidc_login_attempts::expires_at.lt(new_attempt.created_at)),
+    )
+    .execute(&mut conn)?;
+    diesel::insert_into(oidc_login_attempts::table)
+        .values(&new_attempt)
+        .execute(&mut conn)?;
+    Ok(())
+}
+
+/// Each `state` can be used once, so a replayed redirect gets nowhere.
+fn db_redeem_login_attempt(
+    conn: &mut PgConnection,
+    plain_state: &str,
+) -> Result<Option<OidcLoginAttempt>, diesel::result::Error> {
+    let now = chrono::Local::now().naive_local();
+    diesel::update(oidc_login_attempts::table)
+        .filter(oidc_login_attempts::state_hash.eq(token::hash_token(plain_state)))
+        .filter(oidc_login_attempts::used_at.is_null())
+        .filter(oidc_login_attempts::expires_at.gt(now))
+        .set(oidc_login_attempts::used_at.eq(now))
+        .returning(OidcLoginAttempt::as_returning())
+        .get_result(conn)
+        .optional()
+}
+
+fn redeem_login_attempt(
+    pool: web::Data<Pool>,
+    plain_state: String,
+) -> Result<Option<OidcLoginAttempt>, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    db_redeem_login_attempt(&mut conn, &plain_state)
+}
+
+/// The address to sign in with, if the provider vouches for it.
+fn verified_email(claims: &IdTokenClaims) -> Option<String> {
+    claims.email.clone().filter(|_| claims.email_verified)
+}
+
+/// The provider's names when it sends them, otherwise whatever can be made of
+/// the full name or the address.
+fn names_from_claims(claims: &IdTokenClaims, email: &str) -> (String, String) {
+    if let Some(given_name) = &claims.given_name {
+        return (
+            given_name.clone(),
+            claims.family_name.clone().unwrap_or_default(),
+        );
+    }
+    if let Some((first, last)) = claims.name.as_deref().and_then(|name| name.split_once(' ')) {
+        return (first.to_string(), last.trim().to_string());
+    }
+    let fallback = claims
+        .name
+        .clone()
+        .unwrap_or_else(|| email.split('@').next().unwrap_or(email).to_string());
+    (fallback, String::new())
+}
+
+/// Finds the user by their identity at the provider, then by verified email,
+/// creating one when neither matches. The provider vouched for the address,
+/// so a new user has it verified from the start. `None` when the address
+/// belongs to an account that never verified it: whoever created that
+/// account may not own the address, so the owner has to sign in locally and
+/// link the identity themselves.
+fn db_find_or_create_oidc_user(
+    conn: &mut PgConnection,
+    claims: &IdTokenClaims,
+    email: &str,
+) -> Result<Option<User>, diesel::result::Error> {
+    let now = chrono::Local::now().naive_local();
+    conn.transaction(|conn| {
+        let linked = diesel::update(oidc_identities::table)
+            .filter(oidc_identities::issuer.eq(&claims.iss))
+            .filter(oidc_identities::subject.eq(&claims.sub))
+            .set((
+                oidc_identities::email.eq(email),
+                oidc_identities::last_login_at.eq(now),
+            ))
+            .returning(OidcIdentity::as_returning())
+            .get_result(conn)
+            .optional()?;
+        if let Some(identity) = linked {
+            return users::table
+                .find(identity.user_id)
+                .first::<User>(conn)
+                .map(Some);
+        }
+        let existing = users::table
+            .filter(lower(users::email).eq(email.to_lowercase()))
+            .first::<User>(conn)
+            .optional()?;
+        let user = match existing {
+            Some(user) if user.email_verified_at.is_none() => return Ok(None),
+            Some(user) => user,
+            None => {
+                let (first_name, last_name) = names_from_claims(claims, email);
+                let new_user = NewUser {
+                    first_name: &first_name,
+                    last_name: &last_name,
+                    email,
+                    created_at: now,
+                    modified_at: now,
+                };
+                let user: User = diesel::insert_into(users::table)
+                    .values(&new_user)
+                    .get_result(conn)?;
+                db_create_workspace(conn, user.id, PERSONAL_WORKSPACE_NAME)?;
+                let user = diesel::update(users::table.find(user.id))
+                    .set(users::email_verified_at.eq(now))
+                    .get_result::<User>(conn)?;
+                record_event(conn, "user.created", &user, &[user.id])?;
+                user
+            }
+        };
+        diesel::insert_into(oidc_identities::table)
+            .values(NewOidcIdentity {
+                user_id: user.id,
+                issuer: claims.iss.clone(),
+                subject: claims.sub.clone(),
+                email: email.to_string(),
+                created_at: now,
+                last_login_at: now,
+            })
+            .execute(conn)?;
+        Ok(Some(user))
+    })
+}
+
+fn sign_in_with_oidc(
+    pool: web::Data<Pool>,
+    client: ClientInfo,
+    claims: IdTokenClaims,
+    email: String,
+) -> Result<Option<LoginOutcome>, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    let Some(user) = db_find_or_create_oidc_user(&mut conn, &claims, &email)? else {
+        return Ok(None);
+    };
+    db_begin_sign_in(&mut conn, user, &client).map(Some)
+}
+
+/// Links the identity to the signed-in caller. The caller's address counts as
+/// verified when the provider vouches for the same one. `None` when the
+/// identity already belongs to someone else.
+fn link_oidc_identity(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    claims: IdTokenClaims,
+    email: String,
+) -> Result<Option<OidcIdentity>, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    let now = chrono::Local::now().naive_local();
+    conn.transaction(|conn| {
+        let existing = oidc_identities::table
+            .filter(oidc_identities::issuer.eq(&claims.iss))
+            .filter(oidc_identities::subject.eq(&claims.sub))
+            .select(OidcIdentity::as_select())
+            .for_update()
+            .first(conn)
+            .optional()?;
+        let identity = match existing {
+            Some(identity) if identity.user_id != caller.id => return Ok(None),
+            Some(identity) => diesel::update(oidc_identities::table.find(identity.id))
+                .set(oidc_identities::email.eq(&email))
+                .returning(OidcIdentity::as_returning())
+                .get_result(conn)?,
+            None => diesel::insert_into(oidc_identities::table)
+                .values(NewOidcIdentity {
+                    user_id: caller.id,
+                    issuer: claims.iss.clone(),
+                    subject: claims.sub.clone(),
+                    email: email.clone(),
+                    created_at: now,
+                    last_login_at: now,
+                })
+                .returning(OidcIdentity::as_returning())
+                .get_result(conn)?,
+        };
+        if claims.email_verified {
+            diesel::update(users::table.find(caller.id))
+                .filter(lower(users::email).eq(email.to_lowercase()))
+                .filter(users::email_verified_at.is_null())
+                .set(users::email_verified_at.eq(now))
+                .execute(conn)?;
+        }
+        Ok(Some(identity))
+    })
+}
+
+/// A provider that refused the code or sent a token that doesn't check out
+/// means the sign-in failed; anything else means it couldn't be reached.
+fn throw_response_oidc_error(error: OidcError) -> HttpResponse {
+    eprintln!("OIDC sign-in failed: {}", error);
+    match error {
+        OidcError::Http(e) if e.status().is_none() => throw_response_bad_gateway(),
+        _ => throw_response_unauthorized(),
+    }
+}
+
+#[post("/auth/oidc/authorize")]
+pub async fn oidc_authorize(
+    db: web::Data<Pool>,
+    provider: web::Data<Option<Arc<OidcProvider>>>,
+) -> HttpResponse {
+    let Some(provider) = provider.as_deref() else {
+        return throw_response_not_found();
+    };
+    let state = token::generate_token();
+    let nonce = token::generate_token();
+    let code_verifier = generate_code_verifier();
+    let now = chrono::Local::now().naive_local();
+    let new_attempt = NewOidcLoginAttempt {
+        state_hash: token::hash_token(&state),
+        code_verifier: code_verifier.clone(),
+        nonce: nonce.clone(),
+        expires_at: now + chrono::Duration::minutes(OIDC_LOGIN_MINUTES),
+        created_at: now,
+    };
+    let expires_at = new_attempt.expires_at;
+    let authorization_url = match provider
+        .authorization_url(&state, &nonce, &code_verifier)
+        .await
+    {
+        Ok(url) => url,
+        Err(e) => {
+            eprintln!("Failed to reach the identity provider: {}", e);
+            return throw_response_bad_gateway();
+        }
+    };
+    match web::block(move || start_login_attempt(db, new_attempt)).await {
+        Ok(Ok(())) => match serde_json::to_value(OidcAuthorization {
+            authorization_url,
+            state,
+            expires_at,
+        }) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to start OIDC sign-in: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => {
+            eprintln!("Failed to start OIDC sign-in: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
+
+/// Redeems the callback's `state` and code for the provider's verified
+/// claims, or the response to send instead.
+async fn redeem_callback(
+    db: web::Data<Pool>,
+    provider: &OidcProvider,
+    callback: OidcCallback,
+) -> Result<IdTokenClaims, HttpResponse> {
+    let OidcCallback { code, state } = callback;
+    let attempt = match web::block(move || redeem_login_attempt(db, state)).await {
+        Ok(Ok(Some(attempt))) => attempt,
+        Ok(Ok(None)) => return Err(throw_response_bad_request()),
+        Ok(Err(e)) => {
+            eprintln!("Failed to finish OIDC sign-in: {}", e);
+            return Err(throw_response_error());
+        }
+        Err(_) => return Err(throw_response_error()),
+    };
+    provider
+        .exchange_code(&code, &attempt.code_verifier, &attempt.nonce)
+        .await
+        .map_err(throw_response_oidc_error)
+}
+
+#[post("/auth/oidc/callback")]
+pub async fn oidc_callback(
+    db: web::Data<Pool>,
+    provider: web::Data<Option<Arc<OidcProvider>>>,
+    req: HttpRequest,
+    item: web::Json<OidcCallback>,
+) -> HttpResponse {
+    let Some(provider) = provider.as_deref() else {
+        return throw_response_not_found();
+    };
+    let claims = match redeem_callback(db.clone(), provider, item.into_inner()).await {
+        Ok(claims) => claims,
+        Err(response) => return response,
+    };
+    let Some(email) = verified_email(&claims) else {
+        return throw_response_forbidden();
+    };
+    let client = ClientInfo::from_request(&req);
+    match web::block(move || sign_in_with_oidc(db, client, claims, email)).await {
+        Ok(Ok(Some(outcome))) => match serde_json::to_value(outcome) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to finish OIDC sign-in: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Ok(None)) => throw_response_conflict(),
+        Ok(Err(e)) => {
+            eprintln!("Failed to finish OIDC sign-in: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
+
+/// Finishes a sign-in at the provider started with `/auth/oidc/authorize`,
+/// linking the identity to the signed-in caller instead of signing in with it.
+#[post("/auth/oidc/link")]
+pub async fn oidc_link(
+    db: web::Data<Pool>,
+    provider: web::Data<Option<Arc<OidcProvider>>>,
+    caller: AuthenticatedUser,
+    item: web::Json<OidcCallback>,
+) -> HttpResponse {
+    let Some(provider) = provider.as_deref() else {
+        return throw_response_not_found();
+    };
+    let claims = match redeem_callback(db.clone(), provider, item.into_inner()).await {
+        Ok(claims) => claims,
+        Err(response) => return response,
+    };
+    let email = claims.email.clone().unwrap_or_default();
+    match web::block(move || link_oidc_identity(db, caller, claims, email)).await {
+        Ok(Ok(Some(identity))) => match serde_json::to_value(identity) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to link OIDC identity: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Ok(None)) => throw_response_conflict(),
+        Ok(Err(e)) => {
+            eprintln!("Failed to link OIDC identity: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::{db_insert_user, test_connection};
+
+    fn claims(subject: &str, email: &str, email_verified: bool) -> IdTokenClaims {
+        IdTokenClaims {
+            iss: String::from("https://id.example.com"),
+            sub: subject.to_string(),
+            nonce: None,
+            email: Some(email.to_string()),
+            email_verified,
+            given_name: None,
+            family_name: None,
+            name: Some(String::from("Sam Doe")),
+        }
+    }
+
+    fn db_insert_attempt(conn: &mut PgConnection, state: &str, expires_in_minutes: i64) {
+        let now = chrono::Local::now().naive_local();
+        diesel::insert_into(oidc_login_attempts::table)
+            .values(&NewOidcLoginAttempt {
+                state_hash: token::hash_token(state),
+                code_verifier: String::from("the-verifier"),
+                nonce: String::from("the-nonce"),
+                expires_at: now + chrono::Duration::minutes(expires_in_minutes),
+                created_at: now,
+            })
+            .execute(conn)
+            .unwrap();
+    }
+
+    fn db_identity_count(conn: &mut PgConnection, user_id: i32) -> i64 {
+        oidc_identities::table
+            .filter(oidc_identities::user_id.eq(user_id))
+            .count()
+            .get_result(conn)
+            .unwrap()
+    }
+
+    #[test]
+    fn unverified_provider_emails_are_not_used() {
+        assert_eq!(
+            verified_email(&claims("s", "someone@example.com", true)).as_deref(),
+            Some("someone@example.com")
+        );
+        assert_eq!(
+            verified_email(&claims("s", "someone@example.com", false)),
+            None
+        );
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn states_redeem_once() {
+        let mut conn = test_connection();
+        let state = token::generate_token();
+        db_insert_attempt(&mut conn, &state, OIDC_LOGIN_MINUTES);
+        let attempt = db_redeem_login_attempt(&mut conn, &state).unwrap().unwrap();
+        assert_eq!(attempt.code_verifier, "the-verifier");
+        assert_eq!(attempt.nonce, "the-nonce");
+        assert!(db_redeem_login_attempt(&mut conn, &state)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn unknown_and_expired_states_are_refused() {
+        let mut conn = test_connection();
+        let state = token::generate_token();
+        db_insert_attempt(&mut conn, &state, -1);
+        assert!(db_redeem_login_attempt(&mut conn, &state)
+            .unwrap()
+            .is_none());
+        assert!(db_redeem_login_attempt(&mut conn, &token::generate_token())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn unverified_accounts_are_not_linked_by_email() {
+        let mut conn = test_connection();
+        let user = db_insert_user(&mut conn, false);
+        let found =
+            db_find_or_create_oidc_user(&mut conn, &claims("s", &user.email, true), &user.email)
+                .unwrap();
+        assert!(found.is_none());
+        assert_eq!(db_identity_count(&mut conn, user.id), 0);
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn verified_accounts_are_linked_by_email() {
+        let mut conn = test_connection();
+        let user = db_insert_user(&mut conn, true);
+        let address = user.email.to_uppercase();
+        let found = db_find_or_create_oidc_user(&mut conn, &claims("s", &address, true), &address)
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, user.id);
+        assert_eq!(db_identity_count(&mut conn, user.id), 1);
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn linked_identities_sign_in_after_the_address_changes() {
+        let mut conn = test_connection();
+        let address = format!("{}@example.com", &token::generate_token()[..16]);
+        let created =
+            db_find_or_create_oidc_user(&mut conn, &claims("s", &address, true), &address)
+                .unwrap()
+                .unwrap();
+        assert!(created.email_verified_at.is_some());
+        assert_eq!(created.first_name, "Sam");
+        let workspace_name: String = crate::schema::workspaces::table
+            .find(created.active_workspace_id.unwrap())
+            .select(crate::schema::workspaces::name)
+            .first(&mut conn)
+            .unwrap();
+        assert_eq!(workspace_name, PERSONAL_WORKSPACE_NAME);
+
+        let moved = format!("moved-{}", address);
+        let found = db_find_or_create_oidc_user(&mut conn, &claims("s", &moved, true), &moved)
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, created.id);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::models::reminder_preference::*;
+use crate::models::tailored_response::*;
+use crate::schema::reminder_preferences::dsl::*;
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::access::AccessError;
+use crate::utils::database::connection::Pool;
+use actix_web::web::{self};
+use actix_web::{get, patch, HttpResponse};
+use diesel::insert_into;
+use diesel::prelude::*;
+
+fn db_get_preferences(
+    conn: &mut PgConnection,
+    target_user_id: i32,
+) -> Result<ReminderPreference, diesel::result::Error> {
+    let preference = reminder_preferences
+        .find(target_user_id)
+        .get_result::<ReminderPreference>(conn)
+        .optional()?;
+    Ok(preference.unwrap_or_else(|| ReminderPreference::default_for(target_user_id)))
+}
+
+/// Users may only see and change their own preferences.
+fn get_user_preferences(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: i32,
+) -> Result<ReminderPreference, AccessError> {
+    if caller.id != target_user_id {
+        return Err(AccessError::Forbidden);
+    }
+    let mut conn = pool.get().unwrap();
+    Ok(db_get_preferences(&mut conn, target_user_id)?)
+}
+
+fn update_user_preferences(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: i32,
+    item: web::Json<UpdateReminderPreference>,
+) -> Result<ReminderPreference, AccessError> {
+    if caller.id != target_user_id {
+        return Err(AccessError::Forbidden);
+    }
+    let mut conn = pool.get().unwrap();
+    conn.transaction(|conn| {
+        let current = db_get_preferences(conn, target_user_id)?;
+        let updated = ReminderPreference {
+            user_id: target_user_id,
+            email_enabled: item.email_enabled.unwrap_or(current.email_enabled),
+            window_hours: item.window_hours.unwrap_or(current.window_hours),
+            digest: item.digest.unwrap_or(current.digest),
+            modified_at: chrono::Local::now().naive_local(),
+        };
+        let preference = insert_into(reminder_preferences)
+            .values(&updated)
+            .on_conflict(user_id)
+            .do_update()
+            .set(&updated)
+            .get_result(conn)?;
+        Ok(preference)
+    })
+}
+
+#[get("/users/{id}/reminder-preferences")]
+pub async fn get_reminder_preferences(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || get_user_preferences(db, caller, target_user_id.into_inner())).await {
+        Ok(Ok(preference)) => match serde_json::to_value(preference) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize reminder preferences: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[patch("/users/{id}/reminder-preferences")]
+pub async fn patch_reminder_preferences(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: web::Path<i32>,
+    item: web::Json<UpdateReminderPreference>,
+) -> HttpResponse {
+    if item
+        .window_hours
+        .is_some_and(|hours| !(MIN_WINDOW_HOURS..=MAX_WINDOW_HOURS).contains(&hours))
+    {
+        return throw_response_bad_request();
+    }
+    match web::block(move || update_user_preferences(db, caller, target_user_id.into_inner(), item))
+        .await
+    {
+        Ok(Ok(preference)) => match serde_json::to_value(preference) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to patch reminder preferences: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
@@ -0,0 +1,130 @@
+use crate::models::session::*;
+use crate::models::tailored_response::*;
+use crate::schema::sessions;
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::connection::Pool;
+use crate::utils::sessions::{db_refresh_session, db_revoke_sessions, ClientInfo};
+use actix_web::web::{self};
+use actix_web::{delete, get, post, HttpRequest, HttpResponse};
+use diesel::prelude::*;
+
+fn get_active_sessions(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+) -> Result<Vec<ActiveSession>, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    let active = sessions::table
+        .filter(sessions::user_id.eq(caller.id))
+        .filter(sessions::revoked_at.is_null())
+        .filter(sessions::expires_at.gt(chrono::Local::now().naive_local()))
+        .order(sessions::last_seen_at.desc())
+        .select(Session::as_select())
+        .load(&mut conn)?;
+    Ok(active
+        .into_iter()
+        .map(|session| ActiveSession {
+            current: caller.session_id == Some(session.id),
+            session,
+        })
+        .collect())
+}
+
+/// Signing out another device is a remote logout; signing out the current
+/// one is a plain logout.
+fn revoke_session(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_session_id: i32,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    let reason = if caller.session_id == Some(target_session_id) {
+        RevokedReason::Logout
+    } else {
+        RevokedReason::RemoteLogout
+    };
+    db_revoke_sessions(&mut conn, caller.id, Some(target_session_id), reason)
+}
+
+#[post("/auth/refresh")]
+pub async fn refresh_session(
+    db: web::Data<Pool>,
+    req: HttpRequest,
+    item: web::Json<RefreshSession>,
+) -> HttpResponse {
+    let client = ClientInfo::from_request(&req);
+    let refresh_token = item.into_inner().refresh_token;
+    match web::block(move || {
+        let mut conn = db.get().unwrap();
+        db_refresh_session(&mut conn, &refresh_token, &client)
+    })
+    .await
+    {
+        Ok(Ok(Some(tokens))) => match serde_json::to_value(tokens) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to refresh session: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Ok(None)) => throw_response_unauthorized(),
+        Ok(Err(e)) => {
+            eprintln!("Failed to refresh session: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
+
+/// Ends the session the request was made with.
+#[post("/auth/logout")]
+pub async fn logout(db: web::Data<Pool>, caller: AuthenticatedUser) -> HttpResponse {
+    let Some(session_id) = caller.session_id else {
+        return throw_response_bad_request();
+    };
+    match web::block(move || revoke_session(db, caller, session_id)).await {
+        Ok(Ok(deletion)) => match serde_json::to_value(deletion) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to log out: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e.into()),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[get("/auth/sessions")]
+pub async fn get_sessions(db: web::Data<Pool>, caller: AuthenticatedUser) -> HttpResponse {
+    match web::block(move || get_active_sessions(db, caller)).await {
+        Ok(Ok(active)) => match serde_json::to_value(active) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to list sessions: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e.into()),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[delete("/auth/sessions/{id}")]
+pub async fn delete_session(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_session_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || revoke_session(db, caller, target_session_id.into_inner())).await {
+        Ok(Ok(0)) => throw_response_not_found(),
+        Ok(Ok(deletion)) => match serde_json::to_value(deletion) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to revoke session: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e.into()),
+        Err(_) => throw_response_error(),
+    }
+}
@@ -0,0 +1,194 @@
+use crate::models::list_member::ListRole;
+use crate::models::share_link::*;
+use crate::models::tailored_response::*;
+use crate::models::{todo_list::TodoList, todo_task::TodoTask};
+use crate::schema::share_links::dsl::*;
+use crate::schema::{todolists, todotasks};
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::access::{db_require_list_role, AccessError};
+use crate::utils::database::connection::Pool;
+use crate::utils::{password, token};
+use actix_web::web::{self};
+use actix_web::{delete, get, post, HttpRequest, HttpResponse};
+use diesel::insert_into;
+use diesel::prelude::*;
+
+pub const SHARE_PASSWORD_HEADER: &str = "X-Share-Password";
+
+fn add_share_link(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_list_id: i32,
+    item: web::Json<InputShareLink>,
+) -> Result<CreatedShareLink, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_list_role(&mut conn, target_list_id, caller.id, ListRole::Owner)?;
+    let plain_token = token::generate_token();
+    let hashed_password = item
+        .password
+        .as_deref()
+        .filter(|plain| !plain.is_empty())
+        .map(|plain| password::hash_password(plain).expect("Failed to hash password"));
+    let new_link = NewShareLink {
+        list_id: target_list_id,
+        created_by: Some(caller.id),
+        token_hash: token::hash_token(&plain_token),
+        password_hash: hashed_password,
+        expires_at: item.expires_at,
+        created_at: chrono::Local::now().naive_local(),
+    };
+    let link = insert_into(share_links)
+        .values(&new_link)
+        .returning(ShareLink::as_returning())
+        .get_result(&mut conn)?;
+    Ok(CreatedShareLink {
+        link,
+        token: plain_token,
+    })
+}
+
+fn get_list_share_links(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_list_id: i32,
+) -> Result<Vec<ShareLink>, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_list_role(&mut conn, target_list_id, caller.id, ListRole::Owner)?;
+    let links = share_links
+        .filter(list_id.eq(target_list_id))
+        .order(created_at.asc())
+        .select(ShareLink::as_select())
+        .load(&mut conn)?;
+    Ok(links)
+}
+
+fn revoke_share_link(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_list_id: i32,
+    link_id: i32,
+) -> Result<ShareLink, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_list_role(&mut conn, target_list_id, caller.id, ListRole::Owner)?;
+    let link = diesel::update(share_links.find(link_id))
+        .filter(list_id.eq(target_list_id))
+        .set(revoked_at.eq(chrono::Local::now().naive_local()))
+        .returning(ShareLink::as_returning())
+        .get_result(&mut conn)?;
+    Ok(link)
+}
+
+/// Expired, revoked and unknown tokens all look the same to the caller.
+fn db_get_shared_list(
+    pool: web::Data<Pool>,
+    plain_token: String,
+    supplied_password: Option<String>,
+) -> Result<SharedList, AccessError> {
+    let mut conn = pool.get().unwrap();
+    let link = share_links
+        .filter(token_hash.eq(token::hash_token(&plain_token)))
+        .select(ShareLink::as_select())
+        .get_result(&mut conn)?;
+    if !link.is_active(chrono::Local::now().naive_local()) {
+        return Err(diesel::result::Error::NotFound.into());
+    }
+    if let Some(expected) = &link.password_hash {
+        match supplied_password {
+            Some(plain) if password::verify_password(&plain, expected) => {}
+            _ => return Err(AccessError::Forbidden),
+        }
+    }
+    let list = todolists::table
+        .find(link.list_id)
+        .get_result::<TodoList>(&mut conn)?;
+    let tasks = todotasks::table
+        .filter(todotasks::todolist_id.eq(link.list_id))
+        .order(todotasks::id.asc())
+        .load::<TodoTask>(&mut conn)?;
+    Ok(SharedList { list, tasks })
+}
+
+#[post("/lists/{id}/share-links")]
+pub async fn create_share_link(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_list_id: web::Path<i32>,
+    item: web::Json<InputShareLink>,
+) -> HttpResponse {
+    match web::block(move || add_share_link(db, caller, target_list_id.into_inner(), item)).await {
+        Ok(Ok(link)) => match serde_json::to_value(link) {
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to create share link: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[get("/lists/{id}/share-links")]
+pub async fn get_share_links(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_list_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || get_list_share_links(db, caller, target_list_id.into_inner())).await {
+        Ok(Ok(links)) => match serde_json::to_value(links) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize share links: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[delete("/lists/{id}/share-links/{link_id}")]
+pub async fn delete_share_link(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    path: web::Path<(i32, i32)>,
+) -> HttpResponse {
+    let (target_list_id, link_id) = path.into_inner();
+    match web::block(move || revoke_share_link(db, caller, target_list_id, link_id)).await {
+        Ok(Ok(link)) => match serde_json::to_value(link) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to revoke share link: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[get("/shared/{token}")]
+pub async fn get_shared_list(
+    db: web::Data<Pool>,
+    req: HttpRequest,
+    plain_token: web::Path<String>,
+) -> HttpResponse {
+    let supplied_password = req
+        .headers()
+        .get(SHARE_PASSWORD_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+    match web::block(move || db_get_shared_list(db, plain_token.into_inner(), supplied_password))
+        .await
+    {
+        Ok(Ok(shared)) => match serde_json::to_value(shared) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize shared list: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
@@ -0,0 +1,332 @@
+use crate::models::list_member::ListRole;
+use crate::models::sync::*;
+use crate::models::tailored_response::*;
+use crate::models::todo_list::TodoList;
+use crate::models::todo_task::TodoTask;
+use crate::models::tombstone::Tombstone;
+use crate::routes::attachment::{
+    db_list_attachment_hashes, db_release_attachment_blobs, db_task_attachment_hashes,
+};
+use crate::routes::todo_list::{db_delete_list, db_insert_list, db_update_list};
+use crate::routes::todo_task::{db_delete_task, db_insert_task, db_update_task};
+use crate::schema::{list_members, todolists, todotasks, tombstones};
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::access::{
+    db_get_active_workspace, db_require_list_role, db_require_task_role, visible_list_ids,
+    AccessError,
+};
+use crate::utils::database::connection::Pool;
+use crate::utils::events::{db_pruned_event_id, db_settled_event_id};
+use crate::utils::storage::AttachmentStorage;
+use actix_web::web::{self};
+use actix_web::{get, post, HttpResponse};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use std::collections::HashSet;
+
+const MAX_BATCH_SIZE: usize = 500;
+
+enum SyncOutcome {
+    Applied(serde_json::Value),
+    Conflict(serde_json::Value),
+    Invalid(&'static str),
+}
+
+fn to_json<T: serde::Serialize>(value: T) -> serde_json::Value {
+    serde_json::to_value(value).expect("Failed to serialize sync row")
+}
+
+/// Runs in a single repeatable-read snapshot. The token handed out is the
+/// settled event id, a safe resume point even while other writers are still
+/// committing; rows stamped past it are sent again next time. `None` when
+/// `since` is older than the retained tombstones.
+fn get_changes(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    since: Option<i64>,
+) -> Result<Option<SyncChanges>, AccessError> {
+    let mut conn = pool.get().unwrap();
+    conn.build_transaction()
+        .repeatable_read()
+        .read_only()
+        .run(|conn| {
+            let workspace_id = db_get_active_workspace(conn, caller.id)?;
+            let pruned = db_pruned_event_id(conn)?;
+            if since.is_some_and(|since| since < pruned) {
+                return Ok(None);
+            }
+            let token = db_settled_event_id(conn)?;
+            let visible: Vec<i32> = visible_list_ids(caller.id, workspace_id).load(conn)?;
+
+            let Some(since) = since else {
+                let lists = todolists::table
+                    .filter(todolists::id.eq_any(&visible))
+                    .order(todolists::id.asc())
+                    .load::<TodoList>(conn)?;
+                let tasks = todotasks::table
+                    .filter(todotasks::todolist_id.eq_any(&visible))
+                    .order(todotasks::id.asc())
+                    .load::<TodoTask>(conn)?;
+                return Ok(Some(SyncChanges {
+                    token,
+                    lists,
+                    tasks,
+                    tombstones: Vec::new(),
+                }));
+            };
+
+            // lists shared with the caller since the token are sent in full
+            let joined: Vec<i32> = list_members::table
+                .filter(list_members::user_id.eq(caller.id))
+                .filter(list_members::list_id.eq_any(&visible))
+                .filter(list_members::change_seq.gt(since))
+                .select(list_members::list_id)
+                .load(conn)?;
+            let lists = todolists::table
+                .filter(todolists::id.eq_any(&visible))
+                .filter(
+                    todolists::change_seq
+                        .gt(since)
+                        .or(todolists::id.eq_any(&joined)),
+                )
+                .order(todolists::id.asc())
+                .load::<TodoList>(conn)?;
+            let tasks = todotasks::table
+                .filter(todotasks::todolist_id.eq_any(&visible))
+                .filter(
+                    todotasks::change_seq
+                        .gt(since)
+                        .or(todotasks::todolist_id.eq_any(&joined)),
+                )
+                .order(todotasks::id.asc())
+                .load::<TodoTask>(conn)?;
+
+            // Deleted lists have no members left to check against, so their
+            // tombstones go to the whole workspace; they only carry ids.
+            let live_tasks: HashSet<i32> = tasks.iter().map(|task| task.id).collect();
+            let tombstones = tombstones::table
+                .filter(tombstones::change_seq.gt(since))
+                .filter(
+                    tombstones::entity
+                        .eq("task")
+                        .and(tombstones::list_id.eq_any(&visible))
+                        .or(tombstones::entity
+                            .eq("list")
+                            .and(tombstones::workspace_id.eq(workspace_id))),
+                )
+                .order(tombstones::change_seq.asc())
+                .select(Tombstone::as_select())
+                .load::<Tombstone>(conn)?
+                .into_iter()
+                .filter(|tombstone| {
+                    tombstone.entity != "task" || !live_tasks.contains(&tombstone.entity_id)
+                })
+                .collect();
+
+            Ok(Some(SyncChanges {
+                token,
+                lists,
+                tasks,
+                tombstones,
+            }))
+        })
+}
+
+/// Viewer access is checked before the row is compared, so a conflict never
+/// reveals a task the caller can't see.
+fn lock_task(
+    conn: &mut PgConnection,
+    caller: AuthenticatedUser,
+    task_id: i32,
+) -> Result<TodoTask, AccessError> {
+    db_require_task_role(conn, task_id, caller.id, ListRole::Viewer)?;
+    let task = todotasks::table
+        .find(task_id)
+        .for_update()
+        .first::<TodoTask>(conn)?;
+    Ok(task)
+}
+
+fn lock_list(
+    conn: &mut PgConnection,
+    caller: AuthenticatedUser,
+    list_id: i32,
+) -> Result<TodoList, AccessError> {
+    db_require_list_role(conn, list_id, caller.id, ListRole::Viewer)?;
+    let list = todolists::table
+        .find(list_id)
+        .for_update()
+        .first::<TodoList>(conn)?;
+    Ok(list)
+}
+
+fn apply_mutation(
+    conn: &mut PgConnection,
+    caller: AuthenticatedUser,
+    mutation: &SyncMutation,
+) -> Result<SyncOutcome, AccessError> {
+    let stale = |current: NaiveDateTime, base: &NaiveDateTime| current != *base;
+    let invalid = match mutation {
+        SyncMutation::CreateTask { task, .. } => task.validate().err(),
+        SyncMutation::UpdateTask { changes, .. } => changes.validate().err(),
+        _ => None,
+    };
+    if let Some((_, message)) = invalid {
+        return Ok(SyncOutcome::Invalid(message));
+    }
+    conn.transaction(|conn| match mutation {
+        SyncMutation::CreateTask { task, .. } => Ok(SyncOutcome::Applied(to_json(db_insert_task(
+            conn, caller, task,
+        )?))),
+        SyncMutation::UpdateTask {
+            id,
+            base_modified_at,
+            changes,
+        } => {
+            let current = lock_task(conn, caller, *id)?;
+            if stale(current.modified_at, base_modified_at) {
+                return Ok(SyncOutcome::Conflict(to_json(current)));
+            }
+            let task = db_update_task(conn, caller, *id, changes)?;
+            Ok(SyncOutcome::Applied(to_json(task)))
+        }
+        SyncMutation::DeleteTask {
+            id,
+            base_modified_at,
+        } => {
+            let current = lock_task(conn, caller, *id)?;
+            if stale(current.modified_at, base_modified_at) {
+                return Ok(SyncOutcome::Conflict(to_json(current)));
+            }
+            db_delete_task(conn, caller, *id)?;
+            Ok(SyncOutcome::Applied(serde_json::Value::Null))
+        }
+        SyncMutation::CreateList { list, .. } => Ok(SyncOutcome::Applied(to_json(db_insert_list(
+            conn, caller, list,
+        )?))),
+        SyncMutation::UpdateList {
+            id,
+            base_modified_at,
+            changes,
+        } => {
+            let current = lock_list(conn, caller, *id)?;
+            if stale(current.modified_at, base_modified_at) {
+                return Ok(SyncOutcome::Conflict(to_json(current)));
+            }
+            let list = db_update_list(conn, caller, *id, changes)?;
+            Ok(SyncOutcome::Applied(to_json(list)))
+        }
+        SyncMutation::DeleteList {
+            id,
+            base_modified_at,
+        } => {
+            let current = lock_list(conn, caller, *id)?;
+            if stale(current.modified_at, base_modified_at) {
+                return Ok(SyncOutcome::Conflict(to_json(current)));
+            }
+            db_delete_list(conn, caller, *id)?;
+            Ok(SyncOutcome::Applied(serde_json::Value::Null))
+        }
+    })
+}
+
+/// Each mutation commits on its own, so one failure doesn't roll back the
+/// rest of an offline session; the results say what happened to each.
+fn apply_mutations(
+    pool: web::Data<Pool>,
+    storage: web::Data<dyn AttachmentStorage>,
+    caller: AuthenticatedUser,
+    mutations: Vec<SyncMutation>,
+) -> Vec<SyncResult> {
+    let mut conn = pool.get().unwrap();
+    mutations
+        .into_iter()
+        .enumerate()
+        .map(|(index, mutation)| {
+            let client_id = match &mutation {
+                SyncMutation::CreateTask { client_id, .. }
+                | SyncMutation::CreateList { client_id, .. } => client_id.clone(),
+                _ => None,
+            };
+            // a failed lookup only means there are no blobs to release
+            let hashes = match &mutation {
+                SyncMutation::DeleteTask { id, .. } => db_task_attachment_hashes(&mut conn, &[*id]),
+                SyncMutation::DeleteList { id, .. } => db_list_attachment_hashes(&mut conn, *id),
+                _ => Ok(Vec::new()),
+            }
+            .unwrap_or_default();
+            let mut error = None;
+            let (status, current) = match apply_mutation(&mut conn, caller, &mutation) {
+                Ok(SyncOutcome::Applied(row)) => {
+                    db_release_attachment_blobs(&mut conn, storage.get_ref(), &hashes);
+                    (SyncStatus::Applied, Some(row))
+                }
+                Ok(SyncOutcome::Conflict(row)) => (SyncStatus::Conflict, Some(row)),
+                Ok(SyncOutcome::Invalid(message)) => {
+                    error = Some(message);
+                    (SyncStatus::Invalid, None)
+                }
+                Err(AccessError::Forbidden) => (SyncStatus::Forbidden, None),
+                Err(AccessError::Database(diesel::result::Error::NotFound)) => {
+                    (SyncStatus::NotFound, None)
+                }
+                Err(e) => {
+                    eprintln!("Failed to apply sync mutation: {}", e);
+                    (SyncStatus::Failed, None)
+                }
+            };
+            SyncResult {
+                index,
+                status,
+                client_id,
+                current: current.filter(|row| !row.is_null()),
+                error,
+            }
+        })
+        .collect()
+}
+
+/// Tokens older than the retained log get 410 Gone; the client then syncs
+/// again without `since`.
+#[get("/sync")]
+pub async fn get_sync(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    query: web::Query<SyncQuery>,
+) -> HttpResponse {
+    match web::block(move || get_changes(db, caller, query.since)).await {
+        Ok(Ok(None)) => throw_response_gone(),
+        Ok(Ok(Some(changes))) => match serde_json::to_value(changes) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize sync changes: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/sync")]
+pub async fn post_sync(
+    db: web::Data<Pool>,
+    storage: web::Data<dyn AttachmentStorage>,
+    caller: AuthenticatedUser,
+    item: web::Json<SyncRequest>,
+) -> HttpResponse {
+    let mutations = item.into_inner().mutations;
+    if mutations.len() > MAX_BATCH_SIZE {
+        return throw_response_bad_request();
+    }
+    match web::block(move || apply_mutations(db, storage, caller, mutations)).await {
+        Ok(results) => match serde_json::to_value(results) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize sync results: {}", e);
+                throw_response_error()
+            }
+        },
+        Err(_) => throw_response_error(),
+    }
+}
@@ -0,0 +1,171 @@
+use crate::models::list_member::ListRole;
+use crate::models::tailored_response::*;
+use crate::models::{task_assignee::*, todo_task::TodoTask};
+use crate::schema::task_assignees::dsl::*;
+use crate::schema::todotasks;
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::access::{
+    db_check_task_participant, db_get_active_workspace, db_require_task_role, visible_list_ids,
+    AccessError,
+};
+use crate::utils::database::connection::Pool;
+use crate::utils::inbox::db_notify_task_assigned;
+use actix_web::web::{self};
+use actix_web::{delete, get, post, HttpResponse};
+use diesel::prelude::*;
+use diesel::{delete, insert_into};
+
+fn get_task_assignees(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: i32,
+) -> Result<Vec<TaskAssignee>, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_task_role(&mut conn, target_task_id, caller.id, ListRole::Viewer)?;
+    let items = task_assignees
+        .filter(task_id.eq(target_task_id))
+        .order(created_at.asc())
+        .load::<TaskAssignee>(&mut conn)?;
+    Ok(items)
+}
+
+fn get_assigned_tasks(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: i32,
+) -> Result<Vec<TodoTask>, AccessError> {
+    let mut conn = pool.get().unwrap();
+    let workspace_id = db_get_active_workspace(&mut conn, caller.id)?;
+    let tasks = todotasks::table
+        .inner_join(task_assignees)
+        .filter(user_id.eq(target_user_id))
+        .filter(todotasks::todolist_id.eq_any(visible_list_ids(caller.id, workspace_id)))
+        .select(todotasks::all_columns)
+        .order(todotasks::id.asc())
+        .load::<TodoTask>(&mut conn)?;
+    Ok(tasks)
+}
+
+fn add_task_assignee(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: i32,
+    item: web::Json<InputTaskAssignee>,
+) -> Result<TaskAssignee, AccessError> {
+    let mut conn = pool.get().unwrap();
+    let minimum = ListRole::Editor;
+    let task = db_require_task_role(&mut conn, target_task_id, caller.id, minimum)?;
+    db_check_task_participant(&mut conn, target_task_id, item.user_id)?;
+    let new_assignee = NewTaskAssignee {
+        task_id: target_task_id,
+        user_id: item.user_id,
+        created_at: chrono::Local::now().naive_local(),
+    };
+    let res = conn.transaction(|conn| {
+        let inserted = insert_into(task_assignees)
+            .values(&new_assignee)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+        if inserted > 0 {
+            db_notify_task_assigned(conn, caller.id, &task, item.user_id)?;
+        }
+        task_assignees
+            .find((target_task_id, item.user_id))
+            .get_result(conn)
+    })?;
+    Ok(res)
+}
+
+fn delete_task_assignee(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: i32,
+    target_user_id: i32,
+) -> Result<usize, AccessError> {
+    let mut conn = pool.get().unwrap();
+    let minimum = ListRole::Editor;
+    db_require_task_role(&mut conn, target_task_id, caller.id, minimum)?;
+    let deletion =
+        delete(task_assignees.find((target_task_id, target_user_id))).execute(&mut conn)?;
+    Ok(deletion)
+}
+
+#[get("/tasks/{id}/assignees")]
+pub async fn get_assignees(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || get_task_assignees(db, caller, target_task_id.into_inner())).await {
+        Ok(Ok(assignees)) => match serde_json::to_value(assignees) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize assignees: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/tasks/{id}/assignees")]
+pub async fn assign_task(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: web::Path<i32>,
+    item: web::Json<InputTaskAssignee>,
+) -> HttpResponse {
+    match web::block(move || add_task_assignee(db, caller, target_task_id.into_inner(), item)).await
+    {
+        Ok(Ok(assignee)) => match serde_json::to_value(assignee) {
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize assignee: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[delete("/tasks/{id}/assignees/{user_id}")]
+pub async fn unassign_task(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    path: web::Path<(i32, i32)>,
+) -> HttpResponse {
+    let (target_task_id, target_user_id) = path.into_inner();
+    match web::block(move || delete_task_assignee(db, caller, target_task_id, target_user_id)).await
+    {
+        Ok(Ok(deletion)) => match serde_json::to_value(deletion) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to unassign task: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[get("/users/{id}/assigned")]
+pub async fn get_user_assigned_tasks(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || get_assigned_tasks(db, caller, target_user_id.into_inner())).await {
+        Ok(Ok(tasks)) => match serde_json::to_value(tasks) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize assigned tasks: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
@@ -0,0 +1,298 @@
+use crate::models::list_member::ListRole;
+use crate::models::tailored_response::*;
+use crate::models::task_csv::*;
+use crate::models::todo_task::{InputTodoTask, TodoTask};
+use crate::routes::todo_task::db_insert_task;
+use crate::schema::todotasks;
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::access::{db_require_list_role, AccessError};
+use crate::utils::database::connection::Pool;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web::{self};
+use actix_web::{get, post, HttpResponse};
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use std::collections::HashSet;
+
+const MAX_IMPORT_ROWS: usize = 2000;
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Spreadsheets run cells starting with these as formulas.
+const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+/// Quotes cells a spreadsheet would otherwise evaluate.
+fn guard_formula(value: &str) -> String {
+    match value.starts_with(FORMULA_PREFIXES) {
+        true => format!("'{}", value),
+        false => value.to_string(),
+    }
+}
+
+/// Undoes `guard_formula`, so exported files import unchanged.
+fn unguard_formula(value: &str) -> &str {
+    match value.strip_prefix('\'') {
+        Some(rest) if rest.starts_with(FORMULA_PREFIXES) => rest,
+        _ => value,
+    }
+}
+
+enum ImportError {
+    Access(AccessError),
+    BadFile,
+    TooManyRows,
+}
+
+impl From<AccessError> for ImportError {
+    fn from(e: AccessError) -> Self {
+        ImportError::Access(e)
+    }
+}
+
+impl From<diesel::result::Error> for ImportError {
+    fn from(e: diesel::result::Error) -> Self {
+        ImportError::Access(e.into())
+    }
+}
+
+/// Column positions of each task field in the file.
+struct ColumnMap {
+    name: usize,
+    description: Option<usize>,
+    due_date: Option<usize>,
+    parent_task_id: Option<usize>,
+}
+
+impl ColumnMap {
+    /// A header the caller named explicitly must exist; the defaults may be
+    /// absent, except for the task name.
+    fn resolve(headers: &csv::StringRecord, query: &TaskCsvImportQuery) -> Option<ColumnMap> {
+        let find = |mapped: &Option<String>, field: &str| -> Result<Option<usize>, ()> {
+            let header = mapped.as_deref().unwrap_or(field);
+            match headers.iter().position(|h| h.trim() == header) {
+                Some(index) => Ok(Some(index)),
+                None if mapped.is_some() => Err(()),
+                None => Ok(None),
+            }
+        };
+        Some(ColumnMap {
+            name: find(&query.name, "name").ok()??,
+            description: find(&query.description, "description").ok()?,
+            due_date: find(&query.due_date, "due_date").ok()?,
+            parent_task_id: find(&query.parent_task_id, "parent_task_id").ok()?,
+        })
+    }
+}
+
+fn cell(record: &csv::StringRecord, column: Option<usize>) -> Option<&str> {
+    column
+        .and_then(|index| record.get(index))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+/// Turns one row into a task, or says which field is wrong. Parents must
+/// already be tasks of the list; rows can't point at each other.
+fn parse_row(
+    record: &csv::StringRecord,
+    columns: &ColumnMap,
+    list_id: i32,
+    list_task_ids: &HashSet<i32>,
+) -> Result<InputTodoTask, (&'static str, String)> {
+    let due_date = match cell(record, columns.due_date) {
+        Some(raw) => Some(
+            NaiveDate::parse_from_str(raw, DATE_FORMAT)
+                .map_err(|_| ("due_date", format!("{:?} is not a YYYY-MM-DD date", raw)))?,
+        ),
+        None => None,
+    };
+    let parent_task_id = match cell(record, columns.parent_task_id) {
+        Some(raw) => {
+            let parent = raw
+                .parse::<i32>()
+                .map_err(|_| ("parent_task_id", format!("{:?} is not a task id", raw)))?;
+            if !list_task_ids.contains(&parent) {
+                return Err((
+                    "parent_task_id",
+                    format!("task {} is not in this list", parent),
+                ));
+            }
+            Some(parent)
+        }
+        None => None,
+    };
+    let item = InputTodoTask {
+        todolist_id: list_id,
+        name: unguard_formula(cell(record, Some(columns.name)).unwrap_or_default()).to_string(),
+        description: cell(record, columns.description).map(|raw| unguard_formula(raw).to_string()),
+        parent_task_id,
+        due_date,
+        priority: None,
+        completed_at: None,
+    };
+    item.validate()
+        .map_err(|(field, message)| (field, message.to_string()))?;
+    Ok(item)
+}
+
+fn db_export_list(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    list_id: i32,
+) -> Result<Vec<TodoTask>, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_list_role(&mut conn, list_id, caller.id, ListRole::Viewer)?;
+    Ok(todotasks::table
+        .filter(todotasks::todolist_id.eq(list_id))
+        .order(todotasks::id.asc())
+        .load::<TodoTask>(&mut conn)?)
+}
+
+fn write_csv(tasks: &[TodoTask]) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for task in tasks {
+        writer.serialize(TaskCsvRecord {
+            id: task.id,
+            name: guard_formula(&task.name),
+            description: guard_formula(task.description.as_deref().unwrap_or_default()),
+            parent_task_id: task.parent_task_id,
+            due_date: task.due_date,
+            created_at: &task.created_at,
+            modified_at: &task.modified_at,
+        })?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| csv::Error::from(e.into_error()))
+}
+
+/// Every row is checked before anything is written, and the rows go in
+/// together or not at all.
+fn db_import_list(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    list_id: i32,
+    query: TaskCsvImportQuery,
+    body: String,
+) -> Result<TaskCsvImportReport, ImportError> {
+    let mut conn = pool.get().unwrap();
+    db_require_list_role(&mut conn, list_id, caller.id, ListRole::Editor)?;
+    let list_task_ids: HashSet<i32> = todotasks::table
+        .filter(todotasks::todolist_id.eq(list_id))
+        .select(todotasks::id)
+        .load::<i32>(&mut conn)?
+        .into_iter()
+        .collect();
+
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(body.as_bytes());
+    let headers = reader.headers().map_err(|_| ImportError::BadFile)?.clone();
+    let columns = ColumnMap::resolve(&headers, &query).ok_or(ImportError::BadFile)?;
+
+    let mut items = Vec::new();
+    let mut errors = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        if index >= MAX_IMPORT_ROWS {
+            return Err(ImportError::TooManyRows);
+        }
+        // the header is line 1 and each record here spans one line
+        let fallback_line = index as u64 + 2;
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                errors.push(CsvRowError {
+                    line: e.position().map_or(fallback_line, |pos| pos.line()),
+                    field: None,
+                    message: String::from("row is not valid CSV"),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map_or(fallback_line, |pos| pos.line());
+        match parse_row(&record, &columns, list_id, &list_task_ids) {
+            Ok(item) => items.push(item),
+            Err((field, message)) => errors.push(CsvRowError {
+                line,
+                field: Some(field),
+                message,
+            }),
+        }
+    }
+
+    let rows = items.len() + errors.len();
+    if !errors.is_empty() || query.dry_run {
+        return Ok(TaskCsvImportReport {
+            dry_run: query.dry_run,
+            rows,
+            errors,
+            tasks: Vec::new(),
+        });
+    }
+    let tasks = conn.transaction(|conn| {
+        items
+            .iter()
+            .map(|item| db_insert_task(conn, caller, item))
+            .collect::<Result<Vec<TodoTask>, AccessError>>()
+    })?;
+    Ok(TaskCsvImportReport {
+        dry_run: false,
+        rows,
+        errors,
+        tasks,
+    })
+}
+
+#[get("/lists/{id}/export.csv")]
+pub async fn export_list_csv(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    list_id: web::Path<i32>,
+) -> HttpResponse {
+    let list_id = list_id.into_inner();
+    match web::block(move || db_export_list(db, caller, list_id)).await {
+        Ok(Ok(tasks)) => match write_csv(&tasks) {
+            Ok(body) => HttpResponse::Ok()
+                .content_type("text/csv; charset=utf-8")
+                .insert_header(ContentDisposition {
+                    disposition: DispositionType::Attachment,
+                    parameters: vec![DispositionParam::Filename(format!("list-{}.csv", list_id))],
+                })
+                .body(body),
+            Err(e) => {
+                eprintln!("Failed to write list CSV: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/lists/{id}/import")]
+pub async fn import_list_csv(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    list_id: web::Path<i32>,
+    query: web::Query<TaskCsvImportQuery>,
+    body: String,
+) -> HttpResponse {
+    let list_id = list_id.into_inner();
+    let query = query.into_inner();
+    match web::block(move || db_import_list(db, caller, list_id, query, body)).await {
+        Ok(Ok(report)) => match serde_json::to_value(&report) {
+            Ok(response_body) if !report.errors.is_empty() => {
+                throw_response_unprocessable_entity(response_body)
+            }
+            Ok(response_body) if report.dry_run => throw_response_ok(response_body),
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize import report: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(ImportError::Access(e))) => throw_response_access_error(e),
+        Ok(Err(ImportError::BadFile)) => throw_response_bad_request(),
+        Ok(Err(ImportError::TooManyRows)) => throw_response_payload_too_large(),
+        Err(_) => throw_response_error(),
+    }
+}
@@ -0,0 +1,277 @@
+use crate::models::list_member::ListRole;
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::access::db_require_list_role;
+use crate::utils::database::connection::Pool;
+use crate::utils::events::{EventHub, TaskEvent};
+use actix_web::web::{self};
+use actix_web::{get, Error, HttpRequest, HttpResponse};
+use actix_ws::{Message, MessageStream, Session};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::{
+    self,
+    error::{RecvError, TryRecvError},
+};
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(90);
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+enum ClientMessage {
+    Subscribe { list_ids: Vec<i32> },
+    Unsubscribe { list_ids: Vec<i32> },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage<'a> {
+    Subscribed {
+        list_ids: Vec<i32>,
+        rejected: Vec<i32>,
+    },
+    Unsubscribed {
+        list_ids: Vec<i32>,
+    },
+    /// The caller lost access to these lists and no longer gets their events.
+    Revoked {
+        list_ids: Vec<i32>,
+    },
+    /// The session fell behind and dropped events; clients should re-fetch
+    /// the lists they follow.
+    Lagged {
+        missed: u64,
+    },
+    Error {
+        message: String,
+    },
+    TaskEvent(&'a TaskEvent),
+}
+
+/// Splits `requested` into the lists the caller may view and the rest.
+fn authorize_lists(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    requested: Vec<i32>,
+) -> (Vec<i32>, Vec<i32>) {
+    let mut conn = pool.get().unwrap();
+    requested.into_iter().partition(|list_id| {
+        db_require_list_role(&mut conn, *list_id, caller.id, ListRole::Viewer).is_ok()
+    })
+}
+
+async fn send(session: &mut Session, message: &ServerMessage<'_>) -> Result<(), actix_ws::Closed> {
+    let text = serde_json::to_string(message).expect("Failed to serialize feed message");
+    session.text(text).await
+}
+
+async fn handle_client_message(
+    db: &web::Data<Pool>,
+    caller: AuthenticatedUser,
+    subscriptions: &mut HashSet<i32>,
+    session: &mut Session,
+    text: &str,
+) -> Result<(), actix_ws::Closed> {
+    match serde_json::from_str::<ClientMessage>(text) {
+        Ok(ClientMessage::Subscribe { list_ids }) => {
+            let pool = db.clone();
+            match web::block(move || authorize_lists(pool, caller, list_ids)).await {
+                Ok((allowed, rejected)) => {
+                    subscriptions.extend(&allowed);
+                    send(
+                        session,
+                        &ServerMessage::Subscribed {
+                            list_ids: allowed,
+                            rejected,
+                        },
+                    )
+                    .await
+                }
+                Err(e) => {
+                    eprintln!("Failed to authorize feed subscription: {}", e);
+                    let message = String::from("subscription failed");
+                    send(session, &ServerMessage::Error { message }).await
+                }
+            }
+        }
+        Ok(ClientMessage::Unsubscribe { list_ids }) => {
+            for list_id in &list_ids {
+                subscriptions.remove(list_id);
+            }
+            send(session, &ServerMessage::Unsubscribed { list_ids }).await
+        }
+        Err(e) => {
+            let message = format!("invalid message: {}", e);
+            send(session, &ServerMessage::Error { message }).await
+        }
+    }
+}
+
+/// Checks every subscription again, dropping the lists the caller can no
+/// longer view.
+async fn recheck_subscriptions(
+    db: &web::Data<Pool>,
+    caller: AuthenticatedUser,
+    subscriptions: &mut HashSet<i32>,
+    session: &mut Session,
+) -> Result<(), actix_ws::Closed> {
+    if subscriptions.is_empty() {
+        return Ok(());
+    }
+    let pool = db.clone();
+    let current: Vec<i32> = subscriptions.iter().copied().collect();
+    let revoked = match web::block(move || authorize_lists(pool, caller, current)).await {
+        Ok((_, rejected)) => rejected,
+        Err(e) => {
+            // without knowing what is still allowed, nothing is
+            eprintln!("Failed to recheck feed subscriptions: {}", e);
+            subscriptions.drain().collect()
+        }
+    };
+    if revoked.is_empty() {
+        return Ok(());
+    }
+    for list_id in &revoked {
+        subscriptions.remove(list_id);
+    }
+    send(session, &ServerMessage::Revoked { list_ids: revoked }).await
+}
+
+/// Drains the access changes already queued, telling whether any concerns
+/// `user_id`.
+fn take_access_changes(
+    access_changes: &mut broadcast::Receiver<Option<i32>>,
+    user_id: i32,
+) -> bool {
+    let mut changed = false;
+    loop {
+        match access_changes.try_recv() {
+            Ok(Some(changed_user)) => changed |= changed_user == user_id,
+            Ok(None) | Err(TryRecvError::Lagged(_)) => changed = true,
+            Err(TryRecvError::Empty) | Err(TryRecvError::Closed) => return changed,
+        }
+    }
+}
+
+/// Sends `event` if the caller follows one of its lists. An access change
+/// committed before the event is dispatched before it too, so applying the
+/// queued ones first keeps a removed member from seeing what came after.
+async fn forward_event(
+    db: &web::Data<Pool>,
+    caller: AuthenticatedUser,
+    subscriptions: &mut HashSet<i32>,
+    access_changes: &mut broadcast::Receiver<Option<i32>>,
+    session: &mut Session,
+    event: &TaskEvent,
+) -> Result<(), actix_ws::Closed> {
+    if !subscriptions
+        .iter()
+        .any(|list_id| event.involves_list(*list_id))
+    {
+        return Ok(());
+    }
+    if take_access_changes(access_changes, caller.id) {
+        recheck_subscriptions(db, caller, subscriptions, session).await?;
+        if !subscriptions
+            .iter()
+            .any(|list_id| event.involves_list(*list_id))
+        {
+            return Ok(());
+        }
+    }
+    send(session, &ServerMessage::TaskEvent(event)).await
+}
+
+/// Access is checked when a list is subscribed to, and again whenever the
+/// caller's list or workspace memberships change.
+async fn run_session(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    mut events: broadcast::Receiver<Arc<TaskEvent>>,
+    mut access_changes: broadcast::Receiver<Option<i32>>,
+    mut session: Session,
+    mut stream: MessageStream,
+) {
+    let mut subscriptions: HashSet<i32> = HashSet::new();
+    let mut heartbeat = actix_web::rt::time::interval(HEARTBEAT_INTERVAL);
+    let mut last_seen = Instant::now();
+
+    loop {
+        let outcome = tokio::select! {
+            message = stream.recv() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    last_seen = Instant::now();
+                    handle_client_message(&db, caller, &mut subscriptions, &mut session, &text).await
+                }
+                Some(Ok(Message::Ping(bytes))) => {
+                    last_seen = Instant::now();
+                    session.pong(&bytes).await
+                }
+                Some(Ok(Message::Pong(_))) => {
+                    last_seen = Instant::now();
+                    Ok(())
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => Ok(()),
+            },
+            event = events.recv() => match event {
+                Ok(event) => {
+                    forward_event(
+                        &db,
+                        caller,
+                        &mut subscriptions,
+                        &mut access_changes,
+                        &mut session,
+                        &event,
+                    )
+                    .await
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    send(&mut session, &ServerMessage::Lagged { missed }).await
+                }
+                Err(RecvError::Closed) => break,
+            },
+            change = access_changes.recv() => match change {
+                Ok(Some(user_id)) if user_id != caller.id => Ok(()),
+                Ok(_) | Err(RecvError::Lagged(_)) => {
+                    recheck_subscriptions(&db, caller, &mut subscriptions, &mut session).await
+                }
+                Err(RecvError::Closed) => break,
+            },
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > CLIENT_TIMEOUT {
+                    break;
+                }
+                session.ping(b"").await
+            }
+        };
+        if outcome.is_err() {
+            return;
+        }
+    }
+    let _ = session.close(None).await;
+}
+
+/// Upgrades to a WebSocket that streams task events for the lists the client
+/// subscribes to with `{"action": "subscribe", "list_ids": [..]}`.
+#[get("/ws/tasks")]
+pub async fn task_feed(
+    req: HttpRequest,
+    body: web::Payload,
+    db: web::Data<Pool>,
+    hub: web::Data<EventHub>,
+    caller: AuthenticatedUser,
+) -> Result<HttpResponse, Error> {
+    let (response, session, stream) = actix_ws::handle(&req, body)?;
+    actix_web::rt::spawn(run_session(
+        db,
+        caller,
+        hub.subscribe_tasks(),
+        hub.subscribe_access_changes(),
+        session,
+        stream,
+    ));
+    Ok(response)
+}
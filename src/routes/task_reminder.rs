@@ -0,0 +1,244 @@
+use crate::models::list_member::ListRole;
+use crate::models::tailored_response::*;
+use crate::models::task_reminder::*;
+use crate::schema::task_reminders::dsl::*;
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::access::{db_require_task_role, AccessError};
+use crate::utils::database::connection::Pool;
+use actix_web::web::{self};
+use actix_web::{delete, get, patch, post, HttpResponse};
+use diesel::prelude::*;
+use diesel::{delete, insert_into};
+
+/// The longest a reminder may be snoozed for at once: a week.
+const MAX_SNOOZE_MINUTES: i64 = 7 * 24 * 60;
+
+/// Reminders are private, so other users' reminders are reported as missing.
+fn db_get_own_reminder(
+    conn: &mut PgConnection,
+    caller: AuthenticatedUser,
+    target_task_id: i32,
+    reminder_id: i32,
+) -> Result<TaskReminder, AccessError> {
+    db_require_task_role(conn, target_task_id, caller.id, ListRole::Viewer)?;
+    let reminder = task_reminders
+        .find(reminder_id)
+        .filter(task_id.eq(target_task_id))
+        .filter(user_id.eq(caller.id))
+        .get_result::<TaskReminder>(conn)?;
+    Ok(reminder)
+}
+
+fn get_task_reminders(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: i32,
+) -> Result<Vec<TaskReminder>, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_task_role(&mut conn, target_task_id, caller.id, ListRole::Viewer)?;
+    let items = task_reminders
+        .filter(task_id.eq(target_task_id))
+        .filter(user_id.eq(caller.id))
+        .order(id.asc())
+        .load::<TaskReminder>(&mut conn)?;
+    Ok(items)
+}
+
+fn add_task_reminder(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: i32,
+    item: web::Json<InputTaskReminder>,
+) -> Result<TaskReminder, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_task_role(&mut conn, target_task_id, caller.id, ListRole::Viewer)?;
+    let now = chrono::Local::now().naive_local();
+    let new_reminder = NewTaskReminder {
+        task_id: target_task_id,
+        user_id: caller.id,
+        remind_at: item.remind_at,
+        minutes_before_due: item.minutes_before_due,
+        channel: item.channel,
+        created_at: now,
+        modified_at: now,
+    };
+    let reminder = insert_into(task_reminders)
+        .values(&new_reminder)
+        .get_result(&mut conn)?;
+    Ok(reminder)
+}
+
+fn update_task_reminder(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: i32,
+    reminder_id: i32,
+    item: web::Json<UpdateTaskReminder>,
+) -> Result<TaskReminder, AccessError> {
+    let mut conn = pool.get().unwrap();
+    let current = db_get_own_reminder(&mut conn, caller, target_task_id, reminder_id)?;
+    let (new_remind_at, new_minutes_before_due) = match (item.remind_at, item.minutes_before_due) {
+        (None, None) => (current.remind_at, current.minutes_before_due),
+        changed => changed,
+    };
+    let trigger = TaskReminderTrigger {
+        remind_at: new_remind_at,
+        minutes_before_due: new_minutes_before_due,
+        channel: item.channel.unwrap_or(current.channel),
+        snoozed_until: None,
+        sent_at: None,
+        modified_at: chrono::Local::now().naive_local(),
+    };
+    let reminder = diesel::update(task_reminders.find(reminder_id))
+        .set(&trigger)
+        .get_result(&mut conn)?;
+    Ok(reminder)
+}
+
+/// Snoozing also re-arms reminders that have already fired.
+fn snooze_task_reminder(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: i32,
+    reminder_id: i32,
+    item: web::Json<SnoozeTaskReminder>,
+) -> Result<TaskReminder, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_get_own_reminder(&mut conn, caller, target_task_id, reminder_id)?;
+    let now = chrono::Local::now().naive_local();
+    let reminder = diesel::update(task_reminders.find(reminder_id))
+        .set((
+            snoozed_until.eq(now + chrono::Duration::minutes(item.minutes)),
+            sent_at.eq(None::<chrono::NaiveDateTime>),
+            modified_at.eq(now),
+        ))
+        .get_result(&mut conn)?;
+    Ok(reminder)
+}
+
+fn delete_task_reminder(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: i32,
+    reminder_id: i32,
+) -> Result<usize, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_get_own_reminder(&mut conn, caller, target_task_id, reminder_id)?;
+    let deletion = delete(task_reminders.find(reminder_id)).execute(&mut conn)?;
+    Ok(deletion)
+}
+
+#[get("/tasks/{id}/reminders")]
+pub async fn get_reminders(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || get_task_reminders(db, caller, target_task_id.into_inner())).await {
+        Ok(Ok(reminders)) => match serde_json::to_value(reminders) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize task reminders: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/tasks/{id}/reminders")]
+pub async fn add_reminder(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: web::Path<i32>,
+    item: web::Json<InputTaskReminder>,
+) -> HttpResponse {
+    if !item.is_valid() {
+        return throw_response_bad_request();
+    }
+    match web::block(move || add_task_reminder(db, caller, target_task_id.into_inner(), item)).await
+    {
+        Ok(Ok(reminder)) => match serde_json::to_value(reminder) {
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to add task reminder: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[patch("/tasks/{id}/reminders/{reminder_id}")]
+pub async fn patch_reminder(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    path: web::Path<(i32, i32)>,
+    item: web::Json<UpdateTaskReminder>,
+) -> HttpResponse {
+    if !item.is_valid() {
+        return throw_response_bad_request();
+    }
+    let (target_task_id, reminder_id) = path.into_inner();
+    match web::block(move || update_task_reminder(db, caller, target_task_id, reminder_id, item))
+        .await
+    {
+        Ok(Ok(reminder)) => match serde_json::to_value(reminder) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to patch task reminder: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/tasks/{id}/reminders/{reminder_id}/snooze")]
+pub async fn snooze_reminder(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    path: web::Path<(i32, i32)>,
+    item: web::Json<SnoozeTaskReminder>,
+) -> HttpResponse {
+    if !(1..=MAX_SNOOZE_MINUTES).contains(&item.minutes) {
+        return throw_response_bad_request();
+    }
+    let (target_task_id, reminder_id) = path.into_inner();
+    match web::block(move || snooze_task_reminder(db, caller, target_task_id, reminder_id, item))
+        .await
+    {
+        Ok(Ok(reminder)) => match serde_json::to_value(reminder) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to snooze task reminder: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[delete("/tasks/{id}/reminders/{reminder_id}")]
+pub async fn delete_reminder(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    path: web::Path<(i32, i32)>,
+) -> HttpResponse {
+    let (target_task_id, reminder_id) = path.into_inner();
+    match web::block(move || delete_task_reminder(db, caller, target_task_id, reminder_id)).await {
+        Ok(Ok(deletion)) => match serde_json::to_value(deletion) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to delete task reminder: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
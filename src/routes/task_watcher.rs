@@ -0,0 +1,135 @@
+use crate::models::list_member::ListRole;
+use crate::models::tailored_response::*;
+use crate::models::task_watcher::*;
+use crate::schema::task_watchers::dsl::*;
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::access::{
+    db_check_task_participant, db_require_task_role, AccessError,
+};
+use crate::utils::database::connection::Pool;
+use actix_web::web::{self};
+use actix_web::{delete, get, post, HttpResponse};
+use diesel::prelude::*;
+use diesel::{delete, insert_into};
+
+fn get_task_watchers(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: i32,
+) -> Result<Vec<TaskWatcher>, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_task_role(&mut conn, target_task_id, caller.id, ListRole::Viewer)?;
+    let items = task_watchers
+        .filter(task_id.eq(target_task_id))
+        .order(created_at.asc())
+        .load::<TaskWatcher>(&mut conn)?;
+    Ok(items)
+}
+
+fn add_task_watcher(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: i32,
+    item: web::Json<InputTaskWatcher>,
+) -> Result<TaskWatcher, AccessError> {
+    let mut conn = pool.get().unwrap();
+    let minimum = if item.user_id == caller.id {
+        ListRole::Viewer
+    } else {
+        ListRole::Editor
+    };
+    db_require_task_role(&mut conn, target_task_id, caller.id, minimum)?;
+    db_check_task_participant(&mut conn, target_task_id, item.user_id)?;
+    let new_watcher = NewTaskWatcher {
+        task_id: target_task_id,
+        user_id: item.user_id,
+        created_at: chrono::Local::now().naive_local(),
+    };
+    insert_into(task_watchers)
+        .values(&new_watcher)
+        .on_conflict_do_nothing()
+        .execute(&mut conn)?;
+    let res = task_watchers
+        .find((target_task_id, item.user_id))
+        .get_result(&mut conn)?;
+    Ok(res)
+}
+
+fn delete_task_watcher(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: i32,
+    target_user_id: i32,
+) -> Result<usize, AccessError> {
+    let mut conn = pool.get().unwrap();
+    let minimum = if target_user_id == caller.id {
+        ListRole::Viewer
+    } else {
+        ListRole::Editor
+    };
+    db_require_task_role(&mut conn, target_task_id, caller.id, minimum)?;
+    let deletion =
+        delete(task_watchers.find((target_task_id, target_user_id))).execute(&mut conn)?;
+    Ok(deletion)
+}
+
+#[get("/tasks/{id}/watchers")]
+pub async fn get_watchers(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || get_task_watchers(db, caller, target_task_id.into_inner())).await {
+        Ok(Ok(watchers)) => match serde_json::to_value(watchers) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize watchers: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/tasks/{id}/watchers")]
+pub async fn watch_task(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_task_id: web::Path<i32>,
+    item: web::Json<InputTaskWatcher>,
+) -> HttpResponse {
+    match web::block(move || add_task_watcher(db, caller, target_task_id.into_inner(), item)).await
+    {
+        Ok(Ok(watcher)) => match serde_json::to_value(watcher) {
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize watcher: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[delete("/tasks/{id}/watchers/{user_id}")]
+pub async fn unwatch_task(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    path: web::Path<(i32, i32)>,
+) -> HttpResponse {
+    let (target_task_id, target_user_id) = path.into_inner();
+    match web::block(move || delete_task_watcher(db, caller, target_task_id, target_user_id)).await
+    {
+        Ok(Ok(deletion)) => match serde_json::to_value(deletion) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to unwatch task: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
@@ -0,0 +1,289 @@
+use crate::models::api_key::ApiScope;
+use crate::models::list_member::{ListRole, NewListMember};
+use crate::models::tailored_response::*;
+use crate::models::todo_list::*;
+use crate::routes::attachment::{db_list_attachment_hashes, db_release_attachment_blobs};
+use crate::schema::todolists::dsl::*;
+use crate::schema::{list_members, todotasks};
+use crate::utils::auth::{AuthenticatedUser, ScopedUser};
+use crate::utils::database::access::{
+    db_get_active_workspace, db_require_list_role, visible_list_ids, AccessError,
+};
+use crate::utils::database::connection::Pool;
+use crate::utils::events::{list_audience, publish_list_event, ListEventKind};
+use crate::utils::inbox::db_notify_list_deleted;
+use crate::utils::storage::AttachmentStorage;
+use actix_web::web::{self};
+use actix_web::{delete, get, patch, post, HttpResponse};
+use diesel::prelude::*;
+use diesel::{delete, insert_into};
+
+fn get_all_lists(pool: web::Data<Pool>, caller: ScopedUser) -> Result<Vec<TodoList>, AccessError> {
+    let caller = caller.require(ApiScope::TasksRead)?;
+    let mut conn = pool.get().unwrap();
+    let active_workspace_id = db_get_active_workspace(&mut conn, caller.id)?;
+    let lists = todolists
+        .filter(id.eq_any(visible_list_ids(caller.id, active_workspace_id)))
+        .order(id.asc())
+        .load::<TodoList>(&mut conn)?;
+    Ok(lists)
+}
+
+fn db_get_list_by_id(
+    pool: web::Data<Pool>,
+    caller: ScopedUser,
+    list_id: i32,
+) -> Result<TodoList, AccessError> {
+    let caller = caller.require(ApiScope::TasksRead)?;
+    let mut conn = pool.get().unwrap();
+    db_require_list_role(&mut conn, list_id, caller.id, ListRole::Viewer)?;
+    let list = todolists.find(list_id).get_result::<TodoList>(&mut conn)?;
+    Ok(list)
+}
+
+pub(crate) fn db_insert_list(
+    conn: &mut PgConnection,
+    caller: AuthenticatedUser,
+    item: &InputTodoList,
+) -> Result<TodoList, AccessError> {
+    let now = chrono::Local::now().naive_local();
+    let new_list = NewTodoList {
+        user_id: caller.id,
+        name: item.name.clone(),
+        description: item.description.clone().unwrap_or_default(),
+        created_at: now,
+        modified_at: now,
+        workspace_id: db_get_active_workspace(conn, caller.id)?,
+    };
+    let list = conn.transaction(|conn| {
+        let list: TodoList = insert_into(todolists).values(&new_list).get_result(conn)?;
+        insert_into(list_members::table)
+            .values(&NewListMember {
+                list_id: list.id,
+                user_id: caller.id,
+                role: ListRole::Owner,
+                invited_by: None,
+                created_at: now,
+                accepted_at: Some(now),
+            })
+            .execute(conn)?;
+        let list = publish_list_event(conn, ListEventKind::Created, list, &[caller.id])?;
+        Ok::<_, diesel::result::Error>(list)
+    })?;
+    Ok(list)
+}
+
+fn add_single_list(
+    pool: web::Data<Pool>,
+    caller: ScopedUser,
+    item: web::Json<InputTodoList>,
+) -> Result<TodoList, AccessError> {
+    let caller = caller.require(ApiScope::ListsAdmin)?;
+    let mut conn = pool.get().unwrap();
+    db_insert_list(&mut conn, caller, &item)
+}
+
+pub(crate) fn db_delete_list(
+    conn: &mut PgConnection,
+    caller: AuthenticatedUser,
+    list_id: i32,
+) -> Result<usize, AccessError> {
+    db_require_list_role(conn, list_id, caller.id, ListRole::Owner)?;
+    let deletion = db_remove_list(conn, caller.id, list_id)?;
+    Ok(deletion)
+}
+
+/// Deletes the list and its tasks on behalf of `actor` without checking their
+/// role, telling the remaining members who did it.
+pub(crate) fn db_remove_list(
+    conn: &mut PgConnection,
+    actor: i32,
+    list_id: i32,
+) -> Result<usize, diesel::result::Error> {
+    conn.transaction(|conn| {
+        let list = todolists.find(list_id).get_result::<TodoList>(conn)?;
+        let audience = list_audience(conn, &[list_id])?;
+        publish_list_event(conn, ListEventKind::Deleted, list, &audience)?;
+        db_notify_list_deleted(conn, actor, list_id)?;
+        delete(todotasks::table.filter(todotasks::todolist_id.eq(list_id))).execute(conn)?;
+        delete(todolists.find(list_id)).execute(conn)
+    })
+}
+
+fn delete_single_list(
+    pool: web::Data<Pool>,
+    storage: web::Data<dyn AttachmentStorage>,
+    caller: ScopedUser,
+    list_id: i32,
+) -> Result<usize, AccessError> {
+    let caller = caller.require(ApiScope::ListsAdmin)?;
+    let mut conn = pool.get().unwrap();
+    let hashes = db_list_attachment_hashes(&mut conn, list_id)?;
+    let deletion = db_delete_list(&mut conn, caller, list_id)?;
+    db_release_attachment_blobs(&mut conn, storage.get_ref(), &hashes);
+    Ok(deletion)
+}
+
+pub(crate) fn db_update_list(
+    conn: &mut PgConnection,
+    caller: AuthenticatedUser,
+    list_id: i32,
+    changes: &UpdateTodoList,
+) -> Result<TodoList, AccessError> {
+    db_require_list_role(conn, list_id, caller.id, ListRole::Editor)?;
+    let list = conn.transaction(|conn| {
+        let list: TodoList = diesel::update(todolists.find(list_id))
+            .set((changes, modified_at.eq(chrono::Local::now().naive_local())))
+            .get_result(conn)?;
+        let audience = list_audience(conn, &[list_id])?;
+        let list = publish_list_event(conn, ListEventKind::Patched, list, &audience)?;
+        Ok::<_, diesel::result::Error>(list)
+    })?;
+    Ok(list)
+}
+
+fn update_single_list_name(
+    pool: web::Data<Pool>,
+    caller: ScopedUser,
+    list_id: i32,
+    item: web::Json<UpdateTodoListName>,
+) -> Result<TodoList, AccessError> {
+    let caller = caller.require(ApiScope::ListsAdmin)?;
+    let mut conn = pool.get().unwrap();
+    let changes = UpdateTodoList {
+        name: Some(item.into_inner().name),
+        ..Default::default()
+    };
+    db_update_list(&mut conn, caller, list_id, &changes)
+}
+
+fn update_single_list_description(
+    pool: web::Data<Pool>,
+    caller: ScopedUser,
+    list_id: i32,
+    item: web::Json<UpdateTodoListDescription>,
+) -> Result<TodoList, AccessError> {
+    let caller = caller.require(ApiScope::ListsAdmin)?;
+    let mut conn = pool.get().unwrap();
+    let changes = UpdateTodoList {
+        description: Some(item.into_inner().description),
+        ..Default::default()
+    };
+    db_update_list(&mut conn, caller, list_id, &changes)
+}
+
+#[get("/lists")]
+pub async fn get_lists(db: web::Data<Pool>, caller: ScopedUser) -> HttpResponse {
+    match web::block(move || get_all_lists(db, caller)).await {
+        Ok(Ok(lists)) => match serde_json::to_value(lists) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize lists: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[get("/lists/{id}")]
+pub async fn get_list_by_id(
+    db: web::Data<Pool>,
+    caller: ScopedUser,
+    list_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || db_get_list_by_id(db, caller, list_id.into_inner())).await {
+        Ok(Ok(list)) => match serde_json::to_value(list) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize list: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/lists/new")]
+pub async fn add_list(
+    db: web::Data<Pool>,
+    caller: ScopedUser,
+    item: web::Json<InputTodoList>,
+) -> HttpResponse {
+    match web::block(move || add_single_list(db, caller, item)).await {
+        Ok(Ok(list)) => match serde_json::to_value(list) {
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to create list: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[delete("/lists/{id}")]
+pub async fn delete_list(
+    db: web::Data<Pool>,
+    storage: web::Data<dyn AttachmentStorage>,
+    caller: ScopedUser,
+    list_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || delete_single_list(db, storage, caller, list_id.into_inner())).await {
+        Ok(Ok(deletion)) => match serde_json::to_value(deletion) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to delete list: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[patch("/lists/update/name/{id}")]
+pub async fn patch_list_name(
+    db: web::Data<Pool>,
+    caller: ScopedUser,
+    list_id: web::Path<i32>,
+    item: web::Json<UpdateTodoListName>,
+) -> HttpResponse {
+    match web::block(move || update_single_list_name(db, caller, list_id.into_inner(), item)).await
+    {
+        Ok(Ok(list)) => match serde_json::to_value(list) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to patch list name: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[patch("/lists/update/description/{id}")]
+pub async fn patch_list_description(
+    db: web::Data<Pool>,
+    caller: ScopedUser,
+    list_id: web::Path<i32>,
+    item: web::Json<UpdateTodoListDescription>,
+) -> HttpResponse {
+    match web::block(move || update_single_list_description(db, caller, list_id.into_inner(), item))
+        .await
+    {
+        Ok(Ok(list)) => match serde_json::to_value(list) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to patch list description: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
@@ -1,32 +1,120 @@
+use crate::models::api_key::ApiScope;
+use crate::models::list_member::ListRole;
 use crate::models::tailored_response::*;
+use crate::routes::attachment::{db_release_attachment_blobs, db_task_attachment_hashes};
 use crate::schema::todotasks::dsl::*;
+use crate::utils::auth::{AuthenticatedUser, ScopedUser};
+use crate::utils::events::{publish_task_event, TaskEventKind};
+use crate::utils::inbox::db_notify_task_change;
+use crate::utils::storage::AttachmentStorage;
+use crate::utils::database::access::{
+    db_get_active_workspace, db_require_list_role, db_require_task_role, visible_list_ids,
+    AccessError,
+};
 use crate::{models::todo_task::*, utils::database::connection::Pool};
 use actix_web::web::{self};
 use actix_web::{delete, get, patch, post, Error, HttpResponse, Responder};
 use diesel::prelude::*;
-use diesel::{delete, insert_into, update, QueryDsl, RunQueryDsl};
+use diesel::{delete, insert_into, QueryDsl, RunQueryDsl};
+use std::collections::HashSet;
 
-fn get_all_tasks(pool: web::Data<Pool>) -> Result<Vec<TodoTask>, diesel::result::Error> {
+fn get_all_tasks(
+    pool: web::Data<Pool>,
+    caller: ScopedUser,
+) -> Result<Vec<TodoTask>, AccessError> {
+    let caller = caller.require(ApiScope::TasksRead)?;
     let mut conn = pool.get().unwrap();
-    let items = todotasks.load::<TodoTask>(&mut conn)?;
+    let workspace_id = db_get_active_workspace(&mut conn, caller.id)?;
+    let items = todotasks
+        .filter(todolist_id.eq_any(visible_list_ids(caller.id, workspace_id)))
+        .load::<TodoTask>(&mut conn)?;
     Ok(items)
 }
 
 fn db_get_task_by_id(
     pool: web::Data<Pool>,
+    caller: ScopedUser,
     task_id: i32,
-) -> Result<TodoTask, diesel::result::Error> {
+) -> Result<TodoTask, AccessError> {
+    let caller = caller.require(ApiScope::TasksRead)?;
     let mut conn = pool.get().unwrap();
-    todotasks.find(task_id).get_result::<TodoTask>(&mut conn)
+    db_require_task_role(&mut conn, task_id, caller.id, ListRole::Viewer)
 }
 
-fn add_single_task(
-    pool: web::Data<Pool>,
-    item: web::Json<InputTodoTask>,
+/// Subtasks live in their parent's list. A parent in another list, or one
+/// that is `task_id` itself or below it, is treated as not found.
+pub(crate) fn db_check_parent_task(
+    conn: &mut PgConnection,
+    list_id: i32,
+    task_id: Option<i32>,
+    parent: Option<i32>,
+) -> Result<(), AccessError> {
+    let Some(parent) = parent else {
+        return Ok(());
+    };
+    let parent_list_id = todotasks
+        .find(parent)
+        .select(todolist_id)
+        .first::<i32>(conn)?;
+    if parent_list_id != list_id {
+        return Err(diesel::result::Error::NotFound.into());
+    }
+    let mut seen = HashSet::new();
+    let mut ancestor = Some(parent);
+    while let Some(current) = ancestor.filter(|current| seen.insert(*current)) {
+        if Some(current) == task_id {
+            return Err(diesel::result::Error::NotFound.into());
+        }
+        ancestor = todotasks
+            .find(current)
+            .select(parent_task_id)
+            .first::<Option<i32>>(conn)?;
+    }
+    Ok(())
+}
+
+/// A task moved to another list leaves its parent behind, and its subtasks
+/// that stayed behind become top-level tasks of their list.
+fn db_detach_moved_task(
+    conn: &mut PgConnection,
+    task: TodoTask,
 ) -> Result<TodoTask, diesel::result::Error> {
-    let mut conn = pool.get().unwrap();
+    let left_behind: Vec<TodoTask> = diesel::update(todotasks)
+        .filter(parent_task_id.eq(task.id))
+        .filter(todolist_id.ne(task.todolist_id))
+        .set((
+            parent_task_id.eq(None::<i32>),
+            modified_at.eq(chrono::Local::now().naive_local()),
+        ))
+        .get_results(conn)?;
+    for subtask in left_behind {
+        publish_task_event(conn, TaskEventKind::Patched, subtask, None)?;
+    }
+    let parent_list_id = match task.parent_task_id {
+        Some(parent) => todotasks
+            .find(parent)
+            .select(todolist_id)
+            .first::<i32>(conn)
+            .optional()?,
+        None => return Ok(task),
+    };
+    if parent_list_id == Some(task.todolist_id) {
+        return Ok(task);
+    }
+    diesel::update(todotasks.find(task.id))
+        .set(parent_task_id.eq(None::<i32>))
+        .get_result(conn)
+}
+
+pub(crate) fn db_insert_task(
+    conn: &mut PgConnection,
+    caller: AuthenticatedUser,
+    item: &InputTodoTask,
+) -> Result<TodoTask, AccessError> {
+    db_require_list_role(conn, item.todolist_id, caller.id, ListRole::Editor)?;
+    db_check_parent_task(conn, item.todolist_id, None, item.parent_task_id)?;
     let new_task = NewTodoTask {
-        user_id: item.user_id,
+        user_id: caller.id,
         todolist_id: item.todolist_id,
         name: item.name.clone(),
         description: item.description.clone(),
@@ -34,77 +122,190 @@ fn add_single_task(
         due_date: item.due_date,
         created_at: chrono::Local::now().naive_local(),
         modified_at: chrono::Local::now().naive_local(),
+        priority: item.priority.clone(),
+        completed_at: item.completed_at,
     };
 
-    let res = insert_into(todotasks)
-        .values(&new_task)
-        .get_result(&mut conn)?;
+    let res = conn.transaction(|conn| {
+        let task: TodoTask = insert_into(todotasks).values(&new_task).get_result(conn)?;
+        let task = publish_task_event(conn, TaskEventKind::Created, task, None)?;
+        db_notify_task_change(conn, caller.id, TaskEventKind::Created, None, &task)?;
+        Ok::<_, diesel::result::Error>(task)
+    })?;
     Ok(res)
 }
 
-fn delete_single_task(db: web::Data<Pool>, task_id: i32) -> Result<usize, diesel::result::Error> {
+fn add_single_task(
+    pool: web::Data<Pool>,
+    caller: ScopedUser,
+    item: web::Json<InputTodoTask>,
+) -> Result<TodoTask, AccessError> {
+    let caller = caller.require(ApiScope::TasksWrite)?;
+    let mut conn = pool.get().unwrap();
+    db_insert_task(&mut conn, caller, &item)
+}
+
+pub(crate) fn db_delete_task(
+    conn: &mut PgConnection,
+    caller: AuthenticatedUser,
+    task_id: i32,
+) -> Result<usize, AccessError> {
+    let task = db_require_task_role(conn, task_id, caller.id, ListRole::Editor)?;
+    let deletion = conn.transaction(|conn| {
+        db_notify_task_change(conn, caller.id, TaskEventKind::Deleted, None, &task)?;
+        let deletion = delete(todotasks.find(task_id)).execute(conn)?;
+        publish_task_event(conn, TaskEventKind::Deleted, task, None)?;
+        Ok::<_, diesel::result::Error>(deletion)
+    })?;
+    Ok(deletion)
+}
+
+fn delete_single_task(
+    db: web::Data<Pool>,
+    storage: web::Data<dyn AttachmentStorage>,
+    caller: ScopedUser,
+    task_id: i32,
+) -> Result<usize, AccessError> {
+    let caller = caller.require(ApiScope::TasksWrite)?;
     let mut conn = db.get().unwrap();
-    let deletion = delete(todotasks.find(task_id)).execute(&mut conn)?;
+    let hashes = db_task_attachment_hashes(&mut conn, &[task_id])?;
+    let deletion = db_delete_task(&mut conn, caller, task_id)?;
+    db_release_attachment_blobs(&mut conn, storage.get_ref(), &hashes);
     Ok(deletion)
 }
 
+/// Applies several field changes at once; used by sync, where a client
+/// replays whatever it edited while offline.
+pub(crate) fn db_update_task(
+    conn: &mut PgConnection,
+    caller: AuthenticatedUser,
+    task_id: i32,
+    changes: &UpdateTodoTask,
+) -> Result<TodoTask, AccessError> {
+    let previous = db_require_task_role(conn, task_id, caller.id, ListRole::Editor)?;
+    let moved_to = changes
+        .todolist_id
+        .filter(|target| *target != previous.todolist_id);
+    if let Some(target) = moved_to {
+        db_require_list_role(conn, target, caller.id, ListRole::Editor)?;
+    }
+    if let Some(parent) = changes.parent_task_id {
+        let target = moved_to.unwrap_or(previous.todolist_id);
+        db_check_parent_task(conn, target, Some(task_id), parent)?;
+    }
+    let task = conn.transaction(|conn| {
+        let mut task: TodoTask = diesel::update(todotasks.find(task_id))
+            .set((changes, modified_at.eq(chrono::Local::now().naive_local())))
+            .get_result(conn)?;
+        if moved_to.is_some() {
+            task = db_detach_moved_task(conn, task)?;
+        }
+        let kind = match moved_to {
+            Some(_) => TaskEventKind::Moved,
+            None => TaskEventKind::Patched,
+        };
+        let task = publish_task_event(conn, kind, task, moved_to.map(|_| previous.todolist_id))?;
+        db_notify_task_change(conn, caller.id, kind, Some(&previous), &task)?;
+        Ok::<_, diesel::result::Error>(task)
+    })?;
+    Ok(task)
+}
+
 fn update_single_task_name(
     db: web::Data<Pool>,
+    caller: ScopedUser,
     item: web::Json<UpdateTodoTaskName>,
-) -> Result<TodoTask, diesel::result::Error> {
+) -> Result<TodoTask, AccessError> {
+    let caller = caller.require(ApiScope::TasksWrite)?;
     let mut conn = db.get().unwrap();
-    let task = diesel::update(todotasks)
-        .set(name.eq(&item.name))
-        .filter(id.eq(&item.task_id))
-        .get_result(&mut conn)?;
-    let _ = update(todotasks)
-        .set(modified_at.eq(chrono::Local::now().naive_local()))
-        .filter(id.eq(&item.task_id));
+    let previous = db_require_task_role(&mut conn, item.task_id, caller.id, ListRole::Editor)?;
+    let task = conn.transaction(|conn| {
+        let task: TodoTask = diesel::update(todotasks)
+            .set((
+                name.eq(&item.name),
+                modified_at.eq(chrono::Local::now().naive_local()),
+            ))
+            .filter(id.eq(&item.task_id))
+            .get_result(conn)?;
+        let task = publish_task_event(conn, TaskEventKind::Patched, task, None)?;
+        db_notify_task_change(conn, caller.id, TaskEventKind::Patched, Some(&previous), &task)?;
+        Ok::<_, diesel::result::Error>(task)
+    })?;
     Ok(task)
 }
 
 fn update_single_task_description(
     db: web::Data<Pool>,
+    caller: ScopedUser,
     item: web::Json<UpdateTodoTaskDescription>,
-) -> Result<TodoTask, diesel::result::Error> {
+) -> Result<TodoTask, AccessError> {
+    let caller = caller.require(ApiScope::TasksWrite)?;
     let mut conn = db.get().unwrap();
-    let task = diesel::update(todotasks)
-        .set(description.eq(&item.description))
-        .filter(id.eq(&item.task_id))
-        .get_result(&mut conn)?;
-    let _ = update(todotasks)
-        .set(modified_at.eq(chrono::Local::now().naive_local()))
-        .filter(id.eq(&item.task_id));
+    let previous = db_require_task_role(&mut conn, item.task_id, caller.id, ListRole::Editor)?;
+    let task = conn.transaction(|conn| {
+        let task: TodoTask = diesel::update(todotasks)
+            .set((
+                description.eq(&item.description),
+                modified_at.eq(chrono::Local::now().naive_local()),
+            ))
+            .filter(id.eq(&item.task_id))
+            .get_result(conn)?;
+        let task = publish_task_event(conn, TaskEventKind::Patched, task, None)?;
+        db_notify_task_change(conn, caller.id, TaskEventKind::Patched, Some(&previous), &task)?;
+        Ok::<_, diesel::result::Error>(task)
+    })?;
     Ok(task)
 }
 
 fn update_single_task_parent_task_id(
     db: web::Data<Pool>,
+    caller: ScopedUser,
     item: web::Json<UpdateTodoTaskParentTaskID>,
-) -> Result<TodoTask, diesel::result::Error> {
+) -> Result<TodoTask, AccessError> {
+    let caller = caller.require(ApiScope::TasksWrite)?;
     let mut conn = db.get().unwrap();
-    let task = diesel::update(todotasks)
-        .set(parent_task_id.eq(&item.parent_task_id))
-        .filter(id.eq(&item.task_id))
-        .get_result(&mut conn)?;
-    let _ = update(todotasks)
-        .set(modified_at.eq(chrono::Local::now().naive_local()))
-        .filter(id.eq(&item.task_id));
+    let previous = db_require_task_role(&mut conn, item.task_id, caller.id, ListRole::Editor)?;
+    db_check_parent_task(
+        &mut conn,
+        previous.todolist_id,
+        Some(item.task_id),
+        Some(item.parent_task_id),
+    )?;
+    let task = conn.transaction(|conn| {
+        let task: TodoTask = diesel::update(todotasks)
+            .set((
+                parent_task_id.eq(&item.parent_task_id),
+                modified_at.eq(chrono::Local::now().naive_local()),
+            ))
+            .filter(id.eq(&item.task_id))
+            .get_result(conn)?;
+        let task = publish_task_event(conn, TaskEventKind::Patched, task, None)?;
+        db_notify_task_change(conn, caller.id, TaskEventKind::Patched, Some(&previous), &task)?;
+        Ok::<_, diesel::result::Error>(task)
+    })?;
     Ok(task)
 }
 
 fn update_single_task_due_date(
     db: web::Data<Pool>,
+    caller: ScopedUser,
     item: web::Json<UpdateTodoTaskDueDate>,
-) -> Result<TodoTask, diesel::result::Error> {
+) -> Result<TodoTask, AccessError> {
+    let caller = caller.require(ApiScope::TasksWrite)?;
     let mut conn = db.get().unwrap();
-    let task = diesel::update(todotasks)
-        .set(due_date.eq(&item.due_date))
-        .filter(id.eq(&item.task_id))
-        .get_result(&mut conn)?;
-    let _ = update(todotasks)
-        .set(modified_at.eq(chrono::Local::now().naive_local()))
-        .filter(id.eq(&item.task_id));
+    let previous = db_require_task_role(&mut conn, item.task_id, caller.id, ListRole::Editor)?;
+    let task = conn.transaction(|conn| {
+        let task: TodoTask = diesel::update(todotasks)
+            .set((
+                due_date.eq(&item.due_date),
+                modified_at.eq(chrono::Local::now().naive_local()),
+            ))
+            .filter(id.eq(&item.task_id))
+            .get_result(conn)?;
+        let task = publish_task_event(conn, TaskEventKind::Patched, task, None)?;
+        db_notify_task_change(conn, caller.id, TaskEventKind::Patched, Some(&previous), &task)?;
+        Ok::<_, diesel::result::Error>(task)
+    })?;
     Ok(task)
 }
 
@@ -115,60 +316,82 @@ to move tasks between workspaces or between users
 
 fn update_single_task_todolist_id(
     db: web::Data<Pool>,
+    caller: ScopedUser,
     item: web::Json<UpdateTodoTaskTodoListID>,
-) -> Result<TodoTask, diesel::result::Error> {
+) -> Result<TodoTask, AccessError> {
+    let caller = caller.require(ApiScope::TasksWrite)?;
     let mut conn = db.get().unwrap();
-    let task = diesel::update(todotasks)
-        .set(todolist_id.eq(&item.todolist_id))
-        .filter(id.eq(&item.task_id))
-        .get_result(&mut conn)?;
-    let _ = update(todotasks)
-        .set(modified_at.eq(chrono::Local::now().naive_local()))
-        .filter(id.eq(&item.task_id));
+    let previous = db_require_task_role(&mut conn, item.task_id, caller.id, ListRole::Editor)?;
+    db_require_list_role(&mut conn, item.todolist_id, caller.id, ListRole::Editor)?;
+    let task = conn.transaction(|conn| {
+        let task: TodoTask = diesel::update(todotasks)
+            .set((
+                todolist_id.eq(&item.todolist_id),
+                modified_at.eq(chrono::Local::now().naive_local()),
+            ))
+            .filter(id.eq(&item.task_id))
+            .get_result(conn)?;
+        let task = db_detach_moved_task(conn, task)?;
+        let task =
+            publish_task_event(conn, TaskEventKind::Moved, task, Some(previous.todolist_id))?;
+        db_notify_task_change(conn, caller.id, TaskEventKind::Moved, Some(&previous), &task)?;
+        Ok::<_, diesel::result::Error>(task)
+    })?;
     Ok(task)
 }
 
 #[post("/tasks/new")]
 pub async fn add_task(
     db: web::Data<Pool>,
+    caller: ScopedUser,
     item: web::Json<InputTodoTask>,
 ) -> Result<HttpResponse, Error> {
-    match web::block(move || add_single_task(db, item)).await {
-        Ok(task) => match serde_json::to_value(task.unwrap()) {
+    if item.validate().is_err() {
+        return Ok(throw_response_bad_request());
+    }
+    match web::block(move || add_single_task(db, caller, item)).await {
+        Ok(Ok(task)) => match serde_json::to_value(task) {
             Ok(response_body) => Ok(throw_response_created(response_body)),
             Err(e) => {
                 eprintln!("Failed to create task: {}", e);
                 Ok(throw_response_error())
             }
         },
+        Ok(Err(e)) => Ok(throw_response_access_error(e)),
         Err(_) => Ok(throw_response_error()),
     }
 }
 
 #[get("/tasks")]
-pub async fn get_tasks(db: web::Data<Pool>) -> HttpResponse {
-    match web::block(move || get_all_tasks(db)).await {
-        Ok(task) => match serde_json::to_value(task.unwrap()) {
+pub async fn get_tasks(db: web::Data<Pool>, caller: ScopedUser) -> HttpResponse {
+    match web::block(move || get_all_tasks(db, caller)).await {
+        Ok(Ok(task)) => match serde_json::to_value(task) {
             Ok(response_body) => throw_response_ok(response_body),
             Err(e) => {
                 eprintln!("Failed to get tasks: {}", e);
                 throw_response_error()
             }
         },
+        Ok(Err(e)) => throw_response_access_error(e),
         Err(_) => throw_response_error(),
     }
 }
 
 #[get("/tasks/{id}")]
-pub async fn get_task_by_id(db: web::Data<Pool>, task_id: web::Path<i32>) -> HttpResponse {
-    match web::block(move || db_get_task_by_id(db, *task_id)).await {
-        Ok(task) => match serde_json::to_value(task.unwrap()) {
+pub async fn get_task_by_id(
+    db: web::Data<Pool>,
+    caller: ScopedUser,
+    task_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || db_get_task_by_id(db, caller, *task_id)).await {
+        Ok(Ok(task)) => match serde_json::to_value(task) {
             Ok(response_body) => throw_response_ok(response_body),
             Err(e) => {
                 eprintln!("Failed to serialize task: {}", e);
                 throw_response_error()
             }
         },
+        Ok(Err(e)) => throw_response_access_error(e),
         Err(_) => throw_response_error(),
     }
 }
@@ -176,16 +399,19 @@ pub async fn get_task_by_id(db: web::Data<Pool>, task_id: web::Path<i32>) -> Htt
 #[delete("/tasks/{id}")]
 pub async fn delete_task(
     db: web::Data<Pool>,
+    storage: web::Data<dyn AttachmentStorage>,
+    caller: ScopedUser,
     task_id: web::Path<i32>,
 ) -> Result<HttpResponse, Error> {
-    match web::block(move || delete_single_task(db, task_id.into_inner())).await {
-        Ok(task) => match serde_json::to_value(task.unwrap()) {
+    match web::block(move || delete_single_task(db, storage, caller, task_id.into_inner())).await {
+        Ok(Ok(task)) => match serde_json::to_value(task) {
             Ok(response_body) => Ok(throw_response_ok(response_body)),
             Err(e) => {
                 eprintln!("Failed to delete task: {}", e);
                 Ok(throw_response_error())
             }
         },
+        Ok(Err(e)) => Ok(throw_response_access_error(e)),
         Err(_) => Ok(throw_response_error()),
     }
 }
@@ -193,16 +419,18 @@ pub async fn delete_task(
 #[patch("/tasks/update/name/{id}")]
 pub async fn patch_task_name(
     db: web::Data<Pool>,
+    caller: ScopedUser,
     task_id: web::Json<UpdateTodoTaskName>,
 ) -> impl Responder {
-    match web::block(move || update_single_task_name(db, task_id)).await {
-        Ok(updated_task) => match serde_json::to_value(updated_task.unwrap()) {
+    match web::block(move || update_single_task_name(db, caller, task_id)).await {
+        Ok(Ok(updated_task)) => match serde_json::to_value(updated_task) {
             Ok(response_body) => throw_response_ok(response_body),
             Err(e) => {
                 eprintln!("Failed to patch task name: {}", e);
                 throw_response_error()
             }
         },
+        Ok(Err(e)) => throw_response_access_error(e),
         Err(_) => throw_response_error(),
     }
 }
@@ -210,64 +438,72 @@ pub async fn patch_task_name(
 #[patch("/tasks/update/description/{id}")]
 pub async fn patch_task_description(
     db: web::Data<Pool>,
+    caller: ScopedUser,
     task_id: web::Json<UpdateTodoTaskDescription>,
 ) -> impl Responder {
-    match web::block(move || update_single_task_description(db, task_id)).await {
-        Ok(updated_task) => match serde_json::to_value(updated_task.unwrap()) {
+    match web::block(move || update_single_task_description(db, caller, task_id)).await {
+        Ok(Ok(updated_task)) => match serde_json::to_value(updated_task) {
             Ok(response_body) => throw_response_ok(response_body),
             Err(e) => {
                 eprintln!("Failed to patch task description: {}", e);
                 throw_response_error()
             }
         },
+        Ok(Err(e)) => throw_response_access_error(e),
         Err(_) => throw_response_error(),
     }
 }
 #[patch("/tasks/update/todolistid/{id}")]
 pub async fn patch_task_todolist_id(
     db: web::Data<Pool>,
+    caller: ScopedUser,
     task_id: web::Json<UpdateTodoTaskTodoListID>,
 ) -> impl Responder {
-    match web::block(move || update_single_task_todolist_id(db, task_id)).await {
-        Ok(updated_task) => match serde_json::to_value(updated_task.unwrap()) {
+    match web::block(move || update_single_task_todolist_id(db, caller, task_id)).await {
+        Ok(Ok(updated_task)) => match serde_json::to_value(updated_task) {
             Ok(response_body) => throw_response_ok(response_body),
             Err(e) => {
                 eprintln!("Failed to patch task todolist id: {}", e);
                 throw_response_error()
             }
         },
+        Ok(Err(e)) => throw_response_access_error(e),
         Err(_) => throw_response_error(),
     }
 }
 #[patch("/tasks/update/duedate/{id}")]
 pub async fn patch_task_due_date(
     db: web::Data<Pool>,
+    caller: ScopedUser,
     task_id: web::Json<UpdateTodoTaskDueDate>,
 ) -> impl Responder {
-    match web::block(move || update_single_task_due_date(db, task_id)).await {
-        Ok(updated_task) => match serde_json::to_value(updated_task.unwrap()) {
+    match web::block(move || update_single_task_due_date(db, caller, task_id)).await {
+        Ok(Ok(updated_task)) => match serde_json::to_value(updated_task) {
             Ok(response_body) => throw_response_ok(response_body),
             Err(e) => {
                 eprintln!("Failed to patch task name: {}", e);
                 throw_response_error()
             }
         },
+        Ok(Err(e)) => throw_response_access_error(e),
         Err(_) => throw_response_error(),
     }
 }
 #[patch("/tasks/update/parenttaskid/{id}")]
 pub async fn patch_task_parent_task_id(
     db: web::Data<Pool>,
+    caller: ScopedUser,
     task_id: web::Json<UpdateTodoTaskParentTaskID>,
 ) -> impl Responder {
-    match web::block(move || update_single_task_parent_task_id(db, task_id)).await {
-        Ok(updated_task) => match serde_json::to_value(updated_task.unwrap()) {
+    match web::block(move || update_single_task_parent_task_id(db, caller, task_id)).await {
+        Ok(Ok(updated_task)) => match serde_json::to_value(updated_task) {
             Ok(response_body) => throw_response_ok(response_body),
             Err(e) => {
                 eprintln!("Failed to patch task name: {}", e);
                 throw_response_error()
             }
         },
+        Ok(Err(e)) => throw_response_access_error(e),
         Err(_) => throw_response_error(),
     }
 }
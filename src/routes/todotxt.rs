@@ -0,0 +1,293 @@
+use crate::models::list_member::ListRole;
+use crate::models::tailored_response::*;
+use crate::models::task_tag::{TaskTag, MAX_TAG_CHARS};
+use crate::models::todo_list::{InputTodoList, TodoList};
+use crate::models::todo_task::{InputTodoTask, TodoTask};
+use crate::models::todotxt::*;
+use crate::routes::todo_list::db_insert_list;
+use crate::routes::todo_task::db_insert_task;
+use crate::schema::{task_tags, todolists, todotasks};
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::access::{
+    db_get_active_workspace, db_require_list_role, visible_list_ids, AccessError,
+};
+use crate::utils::database::connection::Pool;
+use crate::utils::todotxt::{format_line, parse_line, project_name, TodoTxtItem};
+use actix_web::web::{self};
+use actix_web::{get, post, HttpResponse};
+use diesel::prelude::*;
+use std::collections::HashMap;
+
+const MAX_IMPORT_LINES: usize = 2000;
+const INBOX_LIST_NAME: &str = "Inbox";
+
+fn to_item(task: &TodoTask, list_name: &str, tags: Vec<String>) -> TodoTxtItem {
+    TodoTxtItem {
+        completed: task.completed_at.is_some(),
+        completed_on: task.completed_at.map(|at| at.date()),
+        priority: task.priority.clone(),
+        created_on: Some(task.created_at.date()),
+        name: task.name.clone(),
+        project: Some(project_name(list_name)),
+        contexts: tags,
+        due_date: task.due_date,
+    }
+}
+
+fn db_export_todotxt(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+) -> Result<String, AccessError> {
+    let mut conn = pool.get().unwrap();
+    let workspace_id = db_get_active_workspace(&mut conn, caller.id)?;
+    let rows = todotasks::table
+        .inner_join(todolists::table.on(todolists::id.eq(todotasks::todolist_id)))
+        .filter(todotasks::todolist_id.eq_any(visible_list_ids(caller.id, workspace_id)))
+        .order((todolists::id.asc(), todotasks::id.asc()))
+        .select((todotasks::all_columns, todolists::name))
+        .load::<(TodoTask, String)>(&mut conn)?;
+    let task_ids: Vec<i32> = rows.iter().map(|(task, _)| task.id).collect();
+    let mut tags: HashMap<i32, Vec<String>> = HashMap::new();
+    for tag in task_tags::table
+        .filter(task_tags::task_id.eq_any(&task_ids))
+        .order(task_tags::tag.asc())
+        .load::<TaskTag>(&mut conn)?
+    {
+        tags.entry(tag.task_id).or_default().push(tag.tag);
+    }
+    let mut body = String::new();
+    for (task, list_name) in &rows {
+        let item = to_item(task, list_name, tags.remove(&task.id).unwrap_or_default());
+        body.push_str(&format_line(&item));
+        body.push('\n');
+    }
+    Ok(body)
+}
+
+/// Where a line's task goes: a list the caller can already see, or one the
+/// import creates.
+#[derive(Clone, PartialEq)]
+enum Target {
+    Existing(i32),
+    New(String),
+}
+
+/// Projects match the lists they were exported from, so `+Weekly_review`
+/// finds the list "Weekly review".
+fn find_list<'a>(lists: &'a [TodoList], project: &str) -> Option<&'a TodoList> {
+    lists.iter().find(|list| list.name == project).or_else(|| {
+        lists
+            .iter()
+            .find(|list| project_name(&list.name) == project)
+    })
+}
+
+fn db_check_line(
+    conn: &mut PgConnection,
+    caller: AuthenticatedUser,
+    item: &TodoTxtItem,
+    target: &Target,
+) -> Result<Option<String>, diesel::result::Error> {
+    let probe = InputTodoTask {
+        todolist_id: 0,
+        name: item.name.clone(),
+        description: None,
+        parent_task_id: None,
+        due_date: item.due_date,
+        priority: item.priority.clone(),
+        completed_at: None,
+    };
+    if let Err((_, message)) = probe.validate() {
+        return Ok(Some(message.to_string()));
+    }
+    if let Some(context) = item
+        .contexts
+        .iter()
+        .find(|c| c.chars().count() > MAX_TAG_CHARS)
+    {
+        return Ok(Some(format!(
+            "context @{} is longer than 64 characters",
+            context
+        )));
+    }
+    if let Target::Existing(list_id) = target {
+        match db_require_list_role(conn, *list_id, caller.id, ListRole::Editor) {
+            Ok(_) => {}
+            Err(AccessError::Forbidden) => {
+                return Ok(Some(String::from("the list is read-only to you")))
+            }
+            Err(AccessError::Database(e)) => return Err(e),
+        }
+    }
+    Ok(None)
+}
+
+fn db_insert_item(
+    conn: &mut PgConnection,
+    caller: AuthenticatedUser,
+    list_id: i32,
+    item: &TodoTxtItem,
+) -> Result<TodoTask, AccessError> {
+    let input = InputTodoTask {
+        todolist_id: list_id,
+        name: item.name.clone(),
+        description: None,
+        parent_task_id: None,
+        due_date: item.due_date,
+        priority: item.priority.clone(),
+        completed_at: item.completed.then(|| match item.completed_on {
+            Some(day) => day.and_time(chrono::NaiveTime::MIN),
+            None => chrono::Local::now().naive_local(),
+        }),
+    };
+    let mut task = db_insert_task(conn, caller, &input)?;
+    if let Some(created_on) = item.created_on {
+        task = diesel::update(todotasks::table.find(task.id))
+            .set(todotasks::created_at.eq(created_on.and_time(chrono::NaiveTime::MIN)))
+            .get_result(conn)?;
+    }
+    let tags: Vec<TaskTag> = item
+        .contexts
+        .iter()
+        .map(|context| TaskTag {
+            task_id: task.id,
+            tag: context.clone(),
+        })
+        .collect();
+    diesel::insert_into(task_tags::table)
+        .values(&tags)
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    Ok(task)
+}
+
+/// Checks every line first; nothing is written unless all of them pass.
+fn db_import_todotxt(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    query: TodoTxtImportQuery,
+    body: String,
+) -> Result<Option<TodoTxtImportReport>, AccessError> {
+    let mut conn = pool.get().unwrap();
+    let workspace_id = db_get_active_workspace(&mut conn, caller.id)?;
+    let lists = todolists::table
+        .filter(todolists::id.eq_any(visible_list_ids(caller.id, workspace_id)))
+        .order(todolists::id.asc())
+        .load::<TodoList>(&mut conn)?;
+    if let Some(list_id) = query.list_id {
+        db_require_list_role(&mut conn, list_id, caller.id, ListRole::Editor)?;
+    }
+
+    let mut items = Vec::new();
+    let mut errors = Vec::new();
+    for (index, line) in body.lines().enumerate() {
+        let Some(item) = parse_line(line) else {
+            continue;
+        };
+        if items.len() + errors.len() >= MAX_IMPORT_LINES {
+            return Ok(None);
+        }
+        let target = match (&item.project, query.list_id) {
+            (Some(project), _) => match find_list(&lists, project) {
+                Some(list) => Target::Existing(list.id),
+                None => Target::New(project.clone()),
+            },
+            (None, Some(list_id)) => Target::Existing(list_id),
+            (None, None) => match find_list(&lists, INBOX_LIST_NAME) {
+                Some(list) => Target::Existing(list.id),
+                None => Target::New(String::from(INBOX_LIST_NAME)),
+            },
+        };
+        match db_check_line(&mut conn, caller, &item, &target)? {
+            Some(message) => errors.push(TodoTxtLineError {
+                line: index + 1,
+                message,
+            }),
+            None => items.push((item, target)),
+        }
+    }
+
+    let lines = items.len() + errors.len();
+    let mut created_lists: Vec<String> = Vec::new();
+    for (_, target) in &items {
+        if let Target::New(name) = target {
+            if !created_lists.contains(name) {
+                created_lists.push(name.clone());
+            }
+        }
+    }
+    if !errors.is_empty() || query.dry_run {
+        return Ok(Some(TodoTxtImportReport {
+            dry_run: query.dry_run,
+            lines,
+            errors,
+            created_lists,
+            tasks: Vec::new(),
+        }));
+    }
+
+    let tasks = conn.transaction(|conn| {
+        let mut new_lists: HashMap<&str, i32> = HashMap::new();
+        for name in &created_lists {
+            let input = InputTodoList {
+                name: name.clone(),
+                description: None,
+            };
+            new_lists.insert(name, db_insert_list(conn, caller, &input)?.id);
+        }
+        items
+            .iter()
+            .map(|(item, target)| {
+                let list_id = match target {
+                    Target::Existing(list_id) => *list_id,
+                    Target::New(name) => new_lists[name.as_str()],
+                };
+                db_insert_item(conn, caller, list_id, item)
+            })
+            .collect::<Result<Vec<TodoTask>, AccessError>>()
+    })?;
+    Ok(Some(TodoTxtImportReport {
+        dry_run: false,
+        lines,
+        errors,
+        created_lists,
+        tasks,
+    }))
+}
+
+#[get("/export/todotxt")]
+pub async fn export_todotxt(db: web::Data<Pool>, caller: AuthenticatedUser) -> HttpResponse {
+    match web::block(move || db_export_todotxt(db, caller)).await {
+        Ok(Ok(body)) => HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .body(body),
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/import/todotxt")]
+pub async fn import_todotxt(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    query: web::Query<TodoTxtImportQuery>,
+    body: String,
+) -> HttpResponse {
+    let query = query.into_inner();
+    match web::block(move || db_import_todotxt(db, caller, query, body)).await {
+        Ok(Ok(Some(report))) => match serde_json::to_value(&report) {
+            Ok(response_body) if !report.errors.is_empty() => {
+                throw_response_unprocessable_entity(response_body)
+            }
+            Ok(response_body) if report.dry_run => throw_response_ok(response_body),
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize todo.txt import report: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Ok(None)) => throw_response_payload_too_large(),
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
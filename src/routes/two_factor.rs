@@ -0,0 +1,292 @@
+use crate::models::tailored_response::*;
+use crate::models::two_factor::*;
+use crate::schema::{recovery_codes, user_totp, users};
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::config;
+use crate::utils::database::access::AccessError;
+use crate::utils::database::connection::Pool;
+use crate::utils::totp;
+use crate::utils::two_factor::{
+    db_accept_totp_code, db_check_second_factor, db_get_confirmed_totp, db_replace_recovery_codes,
+    db_two_factor_required,
+};
+use actix_web::web::{self};
+use actix_web::{delete, get, post, HttpResponse};
+use diesel::prelude::*;
+
+/// What a two-factor operation can fail on besides access.
+enum TwoFactorError {
+    Access(AccessError),
+    /// Enrolling twice, or managing an enrollment that doesn't exist.
+    WrongState,
+    /// The code given was wrong or already used.
+    InvalidCode,
+}
+
+impl From<AccessError> for TwoFactorError {
+    fn from(error: AccessError) -> Self {
+        TwoFactorError::Access(error)
+    }
+}
+
+impl From<diesel::result::Error> for TwoFactorError {
+    fn from(error: diesel::result::Error) -> Self {
+        TwoFactorError::Access(error.into())
+    }
+}
+
+fn throw_response_two_factor_error(error: TwoFactorError) -> HttpResponse {
+    match error {
+        TwoFactorError::Access(e) => throw_response_access_error(e),
+        TwoFactorError::WrongState => throw_response_bad_request(),
+        TwoFactorError::InvalidCode => throw_response_unauthorized(),
+    }
+}
+
+fn get_totp_status(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: i32,
+) -> Result<UserTotp, AccessError> {
+    if caller.id != target_user_id {
+        return Err(AccessError::Forbidden);
+    }
+    let mut conn = pool.get().unwrap();
+    let enrollment = user_totp::table
+        .find(target_user_id)
+        .select(UserTotp::as_select())
+        .first(&mut conn)?;
+    Ok(enrollment)
+}
+
+/// Issues a new secret unless two-factor is already on.
+fn start_totp_enrollment(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: i32,
+) -> Result<TotpEnrollment, TwoFactorError> {
+    if caller.id != target_user_id {
+        return Err(AccessError::Forbidden.into());
+    }
+    let mut conn = pool.get().unwrap();
+    conn.transaction(|conn| {
+        if db_get_confirmed_totp(conn, target_user_id)?.is_some() {
+            return Err(TwoFactorError::WrongState);
+        }
+        let account = users::table
+            .find(target_user_id)
+            .select(users::email)
+            .first::<String>(conn)?;
+        let new_totp = NewUserTotp {
+            user_id: target_user_id,
+            secret: totp::generate_secret(),
+            confirmed_at: None,
+            last_step: None,
+            created_at: chrono::Local::now().naive_local(),
+        };
+        let enrollment = diesel::insert_into(user_totp::table)
+            .values(&new_totp)
+            .on_conflict(user_totp::user_id)
+            .do_update()
+            .set(&new_totp)
+            .returning(UserTotp::as_returning())
+            .get_result(conn)?;
+        Ok(TotpEnrollment {
+            provisioning_uri: totp::provisioning_uri(
+                &new_totp.secret,
+                &account,
+                &config::get_totp_issuer(),
+            ),
+            secret: new_totp.secret,
+            totp: enrollment,
+        })
+    })
+}
+
+/// The first valid code turns two-factor on and hands out recovery codes.
+fn confirm_totp_enrollment(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: i32,
+    item: TotpCode,
+) -> Result<RecoveryCodes, TwoFactorError> {
+    if caller.id != target_user_id {
+        return Err(AccessError::Forbidden.into());
+    }
+    let mut conn = pool.get().unwrap();
+    conn.transaction(|conn| {
+        let enrollment = user_totp::table
+            .find(target_user_id)
+            .filter(user_totp::confirmed_at.is_null())
+            .select(UserTotp::as_select())
+            .first(conn)
+            .optional()?
+            .ok_or(TwoFactorError::WrongState)?;
+        if !db_accept_totp_code(conn, &enrollment, &item.code)? {
+            return Err(TwoFactorError::InvalidCode);
+        }
+        diesel::update(user_totp::table.find(target_user_id))
+            .set(user_totp::confirmed_at.eq(chrono::Local::now().naive_local()))
+            .execute(conn)?;
+        let recovery_codes = db_replace_recovery_codes(conn, target_user_id)?;
+        Ok(RecoveryCodes { recovery_codes })
+    })
+}
+
+/// Needs a second factor, and is refused while any of the user's workspaces
+/// requires two-factor.
+fn disable_totp(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: i32,
+    factor: SecondFactor,
+) -> Result<usize, TwoFactorError> {
+    if caller.id != target_user_id {
+        return Err(AccessError::Forbidden.into());
+    }
+    let mut conn = pool.get().unwrap();
+    conn.transaction(|conn| {
+        if db_get_confirmed_totp(conn, target_user_id)?.is_none() {
+            return Err(TwoFactorError::WrongState);
+        }
+        if db_two_factor_required(conn, target_user_id)? {
+            return Err(AccessError::Forbidden.into());
+        }
+        if !db_check_second_factor(conn, target_user_id, &factor)? {
+            return Err(TwoFactorError::InvalidCode);
+        }
+        diesel::delete(recovery_codes::table.filter(recovery_codes::user_id.eq(target_user_id)))
+            .execute(conn)?;
+        let deletion = diesel::delete(user_totp::table.find(target_user_id)).execute(conn)?;
+        Ok(deletion)
+    })
+}
+
+fn regenerate_recovery_codes(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: i32,
+    factor: SecondFactor,
+) -> Result<RecoveryCodes, TwoFactorError> {
+    if caller.id != target_user_id {
+        return Err(AccessError::Forbidden.into());
+    }
+    let mut conn = pool.get().unwrap();
+    conn.transaction(|conn| {
+        if db_get_confirmed_totp(conn, target_user_id)?.is_none() {
+            return Err(TwoFactorError::WrongState);
+        }
+        if !db_check_second_factor(conn, target_user_id, &factor)? {
+            return Err(TwoFactorError::InvalidCode);
+        }
+        let recovery_codes = db_replace_recovery_codes(conn, target_user_id)?;
+        Ok(RecoveryCodes { recovery_codes })
+    })
+}
+
+#[get("/users/{id}/totp")]
+pub async fn get_totp(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || get_totp_status(db, caller, target_user_id.into_inner())).await {
+        Ok(Ok(enrollment)) => match serde_json::to_value(enrollment) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize two-factor status: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/users/{id}/totp")]
+pub async fn enroll_totp(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || start_totp_enrollment(db, caller, target_user_id.into_inner())).await {
+        Ok(Ok(enrollment)) => match serde_json::to_value(enrollment) {
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to start two-factor enrollment: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_two_factor_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/users/{id}/totp/confirm")]
+pub async fn confirm_totp(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: web::Path<i32>,
+    item: web::Json<TotpCode>,
+) -> HttpResponse {
+    let item = item.into_inner();
+    match web::block(move || confirm_totp_enrollment(db, caller, target_user_id.into_inner(), item))
+        .await
+    {
+        Ok(Ok(codes)) => match serde_json::to_value(codes) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to confirm two-factor enrollment: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_two_factor_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[delete("/users/{id}/totp")]
+pub async fn delete_totp(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: web::Path<i32>,
+    item: web::Json<SecondFactor>,
+) -> HttpResponse {
+    let factor = item.into_inner();
+    match web::block(move || disable_totp(db, caller, target_user_id.into_inner(), factor)).await {
+        Ok(Ok(deletion)) => match serde_json::to_value(deletion) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to disable two-factor: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_two_factor_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/users/{id}/totp/recovery-codes")]
+pub async fn replace_recovery_codes(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_user_id: web::Path<i32>,
+    item: web::Json<SecondFactor>,
+) -> HttpResponse {
+    let factor = item.into_inner();
+    match web::block(move || {
+        regenerate_recovery_codes(db, caller, target_user_id.into_inner(), factor)
+    })
+    .await
+    {
+        Ok(Ok(codes)) => match serde_json::to_value(codes) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to replace recovery codes: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_two_factor_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
@@ -1,108 +1,214 @@
 use crate::{
     diesel::{QueryDsl, RunQueryDsl},
+    models::account_deletion::InputAccountDeletion,
+    models::api_key::ApiScope,
+    models::email_token::TokenPurpose,
+    models::tailored_response::*,
     models::user::{
         InputUser, NewUser, UpdateUserEmail, UpdateUserFirstName, UpdateUserLastName, User,
     },
+    routes::account_deletion::request_account_deletion,
+    routes::workspace::{db_create_workspace, PERSONAL_WORKSPACE_NAME},
     schema::users::dsl::*,
+    utils::auth::{AuthenticatedUser, ScopedUser},
+    utils::database::access::AccessError,
     utils::database::connection::Pool,
-    models::tailored_response::*
+    utils::email_tokens::{db_issue_email_token, send_email_token},
+    utils::events::{record_event, user_audience},
+    utils::mailer::Mailer,
+    utils::password,
 };
 use actix_web::{
     delete, get, patch, post,
     web::{self},
     Error, HttpResponse, Responder,
 };
-use diesel::{
-    dsl::{delete, insert_into},
-    ExpressionMethods,
-};
+use diesel::result::{DatabaseErrorKind, Error::DatabaseError};
+use diesel::{dsl::insert_into, Connection, ExpressionMethods};
+use std::sync::Arc;
 use std::vec::Vec;
 
-fn get_all_users(pool: web::Data<Pool>) -> Result<Vec<User>, diesel::result::Error> {
+/// Users only ever change their own account: the path and the body have to
+/// name the caller.
+fn require_own_account(
+    caller: ScopedUser,
+    path_user_id: i32,
+    body_user_id: i32,
+) -> Result<AuthenticatedUser, AccessError> {
+    let caller = caller.require(ApiScope::UsersWrite)?;
+    if caller.id != path_user_id || body_user_id != path_user_id {
+        return Err(AccessError::Forbidden);
+    }
+    Ok(caller)
+}
+
+/// The caller and everyone sharing a workspace with them.
+fn get_all_users(pool: web::Data<Pool>, caller: ScopedUser) -> Result<Vec<User>, AccessError> {
+    let caller = caller.require(ApiScope::UsersRead)?;
     let mut conn = pool.get().unwrap();
-    let items = users.load::<User>(&mut conn)?;
+    let audience = user_audience(&mut conn, caller.id)?;
+    let items = users
+        .filter(id.eq_any(audience))
+        .order(id.asc())
+        .load::<User>(&mut conn)?;
     Ok(items)
 }
 
-fn db_get_user_by_id(pool: web::Data<Pool>, user_id: i32) -> Result<User, diesel::result::Error> {
+fn db_get_user_by_id(
+    pool: web::Data<Pool>,
+    caller: ScopedUser,
+    user_id: i32,
+) -> Result<User, AccessError> {
+    let caller = caller.require(ApiScope::UsersRead)?;
+    if caller.id != user_id {
+        return Err(AccessError::Forbidden);
+    }
     let mut conn = pool.get().unwrap();
-    users.find(user_id).get_result::<User>(&mut conn)
+    let user = users.find(user_id).get_result::<User>(&mut conn)?;
+    Ok(user)
 }
 
 fn add_single_user(
     pool: web::Data<Pool>,
+    mailer: web::Data<Option<Arc<Mailer>>>,
     item: web::Json<InputUser>,
 ) -> Result<User, diesel::result::Error> {
     let mut conn = pool.get().unwrap();
     let new_user = NewUser {
         first_name: &item.first_name,
         last_name: &item.last_name,
-        email: &item.email,
+        email: item.email.trim(),
         created_at: chrono::Local::now().naive_local(),
         modified_at: chrono::Local::now().naive_local(),
     };
-    let res = insert_into(users).values(&new_user).get_result(&mut conn)?;
-    Ok(res)
+    let (user, issued) = conn.transaction(|conn| {
+        let user: User = insert_into(users).values(&new_user).get_result(conn)?;
+        db_create_workspace(conn, user.id, PERSONAL_WORKSPACE_NAME)?;
+        let user: User = users.find(user.id).get_result(conn)?;
+        record_event(conn, "user.created", &user, &[user.id])?;
+        let issued = db_issue_email_token(conn, user.id, TokenPurpose::VerifyEmail, &user.email)?;
+        Ok::<_, diesel::result::Error>((user, issued))
+    })?;
+    send_email_token(mailer.as_deref(), &issued);
+    Ok(user)
 }
 
+/// A new address has to be verified again; the old one's tokens stop working
+/// since they no longer match. An address another account has is refused by
+/// the unique index on `lower(email)`.
 fn update_user_email(
     pool: web::Data<Pool>,
-    item: web::Json<UpdateUserEmail>,
-) -> Result<User, diesel::result::Error> {
+    mailer: web::Data<Option<Arc<Mailer>>>,
+    caller: ScopedUser,
+    path_user_id: i32,
+    item: UpdateUserEmail,
+) -> Result<User, AccessError> {
+    let caller = require_own_account(caller, path_user_id, item.id)?;
     let mut conn = pool.get().unwrap();
-    let res_email = diesel::update(users)
-        .set(email.eq(&item.email))
-        .filter(id.eq(&item.id))
-        .get_result(&mut conn)?;
-    let _ = diesel::update(users)
-        .set(modified_at.eq(chrono::Local::now().naive_local()))
-        .filter(id.eq(&item.id));
-    Ok(res_email)
+    let (user, issued) = conn.transaction(|conn| {
+        let previous: User = users.find(caller.id).for_update().get_result(conn)?;
+        let password_matches = previous
+            .password_hash
+            .as_deref()
+            .is_some_and(|expected| password::verify_password(&item.current_password, expected));
+        if !password_matches {
+            return Err(AccessError::Forbidden);
+        }
+        let new_email = item.email.trim();
+        let changed = !previous.email.eq_ignore_ascii_case(new_email);
+        let verified_at = previous.email_verified_at.filter(|_| !changed);
+        let user: User = diesel::update(users)
+            .set((
+                email.eq(new_email),
+                email_verified_at.eq(verified_at),
+                modified_at.eq(chrono::Local::now().naive_local()),
+            ))
+            .filter(id.eq(caller.id))
+            .get_result(conn)?;
+        let audience = user_audience(conn, user.id)?;
+        record_event(conn, "user.patched", &user, &audience)?;
+        let issued = match changed {
+            true => Some(db_issue_email_token(
+                conn,
+                user.id,
+                TokenPurpose::VerifyEmail,
+                &user.email,
+            )?),
+            false => None,
+        };
+        Ok::<_, AccessError>((user, issued))
+    })?;
+    if let Some(issued) = issued {
+        send_email_token(mailer.as_deref(), &issued);
+    }
+    Ok(user)
 }
 
 fn update_user_first_name(
     pool: web::Data<Pool>,
-    item: web::Json<UpdateUserFirstName>,
-) -> Result<User, diesel::result::Error> {
+    caller: ScopedUser,
+    path_user_id: i32,
+    item: UpdateUserFirstName,
+) -> Result<User, AccessError> {
+    let caller = require_own_account(caller, path_user_id, item.id)?;
     let mut conn = pool.get().unwrap();
-    let res_first_name = diesel::update(users)
-        .set(first_name.eq(&item.first_name))
-        .filter(id.eq(&item.id))
-        .get_result(&mut conn)?;
-    let _ = diesel::update(users)
-        .set(modified_at.eq(chrono::Local::now().naive_local()))
-        .filter(id.eq(&item.id));
-    Ok(res_first_name)
+    let user = conn.transaction(|conn| {
+        let user: User = diesel::update(users)
+            .set((
+                first_name.eq(&item.first_name),
+                modified_at.eq(chrono::Local::now().naive_local()),
+            ))
+            .filter(id.eq(caller.id))
+            .get_result(conn)?;
+        let audience = user_audience(conn, user.id)?;
+        record_event(conn, "user.patched", &user, &audience)?;
+        Ok::<_, diesel::result::Error>(user)
+    })?;
+    Ok(user)
 }
 
 fn update_user_last_name(
     pool: web::Data<Pool>,
-    item: web::Json<UpdateUserLastName>,
-) -> Result<User, diesel::result::Error> {
+    caller: ScopedUser,
+    path_user_id: i32,
+    item: UpdateUserLastName,
+) -> Result<User, AccessError> {
+    let caller = require_own_account(caller, path_user_id, item.id)?;
     let mut conn = pool.get().unwrap();
-    let res_email = diesel::update(users)
-        .set(last_name.eq(&item.last_name))
-        .filter(id.eq(&item.id))
-        .get_result(&mut conn)?;
-    let _ = diesel::update(users)
-        .set(modified_at.eq(chrono::Local::now().naive_local()))
-        .filter(id.eq(&item.id));
-    Ok(res_email)
+    let user = conn.transaction(|conn| {
+        let user: User = diesel::update(users)
+            .set((
+                last_name.eq(&item.last_name),
+                modified_at.eq(chrono::Local::now().naive_local()),
+            ))
+            .filter(id.eq(caller.id))
+            .get_result(conn)?;
+        let audience = user_audience(conn, user.id)?;
+        record_event(conn, "user.patched", &user, &audience)?;
+        Ok::<_, diesel::result::Error>(user)
+    })?;
+    Ok(user)
 }
 
 #[patch("/users/update/firstname/{id}")]
 pub async fn patch_user_first_name(
     db: web::Data<Pool>,
+    caller: ScopedUser,
+    path_user_id: web::Path<i32>,
     item: web::Json<UpdateUserFirstName>,
 ) -> impl Responder {
-    match web::block(move || update_user_first_name(db, item)).await {
-        Ok(user) => match serde_json::to_value(user.unwrap()) {
+    let item = item.into_inner();
+    match web::block(move || update_user_first_name(db, caller, path_user_id.into_inner(), item))
+        .await
+    {
+        Ok(Ok(user)) => match serde_json::to_value(user) {
             Ok(response_body) => throw_response_ok(response_body),
             Err(e) => {
                 eprintln!("Failed to patch user's first name: {}", e);
                 throw_response_error()
             }
         },
+        Ok(Err(e)) => throw_response_access_error(e),
         Err(_) => throw_response_error(),
     }
 }
@@ -110,16 +216,22 @@ pub async fn patch_user_first_name(
 #[patch("/users/update/lastname/{id}")]
 pub async fn patch_user_last_name(
     db: web::Data<Pool>,
+    caller: ScopedUser,
+    path_user_id: web::Path<i32>,
     item: web::Json<UpdateUserLastName>,
 ) -> impl Responder {
-    match web::block(move || update_user_last_name(db, item)).await {
-        Ok(user) => match serde_json::to_value(user.unwrap()) {
+    let item = item.into_inner();
+    match web::block(move || update_user_last_name(db, caller, path_user_id.into_inner(), item))
+        .await
+    {
+        Ok(Ok(user)) => match serde_json::to_value(user) {
             Ok(response_body) => throw_response_ok(response_body),
             Err(e) => {
-                eprintln!("Failed to patch user's first name: {}", e);
+                eprintln!("Failed to patch user's last name: {}", e);
                 throw_response_error()
             }
         },
+        Ok(Err(e)) => throw_response_access_error(e),
         Err(_) => throw_response_error(),
     }
 }
@@ -127,36 +239,41 @@ pub async fn patch_user_last_name(
 #[patch("/users/update/email/{id}")]
 pub async fn patch_user_email(
     db: web::Data<Pool>,
+    caller: ScopedUser,
+    mailer: web::Data<Option<Arc<Mailer>>>,
+    path_user_id: web::Path<i32>,
     item: web::Json<UpdateUserEmail>,
 ) -> impl Responder {
-    match web::block(move || update_user_email(db, item)).await {
-        Ok(user) => match serde_json::to_value(user.unwrap()) {
+    let item = item.into_inner();
+    match web::block(move || update_user_email(db, mailer, caller, path_user_id.into_inner(), item))
+        .await
+    {
+        Ok(Ok(user)) => match serde_json::to_value(user) {
             Ok(response_body) => throw_response_ok(response_body),
             Err(e) => {
-                eprintln!("Failed to patch user's first name: {}", e);
+                eprintln!("Failed to patch user's email: {}", e);
                 throw_response_error()
             }
         },
+        Ok(Err(AccessError::Database(DatabaseError(DatabaseErrorKind::UniqueViolation, _)))) => {
+            throw_response_conflict()
+        }
+        Ok(Err(e)) => throw_response_access_error(e),
         Err(_) => throw_response_error(),
     }
 }
 
-fn delete_single_user(db: web::Data<Pool>, user_id: i32) -> Result<usize, diesel::result::Error> {
-    let mut conn = db.get().unwrap();
-    let count = delete(users.find(user_id)).execute(&mut conn)?;
-    Ok(count)
-}
-
 #[get("/users")]
-pub async fn get_users(db: web::Data<Pool>) -> impl Responder {
-    match web::block(move || get_all_users(db)).await {
-        Ok(user) => match serde_json::to_value(user.unwrap()) {
+pub async fn get_users(db: web::Data<Pool>, caller: ScopedUser) -> impl Responder {
+    match web::block(move || get_all_users(db, caller)).await {
+        Ok(Ok(user)) => match serde_json::to_value(user) {
             Ok(response_body) => throw_response_ok(response_body),
             Err(e) => {
                 eprintln!("Failed to serialize user: {}", e);
                 throw_response_error()
             }
         },
+        Ok(Err(e)) => throw_response_access_error(e),
         Err(_) => throw_response_error(),
     }
 }
@@ -164,16 +281,18 @@ pub async fn get_users(db: web::Data<Pool>) -> impl Responder {
 #[get("/users/{id}")]
 pub async fn get_user_by_id(
     db: web::Data<Pool>,
+    caller: ScopedUser,
     user_id: web::Path<i32>,
 ) -> Result<HttpResponse, Error> {
-    match web::block(move || db_get_user_by_id(db, user_id.into_inner())).await {
-        Ok(user) => match serde_json::to_value(user.unwrap()) {
+    match web::block(move || db_get_user_by_id(db, caller, user_id.into_inner())).await {
+        Ok(Ok(user)) => match serde_json::to_value(user) {
             Ok(response_body) => Ok(throw_response_ok(response_body)),
             Err(e) => {
                 eprintln!("Failed to serialize user: {}", e);
                 Ok(throw_response_error())
             }
         },
+        Ok(Err(e)) => Ok(throw_response_access_error(e)),
         Err(e) => {
             eprintln!("Failed to retrieve user: {}", e);
             Ok(throw_response_error())
@@ -184,33 +303,53 @@ pub async fn get_user_by_id(
 #[post("/users")]
 pub async fn add_user(
     db: web::Data<Pool>,
+    mailer: web::Data<Option<Arc<Mailer>>>,
     item: web::Json<InputUser>,
 ) -> Result<HttpResponse, Error> {
-    match web::block(move || add_single_user(db, item)).await {
-        Ok(user) => match serde_json::to_value(user.unwrap()) {
+    match web::block(move || add_single_user(db, mailer, item)).await {
+        Ok(Ok(user)) => match serde_json::to_value(user) {
             Ok(response_body) => Ok(throw_response_created(response_body)),
             Err(e) => {
                 eprintln!("Failed to create user: {}", e);
                 Ok(throw_response_error())
             }
         },
+        Ok(Err(DatabaseError(DatabaseErrorKind::UniqueViolation, _))) => {
+            Ok(throw_response_conflict())
+        }
+        Ok(Err(e)) => {
+            eprintln!("Failed to create user: {}", e);
+            Ok(throw_response_error())
+        }
         Err(_) => Ok(throw_response_error()),
     }
 }
 
+/// Same as requesting the deletion: the account goes once the grace period
+/// is over, and can be cancelled until then.
 #[delete("/users/{id}")]
 pub async fn delete_user(
     db: web::Data<Pool>,
+    caller: ScopedUser,
     user_id: web::Path<i32>,
-) -> Result<HttpResponse, Error> {
-    match web::block(move || delete_single_user(db, user_id.into_inner())).await {
-        Ok(user) => match serde_json::to_value(user.unwrap()) {
-            Ok(response_body) => Ok(throw_response_ok(response_body)),
+    item: web::Json<InputAccountDeletion>,
+) -> HttpResponse {
+    let caller = match caller.require(ApiScope::UsersWrite) {
+        Ok(caller) => caller,
+        Err(e) => return throw_response_access_error(e),
+    };
+    let item = item.into_inner();
+    match web::block(move || request_account_deletion(db, caller, user_id.into_inner(), item)).await
+    {
+        Ok(Ok(Some(created))) => match serde_json::to_value(created) {
+            Ok(response_body) => throw_response_created(response_body),
             Err(e) => {
                 eprintln!("Failed to delete user: {}", e);
-                Ok(throw_response_error())
+                throw_response_error()
             }
         },
-        Err(_) => Ok(throw_response_error()),
+        Ok(Ok(None)) => throw_response_bad_request(),
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
     }
 }
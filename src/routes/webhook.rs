@@ -0,0 +1,285 @@
+use crate::models::list_member::ListRole;
+use crate::models::tailored_response::*;
+use crate::models::webhook::*;
+use crate::models::webhook_delivery::WebhookDelivery;
+use crate::schema::webhook_deliveries;
+use crate::schema::webhooks::dsl::*;
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::access::{db_require_list_role, AccessError};
+use crate::utils::database::connection::Pool;
+use crate::utils::webhooks::{disable_webhook, refused_target};
+use crate::utils::{config, token};
+use actix_web::web::{self};
+use actix_web::{delete, get, patch, post, HttpResponse};
+use diesel::prelude::*;
+use diesel::{delete, insert_into};
+
+const DELIVERY_LOG_LIMIT: i64 = 100;
+
+/// Addresses that can't be delivered to are refused up front; hostnames are
+/// only checked once they are resolved for a delivery.
+fn is_valid_url(raw: &str) -> bool {
+    let valid = reqwest::Url::parse(raw)
+        .map(|parsed| matches!(parsed.scheme(), "http" | "https") && parsed.host().is_some())
+        .unwrap_or(false);
+    valid && refused_target(raw, config::get_webhooks_allow_private()).is_none()
+}
+
+/// Patterns are event kinds such as `task.created`, optionally ending in `*`.
+fn is_valid_event_filter(filter: &str) -> bool {
+    filter.split(',').map(str::trim).all(|pattern| {
+        let body = pattern.strip_suffix('*').unwrap_or(pattern);
+        !pattern.is_empty()
+            && body
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c == '.' || c == '_')
+    })
+}
+
+/// Other users' webhooks are reported as missing.
+fn db_get_owned_webhook(
+    conn: &mut PgConnection,
+    caller: AuthenticatedUser,
+    webhook_id: i32,
+) -> Result<Webhook, diesel::result::Error> {
+    webhooks
+        .find(webhook_id)
+        .filter(user_id.eq(caller.id))
+        .get_result::<Webhook>(conn)
+}
+
+fn get_all_webhooks(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+) -> Result<Vec<Webhook>, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    webhooks
+        .filter(user_id.eq(caller.id))
+        .order(id.asc())
+        .load::<Webhook>(&mut conn)
+}
+
+fn db_get_webhook_by_id(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    webhook_id: i32,
+) -> Result<Webhook, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    db_get_owned_webhook(&mut conn, caller, webhook_id)
+}
+
+fn add_single_webhook(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    item: InputWebhook,
+) -> Result<CreatedWebhook, AccessError> {
+    let mut conn = pool.get().unwrap();
+    if let Some(target_list_id) = item.list_id {
+        db_require_list_role(&mut conn, target_list_id, caller.id, ListRole::Viewer)?;
+    }
+    let signing_secret = token::generate_token();
+    let now = chrono::Local::now().naive_local();
+    let new_webhook = NewWebhook {
+        user_id: caller.id,
+        list_id: item.list_id,
+        url: item.url,
+        secret: signing_secret.clone(),
+        event_filter: item.event_filter.unwrap_or_else(|| String::from("*")),
+        created_at: now,
+        modified_at: now,
+    };
+    let webhook = insert_into(webhooks)
+        .values(&new_webhook)
+        .get_result::<Webhook>(&mut conn)?;
+    Ok(CreatedWebhook {
+        webhook,
+        secret: signing_secret,
+    })
+}
+
+fn update_single_webhook(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    webhook_id: i32,
+    changes: UpdateWebhook,
+) -> Result<Webhook, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    conn.transaction(|conn| {
+        db_get_owned_webhook(conn, caller, webhook_id)?;
+        let now = chrono::Local::now().naive_local();
+        match changes.enabled {
+            Some(true) => {
+                diesel::update(webhooks.find(webhook_id))
+                    .set((
+                        consecutive_failures.eq(0),
+                        disabled_at.eq(None::<chrono::NaiveDateTime>),
+                    ))
+                    .execute(conn)?;
+            }
+            Some(false) => disable_webhook(conn, webhook_id, now)?,
+            None => {}
+        }
+        diesel::update(webhooks.find(webhook_id))
+            .set((&changes, modified_at.eq(now)))
+            .get_result(conn)
+    })
+}
+
+fn delete_single_webhook(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    webhook_id: i32,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    delete(webhooks.find(webhook_id))
+        .filter(user_id.eq(caller.id))
+        .execute(&mut conn)
+}
+
+fn get_webhook_deliveries(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    webhook_id: i32,
+) -> Result<Vec<WebhookDelivery>, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    db_get_owned_webhook(&mut conn, caller, webhook_id)?;
+    webhook_deliveries::table
+        .filter(webhook_deliveries::webhook_id.eq(webhook_id))
+        .order(webhook_deliveries::id.desc())
+        .limit(DELIVERY_LOG_LIMIT)
+        .load::<WebhookDelivery>(&mut conn)
+}
+
+#[get("/webhooks")]
+pub async fn get_webhooks(db: web::Data<Pool>, caller: AuthenticatedUser) -> HttpResponse {
+    match web::block(move || get_all_webhooks(db, caller)).await {
+        Ok(Ok(hooks)) => match serde_json::to_value(hooks) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize webhooks: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => {
+            eprintln!("Failed to get webhooks: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[get("/webhooks/{id}")]
+pub async fn get_webhook_by_id(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    webhook_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || db_get_webhook_by_id(db, caller, webhook_id.into_inner())).await {
+        Ok(Ok(hook)) => match serde_json::to_value(hook) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize webhook: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e.into()),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/webhooks")]
+pub async fn add_webhook(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    item: web::Json<InputWebhook>,
+) -> HttpResponse {
+    let item = item.into_inner();
+    if !is_valid_url(&item.url)
+        || !item
+            .event_filter
+            .as_deref()
+            .is_none_or(is_valid_event_filter)
+    {
+        return throw_response_bad_request();
+    }
+    match web::block(move || add_single_webhook(db, caller, item)).await {
+        Ok(Ok(created)) => match serde_json::to_value(created) {
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to create webhook: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[patch("/webhooks/{id}")]
+pub async fn patch_webhook(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    webhook_id: web::Path<i32>,
+    item: web::Json<UpdateWebhook>,
+) -> HttpResponse {
+    let changes = item.into_inner();
+    if !changes.url.as_deref().is_none_or(is_valid_url)
+        || !changes
+            .event_filter
+            .as_deref()
+            .is_none_or(is_valid_event_filter)
+    {
+        return throw_response_bad_request();
+    }
+    match web::block(move || update_single_webhook(db, caller, webhook_id.into_inner(), changes))
+        .await
+    {
+        Ok(Ok(hook)) => match serde_json::to_value(hook) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to patch webhook: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e.into()),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[delete("/webhooks/{id}")]
+pub async fn delete_webhook(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    webhook_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || delete_single_webhook(db, caller, webhook_id.into_inner())).await {
+        Ok(Ok(deletion)) => match serde_json::to_value(deletion) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to delete webhook: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e.into()),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[get("/webhooks/{id}/deliveries")]
+pub async fn get_deliveries(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    webhook_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || get_webhook_deliveries(db, caller, webhook_id.into_inner())).await {
+        Ok(Ok(deliveries)) => match serde_json::to_value(deliveries) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize webhook deliveries: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e.into()),
+        Err(_) => throw_response_error(),
+    }
+}
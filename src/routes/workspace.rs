@@ -0,0 +1,521 @@
+use crate::models::tailored_response::*;
+use crate::models::workspace::*;
+use crate::models::workspace_member::*;
+use crate::schema::workspaces::dsl::*;
+use crate::schema::{users, workspace_members};
+use crate::utils::auth::AuthenticatedUser;
+use crate::utils::database::access::{db_require_workspace_role, AccessError};
+use crate::utils::database::connection::Pool;
+use crate::utils::events::publish_access_change;
+use actix_web::web::{self};
+use actix_web::{delete, get, patch, post, HttpResponse};
+use diesel::prelude::*;
+use diesel::{delete, insert_into};
+
+/// What the workspace every account starts with is called, matching the
+/// workspaces the migration gave existing accounts.
+pub(crate) const PERSONAL_WORKSPACE_NAME: &str = "Personal";
+
+/// A workspace must always keep at least one admin.
+fn db_check_not_last_admin(
+    conn: &mut PgConnection,
+    target_workspace_id: i32,
+    target_user_id: i32,
+) -> Result<(), AccessError> {
+    let member = workspace_members::table
+        .find((target_workspace_id, target_user_id))
+        .get_result::<WorkspaceMember>(conn)?;
+    if member.role != WorkspaceRole::Admin {
+        return Ok(());
+    }
+    let admins: i64 = workspace_members::table
+        .filter(workspace_members::workspace_id.eq(target_workspace_id))
+        .filter(workspace_members::role.eq(WorkspaceRole::Admin))
+        .count()
+        .get_result(conn)?;
+    if admins <= 1 {
+        return Err(AccessError::Forbidden);
+    }
+    Ok(())
+}
+
+fn get_all_workspaces(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+) -> Result<Vec<Workspace>, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    workspaces
+        .inner_join(workspace_members::table)
+        .filter(workspace_members::user_id.eq(caller.id))
+        .select(workspaces::all_columns())
+        .order(id.asc())
+        .load::<Workspace>(&mut conn)
+}
+
+fn db_get_workspace_by_id(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_workspace_id: i32,
+) -> Result<Workspace, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_workspace_role(
+        &mut conn,
+        target_workspace_id,
+        caller.id,
+        WorkspaceRole::Member,
+    )?;
+    let workspace = workspaces.find(target_workspace_id).get_result(&mut conn)?;
+    Ok(workspace)
+}
+
+/// The creator becomes the first admin, and the new workspace becomes their
+/// active one if they had none. Signups get their personal workspace this
+/// way, so that new accounts can use lists straight away.
+pub(crate) fn db_create_workspace(
+    conn: &mut PgConnection,
+    user_id: i32,
+    workspace_name: &str,
+) -> Result<Workspace, diesel::result::Error> {
+    let now = chrono::Local::now().naive_local();
+    conn.transaction(|conn| {
+        let workspace: Workspace = insert_into(workspaces)
+            .values(&NewWorkspace {
+                name: workspace_name.to_string(),
+                created_at: now,
+                modified_at: now,
+            })
+            .get_result(conn)?;
+        insert_into(workspace_members::table)
+            .values(&NewWorkspaceMember {
+                workspace_id: workspace.id,
+                user_id,
+                role: WorkspaceRole::Admin,
+                created_at: now,
+            })
+            .execute(conn)?;
+        diesel::update(users::table.find(user_id))
+            .filter(users::active_workspace_id.is_null())
+            .set(users::active_workspace_id.eq(workspace.id))
+            .execute(conn)?;
+        Ok(workspace)
+    })
+}
+
+fn add_single_workspace(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    item: web::Json<InputWorkspace>,
+) -> Result<Workspace, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    db_create_workspace(&mut conn, caller.id, &item.name)
+}
+
+fn update_single_workspace_name(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_workspace_id: i32,
+    item: web::Json<UpdateWorkspaceName>,
+) -> Result<Workspace, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_workspace_role(
+        &mut conn,
+        target_workspace_id,
+        caller.id,
+        WorkspaceRole::Admin,
+    )?;
+    let workspace = diesel::update(workspaces.find(target_workspace_id))
+        .set((
+            name.eq(&item.name),
+            modified_at.eq(chrono::Local::now().naive_local()),
+        ))
+        .get_result(&mut conn)?;
+    Ok(workspace)
+}
+
+fn update_single_workspace_two_factor(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_workspace_id: i32,
+    item: web::Json<UpdateWorkspaceTwoFactor>,
+) -> Result<Workspace, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_workspace_role(
+        &mut conn,
+        target_workspace_id,
+        caller.id,
+        WorkspaceRole::Admin,
+    )?;
+    let workspace = conn.transaction(|conn| {
+        let workspace = diesel::update(workspaces.find(target_workspace_id))
+            .set((
+                require_two_factor.eq(item.require_two_factor),
+                modified_at.eq(chrono::Local::now().naive_local()),
+            ))
+            .get_result(conn)?;
+        let members: Vec<i32> = workspace_members::table
+            .filter(workspace_members::workspace_id.eq(target_workspace_id))
+            .select(workspace_members::user_id)
+            .load(conn)?;
+        publish_access_change(conn, &members)?;
+        Ok::<_, diesel::result::Error>(workspace)
+    })?;
+    Ok(workspace)
+}
+
+fn activate_single_workspace(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_workspace_id: i32,
+) -> Result<Workspace, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_workspace_role(
+        &mut conn,
+        target_workspace_id,
+        caller.id,
+        WorkspaceRole::Member,
+    )?;
+    let workspace = conn.transaction(|conn| {
+        diesel::update(users::table.find(caller.id))
+            .set(users::active_workspace_id.eq(target_workspace_id))
+            .execute(conn)?;
+        publish_access_change(conn, &[caller.id])?;
+        workspaces.find(target_workspace_id).get_result(conn)
+    })?;
+    Ok(workspace)
+}
+
+fn get_all_workspace_members(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_workspace_id: i32,
+) -> Result<Vec<WorkspaceMember>, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_workspace_role(
+        &mut conn,
+        target_workspace_id,
+        caller.id,
+        WorkspaceRole::Member,
+    )?;
+    let members = workspace_members::table
+        .filter(workspace_members::workspace_id.eq(target_workspace_id))
+        .order(workspace_members::created_at.asc())
+        .load::<WorkspaceMember>(&mut conn)?;
+    Ok(members)
+}
+
+fn add_single_workspace_member(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_workspace_id: i32,
+    item: web::Json<InputWorkspaceMember>,
+) -> Result<WorkspaceMember, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_workspace_role(
+        &mut conn,
+        target_workspace_id,
+        caller.id,
+        WorkspaceRole::Admin,
+    )?;
+    users::table
+        .find(item.user_id)
+        .select(users::id)
+        .first::<i32>(&mut conn)?;
+    insert_into(workspace_members::table)
+        .values(&NewWorkspaceMember {
+            workspace_id: target_workspace_id,
+            user_id: item.user_id,
+            role: item.role,
+            created_at: chrono::Local::now().naive_local(),
+        })
+        .on_conflict_do_nothing()
+        .execute(&mut conn)?;
+    let member = workspace_members::table
+        .find((target_workspace_id, item.user_id))
+        .get_result(&mut conn)?;
+    Ok(member)
+}
+
+fn update_workspace_member_role(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_workspace_id: i32,
+    target_user_id: i32,
+    item: web::Json<UpdateWorkspaceMemberRole>,
+) -> Result<WorkspaceMember, AccessError> {
+    let mut conn = pool.get().unwrap();
+    db_require_workspace_role(
+        &mut conn,
+        target_workspace_id,
+        caller.id,
+        WorkspaceRole::Admin,
+    )?;
+    conn.transaction(|conn| {
+        if item.role != WorkspaceRole::Admin {
+            db_check_not_last_admin(conn, target_workspace_id, target_user_id)?;
+        }
+        let member =
+            diesel::update(workspace_members::table.find((target_workspace_id, target_user_id)))
+                .set(workspace_members::role.eq(item.role))
+                .get_result(conn)?;
+        Ok(member)
+    })
+}
+
+/// Removing someone from a workspace cuts off every list in it at once, since
+/// list access requires workspace membership; their list memberships are kept
+/// so that re-adding them restores access.
+fn remove_workspace_member(
+    pool: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_workspace_id: i32,
+    target_user_id: i32,
+) -> Result<usize, AccessError> {
+    let mut conn = pool.get().unwrap();
+    if target_user_id != caller.id {
+        db_require_workspace_role(
+            &mut conn,
+            target_workspace_id,
+            caller.id,
+            WorkspaceRole::Admin,
+        )?;
+    }
+    conn.transaction(|conn| {
+        db_check_not_last_admin(conn, target_workspace_id, target_user_id)?;
+        let deletion = delete(workspace_members::table.find((target_workspace_id, target_user_id)))
+            .execute(conn)?;
+        diesel::update(users::table.find(target_user_id))
+            .filter(users::active_workspace_id.eq(target_workspace_id))
+            .set(users::active_workspace_id.eq(None::<i32>))
+            .execute(conn)?;
+        publish_access_change(conn, &[target_user_id])?;
+        Ok(deletion)
+    })
+}
+
+#[get("/workspaces")]
+pub async fn get_workspaces(db: web::Data<Pool>, caller: AuthenticatedUser) -> HttpResponse {
+    match web::block(move || get_all_workspaces(db, caller)).await {
+        Ok(Ok(items)) => match serde_json::to_value(items) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize workspaces: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => {
+            eprintln!("Failed to get workspaces: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[get("/workspaces/{id}")]
+pub async fn get_workspace_by_id(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_workspace_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || db_get_workspace_by_id(db, caller, target_workspace_id.into_inner()))
+        .await
+    {
+        Ok(Ok(workspace)) => match serde_json::to_value(workspace) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize workspace: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/workspaces")]
+pub async fn add_workspace(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    item: web::Json<InputWorkspace>,
+) -> HttpResponse {
+    match web::block(move || add_single_workspace(db, caller, item)).await {
+        Ok(Ok(workspace)) => match serde_json::to_value(workspace) {
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to create workspace: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => {
+            eprintln!("Failed to create workspace: {}", e);
+            throw_response_error()
+        }
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[patch("/workspaces/update/name/{id}")]
+pub async fn patch_workspace_name(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_workspace_id: web::Path<i32>,
+    item: web::Json<UpdateWorkspaceName>,
+) -> HttpResponse {
+    match web::block(move || {
+        update_single_workspace_name(db, caller, target_workspace_id.into_inner(), item)
+    })
+    .await
+    {
+        Ok(Ok(workspace)) => match serde_json::to_value(workspace) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to patch workspace name: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[patch("/workspaces/update/two-factor/{id}")]
+pub async fn patch_workspace_two_factor(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_workspace_id: web::Path<i32>,
+    item: web::Json<UpdateWorkspaceTwoFactor>,
+) -> HttpResponse {
+    match web::block(move || {
+        update_single_workspace_two_factor(db, caller, target_workspace_id.into_inner(), item)
+    })
+    .await
+    {
+        Ok(Ok(workspace)) => match serde_json::to_value(workspace) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to patch workspace two-factor policy: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/workspaces/{id}/activate")]
+pub async fn activate_workspace(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_workspace_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || {
+        activate_single_workspace(db, caller, target_workspace_id.into_inner())
+    })
+    .await
+    {
+        Ok(Ok(workspace)) => match serde_json::to_value(workspace) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to activate workspace: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[get("/workspaces/{id}/members")]
+pub async fn get_workspace_members(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_workspace_id: web::Path<i32>,
+) -> HttpResponse {
+    match web::block(move || {
+        get_all_workspace_members(db, caller, target_workspace_id.into_inner())
+    })
+    .await
+    {
+        Ok(Ok(members)) => match serde_json::to_value(members) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to serialize workspace members: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[post("/workspaces/{id}/members")]
+pub async fn add_workspace_member(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    target_workspace_id: web::Path<i32>,
+    item: web::Json<InputWorkspaceMember>,
+) -> HttpResponse {
+    match web::block(move || {
+        add_single_workspace_member(db, caller, target_workspace_id.into_inner(), item)
+    })
+    .await
+    {
+        Ok(Ok(member)) => match serde_json::to_value(member) {
+            Ok(response_body) => throw_response_created(response_body),
+            Err(e) => {
+                eprintln!("Failed to add workspace member: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[patch("/workspaces/{id}/members/{user_id}")]
+pub async fn patch_workspace_member_role(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    path: web::Path<(i32, i32)>,
+    item: web::Json<UpdateWorkspaceMemberRole>,
+) -> HttpResponse {
+    let (target_workspace_id, target_user_id) = path.into_inner();
+    match web::block(move || {
+        update_workspace_member_role(db, caller, target_workspace_id, target_user_id, item)
+    })
+    .await
+    {
+        Ok(Ok(member)) => match serde_json::to_value(member) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to patch workspace member role: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
+
+#[delete("/workspaces/{id}/members/{user_id}")]
+pub async fn delete_workspace_member(
+    db: web::Data<Pool>,
+    caller: AuthenticatedUser,
+    path: web::Path<(i32, i32)>,
+) -> HttpResponse {
+    let (target_workspace_id, target_user_id) = path.into_inner();
+    match web::block(move || {
+        remove_workspace_member(db, caller, target_workspace_id, target_user_id)
+    })
+    .await
+    {
+        Ok(Ok(deletion)) => match serde_json::to_value(deletion) {
+            Ok(response_body) => throw_response_ok(response_body),
+            Err(e) => {
+                eprintln!("Failed to remove workspace member: {}", e);
+                throw_response_error()
+            }
+        },
+        Ok(Err(e)) => throw_response_access_error(e),
+        Err(_) => throw_response_error(),
+    }
+}
@@ -1,15 +1,304 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    account_deletions (id) {
+        id -> Int4,
+        user_id -> Nullable<Int4>,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        #[max_length = 16]
+        owned_lists -> Varchar,
+        requested_at -> Timestamp,
+        scheduled_for -> Timestamp,
+        completed_at -> Nullable<Timestamp>,
+        receipt -> Nullable<Jsonb>,
+    }
+}
+
+diesel::table! {
+    api_keys (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 100]
+        name -> Varchar,
+        #[max_length = 16]
+        prefix -> Varchar,
+        #[max_length = 64]
+        key_hash -> Varchar,
+        scopes -> Text,
+        expires_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    attachments (id) {
+        id -> Int4,
+        task_id -> Int4,
+        #[max_length = 255]
+        file_name -> Varchar,
+        #[max_length = 255]
+        content_type -> Varchar,
+        size_bytes -> Int8,
+        #[max_length = 64]
+        content_hash -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    calendar_feeds (user_id) {
+        user_id -> Int4,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    dav_objects (task_id) {
+        task_id -> Int4,
+        uid -> Text,
+        #[max_length = 255]
+        name -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    email_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 16]
+        purpose -> Varchar,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        email -> Text,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    event_log_state (singleton) {
+        singleton -> Bool,
+        settled_id -> Int8,
+        pruned_id -> Int8,
+    }
+}
+
+diesel::table! {
+    event_recipients (user_id, event_id) {
+        event_id -> Int8,
+        user_id -> Int4,
+    }
+}
+
+diesel::table! {
+    events (id) {
+        id -> Int8,
+        #[max_length = 32]
+        kind -> Varchar,
+        payload -> Jsonb,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    list_members (list_id, user_id) {
+        list_id -> Int4,
+        user_id -> Int4,
+        #[max_length = 16]
+        role -> Varchar,
+        invited_by -> Nullable<Int4>,
+        created_at -> Timestamp,
+        accepted_at -> Nullable<Timestamp>,
+        change_seq -> Int8,
+    }
+}
+
+diesel::table! {
+    login_challenges (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        attempts -> Int4,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    notifications (id) {
+        id -> Int8,
+        user_id -> Int4,
+        #[max_length = 32]
+        kind -> Varchar,
+        task_id -> Nullable<Int4>,
+        list_id -> Nullable<Int4>,
+        title -> Text,
+        body -> Text,
+        read_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        actor_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    oidc_identities (id) {
+        id -> Int4,
+        user_id -> Int4,
+        issuer -> Text,
+        subject -> Text,
+        email -> Text,
+        created_at -> Timestamp,
+        last_login_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    oidc_login_attempts (id) {
+        id -> Int4,
+        #[max_length = 64]
+        state_hash -> Varchar,
+        #[max_length = 128]
+        code_verifier -> Varchar,
+        #[max_length = 64]
+        nonce -> Varchar,
+        expires_at -> Timestamp,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    recovery_codes (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 64]
+        code_hash -> Varchar,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    refresh_tokens (id) {
+        id -> Int4,
+        session_id -> Int4,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    reminder_preferences (user_id) {
+        user_id -> Int4,
+        email_enabled -> Bool,
+        window_hours -> Int4,
+        digest -> Bool,
+        modified_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    sent_reminders (task_id, user_id, due_date) {
+        task_id -> Int4,
+        user_id -> Int4,
+        due_date -> Date,
+        sent_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    sessions (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 64]
+        access_token_hash -> Varchar,
+        access_expires_at -> Timestamp,
+        expires_at -> Timestamp,
+        user_agent -> Nullable<Text>,
+        ip_address -> Nullable<Text>,
+        created_at -> Timestamp,
+        last_seen_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
+        #[max_length = 32]
+        revoked_reason -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    share_links (id) {
+        id -> Int4,
+        list_id -> Int4,
+        created_by -> Nullable<Int4>,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        password_hash -> Nullable<Text>,
+        expires_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    task_assignees (task_id, user_id) {
+        task_id -> Int4,
+        user_id -> Int4,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    task_reminders (id) {
+        id -> Int4,
+        task_id -> Int4,
+        user_id -> Int4,
+        remind_at -> Nullable<Timestamp>,
+        minutes_before_due -> Nullable<Int4>,
+        #[max_length = 16]
+        channel -> Varchar,
+        snoozed_until -> Nullable<Timestamp>,
+        sent_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        modified_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    task_tags (task_id, tag) {
+        task_id -> Int4,
+        #[max_length = 64]
+        tag -> Varchar,
+    }
+}
+
+diesel::table! {
+    task_watchers (task_id, user_id) {
+        task_id -> Int4,
+        user_id -> Int4,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     todolists (id) {
         id -> Int4,
         user_id -> Int4,
-        shared_with -> Nullable<Text>,
         #[max_length = 255]
         name -> Varchar,
         description -> Text,
         created_at -> Timestamp,
         modified_at -> Timestamp,
+        workspace_id -> Int4,
+        change_seq -> Int8,
     }
 }
 
@@ -25,6 +314,34 @@ diesel::table! {
         due_date -> Nullable<Date>,
         created_at -> Timestamp,
         modified_at -> Timestamp,
+        change_seq -> Int8,
+        #[max_length = 1]
+        priority -> Nullable<Varchar>,
+        completed_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    tombstones (id) {
+        id -> Int4,
+        #[max_length = 8]
+        entity -> Varchar,
+        entity_id -> Int4,
+        list_id -> Int4,
+        workspace_id -> Int4,
+        change_seq -> Int8,
+        deleted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    user_totp (user_id) {
+        user_id -> Int4,
+        #[max_length = 64]
+        secret -> Varchar,
+        confirmed_at -> Nullable<Timestamp>,
+        last_step -> Nullable<Int8>,
+        created_at -> Timestamp,
     }
 }
 
@@ -36,11 +353,135 @@ diesel::table! {
         email -> Text,
         created_at -> Timestamp,
         modified_at -> Timestamp,
+        active_workspace_id -> Nullable<Int4>,
+        password_hash -> Nullable<Text>,
+        email_verified_at -> Nullable<Timestamp>,
     }
 }
 
+diesel::table! {
+    webhook_deliveries (id) {
+        id -> Int8,
+        webhook_id -> Int4,
+        event_id -> Int8,
+        #[max_length = 16]
+        status -> Varchar,
+        attempts -> Int4,
+        next_attempt_at -> Timestamp,
+        response_status -> Nullable<Int4>,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamp,
+        delivered_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    webhooks (id) {
+        id -> Int4,
+        user_id -> Int4,
+        list_id -> Nullable<Int4>,
+        url -> Text,
+        #[max_length = 64]
+        secret -> Varchar,
+        event_filter -> Text,
+        enabled -> Bool,
+        consecutive_failures -> Int4,
+        disabled_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        modified_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    workspace_members (workspace_id, user_id) {
+        workspace_id -> Int4,
+        user_id -> Int4,
+        #[max_length = 16]
+        role -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    workspaces (id) {
+        id -> Int4,
+        #[max_length = 255]
+        name -> Varchar,
+        created_at -> Timestamp,
+        modified_at -> Timestamp,
+        require_two_factor -> Bool,
+    }
+}
+
+diesel::joinable!(account_deletions -> users (user_id));
+diesel::joinable!(api_keys -> users (user_id));
+diesel::joinable!(attachments -> todotasks (task_id));
+diesel::joinable!(calendar_feeds -> users (user_id));
+diesel::joinable!(email_tokens -> users (user_id));
+diesel::joinable!(event_recipients -> events (event_id));
+diesel::joinable!(event_recipients -> users (user_id));
+diesel::joinable!(list_members -> todolists (list_id));
+diesel::joinable!(login_challenges -> users (user_id));
+diesel::joinable!(notifications -> todolists (list_id));
+diesel::joinable!(notifications -> todotasks (task_id));
+diesel::joinable!(oidc_identities -> users (user_id));
+diesel::joinable!(recovery_codes -> users (user_id));
+diesel::joinable!(refresh_tokens -> sessions (session_id));
+diesel::joinable!(reminder_preferences -> users (user_id));
+diesel::joinable!(sent_reminders -> todotasks (task_id));
+diesel::joinable!(sent_reminders -> users (user_id));
+diesel::joinable!(sessions -> users (user_id));
+diesel::joinable!(share_links -> todolists (list_id));
+diesel::joinable!(share_links -> users (created_by));
+diesel::joinable!(task_assignees -> todotasks (task_id));
+diesel::joinable!(task_assignees -> users (user_id));
+diesel::joinable!(task_reminders -> todotasks (task_id));
+diesel::joinable!(task_reminders -> users (user_id));
+diesel::joinable!(task_tags -> todotasks (task_id));
+diesel::joinable!(task_watchers -> todotasks (task_id));
+diesel::joinable!(task_watchers -> users (user_id));
+diesel::joinable!(todolists -> workspaces (workspace_id));
+diesel::joinable!(user_totp -> users (user_id));
+diesel::joinable!(users -> workspaces (active_workspace_id));
+diesel::joinable!(webhook_deliveries -> events (event_id));
+diesel::joinable!(webhook_deliveries -> webhooks (webhook_id));
+diesel::joinable!(webhooks -> todolists (list_id));
+diesel::joinable!(webhooks -> users (user_id));
+diesel::joinable!(workspace_members -> users (user_id));
+diesel::joinable!(workspace_members -> workspaces (workspace_id));
+
 diesel::allow_tables_to_appear_in_same_query!(
+    account_deletions,
+    api_keys,
+    attachments,
+    calendar_feeds,
+    dav_objects,
+    email_tokens,
+    event_log_state,
+    event_recipients,
+    events,
+    list_members,
+    login_challenges,
+    notifications,
+    oidc_identities,
+    oidc_login_attempts,
+    recovery_codes,
+    refresh_tokens,
+    reminder_preferences,
+    sent_reminders,
+    sessions,
+    share_links,
+    task_assignees,
+    task_reminders,
+    task_tags,
+    task_watchers,
     todolists,
     todotasks,
+    tombstones,
+    user_totp,
     users,
+    webhook_deliveries,
+    webhooks,
+    workspace_members,
+    workspaces,
 );
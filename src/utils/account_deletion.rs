@@ -0,0 +1,328 @@
+use crate::models::account_deletion::{
+    AccountDeletion, DeletionReceipt, OwnedListPolicy, TransferredList,
+};
+use crate::models::list_member::ListRole;
+use crate::models::todo_list::TodoList;
+use crate::models::user::User;
+use crate::routes::attachment::{db_list_attachment_hashes, db_release_attachment_blobs};
+use crate::routes::todo_list::db_remove_list;
+use crate::schema::{account_deletions, list_members, todolists, users};
+use crate::utils::database::connection::Pool;
+use crate::utils::events::{
+    list_audience, publish_access_change, publish_list_event, record_event, user_audience,
+    ListEventKind,
+};
+use crate::utils::storage::AttachmentStorage;
+use actix_web::web;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Text};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Due deletions are carried out within a minute of their scheduled time.
+const DELETION_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Stands in for the deleted user's name and address in other people's
+/// notifications.
+const ANONYMOUS_NAME: &str = "A deleted user";
+
+/// Lists the user owns, either as an accepted owner or as their creator.
+fn db_owned_lists(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> Result<Vec<TodoList>, diesel::result::Error> {
+    let owner_of = list_members::table
+        .filter(list_members::user_id.eq(user_id))
+        .filter(list_members::role.eq(ListRole::Owner))
+        .filter(list_members::accepted_at.is_not_null())
+        .select(list_members::list_id);
+    todolists::table
+        .filter(
+            todolists::user_id
+                .eq(user_id)
+                .or(todolists::id.eq_any(owner_of)),
+        )
+        .order(todolists::id.asc())
+        .load::<TodoList>(conn)
+}
+
+/// Another accepted owner if there is one, else the most privileged of the
+/// remaining members, the longest-standing one winning a tie.
+fn db_find_successor(
+    conn: &mut PgConnection,
+    list_id: i32,
+    user_id: i32,
+) -> Result<Option<(i32, ListRole)>, diesel::result::Error> {
+    let members: Vec<(i32, ListRole, NaiveDateTime)> = list_members::table
+        .filter(list_members::list_id.eq(list_id))
+        .filter(list_members::user_id.ne(user_id))
+        .filter(list_members::accepted_at.is_not_null())
+        .select((
+            list_members::user_id,
+            list_members::role,
+            list_members::created_at,
+        ))
+        .load(conn)?;
+    Ok(members
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.2.cmp(&a.2)))
+        .map(|(member, member_role, _)| (member, member_role)))
+}
+
+/// Gives the list to `successor`, promoting them to owner if they weren't.
+fn db_transfer_list(
+    conn: &mut PgConnection,
+    list: TodoList,
+    successor: i32,
+) -> Result<(), diesel::result::Error> {
+    diesel::update(list_members::table.find((list.id, successor)))
+        .set(list_members::role.eq(ListRole::Owner))
+        .execute(conn)?;
+    let list: TodoList = diesel::update(todolists::table.find(list.id))
+        .set((
+            todolists::user_id.eq(successor),
+            todolists::modified_at.eq(chrono::Local::now().naive_local()),
+        ))
+        .get_result(conn)?;
+    let audience = list_audience(conn, &[list.id])?;
+    publish_list_event(conn, ListEventKind::Patched, list, &audience)?;
+    Ok(())
+}
+
+/// Tasks the user created stay where they are, attributed to their list's
+/// owner.
+fn db_reassign_tasks(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> Result<usize, diesel::result::Error> {
+    diesel::sql_query(
+        "UPDATE todotasks SET user_id = todolists.user_id FROM todolists \
+         WHERE todolists.id = todotasks.todolist_id AND todotasks.user_id = $1",
+    )
+    .bind::<Integer, _>(user_id)
+    .execute(conn)
+}
+
+fn display_name(user: &User) -> String {
+    let display_name = format!("{} {}", user.first_name, user.last_name)
+        .trim()
+        .to_string();
+    if display_name.is_empty() {
+        user.email.clone()
+    } else {
+        display_name
+    }
+}
+
+/// `text` as a POSIX regular expression matching itself.
+fn literal_pattern(text: &str) -> String {
+    let mut pattern = String::new();
+    for c in text.chars() {
+        if "\\.^$|?*+()[]{}".contains(c) {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern
+}
+
+/// The log keeps that the account existed and changed, but not who it was.
+/// Events about the user themselves, and the reminders they were sent, are
+/// replaced outright. Task events only ever name other people by address, in
+/// `@` mentions, so the address is blanked out in those the user received as
+/// a list member; list and membership events carry ids only and are kept.
+/// Must run before the user's rows in `event_recipients` are gone.
+fn db_anonymize_events(
+    conn: &mut PgConnection,
+    user: &User,
+) -> Result<usize, diesel::result::Error> {
+    let own = diesel::sql_query(
+        "UPDATE events SET payload = jsonb_build_object('id', $1, 'anonymized', true) \
+         WHERE (kind LIKE 'user.%' AND payload->>'id' = $1::text) \
+            OR (kind = 'task.reminder' AND payload->>'user_id' = $1::text)",
+    )
+    .bind::<Integer, _>(user.id)
+    .execute(conn)?;
+    let mentioned = diesel::sql_query(
+        "UPDATE events SET payload = regexp_replace(payload::text, $2, $3, 'gi')::jsonb \
+         WHERE kind LIKE 'task.%' \
+           AND id IN (SELECT event_id FROM event_recipients WHERE user_id = $1) \
+           AND strpos(lower(payload::text), lower($4)) > 0",
+    )
+    .bind::<Integer, _>(user.id)
+    .bind::<Text, _>(literal_pattern(&user.email))
+    .bind::<Text, _>(ANONYMOUS_NAME)
+    .bind::<Text, _>(&user.email)
+    .execute(conn)?;
+    Ok(own + mentioned)
+}
+
+/// Notifications are rendered text, so the user's display name and address
+/// are replaced in the ones they caused, and only in those.
+fn db_anonymize_notifications(
+    conn: &mut PgConnection,
+    user: &User,
+) -> Result<usize, diesel::result::Error> {
+    diesel::sql_query(
+        "UPDATE notifications \
+         SET title = replace(replace(title, $2, $4), $3, $4), \
+             body = replace(replace(body, $2, $4), $3, $4), \
+             actor_id = NULL \
+         WHERE actor_id = $1",
+    )
+    .bind::<Integer, _>(user.id)
+    .bind::<Text, _>(display_name(user))
+    .bind::<Text, _>(&user.email)
+    .bind::<Text, _>(ANONYMOUS_NAME)
+    .execute(conn)
+}
+
+/// Removes the account and everything that still identifies the person
+/// behind it. Must run inside a transaction. The attachment hashes of deleted
+/// lists are added to `orphaned`, to be released once it commits.
+fn db_purge_account(
+    conn: &mut PgConnection,
+    user: &User,
+    policy: OwnedListPolicy,
+    orphaned: &mut Vec<String>,
+) -> Result<DeletionReceipt, diesel::result::Error> {
+    let mut lists_transferred = Vec::new();
+    let mut lists_deleted = Vec::new();
+    for list in db_owned_lists(conn, user.id)? {
+        let successor = match db_find_successor(conn, list.id, user.id)? {
+            // a co-owned list someone else created simply loses an owner
+            Some((_, ListRole::Owner)) if list.user_id != user.id => continue,
+            Some((member, ListRole::Owner)) => Some(member),
+            Some((member, _)) if policy == OwnedListPolicy::Transfer => Some(member),
+            _ => None,
+        };
+        match successor {
+            Some(new_owner_id) => {
+                lists_transferred.push(TransferredList {
+                    list_id: list.id,
+                    new_owner_id,
+                });
+                db_transfer_list(conn, list, new_owner_id)?;
+            }
+            None => {
+                lists_deleted.push(list.id);
+                orphaned.extend(db_list_attachment_hashes(conn, list.id)?);
+                db_remove_list(conn, user.id, list.id)?;
+            }
+        }
+    }
+
+    let tasks_reassigned = db_reassign_tasks(conn, user.id)?;
+    let events_anonymized = db_anonymize_events(conn, user)?;
+    let notifications_anonymized = db_anonymize_notifications(conn, user)?;
+    let memberships_removed =
+        diesel::delete(list_members::table.filter(list_members::user_id.eq(user.id)))
+            .execute(conn)?;
+    publish_access_change(conn, &[user.id])?;
+
+    let mut audience = user_audience(conn, user.id)?;
+    audience.retain(|member| *member != user.id);
+    diesel::delete(users::table.find(user.id)).execute(conn)?;
+    record_event(
+        conn,
+        "user.deleted",
+        &serde_json::json!({ "id": user.id }),
+        &audience,
+    )?;
+
+    Ok(DeletionReceipt {
+        completed_at: chrono::Local::now().naive_local(),
+        lists_transferred,
+        lists_deleted,
+        memberships_removed,
+        tasks_reassigned,
+        events_anonymized,
+        notifications_anonymized,
+    })
+}
+
+/// Deletes the account now, honouring and completing its pending deletion
+/// request if there is one. Returns `None` for unknown users.
+pub(crate) fn db_delete_account(
+    conn: &mut PgConnection,
+    user_id: i32,
+    orphaned: &mut Vec<String>,
+) -> Result<Option<DeletionReceipt>, diesel::result::Error> {
+    conn.transaction(|conn| {
+        let Some(user) = users::table
+            .find(user_id)
+            .for_update()
+            .get_result::<User>(conn)
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        let pending = account_deletions::table
+            .filter(account_deletions::user_id.eq(user_id))
+            .select(AccountDeletion::as_select())
+            .first::<AccountDeletion>(conn)
+            .optional()?;
+        let policy = pending
+            .as_ref()
+            .map_or(OwnedListPolicy::default(), |deletion| deletion.owned_lists);
+        let receipt = db_purge_account(conn, &user, policy, orphaned)?;
+        if let Some(deletion) = pending {
+            diesel::update(account_deletions::table.find(deletion.id))
+                .set((
+                    account_deletions::completed_at.eq(receipt.completed_at),
+                    account_deletions::receipt.eq(serde_json::to_value(&receipt)
+                        .expect("Failed to serialize deletion receipt")),
+                ))
+                .execute(conn)?;
+        }
+        Ok(Some(receipt))
+    })
+}
+
+/// One scheduler tick. Each account is purged in its own transaction, so one
+/// failure doesn't hold back the rest.
+fn run_due_deletions(
+    pool: web::Data<Pool>,
+    storage: web::Data<dyn AttachmentStorage>,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    let now = chrono::Local::now().naive_local();
+    let due: Vec<i32> = account_deletions::table
+        .filter(account_deletions::completed_at.is_null())
+        .filter(account_deletions::scheduled_for.le(now))
+        .select(account_deletions::user_id.assume_not_null())
+        .filter(account_deletions::user_id.is_not_null())
+        .load(&mut conn)?;
+    let mut deleted = 0;
+    for user_id in due {
+        let mut orphaned = Vec::new();
+        match db_delete_account(&mut conn, user_id, &mut orphaned) {
+            Ok(Some(_)) => {
+                db_release_attachment_blobs(&mut conn, storage.get_ref(), &orphaned);
+                deleted += 1;
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("Failed to delete account {}: {}", user_id, e),
+        }
+    }
+    Ok(deleted)
+}
+
+pub(crate) fn start_account_deletion_scheduler(pool: Pool, storage: Arc<dyn AttachmentStorage>) {
+    let pool = web::Data::new(pool);
+    let storage = web::Data::from(storage);
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(DELETION_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let pool = pool.clone();
+            let storage = storage.clone();
+            match web::block(move || run_due_deletions(pool, storage)).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => eprintln!("Failed to run account deletions: {}", e),
+                Err(e) => eprintln!("Account deletion scheduler failed: {}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,52 @@
+use crate::models::api_key::{ApiKey, API_KEY_PREFIX};
+use crate::schema::api_keys;
+use crate::utils::token;
+use diesel::prelude::*;
+
+/// Scripts may call many times a second; the last-used time only needs to be
+/// roughly right.
+const LAST_USED_GRANULARITY_SECS: i64 = 60;
+
+/// How much of a key is kept in plain to tell keys apart.
+const DISPLAYED_PREFIX_CHARS: usize = 12;
+
+/// A new key and the prefix stored alongside its hash.
+pub fn generate_api_key() -> (String, String) {
+    let key = format!("{}{}", API_KEY_PREFIX, token::generate_token());
+    let prefix = key.chars().take(DISPLAYED_PREFIX_CHARS).collect();
+    (key, prefix)
+}
+
+pub fn is_api_key(credential: &str) -> bool {
+    credential.starts_with(API_KEY_PREFIX)
+}
+
+/// The unexpired key matching `plain_key`, recording that it was used.
+pub(crate) fn db_authenticate_api_key(
+    conn: &mut PgConnection,
+    plain_key: &str,
+) -> Result<Option<ApiKey>, diesel::result::Error> {
+    let now = chrono::Local::now().naive_local();
+    let Some(api_key) = api_keys::table
+        .filter(api_keys::key_hash.eq(token::hash_token(plain_key)))
+        .filter(
+            api_keys::expires_at
+                .is_null()
+                .or(api_keys::expires_at.gt(now)),
+        )
+        .select(ApiKey::as_select())
+        .first(conn)
+        .optional()?
+    else {
+        return Ok(None);
+    };
+    let stale = api_key.last_used_at.is_none_or(|last_used| {
+        now - last_used >= chrono::Duration::seconds(LAST_USED_GRANULARITY_SECS)
+    });
+    if stale {
+        diesel::update(api_keys::table.find(api_key.id))
+            .set(api_keys::last_used_at.eq(now))
+            .execute(conn)?;
+    }
+    Ok(Some(api_key))
+}
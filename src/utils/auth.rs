@@ -0,0 +1,207 @@
+use crate::models::api_key::{ApiScope, ApiScopes};
+use crate::schema::users;
+use crate::utils::api_keys::{db_authenticate_api_key, is_api_key};
+use crate::utils::database::access::AccessError;
+use crate::utils::database::connection::Pool;
+use crate::utils::sessions::{db_authenticate_access_token, ClientInfo};
+use actix_web::error::{ErrorInternalServerError, ErrorUnauthorized, InternalError};
+use actix_web::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use actix_web::{dev::Payload, web, Error, FromRequest, HttpRequest, HttpResponse};
+use data_encoding::BASE64;
+use diesel::prelude::*;
+use futures_util::future::LocalBoxFuture;
+
+/// Named in the Basic challenge CalDAV clients get.
+const DAV_REALM: &str = "Todoer CalDAV";
+
+/// The user a request acts on behalf of, proven by the access token of their
+/// session sent as `Authorization: Bearer <token>`. Requests without one are
+/// refused, and every list and task route checks that user's list role.
+#[derive(Debug, Clone, Copy)]
+pub struct AuthenticatedUser {
+    pub id: i32,
+    /// Set when the request came with a session's access token rather than
+    /// an API key.
+    pub session_id: Option<i32>,
+}
+
+/// A caller that may also be a script holding a personal API key. Only the
+/// routes taking this instead of `AuthenticatedUser` accept keys, and each
+/// states the scope it needs through `require`.
+#[derive(Debug, Clone)]
+pub struct ScopedUser {
+    pub user: AuthenticatedUser,
+    /// `None` for sessions, which may do anything the user may.
+    pub scopes: Option<ApiScopes>,
+}
+
+impl ScopedUser {
+    pub fn allows(&self, scope: ApiScope) -> bool {
+        self.scopes
+            .as_ref()
+            .is_none_or(|scopes| scopes.contains(scope))
+    }
+
+    pub fn require(self, scope: ApiScope) -> Result<AuthenticatedUser, AccessError> {
+        if !self.allows(scope) {
+            return Err(AccessError::Forbidden);
+        }
+        Ok(self.user)
+    }
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<String> {
+    let value = req.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim().to_string())
+}
+
+/// The user name and password of `Authorization: Basic`.
+fn basic_credentials(req: &HttpRequest) -> Option<(String, String)> {
+    let value = req.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, encoded) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = String::from_utf8(BASE64.decode(encoded.trim().as_bytes()).ok()?).ok()?;
+    let (name, password) = decoded.split_once(':')?;
+    Some((name.to_string(), password.to_string()))
+}
+
+fn db_authenticate_caller(
+    conn: &mut PgConnection,
+    credential: &str,
+    client: &ClientInfo,
+) -> Result<Option<ScopedUser>, diesel::result::Error> {
+    if is_api_key(credential) {
+        let api_key = db_authenticate_api_key(conn, credential)?;
+        return Ok(api_key.map(|api_key| ScopedUser {
+            user: AuthenticatedUser {
+                id: api_key.user_id,
+                session_id: None,
+            },
+            scopes: Some(api_key.scopes),
+        }));
+    }
+    let session = db_authenticate_access_token(conn, credential, client)?;
+    Ok(session.map(|session| ScopedUser {
+        user: AuthenticatedUser {
+            id: session.user_id,
+            session_id: Some(session.id),
+        },
+        scopes: None,
+    }))
+}
+
+fn authenticate(
+    req: &HttpRequest,
+    accept_api_keys: bool,
+) -> LocalBoxFuture<'static, Result<ScopedUser, Error>> {
+    let Some(credential) = bearer_token(req) else {
+        return Box::pin(async { Err(ErrorUnauthorized("missing bearer token")) });
+    };
+    if is_api_key(&credential) && !accept_api_keys {
+        return Box::pin(async { Err(ErrorUnauthorized("API keys are not accepted here")) });
+    }
+    authenticate_credential(req, credential, None)
+}
+
+/// With `account_email`, the credential also has to belong to the account
+/// with that address.
+fn authenticate_credential(
+    req: &HttpRequest,
+    credential: String,
+    account_email: Option<String>,
+) -> LocalBoxFuture<'static, Result<ScopedUser, Error>> {
+    let pool = req.app_data::<web::Data<Pool>>().cloned();
+    let client = ClientInfo::from_request(req);
+    Box::pin(async move {
+        let pool = pool.ok_or_else(|| ErrorInternalServerError("database unavailable"))?;
+        let caller = web::block(move || {
+            let mut conn = pool.get().unwrap();
+            let caller = db_authenticate_caller(&mut conn, &credential, &client)?;
+            let (Some(caller), Some(account_email)) = (caller.clone(), account_email) else {
+                return Ok(caller);
+            };
+            let address = users::table
+                .find(caller.user.id)
+                .select(users::email)
+                .first::<String>(&mut conn)?;
+            Ok::<_, diesel::result::Error>(
+                address
+                    .trim()
+                    .eq_ignore_ascii_case(account_email.trim())
+                    .then_some(caller),
+            )
+        })
+        .await
+        .map_err(|_| ErrorInternalServerError("failed to check credentials"))?
+        .map_err(|e| {
+            eprintln!("Failed to check credentials: {}", e);
+            ErrorInternalServerError("failed to check credentials")
+        })?;
+        caller.ok_or_else(|| ErrorUnauthorized("invalid or expired credentials"))
+    })
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let caller = authenticate(req, false);
+        Box::pin(async move { Ok(caller.await?.user) })
+    }
+}
+
+impl FromRequest for ScopedUser {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        authenticate(req, true)
+    }
+}
+
+/// A calendar app's caller. Those apps can't go through the sign-in flow, so
+/// besides a bearer token they may send HTTP Basic credentials: the account's
+/// email as the user name and one of its API keys as the password. Refusals
+/// carry a Basic challenge so the app asks for them.
+#[derive(Debug, Clone)]
+pub struct DavUser(pub ScopedUser);
+
+fn dav_unauthorized(reason: &'static str) -> Error {
+    let response = HttpResponse::Unauthorized()
+        .insert_header((WWW_AUTHENTICATE, format!("Basic realm=\"{}\"", DAV_REALM)))
+        .finish();
+    InternalError::from_response(reason, response).into()
+}
+
+impl FromRequest for DavUser {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let caller = match (bearer_token(req), basic_credentials(req)) {
+            (Some(credential), _) => authenticate_credential(req, credential, None),
+            (None, Some((name, password))) if is_api_key(&password) => {
+                authenticate_credential(req, password, Some(name))
+            }
+            (None, Some(_)) => {
+                return Box::pin(async { Err(dav_unauthorized("the password must be an API key")) })
+            }
+            (None, None) => {
+                return Box::pin(async { Err(dav_unauthorized("missing credentials")) })
+            }
+        };
+        Box::pin(async move {
+            match caller.await {
+                Ok(caller) => Ok(DavUser(caller)),
+                Err(e) if e.as_response_error().status_code().is_server_error() => Err(e),
+                Err(_) => Err(dav_unauthorized("invalid or expired credentials")),
+            }
+        })
+    }
+}
@@ -1,4 +1,5 @@
 use dotenv;
+use std::net::IpAddr;
 
 pub fn init() {
     let _ = dotenv::from_path("../.env");
@@ -10,5 +11,175 @@ pub fn init() {
 }
 
 pub fn get_connection_string() -> String {
-    return dotenv::var("DATABASE_URL").unwrap();
+    dotenv::var("DATABASE_URL").unwrap()
+}
+
+pub fn get_attachments_dir() -> String {
+    dotenv::var("ATTACHMENTS_DIR").unwrap_or_else(|_| String::from("./attachments"))
+}
+
+pub fn get_attachments_max_bytes() -> usize {
+    dotenv::var("ATTACHMENTS_MAX_BYTES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(10 * 1024 * 1024)
+}
+
+pub fn get_attachments_allowed_types() -> Vec<String> {
+    dotenv::var("ATTACHMENTS_ALLOWED_TYPES")
+        .unwrap_or_else(|_| {
+            String::from("image/png,image/jpeg,image/gif,image/webp,application/pdf")
+        })
+        .split(',')
+        .map(|content_type| content_type.trim().to_lowercase())
+        .filter(|content_type| !content_type.is_empty())
+        .collect()
+}
+
+/// Email is off unless an SMTP relay is configured.
+pub fn get_smtp_host() -> Option<String> {
+    dotenv::var("SMTP_HOST")
+        .ok()
+        .filter(|host| !host.is_empty())
+}
+
+pub fn get_smtp_port() -> Option<u16> {
+    dotenv::var("SMTP_PORT")
+        .ok()
+        .and_then(|value| value.parse().ok())
+}
+
+/// `none` (plain, for local sinks), `starttls` or `tls`.
+pub fn get_smtp_tls() -> String {
+    dotenv::var("SMTP_TLS").unwrap_or_else(|_| String::from("starttls"))
+}
+
+pub fn get_smtp_credentials() -> Option<(String, String)> {
+    let username = dotenv::var("SMTP_USERNAME").ok()?;
+    let password = dotenv::var("SMTP_PASSWORD").ok()?;
+    Some((username, password))
+}
+
+pub fn get_smtp_from() -> String {
+    dotenv::var("SMTP_FROM").unwrap_or_else(|_| String::from("Todoer <noreply@localhost>"))
+}
+
+pub fn get_reminder_interval_secs() -> u64 {
+    dotenv::var("REMINDER_INTERVAL_SECS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(15 * 60)
+}
+
+/// Lets webhooks reach loopback, private and link-local addresses, which are
+/// refused by default. Meant for local development only.
+pub fn get_webhooks_allow_private() -> bool {
+    dotenv::var("WEBHOOKS_ALLOW_PRIVATE").is_ok_and(|value| value == "true")
+}
+
+/// Service exports run far past the default request body limit.
+pub fn get_import_max_bytes() -> usize {
+    dotenv::var("IMPORT_MAX_BYTES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(20 * 1024 * 1024)
+}
+
+/// How long a requested account deletion can still be cancelled.
+pub fn get_account_deletion_grace_days() -> i64 {
+    dotenv::var("ACCOUNT_DELETION_GRACE_DAYS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|days| *days >= 0)
+        .unwrap_or(14)
+}
+
+/// How long the event log and deletion tombstones are kept. Clients whose
+/// cursor or sync token is older have to sync from scratch.
+pub fn get_event_retention_days() -> i64 {
+    dotenv::var("EVENT_RETENTION_DAYS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|days| *days >= 1)
+        .unwrap_or(30)
+}
+
+/// Shown next to the account in authenticator apps.
+pub fn get_totp_issuer() -> String {
+    dotenv::var("TOTP_ISSUER").unwrap_or_else(|_| String::from("Todoer"))
+}
+
+/// Access tokens are checked on every request, so they only live long enough
+/// to keep refreshes infrequent.
+pub fn get_access_token_minutes() -> i64 {
+    dotenv::var("ACCESS_TOKEN_MINUTES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|minutes| *minutes > 0)
+        .unwrap_or(15)
+}
+
+/// A session left unused this long has to sign in again.
+pub fn get_refresh_token_days() -> i64 {
+    dotenv::var("REFRESH_TOKEN_DAYS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|days| *days > 0)
+        .unwrap_or(30)
+}
+
+/// Proxies whose `Forwarded`/`X-Forwarded-For` headers are believed when
+/// recording a session's address. Without any, the peer address is used.
+pub fn get_trusted_proxies() -> Vec<IpAddr> {
+    dotenv::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|proxy| proxy.trim().parse().ok())
+        .collect()
+}
+
+/// Single sign-on is off unless an issuer and a client id are configured.
+pub fn get_oidc_issuer() -> Option<String> {
+    dotenv::var("OIDC_ISSUER")
+        .ok()
+        .map(|issuer| issuer.trim_end_matches('/').to_string())
+        .filter(|issuer| !issuer.is_empty())
+}
+
+/// Defaults to the issuer's `/.well-known/openid-configuration`.
+pub fn get_oidc_discovery_url() -> Option<String> {
+    dotenv::var("OIDC_DISCOVERY_URL")
+        .ok()
+        .filter(|url| !url.is_empty())
+}
+
+/// Overrides the `jwks_uri` from discovery.
+pub fn get_oidc_jwks_url() -> Option<String> {
+    dotenv::var("OIDC_JWKS_URL")
+        .ok()
+        .filter(|url| !url.is_empty())
+}
+
+pub fn get_oidc_client_id() -> Option<String> {
+    dotenv::var("OIDC_CLIENT_ID")
+        .ok()
+        .filter(|id| !id.is_empty())
+}
+
+/// Public clients rely on PKCE alone and leave this unset.
+pub fn get_oidc_client_secret() -> Option<String> {
+    dotenv::var("OIDC_CLIENT_SECRET")
+        .ok()
+        .filter(|secret| !secret.is_empty())
+}
+
+/// Where the provider sends the browser back to; the client posts the code
+/// from there to `/auth/oidc/callback`.
+pub fn get_oidc_redirect_uri() -> String {
+    dotenv::var("OIDC_REDIRECT_URI")
+        .unwrap_or_else(|_| String::from("http://localhost:3000/auth/callback"))
+}
+
+pub fn get_oidc_scopes() -> String {
+    dotenv::var("OIDC_SCOPES").unwrap_or_else(|_| String::from("openid email profile"))
 }
@@ -0,0 +1,153 @@
+use crate::models::workspace_member::WorkspaceRole;
+use crate::models::{list_member::ListRole, todo_task::TodoTask};
+use crate::schema::{list_members, todolists, todotasks, users, workspace_members, workspaces};
+use crate::utils::two_factor::db_get_confirmed_totp;
+use derive_more::{Display, From};
+use diesel::prelude::*;
+
+#[derive(Debug, Display, From)]
+pub(crate) enum AccessError {
+    Database(diesel::result::Error),
+    Forbidden,
+}
+
+/// The workspace every list and task query of `user_id` is scoped to. Having
+/// no active workspace, having been removed from it, or lacking the
+/// two-factor it requires, forbids everything.
+pub(crate) fn db_get_active_workspace(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> Result<i32, AccessError> {
+    let active = users::table
+        .find(user_id)
+        .select(users::active_workspace_id)
+        .first::<Option<i32>>(conn)?;
+    let workspace_id = active.ok_or(AccessError::Forbidden)?;
+    if db_get_workspace_role(conn, workspace_id, user_id)?.is_none() {
+        return Err(AccessError::Forbidden);
+    }
+    let requires_two_factor = workspaces::table
+        .find(workspace_id)
+        .select(workspaces::require_two_factor)
+        .first::<bool>(conn)?;
+    if requires_two_factor && db_get_confirmed_totp(conn, user_id)?.is_none() {
+        return Err(AccessError::Forbidden);
+    }
+    Ok(workspace_id)
+}
+
+pub(crate) fn db_get_workspace_role(
+    conn: &mut PgConnection,
+    workspace_id: i32,
+    user_id: i32,
+) -> Result<Option<WorkspaceRole>, diesel::result::Error> {
+    workspace_members::table
+        .find((workspace_id, user_id))
+        .select(workspace_members::role)
+        .first::<WorkspaceRole>(conn)
+        .optional()
+}
+
+/// Non-members get `NotFound` rather than `Forbidden`, as with lists.
+pub(crate) fn db_require_workspace_role(
+    conn: &mut PgConnection,
+    workspace_id: i32,
+    user_id: i32,
+    minimum: WorkspaceRole,
+) -> Result<WorkspaceRole, AccessError> {
+    match db_get_workspace_role(conn, workspace_id, user_id)? {
+        Some(role) if role >= minimum => Ok(role),
+        Some(_) => Err(AccessError::Forbidden),
+        None => Err(diesel::result::Error::NotFound.into()),
+    }
+}
+
+/// The role `user_id` holds on `list_id` within `workspace_id`, ignoring
+/// invitations that have not been accepted yet.
+pub(crate) fn db_get_list_role(
+    conn: &mut PgConnection,
+    list_id: i32,
+    user_id: i32,
+    workspace_id: i32,
+) -> Result<Option<ListRole>, diesel::result::Error> {
+    list_members::table
+        .inner_join(todolists::table)
+        .filter(list_members::list_id.eq(list_id))
+        .filter(list_members::user_id.eq(user_id))
+        .filter(list_members::accepted_at.is_not_null())
+        .filter(todolists::workspace_id.eq(workspace_id))
+        .select(list_members::role)
+        .first::<ListRole>(conn)
+        .optional()
+}
+
+/// Non-members, and lists outside the caller's active workspace, get
+/// `NotFound` rather than `Forbidden` so that list ids can't be probed for
+/// existence.
+pub(crate) fn db_require_list_role(
+    conn: &mut PgConnection,
+    list_id: i32,
+    user_id: i32,
+    minimum: ListRole,
+) -> Result<ListRole, AccessError> {
+    let workspace_id = db_get_active_workspace(conn, user_id)?;
+    match db_get_list_role(conn, list_id, user_id, workspace_id)? {
+        Some(role) if role >= minimum => Ok(role),
+        Some(_) => Err(AccessError::Forbidden),
+        None => Err(diesel::result::Error::NotFound.into()),
+    }
+}
+
+pub(crate) fn db_require_task_role(
+    conn: &mut PgConnection,
+    task_id: i32,
+    user_id: i32,
+    minimum: ListRole,
+) -> Result<TodoTask, AccessError> {
+    let task = todotasks::table
+        .find(task_id)
+        .get_result::<TodoTask>(conn)?;
+    db_require_list_role(conn, task.todolist_id, user_id, minimum)?;
+    Ok(task)
+}
+
+/// Only members of a task's list, who still belong to the list's workspace,
+/// may be assigned to, or watch, that task.
+pub(crate) fn db_check_task_participant(
+    conn: &mut PgConnection,
+    task_id: i32,
+    user_id: i32,
+) -> Result<(), AccessError> {
+    let (list_id, workspace_id) = todotasks::table
+        .inner_join(todolists::table.on(todolists::id.eq(todotasks::todolist_id)))
+        .filter(todotasks::id.eq(task_id))
+        .select((todolists::id, todolists::workspace_id))
+        .first::<(i32, i32)>(conn)?;
+    if db_get_workspace_role(conn, workspace_id, user_id)?.is_none() {
+        return Err(AccessError::Forbidden);
+    }
+    match db_get_list_role(conn, list_id, user_id, workspace_id)? {
+        Some(_) => Ok(()),
+        None => Err(AccessError::Forbidden),
+    }
+}
+
+/// Ids of every list in `workspace_id` the user is an accepted member of, for
+/// filtering collection queries.
+pub(crate) fn visible_list_ids(
+    user_id: i32,
+    workspace_id: i32,
+) -> list_members::BoxedQuery<'static, diesel::pg::Pg, diesel::sql_types::Integer> {
+    list_members::table
+        .filter(list_members::user_id.eq(user_id))
+        .filter(list_members::accepted_at.is_not_null())
+        .filter(
+            list_members::list_id.eq_any(
+                todolists::table
+                    .filter(todolists::workspace_id.eq(workspace_id))
+                    .select(todolists::id),
+            ),
+        )
+        .select(list_members::list_id)
+        .into_boxed()
+}
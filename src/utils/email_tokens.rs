@@ -0,0 +1,228 @@
+use crate::models::email_token::{EmailToken, NewEmailToken, TokenPurpose};
+use crate::schema::{email_tokens, users};
+use crate::utils::mailer::Mailer;
+use crate::utils::token;
+use diesel::prelude::*;
+
+/// A token that was just issued, to be mailed once the transaction that
+/// issued it has committed.
+pub(crate) struct IssuedToken {
+    pub record: EmailToken,
+    pub token: String,
+}
+
+/// Issues a token for `email`, retiring any earlier unused token of the same
+/// purpose so that only the latest mail works.
+pub(crate) fn db_issue_email_token(
+    conn: &mut PgConnection,
+    user_id: i32,
+    purpose: TokenPurpose,
+    email: &str,
+) -> Result<IssuedToken, diesel::result::Error> {
+    let now = chrono::Local::now().naive_local();
+    diesel::update(email_tokens::table)
+        .filter(email_tokens::user_id.eq(user_id))
+        .filter(email_tokens::purpose.eq(purpose))
+        .filter(email_tokens::used_at.is_null())
+        .set(email_tokens::used_at.eq(now))
+        .execute(conn)?;
+    let plain_token = token::generate_token();
+    let new_token = NewEmailToken {
+        user_id,
+        purpose,
+        token_hash: token::hash_token(&plain_token),
+        email: email.to_string(),
+        expires_at: now + purpose.lifetime(),
+        created_at: now,
+    };
+    let record = diesel::insert_into(email_tokens::table)
+        .values(&new_token)
+        .returning(EmailToken::as_returning())
+        .get_result(conn)?;
+    Ok(IssuedToken {
+        record,
+        token: plain_token,
+    })
+}
+
+/// Uses up the token. Unknown, spent and expired tokens, and those mailed to
+/// an address the account no longer has, all get `NotFound`.
+pub(crate) fn db_redeem_email_token(
+    conn: &mut PgConnection,
+    plain_token: &str,
+    purpose: TokenPurpose,
+) -> Result<EmailToken, diesel::result::Error> {
+    let now = chrono::Local::now().naive_local();
+    let redeemed = diesel::update(email_tokens::table)
+        .filter(email_tokens::token_hash.eq(token::hash_token(plain_token)))
+        .filter(email_tokens::purpose.eq(purpose))
+        .filter(email_tokens::used_at.is_null())
+        .filter(email_tokens::expires_at.gt(now))
+        .set(email_tokens::used_at.eq(now))
+        .returning(EmailToken::as_returning())
+        .get_result::<EmailToken>(conn)?;
+    let current_email = users::table
+        .find(redeemed.user_id)
+        .select(users::email)
+        .first::<String>(conn)?;
+    if !current_email.eq_ignore_ascii_case(&redeemed.email) {
+        return Err(diesel::result::Error::NotFound);
+    }
+    Ok(redeemed)
+}
+
+/// Mail failures are only logged: the user can always ask for another token.
+pub(crate) fn send_email_token(mailer: Option<&Mailer>, issued: &IssuedToken) {
+    let Some(mailer) = mailer else {
+        eprintln!(
+            "No SMTP relay configured; not sending {} mail",
+            issued.record.purpose.as_str()
+        );
+        return;
+    };
+    let (subject, action) = match issued.record.purpose {
+        TokenPurpose::VerifyEmail => ("Verify your email address", "verify your email address"),
+        TokenPurpose::ResetPassword => ("Reset your password", "choose a new password"),
+    };
+    let body = format!(
+        "Use this code to {}:\n\n{}\n\nIt expires at {}. If you didn't ask for this, you can ignore this email.",
+        action,
+        issued.token,
+        issued.record.expires_at.format("%Y-%m-%d %H:%M")
+    );
+    if let Err(e) = mailer.send(&issued.record.email, subject, body) {
+        eprintln!(
+            "Failed to send {} mail: {}",
+            issued.record.purpose.as_str(),
+            e
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::{db_insert_user, test_connection, SmtpSink};
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn tokens_are_stored_hashed() {
+        let mut conn = test_connection();
+        let user = db_insert_user(&mut conn, false);
+        let issued =
+            db_issue_email_token(&mut conn, user.id, TokenPurpose::VerifyEmail, &user.email)
+                .unwrap();
+        let stored: String = email_tokens::table
+            .find(issued.record.id)
+            .select(email_tokens::token_hash)
+            .first(&mut conn)
+            .unwrap();
+        assert_ne!(stored, issued.token);
+        assert_eq!(stored, token::hash_token(&issued.token));
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn tokens_redeem_once() {
+        let mut conn = test_connection();
+        let user = db_insert_user(&mut conn, false);
+        let issued =
+            db_issue_email_token(&mut conn, user.id, TokenPurpose::ResetPassword, &user.email)
+                .unwrap();
+        let redeemed =
+            db_redeem_email_token(&mut conn, &issued.token, TokenPurpose::ResetPassword).unwrap();
+        assert_eq!(redeemed.user_id, user.id);
+        assert!(redeemed.used_at.is_some());
+        assert!(matches!(
+            db_redeem_email_token(&mut conn, &issued.token, TokenPurpose::ResetPassword),
+            Err(diesel::result::Error::NotFound)
+        ));
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn tokens_only_redeem_for_their_purpose() {
+        let mut conn = test_connection();
+        let user = db_insert_user(&mut conn, false);
+        let issued =
+            db_issue_email_token(&mut conn, user.id, TokenPurpose::VerifyEmail, &user.email)
+                .unwrap();
+        assert!(matches!(
+            db_redeem_email_token(&mut conn, &issued.token, TokenPurpose::ResetPassword),
+            Err(diesel::result::Error::NotFound)
+        ));
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn expired_tokens_are_refused() {
+        let mut conn = test_connection();
+        let user = db_insert_user(&mut conn, false);
+        let issued =
+            db_issue_email_token(&mut conn, user.id, TokenPurpose::ResetPassword, &user.email)
+                .unwrap();
+        diesel::update(email_tokens::table.find(issued.record.id))
+            .set(email_tokens::expires_at.eq(chrono::Local::now().naive_local()))
+            .execute(&mut conn)
+            .unwrap();
+        assert!(matches!(
+            db_redeem_email_token(&mut conn, &issued.token, TokenPurpose::ResetPassword),
+            Err(diesel::result::Error::NotFound)
+        ));
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn issuing_retires_earlier_tokens() {
+        let mut conn = test_connection();
+        let user = db_insert_user(&mut conn, false);
+        let first =
+            db_issue_email_token(&mut conn, user.id, TokenPurpose::ResetPassword, &user.email)
+                .unwrap();
+        let second =
+            db_issue_email_token(&mut conn, user.id, TokenPurpose::ResetPassword, &user.email)
+                .unwrap();
+        assert!(matches!(
+            db_redeem_email_token(&mut conn, &first.token, TokenPurpose::ResetPassword),
+            Err(diesel::result::Error::NotFound)
+        ));
+        assert!(
+            db_redeem_email_token(&mut conn, &second.token, TokenPurpose::ResetPassword).is_ok()
+        );
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn tokens_for_a_previous_address_are_refused() {
+        let mut conn = test_connection();
+        let user = db_insert_user(&mut conn, false);
+        let issued =
+            db_issue_email_token(&mut conn, user.id, TokenPurpose::VerifyEmail, &user.email)
+                .unwrap();
+        diesel::update(users::table.find(user.id))
+            .set(users::email.eq(format!("moved-{}", user.email)))
+            .execute(&mut conn)
+            .unwrap();
+        assert!(matches!(
+            db_redeem_email_token(&mut conn, &issued.token, TokenPurpose::VerifyEmail),
+            Err(diesel::result::Error::NotFound)
+        ));
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn tokens_are_mailed_to_the_address_they_were_issued_for() {
+        let mut conn = test_connection();
+        let sink = SmtpSink::start(false);
+        let user = db_insert_user(&mut conn, false);
+        let issued =
+            db_issue_email_token(&mut conn, user.id, TokenPurpose::ResetPassword, &user.email)
+                .unwrap();
+        send_email_token(Some(&Mailer::local(sink.port)), &issued);
+        let received = sink.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].recipients, vec![user.email.clone()]);
+        assert!(received[0].data.contains("Subject: Reset your password"));
+        assert!(received[0].data.contains(&issued.token));
+    }
+}
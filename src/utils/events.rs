@@ -0,0 +1,528 @@
+use crate::models::event::{NewEvent, NewEventRecipient};
+use crate::models::list_member::ListMember;
+use crate::models::todo_list::TodoList;
+use crate::models::todo_task::TodoTask;
+use crate::models::tombstone::NewTombstone;
+use crate::schema::{
+    event_log_state, event_recipients, events, list_members, todolists, todotasks, tombstones,
+    workspace_members,
+};
+use crate::utils::config;
+use crate::utils::database::connection::Pool;
+use crate::utils::webhooks::enqueue_deliveries;
+use actix_web::web;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text};
+use postgres::fallible_iterator::FallibleIterator;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+
+pub const TASK_EVENTS_CHANNEL: &str = "task_events";
+pub const EVENT_LOG_CHANNEL: &str = "event_log";
+pub const ACCESS_CHANGES_CHANNEL: &str = "access_changes";
+
+/// Postgres rejects NOTIFY payloads of 8000 bytes or more; larger events are
+/// sent without the task body and clients re-fetch it.
+const MAX_NOTIFY_PAYLOAD: usize = 7900;
+
+/// How long an event id may be missing from the log before it is taken to
+/// belong to a rolled back transaction. Transactions recording events have to
+/// commit well within it, or readers may have moved past their events.
+const EVENT_GAP_TIMEOUT_SECS: i64 = 60;
+
+/// Most events looked at past the stored settled id in one go; the
+/// maintenance run moves that id along so readers rarely get near this.
+const SETTLE_SCAN_LIMIT: i64 = 10_000;
+
+const EVENT_LOG_MAINTENANCE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Fans database notifications out to the feeds of this instance: full task
+/// events and the users whose access changed for WebSocket sessions, and the
+/// id of the newest logged event for SSE streams, which read the log
+/// themselves.
+#[derive(Clone)]
+pub struct EventHub {
+    tasks: broadcast::Sender<Arc<TaskEvent>>,
+    /// `None` when notifications may have been missed and every feed has to
+    /// check again.
+    access: broadcast::Sender<Option<i32>>,
+    log: Arc<watch::Sender<i64>>,
+}
+
+impl EventHub {
+    pub fn subscribe_tasks(&self) -> broadcast::Receiver<Arc<TaskEvent>> {
+        self.tasks.subscribe()
+    }
+
+    pub fn subscribe_access_changes(&self) -> broadcast::Receiver<Option<i32>> {
+        self.access.subscribe()
+    }
+
+    pub fn subscribe_log(&self) -> watch::Receiver<i64> {
+        self.log.subscribe()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskEventKind {
+    Created,
+    Patched,
+    Moved,
+    Deleted,
+}
+
+impl TaskEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskEventKind::Created => "created",
+            TaskEventKind::Patched => "patched",
+            TaskEventKind::Moved => "moved",
+            TaskEventKind::Deleted => "deleted",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListEventKind {
+    Created,
+    Patched,
+    Deleted,
+}
+
+impl ListEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ListEventKind::Created => "list.created",
+            ListEventKind::Patched => "list.patched",
+            ListEventKind::Deleted => "list.deleted",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaskEvent {
+    pub kind: TaskEventKind,
+    pub task_id: i32,
+    pub list_id: i32,
+    pub previous_list_id: Option<i32>,
+    pub task: Option<TodoTask>,
+}
+
+impl TaskEvent {
+    pub fn new(kind: TaskEventKind, task: TodoTask, previous_list_id: Option<i32>) -> Self {
+        TaskEvent {
+            kind,
+            task_id: task.id,
+            list_id: task.todolist_id,
+            previous_list_id,
+            task: Some(task),
+        }
+    }
+
+    /// Moves are relevant to subscribers of both the old and the new list.
+    pub fn involves_list(&self, list_id: i32) -> bool {
+        self.list_id == list_id || self.previous_list_id == Some(list_id)
+    }
+}
+
+/// Accepted members of any of `list_ids`.
+pub(crate) fn list_audience(
+    conn: &mut PgConnection,
+    list_ids: &[i32],
+) -> Result<Vec<i32>, diesel::result::Error> {
+    list_members::table
+        .filter(list_members::list_id.eq_any(list_ids))
+        .filter(list_members::accepted_at.is_not_null())
+        .select(list_members::user_id)
+        .distinct()
+        .load(conn)
+}
+
+/// The user themselves and everyone sharing a workspace with them.
+pub(crate) fn user_audience(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> Result<Vec<i32>, diesel::result::Error> {
+    let shared_workspaces: Vec<i32> = workspace_members::table
+        .filter(workspace_members::user_id.eq(user_id))
+        .select(workspace_members::workspace_id)
+        .load(conn)?;
+    let mut audience: Vec<i32> = workspace_members::table
+        .filter(workspace_members::workspace_id.eq_any(shared_workspaces))
+        .select(workspace_members::user_id)
+        .distinct()
+        .load(conn)?;
+    if !audience.contains(&user_id) {
+        audience.push(user_id);
+    }
+    Ok(audience)
+}
+
+/// Takes the next event id. Must run inside the mutation's transaction.
+///
+/// Writers don't wait on each other, so ids may become visible out of order;
+/// readers only go as far as `db_settled_event_id` to never step over one
+/// whose transaction is still running.
+fn reserve_event_id(conn: &mut PgConnection) -> Result<i64, diesel::result::Error> {
+    diesel::select(diesel::dsl::sql::<BigInt>("nextval('events_id_seq')")).get_result(conn)
+}
+
+/// The newest event id at or below which no more events can appear, which is
+/// what cursors and sync tokens may safely advance to. A gap in the ids is
+/// either a transaction still running or one rolled back; it is waited on
+/// until the event after it is older than `EVENT_GAP_TIMEOUT_SECS`.
+pub(crate) fn db_settled_event_id(conn: &mut PgConnection) -> Result<i64, diesel::result::Error> {
+    let floor = event_log_state::table
+        .select(event_log_state::settled_id)
+        .first::<i64>(conn)?;
+    let recent = events::table
+        .filter(events::id.gt(floor))
+        .order(events::id.asc())
+        .limit(SETTLE_SCAN_LIMIT)
+        .select((events::id, events::created_at))
+        .load::<(i64, NaiveDateTime)>(conn)?;
+    let gaps_abandoned_before =
+        chrono::Local::now().naive_local() - chrono::Duration::seconds(EVENT_GAP_TIMEOUT_SECS);
+    let mut settled = floor;
+    for (event_id, created_at) in recent {
+        if event_id != settled + 1 && created_at > gaps_abandoned_before {
+            break;
+        }
+        settled = event_id;
+    }
+    Ok(settled)
+}
+
+/// Cursors and sync tokens below this have missed events or tombstones that
+/// retention already removed.
+pub(crate) fn db_pruned_event_id(conn: &mut PgConnection) -> Result<i64, diesel::result::Error> {
+    event_log_state::table
+        .select(event_log_state::pruned_id)
+        .first(conn)
+}
+
+/// `list_ids` are the lists the event touches, for matching list webhooks.
+fn append_event<T: Serialize>(
+    conn: &mut PgConnection,
+    event_id: i64,
+    kind: &str,
+    payload: &T,
+    list_ids: &[i32],
+    audience: &[i32],
+) -> Result<(), diesel::result::Error> {
+    let new_event = NewEvent {
+        id: event_id,
+        kind,
+        payload: serde_json::to_value(payload).expect("Failed to serialize event payload"),
+        created_at: chrono::Local::now().naive_local(),
+    };
+    diesel::insert_into(events::table)
+        .values(&new_event)
+        .execute(conn)?;
+    let recipients: Vec<NewEventRecipient> = audience
+        .iter()
+        .map(|user_id| NewEventRecipient {
+            event_id,
+            user_id: *user_id,
+        })
+        .collect();
+    diesel::insert_into(event_recipients::table)
+        .values(&recipients)
+        .on_conflict_do_nothing()
+        .execute(conn)?;
+    enqueue_deliveries(conn, event_id, kind, list_ids, audience)?;
+    diesel::sql_query("SELECT pg_notify($1, $2)")
+        .bind::<Text, _>(EVENT_LOG_CHANNEL)
+        .bind::<Text, _>(event_id.to_string())
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Appends an event to the persisted log that `GET /events` replays. Must run
+/// inside the mutation's transaction.
+pub(crate) fn record_event<T: Serialize>(
+    conn: &mut PgConnection,
+    kind: &str,
+    payload: &T,
+    audience: &[i32],
+) -> Result<i64, diesel::result::Error> {
+    let event_id = reserve_event_id(conn)?;
+    append_event(conn, event_id, kind, payload, &[], audience)?;
+    Ok(event_id)
+}
+
+/// Like `record_event`, for events about something in `list_ids`, so that
+/// webhooks scoped to those lists receive them too.
+pub(crate) fn record_list_event<T: Serialize>(
+    conn: &mut PgConnection,
+    kind: &str,
+    payload: &T,
+    list_ids: &[i32],
+    audience: &[i32],
+) -> Result<i64, diesel::result::Error> {
+    let event_id = reserve_event_id(conn)?;
+    append_event(conn, event_id, kind, payload, list_ids, audience)?;
+    Ok(event_id)
+}
+
+fn insert_tombstone(
+    conn: &mut PgConnection,
+    entity: &str,
+    entity_id: i32,
+    list_id: i32,
+    event_id: i64,
+) -> Result<(), diesel::result::Error> {
+    let workspace_id = todolists::table
+        .find(list_id)
+        .select(todolists::workspace_id)
+        .first::<i32>(conn)?;
+    diesel::insert_into(tombstones::table)
+        .values(&NewTombstone {
+            entity,
+            entity_id,
+            list_id,
+            workspace_id,
+            change_seq: event_id,
+            deleted_at: chrono::Local::now().naive_local(),
+        })
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Logs a list change for `audience` and stamps the list for delta sync,
+/// returning it with its new `change_seq`. For deletions the audience has to
+/// be collected before the members are gone, and this must run before the
+/// list row is deleted.
+pub(crate) fn publish_list_event(
+    conn: &mut PgConnection,
+    kind: ListEventKind,
+    mut list: TodoList,
+    audience: &[i32],
+) -> Result<TodoList, diesel::result::Error> {
+    let event_id = reserve_event_id(conn)?;
+    if kind == ListEventKind::Deleted {
+        insert_tombstone(conn, "list", list.id, list.id, event_id)?;
+    } else {
+        diesel::update(todolists::table.find(list.id))
+            .set(todolists::change_seq.eq(event_id))
+            .execute(conn)?;
+        list.change_seq = event_id;
+    }
+    append_event(conn, event_id, kind.as_str(), &list, &[list.id], audience)?;
+    Ok(list)
+}
+
+/// An accepted invitation makes a whole list, with all of its tasks, newly
+/// visible to the member; delta sync picks that up from the membership's
+/// stamp.
+pub(crate) fn publish_member_joined(
+    conn: &mut PgConnection,
+    mut member: ListMember,
+) -> Result<ListMember, diesel::result::Error> {
+    let audience = list_audience(conn, &[member.list_id])?;
+    let event_id = reserve_event_id(conn)?;
+    diesel::update(list_members::table.find((member.list_id, member.user_id)))
+        .set(list_members::change_seq.eq(event_id))
+        .execute(conn)?;
+    member.change_seq = event_id;
+    append_event(
+        conn,
+        event_id,
+        "list.member_joined",
+        &member,
+        &[member.list_id],
+        &audience,
+    )?;
+    Ok(member)
+}
+
+/// Logs a task change for the members of every list it touches, stamps the
+/// task for delta sync and pushes the change to WebSocket subscribers. Must
+/// run inside the mutation's transaction so that listeners only hear about
+/// committed changes. Returns the task with its new `change_seq`.
+pub(crate) fn publish_task_event(
+    conn: &mut PgConnection,
+    kind: TaskEventKind,
+    mut task: TodoTask,
+    previous_list_id: Option<i32>,
+) -> Result<TodoTask, diesel::result::Error> {
+    let mut list_ids = vec![task.todolist_id];
+    list_ids.extend(previous_list_id);
+    let audience = list_audience(conn, &list_ids)?;
+    let event_id = reserve_event_id(conn)?;
+    if kind == TaskEventKind::Deleted {
+        insert_tombstone(conn, "task", task.id, task.todolist_id, event_id)?;
+    } else {
+        if let Some(previous_list_id) = previous_list_id {
+            insert_tombstone(conn, "task", task.id, previous_list_id, event_id)?;
+        }
+        diesel::update(todotasks::table.find(task.id))
+            .set(todotasks::change_seq.eq(event_id))
+            .execute(conn)?;
+        task.change_seq = event_id;
+    }
+    let event = TaskEvent::new(kind, task.clone(), previous_list_id);
+    append_event(
+        conn,
+        event_id,
+        &format!("task.{}", kind.as_str()),
+        &event,
+        &list_ids,
+        &audience,
+    )?;
+
+    let mut payload = serde_json::to_string(&event).expect("Failed to serialize task event");
+    if payload.len() > MAX_NOTIFY_PAYLOAD {
+        let trimmed = TaskEvent {
+            task: None,
+            ..event
+        };
+        payload = serde_json::to_string(&trimmed).expect("Failed to serialize task event");
+    }
+    diesel::sql_query("SELECT pg_notify($1, $2)")
+        .bind::<Text, _>(TASK_EVENTS_CHANNEL)
+        .bind::<Text, _>(payload)
+        .execute(conn)?;
+    Ok(task)
+}
+
+/// Stores how far the log has settled, so readers don't rescan it, and drops
+/// events and tombstones past the retention period.
+fn run_event_log_maintenance(pool: web::Data<Pool>) -> Result<(), diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    conn.transaction(|conn| {
+        let pruned_id = event_log_state::table
+            .select(event_log_state::pruned_id)
+            .for_update()
+            .first::<i64>(conn)?;
+        let settled_id = db_settled_event_id(conn)?;
+        let expired_before = chrono::Local::now().naive_local()
+            - chrono::Duration::days(config::get_event_retention_days());
+        let prune_through = events::table
+            .filter(events::id.le(settled_id))
+            .filter(events::created_at.lt(expired_before))
+            .select(diesel::dsl::max(events::id))
+            .first::<Option<i64>>(conn)?
+            .map_or(pruned_id, |newest| newest.max(pruned_id));
+        if prune_through > pruned_id {
+            diesel::delete(events::table.filter(events::id.le(prune_through))).execute(conn)?;
+            diesel::delete(tombstones::table.filter(tombstones::change_seq.le(prune_through)))
+                .execute(conn)?;
+        }
+        diesel::update(event_log_state::table)
+            .set((
+                event_log_state::settled_id.eq(settled_id),
+                event_log_state::pruned_id.eq(prune_through),
+            ))
+            .execute(conn)?;
+        Ok(())
+    })
+}
+
+pub(crate) fn start_event_log_maintenance(pool: Pool) {
+    let pool = web::Data::new(pool);
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(EVENT_LOG_MAINTENANCE_INTERVAL);
+        loop {
+            interval.tick().await;
+            let pool = pool.clone();
+            match web::block(move || run_event_log_maintenance(pool)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => eprintln!("Failed to maintain event log: {}", e),
+                Err(e) => eprintln!("Event log maintenance failed: {}", e),
+            }
+        }
+    });
+}
+
+/// Tells the task feeds of `user_ids` to check their subscriptions again once
+/// the transaction that took access away commits. Must run inside it.
+pub(crate) fn publish_access_change(
+    conn: &mut PgConnection,
+    user_ids: &[i32],
+) -> Result<(), diesel::result::Error> {
+    for user_id in user_ids {
+        diesel::sql_query("SELECT pg_notify($1, $2)")
+            .bind::<Text, _>(ACCESS_CHANGES_CHANNEL)
+            .bind::<Text, _>(user_id.to_string())
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+fn dispatch(hub: &EventHub, notification: &postgres::Notification) {
+    match notification.channel() {
+        TASK_EVENTS_CHANNEL => match serde_json::from_str::<TaskEvent>(notification.payload()) {
+            // nobody being subscribed right now is not an error
+            Ok(event) => {
+                let _ = hub.tasks.send(Arc::new(event));
+            }
+            Err(e) => eprintln!("Failed to parse task event: {}", e),
+        },
+        ACCESS_CHANGES_CHANNEL => match notification.payload().parse::<i32>() {
+            Ok(user_id) => {
+                let _ = hub.access.send(Some(user_id));
+            }
+            Err(e) => eprintln!("Failed to parse access change: {}", e),
+        },
+        EVENT_LOG_CHANNEL => match notification.payload().parse::<i64>() {
+            Ok(event_id) => {
+                hub.log.send_if_modified(|latest| {
+                    let newer = event_id > *latest;
+                    if newer {
+                        *latest = event_id;
+                    }
+                    newer
+                });
+            }
+            Err(e) => eprintln!("Failed to parse event log id: {}", e),
+        },
+        _ => {}
+    }
+}
+
+fn listen(hub: &EventHub) -> Result<(), postgres::Error> {
+    let mut client = postgres::Client::connect(&config::get_connection_string(), postgres::NoTls)?;
+    client.batch_execute(&format!(
+        "LISTEN {}; LISTEN {}; LISTEN {}",
+        TASK_EVENTS_CHANNEL, EVENT_LOG_CHANNEL, ACCESS_CHANGES_CHANNEL
+    ))?;
+    // feeds may have slept through notifications while we reconnected
+    hub.log.send_modify(|_| {});
+    let _ = hub.access.send(None);
+    let mut notifications = client.notifications();
+    let mut iter = notifications.blocking_iter();
+    while let Some(notification) = iter.next()? {
+        dispatch(hub, &notification);
+    }
+    Ok(())
+}
+
+/// Every API instance LISTENs on its own connection and fans notifications
+/// out to its local sessions, so clients see changes made through any
+/// instance.
+pub(crate) fn start_event_listener() -> EventHub {
+    let (tasks, _) = broadcast::channel(1024);
+    let (access, _) = broadcast::channel(256);
+    let (log, _) = watch::channel(0);
+    let hub = EventHub {
+        tasks,
+        access,
+        log: Arc::new(log),
+    };
+    let listener_hub = hub.clone();
+    std::thread::Builder::new()
+        .name(String::from("event-listener"))
+        .spawn(move || loop {
+            if let Err(e) = listen(&listener_hub) {
+                eprintln!("Event listener failed: {}", e);
+            }
+            std::thread::sleep(Duration::from_secs(5));
+        })
+        .expect("Failed to start event listener");
+    hub
+}
@@ -0,0 +1,329 @@
+use crate::models::todo_task::TodoTask;
+use chrono::{Days, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+const PRODUCT_ID: &str = "-//Todoer//Todoer API//EN";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Component {
+    Event,
+    Todo,
+}
+
+impl Component {
+    pub(crate) fn from_query(value: Option<&str>) -> Option<Self> {
+        match value {
+            None | Some("vevent") => Some(Component::Event),
+            Some("vtodo") => Some(Component::Todo),
+            Some(_) => None,
+        }
+    }
+}
+
+/// Stable across renames and moves, so clients update entries in place.
+pub(crate) fn task_uid(task_id: i32) -> String {
+    format!("task-{}@todoer", task_id)
+}
+
+fn escape_text(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Timestamps are stored in server local time; iCalendar wants UTC.
+fn format_utc(value: NaiveDateTime) -> String {
+    let utc = chrono::Local
+        .from_local_datetime(&value)
+        .earliest()
+        .map_or_else(|| value.and_utc(), |local| local.with_timezone(&Utc));
+    utc.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// `COMPLETED` has to be UTC, but floating times are taken as server time.
+fn parse_date_time(value: &str) -> Option<NaiveDateTime> {
+    let (value, is_utc) = match value.strip_suffix('Z') {
+        Some(value) => (value, true),
+        None => (value, false),
+    };
+    let parsed = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    if !is_utc {
+        return Some(parsed);
+    }
+    Some(
+        Utc.from_utc_datetime(&parsed)
+            .with_timezone(&Local)
+            .naive_local(),
+    )
+}
+
+/// iCalendar ranks priorities 1 (highest) to 9, with 0 meaning none. Letters
+/// map onto that from `A` = 1; everything past `I` shares 9.
+pub(crate) fn ical_priority(priority: Option<&str>) -> u8 {
+    match priority.and_then(|letter| letter.bytes().next()) {
+        Some(letter) => (letter.saturating_sub(b'A') + 1).min(9),
+        None => 0,
+    }
+}
+
+fn priority_letter(priority: u8) -> Option<String> {
+    (1..=9)
+        .contains(&priority)
+        .then(|| char::from(b'A' + priority - 1).to_string())
+}
+
+fn format_date(value: NaiveDate) -> String {
+    value.format("%Y%m%d").to_string()
+}
+
+/// Folds a content line at 75 octets, never splitting a UTF-8 sequence, and
+/// terminates it with CRLF as RFC 5545 requires.
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+/// A task as it appears in a calendar. Tasks created over CalDAV keep the UID
+/// their client chose; all others get `task_uid`.
+pub(crate) struct CalendarEntry {
+    pub task: TodoTask,
+    pub uid: String,
+    pub parent_uid: Option<String>,
+    pub list_name: String,
+}
+
+/// The content lines of one task, without the BEGIN/END wrapper.
+fn entry_properties(entry: &CalendarEntry, component: Component) -> Vec<String> {
+    let task = &entry.task;
+    let mut lines = vec![
+        format!("UID:{}", escape_text(&entry.uid)),
+        format!("DTSTAMP:{}", format_utc(task.modified_at)),
+        format!("CREATED:{}", format_utc(task.created_at)),
+        format!("LAST-MODIFIED:{}", format_utc(task.modified_at)),
+        format!("SUMMARY:{}", escape_text(&task.name)),
+        format!("CATEGORIES:{}", escape_text(&entry.list_name)),
+    ];
+    if let Some(description) = task.description.as_deref().filter(|d| !d.is_empty()) {
+        lines.push(format!("DESCRIPTION:{}", escape_text(description)));
+    }
+    if let Some(parent_uid) = &entry.parent_uid {
+        lines.push(format!(
+            "RELATED-TO;RELTYPE=PARENT:{}",
+            escape_text(parent_uid)
+        ));
+    }
+    let priority = ical_priority(task.priority.as_deref());
+    if priority > 0 {
+        lines.push(format!("PRIORITY:{}", priority));
+    }
+    if component == Component::Todo {
+        match task.completed_at {
+            Some(completed_at) => {
+                lines.push(String::from("STATUS:COMPLETED"));
+                lines.push(format!("COMPLETED:{}", format_utc(completed_at)));
+                lines.push(String::from("PERCENT-COMPLETE:100"));
+            }
+            None => lines.push(String::from("STATUS:NEEDS-ACTION")),
+        }
+    }
+    match (component, task.due_date) {
+        (Component::Todo, Some(due)) => {
+            lines.push(format!("DUE;VALUE=DATE:{}", format_date(due)));
+        }
+        (Component::Event, Some(due)) => {
+            lines.push(format!("DTSTART;VALUE=DATE:{}", format_date(due)));
+            lines.push(format!(
+                "DTEND;VALUE=DATE:{}",
+                format_date(due + Days::new(1))
+            ));
+            lines.push(String::from("TRANSP:TRANSPARENT"));
+        }
+        (_, None) => {}
+    }
+    lines
+}
+
+fn push_component(out: &mut String, entry: &CalendarEntry, component: Component) {
+    let name = match component {
+        Component::Event => "VEVENT",
+        Component::Todo => "VTODO",
+    };
+    push_line(out, &format!("BEGIN:{}", name));
+    for line in entry_properties(entry, component) {
+        push_line(out, &line);
+    }
+    push_line(out, &format!("END:{}", name));
+}
+
+/// A whole VCALENDAR with one component per entry.
+pub(crate) fn render_calendar(
+    calendar_name: &str,
+    entries: &[CalendarEntry],
+    component: Component,
+) -> String {
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, &format!("PRODID:{}", PRODUCT_ID));
+    push_line(&mut out, "CALSCALE:GREGORIAN");
+    push_line(
+        &mut out,
+        &format!("X-WR-CALNAME:{}", escape_text(calendar_name)),
+    );
+    for entry in entries {
+        push_component(&mut out, entry, component);
+    }
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// The fields of a client's VTODO that map onto a task. Everything else has
+/// nowhere to go and is dropped.
+#[derive(Debug, Default)]
+pub(crate) struct ParsedTodo {
+    pub uid: Option<String>,
+    pub summary: Option<String>,
+    pub description: Option<String>,
+    pub due_date: Option<NaiveDate>,
+    pub parent_uid: Option<String>,
+    /// iCalendar's 0 to 9, 0 being none.
+    pub priority: u8,
+    pub status_completed: bool,
+    pub completed_at: Option<NaiveDateTime>,
+}
+
+impl ParsedTodo {
+    /// Letters past `I` come back as 9 too, so a task whose letter still
+    /// ranks the same keeps it.
+    pub(crate) fn priority(&self, previous: Option<&str>) -> Option<String> {
+        if ical_priority(previous) == self.priority {
+            return previous.map(str::to_string);
+        }
+        priority_letter(self.priority)
+    }
+
+    /// Clients mark completion with `STATUS`, `COMPLETED` or both; one that
+    /// only sets the status keeps the time the task was first completed.
+    pub(crate) fn completed_at(
+        &self,
+        previous: Option<NaiveDateTime>,
+        now: NaiveDateTime,
+    ) -> Option<NaiveDateTime> {
+        match (self.completed_at, self.status_completed) {
+            (Some(completed_at), _) => Some(completed_at),
+            (None, true) => Some(previous.unwrap_or(now)),
+            (None, false) => None,
+        }
+    }
+}
+
+fn unescape_text(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// Splits `NAME;PARAM=..:VALUE` at the first colon outside quoted parameter
+/// values.
+fn split_property(line: &str) -> Option<(&str, &str)> {
+    let mut quoted = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ':' if !quoted => return Some((&line[..index], &line[index + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Accepts both dates and date-times; tasks only keep the day.
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value.get(..8)?, "%Y%m%d").ok()
+}
+
+/// Parses the first VTODO in `body`, or `None` if there isn't one.
+pub(crate) fn parse_vtodo(body: &str) -> Option<ParsedTodo> {
+    let unfolded = body
+        .replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+    let mut todo: Option<ParsedTodo> = None;
+    let mut depth = 0;
+    for line in unfolded.lines() {
+        let Some((head, value)) = split_property(line) else {
+            continue;
+        };
+        let mut params = head.split(';');
+        let name = params.next().unwrap_or_default().to_ascii_uppercase();
+        match (name.as_str(), value.to_ascii_uppercase().as_str()) {
+            ("BEGIN", "VTODO") if todo.is_none() => {
+                todo = Some(ParsedTodo::default());
+                continue;
+            }
+            ("END", "VTODO") if depth == 0 => break,
+            ("BEGIN", _) if todo.is_some() => depth += 1,
+            ("END", _) if todo.is_some() => depth -= 1,
+            _ => {}
+        }
+        // properties of nested components such as VALARM are not the task's
+        let Some(todo) = todo.as_mut().filter(|_| depth == 0) else {
+            continue;
+        };
+        match name.as_str() {
+            "UID" => todo.uid = Some(value.to_string()),
+            "SUMMARY" => todo.summary = Some(unescape_text(value)),
+            "DESCRIPTION" => todo.description = Some(unescape_text(value)),
+            "DUE" => todo.due_date = parse_date(value),
+            "PRIORITY" => todo.priority = value.trim().parse::<u8>().unwrap_or(0).min(9),
+            "STATUS" => todo.status_completed = value.eq_ignore_ascii_case("COMPLETED"),
+            "COMPLETED" => todo.completed_at = parse_date_time(value),
+            "RELATED-TO" => {
+                let is_parent = params.all(|param| {
+                    !param.to_ascii_uppercase().starts_with("RELTYPE=")
+                        || param.eq_ignore_ascii_case("RELTYPE=PARENT")
+                });
+                if is_parent {
+                    todo.parent_uid = Some(value.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    todo
+}
+
+/// The task id behind a UID this server generated.
+pub(crate) fn parse_task_uid(uid: &str) -> Option<i32> {
+    uid.strip_prefix("task-")?
+        .strip_suffix("@todoer")?
+        .parse()
+        .ok()
+}
@@ -0,0 +1,371 @@
+use crate::utils::todotxt::project_name;
+use chrono::NaiveDate;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// What an import will create, independent of the service it came from.
+#[derive(Debug, Default)]
+pub(crate) struct ImportPlan {
+    pub lists: Vec<PlannedList>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct PlannedList {
+    pub source_id: String,
+    pub name: String,
+    pub description: String,
+    pub tasks: Vec<PlannedTask>,
+}
+
+/// `parent` is the source id of another task in the same list.
+#[derive(Debug, Default)]
+pub(crate) struct PlannedTask {
+    pub source_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub parent: Option<String>,
+    pub due_date: Option<NaiveDate>,
+    pub priority: Option<String>,
+    pub completed: bool,
+    pub tags: Vec<String>,
+}
+
+/// Both services have used numeric and string ids over the years.
+fn id_string<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Value::deserialize(deserializer)? {
+        Value::String(id) => Ok(id),
+        Value::Number(id) => Ok(id.to_string()),
+        other => Err(serde::de::Error::custom(format!("unexpected id {}", other))),
+    }
+}
+
+fn optional_id_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Value::deserialize(deserializer)? {
+        Value::Null => Ok(None),
+        Value::String(id) if id.is_empty() => Ok(None),
+        Value::String(id) => Ok(Some(id)),
+        Value::Number(id) => Ok(Some(id.to_string())),
+        other => Err(serde::de::Error::custom(format!("unexpected id {}", other))),
+    }
+}
+
+/// Older exports use `0`/`1` for flags.
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Bool(flag) => *flag,
+        Value::Number(n) => n.as_i64().is_some_and(|n| n != 0),
+        _ => false,
+    }
+}
+
+/// Dates and date-times alike; the time of day is dropped.
+fn leading_date(raw: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(raw.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// Tags follow todo.txt contexts, which can't hold spaces.
+fn tag_name(raw: &str) -> Option<String> {
+    let tag = project_name(raw);
+    (!tag.is_empty()).then_some(tag)
+}
+
+fn non_empty(raw: String) -> Option<String> {
+    (!raw.trim().is_empty()).then_some(raw)
+}
+
+#[derive(Debug, Deserialize)]
+struct TodoistExport {
+    #[serde(default)]
+    projects: Vec<TodoistProject>,
+    #[serde(default)]
+    items: Vec<TodoistItem>,
+    #[serde(default)]
+    labels: Vec<TodoistLabel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TodoistProject {
+    #[serde(deserialize_with = "id_string")]
+    id: String,
+    name: String,
+    #[serde(default)]
+    is_deleted: Value,
+    #[serde(default)]
+    is_archived: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct TodoistLabel {
+    #[serde(deserialize_with = "id_string")]
+    id: String,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TodoistDue {
+    date: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TodoistItem {
+    #[serde(deserialize_with = "id_string")]
+    id: String,
+    #[serde(deserialize_with = "id_string")]
+    project_id: String,
+    content: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default, deserialize_with = "optional_id_string")]
+    parent_id: Option<String>,
+    #[serde(default)]
+    due: Option<TodoistDue>,
+    #[serde(default)]
+    priority: i64,
+    /// Label names, or label ids in older exports.
+    #[serde(default)]
+    labels: Vec<Value>,
+    #[serde(default)]
+    checked: Value,
+    #[serde(default)]
+    is_deleted: Value,
+}
+
+/// Todoist's `4` is its most urgent priority, shown as "p1".
+fn todoist_priority(priority: i64) -> Option<String> {
+    match priority {
+        4 => Some(String::from("A")),
+        3 => Some(String::from("B")),
+        2 => Some(String::from("C")),
+        _ => None,
+    }
+}
+
+/// Reads a Todoist sync export: each project becomes a list and each item a
+/// task, with sub-items as subtasks.
+pub(crate) fn parse_todoist(body: &[u8]) -> Result<ImportPlan, serde_json::Error> {
+    let export: TodoistExport = serde_json::from_slice(body)?;
+    let label_names: HashMap<String, String> = export
+        .labels
+        .into_iter()
+        .map(|label| (label.id, label.name))
+        .collect();
+    let mut plan = ImportPlan::default();
+    let mut list_index: HashMap<String, usize> = HashMap::new();
+    for project in export.projects {
+        if truthy(&project.is_deleted) {
+            continue;
+        }
+        if truthy(&project.is_archived) {
+            plan.warnings.push(format!(
+                "project {:?} is archived and was imported anyway",
+                project.name
+            ));
+        }
+        list_index.insert(project.id.clone(), plan.lists.len());
+        plan.lists.push(PlannedList {
+            source_id: project.id,
+            name: project.name,
+            ..Default::default()
+        });
+    }
+    for item in export.items {
+        if truthy(&item.is_deleted) {
+            continue;
+        }
+        let Some(&index) = list_index.get(&item.project_id) else {
+            plan.warnings.push(format!(
+                "item {} belongs to unknown project {} and was skipped",
+                item.id, item.project_id
+            ));
+            continue;
+        };
+        let tags = item
+            .labels
+            .iter()
+            .filter_map(|label| match label {
+                Value::String(name) => Some(name.clone()),
+                Value::Number(id) => label_names.get(&id.to_string()).cloned(),
+                _ => None,
+            })
+            .filter_map(|name| tag_name(&name))
+            .collect();
+        plan.lists[index].tasks.push(PlannedTask {
+            source_id: item.id,
+            name: item.content,
+            description: non_empty(item.description),
+            parent: item.parent_id,
+            due_date: item.due.and_then(|due| leading_date(&due.date)),
+            priority: todoist_priority(item.priority),
+            completed: truthy(&item.checked),
+            tags,
+        });
+    }
+    Ok(plan)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrelloBoard {
+    id: String,
+    name: String,
+    #[serde(default)]
+    desc: String,
+    #[serde(default)]
+    lists: Vec<TrelloColumn>,
+    #[serde(default)]
+    labels: Vec<TrelloLabel>,
+    #[serde(default)]
+    cards: Vec<TrelloCard>,
+    #[serde(default)]
+    checklists: Vec<TrelloChecklist>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TrelloColumn {
+    id: String,
+    name: String,
+    #[serde(default)]
+    closed: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct TrelloLabel {
+    id: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    color: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrelloCard {
+    id: String,
+    name: String,
+    #[serde(default)]
+    desc: String,
+    #[serde(default)]
+    id_list: String,
+    #[serde(default)]
+    id_labels: Vec<String>,
+    #[serde(default)]
+    due: Option<String>,
+    #[serde(default)]
+    due_complete: bool,
+    #[serde(default)]
+    closed: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrelloChecklist {
+    id_card: String,
+    #[serde(default)]
+    check_items: Vec<TrelloCheckItem>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TrelloCheckItem {
+    id: String,
+    name: String,
+    #[serde(default)]
+    state: String,
+    #[serde(default)]
+    due: Option<String>,
+    #[serde(default)]
+    pos: f64,
+}
+
+/// Reads a Trello board export into one list. Cards become tasks tagged with
+/// their column and labels, and checklist items become their subtasks.
+/// Archived cards and cards in archived columns are left out.
+pub(crate) fn parse_trello(body: &[u8]) -> Result<ImportPlan, serde_json::Error> {
+    let board: TrelloBoard = serde_json::from_slice(body)?;
+    let columns: HashMap<&str, &TrelloColumn> = board
+        .lists
+        .iter()
+        .map(|column| (column.id.as_str(), column))
+        .collect();
+    let labels: HashMap<&str, String> = board
+        .labels
+        .iter()
+        .filter_map(|label| {
+            let name = match label.name.trim() {
+                "" => label.color.clone()?,
+                name => name.to_string(),
+            };
+            Some((label.id.as_str(), name))
+        })
+        .collect();
+    let mut checklists: HashMap<&str, Vec<&TrelloCheckItem>> = HashMap::new();
+    for checklist in &board.checklists {
+        checklists
+            .entry(checklist.id_card.as_str())
+            .or_default()
+            .extend(checklist.check_items.iter());
+    }
+
+    let mut plan = ImportPlan::default();
+    let mut list = PlannedList {
+        source_id: board.id.clone(),
+        name: board.name.clone(),
+        description: board.desc.clone(),
+        tasks: Vec::new(),
+    };
+    let mut archived = 0;
+    for card in &board.cards {
+        let column = columns.get(card.id_list.as_str());
+        if card.closed || column.is_some_and(|column| column.closed) {
+            archived += 1;
+            continue;
+        }
+        let mut tags: Vec<String> = column
+            .and_then(|column| tag_name(&column.name))
+            .into_iter()
+            .collect();
+        for label in &card.id_labels {
+            if let Some(tag) = labels.get(label.as_str()).and_then(|name| tag_name(name)) {
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+        }
+        list.tasks.push(PlannedTask {
+            source_id: card.id.clone(),
+            name: card.name.clone(),
+            description: non_empty(card.desc.clone()),
+            parent: None,
+            due_date: card.due.as_deref().and_then(leading_date),
+            priority: None,
+            completed: card.due_complete,
+            tags,
+        });
+        let mut items = checklists.remove(card.id.as_str()).unwrap_or_default();
+        items.sort_by(|a, b| a.pos.total_cmp(&b.pos));
+        for item in items {
+            list.tasks.push(PlannedTask {
+                source_id: item.id.clone(),
+                name: item.name.clone(),
+                parent: Some(card.id.clone()),
+                due_date: item.due.as_deref().and_then(leading_date),
+                completed: item.state == "complete",
+                ..Default::default()
+            });
+        }
+    }
+    if archived > 0 {
+        plan.warnings
+            .push(format!("{} archived cards were skipped", archived));
+    }
+    plan.lists.push(list);
+    Ok(plan)
+}
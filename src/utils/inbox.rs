@@ -0,0 +1,237 @@
+use crate::models::list_member::ListRole;
+use crate::models::notification::NewNotification;
+use crate::models::todo_task::TodoTask;
+use crate::schema::{list_members, notifications, task_watchers, todolists, users};
+use crate::utils::events::TaskEventKind;
+use diesel::prelude::*;
+
+/// Puts one notification in the inbox of each of `recipients`. `actor` is
+/// whoever's name the text carries, so it can be scrubbed if they leave.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn db_push_notifications(
+    conn: &mut PgConnection,
+    actor: Option<i32>,
+    recipients: &[i32],
+    kind: &str,
+    task_id: Option<i32>,
+    list_id: Option<i32>,
+    title: &str,
+    body: &str,
+) -> Result<(), diesel::result::Error> {
+    let now = chrono::Local::now().naive_local();
+    let rows: Vec<NewNotification> = recipients
+        .iter()
+        .map(|user_id| NewNotification {
+            user_id: *user_id,
+            kind,
+            task_id,
+            list_id,
+            title,
+            body,
+            created_at: now,
+            actor_id: actor,
+        })
+        .collect();
+    if !rows.is_empty() {
+        diesel::insert_into(notifications::table)
+            .values(&rows)
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+fn db_display_name(conn: &mut PgConnection, user_id: i32) -> Result<String, diesel::result::Error> {
+    let (first, last) = users::table
+        .find(user_id)
+        .select((users::first_name, users::last_name))
+        .first::<(String, String)>(conn)?;
+    Ok(format!("{} {}", first, last).trim().to_string())
+}
+
+/// Lower-cased addresses written as `@someone@example.com`.
+fn mentioned_emails(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .filter_map(|word| word.strip_prefix('@'))
+        .map(|word| {
+            word.trim_end_matches(|c: char| !c.is_ascii_alphanumeric())
+                .to_lowercase()
+        })
+        .filter(|email| email.contains('@'))
+        .collect()
+}
+
+fn task_text(task: &TodoTask) -> String {
+    format!(
+        "{} {}",
+        task.name,
+        task.description.as_deref().unwrap_or_default()
+    )
+}
+
+/// Members of the task's list mentioned by the change who were not mentioned
+/// before it.
+fn db_newly_mentioned(
+    conn: &mut PgConnection,
+    previous: Option<&TodoTask>,
+    task: &TodoTask,
+) -> Result<Vec<i32>, diesel::result::Error> {
+    let before = previous
+        .map(task_text)
+        .map_or_else(Vec::new, |text| mentioned_emails(&text));
+    let mentioned: Vec<String> = mentioned_emails(&task_text(task))
+        .into_iter()
+        .filter(|email| !before.contains(email))
+        .collect();
+    if mentioned.is_empty() {
+        return Ok(Vec::new());
+    }
+    let members: Vec<(i32, String)> = list_members::table
+        .inner_join(users::table.on(users::id.eq(list_members::user_id)))
+        .filter(list_members::list_id.eq(task.todolist_id))
+        .filter(list_members::accepted_at.is_not_null())
+        .select((users::id, users::email))
+        .load(conn)?;
+    Ok(members
+        .into_iter()
+        .filter(|(_, email)| mentioned.contains(&email.to_lowercase()))
+        .map(|(user_id, _)| user_id)
+        .collect())
+}
+
+/// Tells mentioned members and the task's watchers about a change `actor`
+/// made. Must run in the mutation's transaction, and for deletions before the
+/// task row, and with it its watchers, is gone.
+pub(crate) fn db_notify_task_change(
+    conn: &mut PgConnection,
+    actor: i32,
+    kind: TaskEventKind,
+    previous: Option<&TodoTask>,
+    task: &TodoTask,
+) -> Result<(), diesel::result::Error> {
+    let actor_name = db_display_name(conn, actor)?;
+    let task_id = match kind {
+        TaskEventKind::Deleted => None,
+        _ => Some(task.id),
+    };
+    let mut mentioned = Vec::new();
+    if kind != TaskEventKind::Deleted {
+        mentioned = db_newly_mentioned(conn, previous, task)?;
+        mentioned.retain(|user_id| *user_id != actor);
+        db_push_notifications(
+            conn,
+            Some(actor),
+            &mentioned,
+            "task.mentioned",
+            task_id,
+            Some(task.todolist_id),
+            &format!("Mentioned in {}", task.name),
+            &format!("{} mentioned you in \"{}\"", actor_name, task.name),
+        )?;
+    }
+    if kind == TaskEventKind::Created {
+        return Ok(());
+    }
+    let watchers: Vec<i32> = task_watchers::table
+        .filter(task_watchers::task_id.eq(task.id))
+        .filter(task_watchers::user_id.ne(actor))
+        .select(task_watchers::user_id)
+        .load::<i32>(conn)?
+        .into_iter()
+        .filter(|user_id| !mentioned.contains(user_id))
+        .collect();
+    let verb = match kind {
+        TaskEventKind::Deleted => "deleted",
+        TaskEventKind::Moved => "moved",
+        _ => "updated",
+    };
+    db_push_notifications(
+        conn,
+        Some(actor),
+        &watchers,
+        &format!("task.{}", kind.as_str()),
+        task_id,
+        Some(task.todolist_id),
+        &format!("{} was {}", task.name, verb),
+        &format!("{} {} \"{}\"", actor_name, verb, task.name),
+    )
+}
+
+pub(crate) fn db_notify_task_assigned(
+    conn: &mut PgConnection,
+    actor: i32,
+    task: &TodoTask,
+    assignee: i32,
+) -> Result<(), diesel::result::Error> {
+    if assignee == actor {
+        return Ok(());
+    }
+    let actor_name = db_display_name(conn, actor)?;
+    db_push_notifications(
+        conn,
+        Some(actor),
+        &[assignee],
+        "task.assigned",
+        Some(task.id),
+        Some(task.todolist_id),
+        &format!("Assigned to {}", task.name),
+        &format!("{} assigned you to \"{}\"", actor_name, task.name),
+    )
+}
+
+pub(crate) fn db_notify_list_invited(
+    conn: &mut PgConnection,
+    actor: i32,
+    list_id: i32,
+    invitee: i32,
+    role: ListRole,
+) -> Result<(), diesel::result::Error> {
+    let actor_name = db_display_name(conn, actor)?;
+    let list_name = todolists::table
+        .find(list_id)
+        .select(todolists::name)
+        .first::<String>(conn)?;
+    db_push_notifications(
+        conn,
+        Some(actor),
+        &[invitee],
+        "list.invited",
+        None,
+        Some(list_id),
+        &format!("Invitation to {}", list_name),
+        &format!(
+            "{} invited you to \"{}\" as {}",
+            actor_name,
+            list_name,
+            role.as_str()
+        ),
+    )
+}
+
+/// For deletions, call before the list's members are removed.
+pub(crate) fn db_notify_list_deleted(
+    conn: &mut PgConnection,
+    actor: i32,
+    list_id: i32,
+) -> Result<(), diesel::result::Error> {
+    let actor_name = db_display_name(conn, actor)?;
+    let list_name = todolists::table
+        .find(list_id)
+        .select(todolists::name)
+        .first::<String>(conn)?;
+    let members: Vec<i32> = list_members::table
+        .filter(list_members::list_id.eq(list_id))
+        .filter(list_members::accepted_at.is_not_null())
+        .filter(list_members::user_id.ne(actor))
+        .select(list_members::user_id)
+        .load(conn)?;
+    db_push_notifications(
+        conn,
+        Some(actor),
+        &members,
+        "list.deleted",
+        None,
+        None,
+        &format!("{} was deleted", list_name),
+        &format!("{} deleted \"{}\"", actor_name, list_name),
+    )
+}
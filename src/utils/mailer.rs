@@ -0,0 +1,67 @@
+use crate::utils::config;
+use derive_more::{Display, From};
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+
+#[derive(Debug, Display, From)]
+pub(crate) enum MailError {
+    Address(lettre::address::AddressError),
+    Message(lettre::error::Error),
+    Transport(lettre::transport::smtp::Error),
+}
+
+/// Sends plain-text mail through the configured SMTP relay. Sending blocks,
+/// so call it from `web::block` or a background thread.
+pub struct Mailer {
+    transport: SmtpTransport,
+    from: Mailbox,
+}
+
+impl Mailer {
+    pub(crate) fn send(&self, to: &str, subject: &str, body: String) -> Result<(), MailError> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse::<Mailbox>()?)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body)?;
+        self.transport.send(&message)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Mailer {
+    /// Sends through a plain SMTP server on this machine, such as a test sink.
+    pub(crate) fn local(port: u16) -> Self {
+        Mailer {
+            transport: SmtpTransport::builder_dangerous("127.0.0.1")
+                .port(port)
+                .build(),
+            from: "Todoer <todoer@localhost>".parse().unwrap(),
+        }
+    }
+}
+
+/// `None` when no SMTP host is configured.
+pub(crate) fn get_mailer() -> Option<Mailer> {
+    let host = config::get_smtp_host()?;
+    let builder = match config::get_smtp_tls().as_str() {
+        "none" => SmtpTransport::builder_dangerous(&host),
+        "tls" => SmtpTransport::relay(&host).expect("Invalid SMTP_HOST"),
+        _ => SmtpTransport::starttls_relay(&host).expect("Invalid SMTP_HOST"),
+    };
+    let builder = match config::get_smtp_port() {
+        Some(port) => builder.port(port),
+        None => builder,
+    };
+    let builder = match config::get_smtp_credentials() {
+        Some((username, password)) => builder.credentials(Credentials::new(username, password)),
+        None => builder,
+    };
+    Some(Mailer {
+        transport: builder.build(),
+        from: config::get_smtp_from().parse().expect("Invalid SMTP_FROM"),
+    })
+}
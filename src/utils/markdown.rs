@@ -0,0 +1,206 @@
+use crate::models::todo_list::TodoList;
+use crate::models::todo_task::TodoTask;
+use std::collections::{HashMap, HashSet};
+
+/// Item text starts this far right of its marker, and children sit there too.
+const INDENT: usize = 2;
+
+/// One `- [ ]` item; `parent` indexes an earlier item of the same checklist.
+#[derive(Debug, Default)]
+pub(crate) struct ChecklistItem {
+    pub line: usize,
+    pub parent: Option<usize>,
+    pub checked: bool,
+    pub name: String,
+    pub description: Option<String>,
+    /// Column of the item's text; its description lines start there.
+    content_column: usize,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Checklist {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub items: Vec<ChecklistItem>,
+}
+
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+/// Splits `- [x] text` into its checked state and text. Plain bullets are
+/// left to whatever paragraph they appear in.
+fn parse_item(trimmed: &str) -> Option<(bool, &str, usize)> {
+    let rest = trimmed
+        .strip_prefix("- ")
+        .or_else(|| trimmed.strip_prefix("* "))
+        .or_else(|| trimmed.strip_prefix("+ "))?;
+    let (checked, text) = if let Some(text) = rest.strip_prefix("[ ]") {
+        (false, text)
+    } else if let Some(text) = rest
+        .strip_prefix("[x]")
+        .or_else(|| rest.strip_prefix("[X]"))
+    {
+        (true, text)
+    } else {
+        return None;
+    };
+    if !(text.is_empty() || text.starts_with(' ')) {
+        return None;
+    }
+    let text_column = trimmed.len() - text.trim_start().len();
+    Some((checked, text.trim(), text_column))
+}
+
+fn join_paragraphs(lines: Vec<String>) -> Option<String> {
+    let joined = lines.join("\n");
+    let trimmed = joined.trim_matches('\n').trim_end();
+    (!trimmed.is_empty()).then(|| trimmed.to_string())
+}
+
+/// Reads a GitHub-style checklist. The first `#` heading names the list and
+/// the text before the first item describes it; text indented under an item
+/// becomes that item's description.
+pub(crate) fn parse_checklist(body: &str) -> Checklist {
+    let mut checklist = Checklist::default();
+    let mut list_description: Vec<String> = Vec::new();
+    let mut descriptions: Vec<Vec<String>> = Vec::new();
+    // (indent of the marker, item index) of the items still open for children
+    let mut open: Vec<(usize, usize)> = Vec::new();
+
+    for (index, line) in body.lines().enumerate() {
+        let indent = indent_width(line);
+        let trimmed = line.trim();
+        if let Some((checked, text, text_column)) = parse_item(trimmed) {
+            while open
+                .last()
+                .is_some_and(|(open_indent, _)| *open_indent >= indent)
+            {
+                open.pop();
+            }
+            checklist.items.push(ChecklistItem {
+                line: index + 1,
+                parent: open.last().map(|(_, item)| *item),
+                checked,
+                name: text.to_string(),
+                description: None,
+                content_column: indent + text_column,
+            });
+            descriptions.push(Vec::new());
+            open.push((indent, checklist.items.len() - 1));
+            continue;
+        }
+        if checklist.items.is_empty() {
+            match trimmed.strip_prefix("# ") {
+                Some(title) if checklist.title.is_none() && list_description.is_empty() => {
+                    checklist.title = Some(title.trim().to_string());
+                }
+                _ => list_description.push(trimmed.to_string()),
+            }
+            continue;
+        }
+        if trimmed.is_empty() {
+            if let Some(lines) = descriptions.last_mut() {
+                lines.push(String::new());
+            }
+            continue;
+        }
+        // text belongs to the innermost item it is indented under; text
+        // outside every item ends the list and describes the list instead
+        while open
+            .last()
+            .is_some_and(|(open_indent, _)| indent <= *open_indent)
+        {
+            open.pop();
+        }
+        let Some(&(_, owner)) = open.last() else {
+            list_description.push(trimmed.to_string());
+            continue;
+        };
+        let column = checklist.items[owner].content_column;
+        let text: String = line.chars().skip(column.min(indent)).collect();
+        descriptions[owner].push(text.trim_end().to_string());
+    }
+
+    checklist.description = join_paragraphs(list_description);
+    for (item, lines) in checklist.items.iter_mut().zip(descriptions) {
+        item.description = join_paragraphs(lines);
+    }
+    checklist
+}
+
+fn single_line(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn render_task(
+    out: &mut String,
+    task: &TodoTask,
+    depth: usize,
+    children: &HashMap<i32, Vec<&TodoTask>>,
+    rendered: &mut HashSet<i32>,
+) {
+    if !rendered.insert(task.id) {
+        return;
+    }
+    let indent = " ".repeat(depth * INDENT);
+    let mark = if task.completed_at.is_some() {
+        'x'
+    } else {
+        ' '
+    };
+    out.push_str(&format!(
+        "{}- [{}] {}\n",
+        indent,
+        mark,
+        single_line(&task.name)
+    ));
+    if let Some(description) = task.description.as_deref().map(str::trim_end) {
+        if !description.trim().is_empty() {
+            // a blank line keeps the paragraph out of the item's own text
+            out.push('\n');
+            let paragraph_indent = " ".repeat((depth + 1) * INDENT);
+            for line in description.lines() {
+                match line.trim().is_empty() {
+                    true => out.push('\n'),
+                    false => out.push_str(&format!("{}{}\n", paragraph_indent, line)),
+                }
+            }
+            out.push('\n');
+        }
+    }
+    for child in children.get(&task.id).into_iter().flatten() {
+        render_task(out, child, depth + 1, children, rendered);
+    }
+}
+
+/// Subtasks are nested under their parents. Tasks whose parent is outside
+/// the list, or that sit in a parent cycle, are listed at the top level.
+pub(crate) fn render_checklist(list: &TodoList, tasks: &[TodoTask]) -> String {
+    let ids: HashSet<i32> = tasks.iter().map(|task| task.id).collect();
+    let mut children: HashMap<i32, Vec<&TodoTask>> = HashMap::new();
+    let mut roots = Vec::new();
+    for task in tasks {
+        match task.parent_task_id.filter(|parent| ids.contains(parent)) {
+            Some(parent) => children.entry(parent).or_default().push(task),
+            None => roots.push(task),
+        }
+    }
+
+    let mut out = format!("# {}\n\n", single_line(&list.name));
+    if !list.description.trim().is_empty() {
+        out.push_str(list.description.trim());
+        out.push_str("\n\n");
+    }
+    let mut rendered = HashSet::new();
+    for task in roots {
+        render_task(&mut out, task, 0, &children, &mut rendered);
+    }
+    for task in tasks {
+        render_task(&mut out, task, 0, &children, &mut rendered);
+    }
+    out
+}
@@ -0,0 +1,113 @@
+use crate::models::task_reminder::ReminderChannel;
+use crate::models::todo_task::TodoTask;
+use crate::schema::users;
+use crate::utils::events::record_list_event;
+use crate::utils::inbox::db_push_notifications;
+use crate::utils::mailer::{MailError, Mailer};
+use derive_more::{Display, From};
+use diesel::prelude::*;
+use serde::Serialize;
+use std::sync::Arc;
+
+#[derive(Debug, Display, From)]
+pub(crate) enum NotifyError {
+    Database(diesel::result::Error),
+    Mail(MailError),
+}
+
+/// Something to tell one user about one task.
+#[derive(Debug, Serialize)]
+pub(crate) struct Notice<'a> {
+    pub user_id: i32,
+    pub kind: &'a str,
+    pub task: &'a TodoTask,
+    pub title: String,
+    pub body: String,
+}
+
+/// A way of reaching a user. Notifiers run inside the caller's transaction,
+/// so database side effects are rolled back if the caller fails later on.
+pub(crate) trait Notifier: Send + Sync {
+    fn notify(&self, conn: &mut PgConnection, notice: &Notice) -> Result<(), NotifyError>;
+}
+
+/// Drops the notice into the user's notification inbox.
+pub(crate) struct InAppNotifier;
+
+impl Notifier for InAppNotifier {
+    fn notify(&self, conn: &mut PgConnection, notice: &Notice) -> Result<(), NotifyError> {
+        db_push_notifications(
+            conn,
+            None,
+            &[notice.user_id],
+            notice.kind,
+            Some(notice.task.id),
+            Some(notice.task.todolist_id),
+            &notice.title,
+            &notice.body,
+        )?;
+        Ok(())
+    }
+}
+
+pub(crate) struct EmailNotifier {
+    mailer: Arc<Mailer>,
+}
+
+impl Notifier for EmailNotifier {
+    fn notify(&self, conn: &mut PgConnection, notice: &Notice) -> Result<(), NotifyError> {
+        let email = users::table
+            .find(notice.user_id)
+            .select(users::email)
+            .first::<String>(conn)?;
+        self.mailer
+            .send(&email, &notice.title, notice.body.clone())?;
+        Ok(())
+    }
+}
+
+/// Logs the notice as an event addressed to the user alone; their webhooks
+/// subscribed to `notice.kind` pick it up from there, with the usual signing
+/// and retries.
+pub(crate) struct WebhookNotifier;
+
+impl Notifier for WebhookNotifier {
+    fn notify(&self, conn: &mut PgConnection, notice: &Notice) -> Result<(), NotifyError> {
+        record_list_event(
+            conn,
+            notice.kind,
+            notice,
+            &[notice.task.todolist_id],
+            &[notice.user_id],
+        )?;
+        Ok(())
+    }
+}
+
+pub(crate) struct Notifiers {
+    in_app: InAppNotifier,
+    email: Option<EmailNotifier>,
+    webhook: WebhookNotifier,
+}
+
+impl Notifiers {
+    pub(crate) fn new(mailer: Option<Arc<Mailer>>) -> Self {
+        Notifiers {
+            in_app: InAppNotifier,
+            email: mailer.map(|mailer| EmailNotifier { mailer }),
+            webhook: WebhookNotifier,
+        }
+    }
+
+    /// Email falls back to the inbox when no SMTP relay is configured.
+    pub(crate) fn for_channel(&self, channel: ReminderChannel) -> &dyn Notifier {
+        match channel {
+            ReminderChannel::InApp => &self.in_app,
+            ReminderChannel::Email => match &self.email {
+                Some(email) => email,
+                None => &self.in_app,
+            },
+            ReminderChannel::Webhook => &self.webhook,
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use crate::models::oidc::IdTokenClaims;
+use crate::utils::{config, token};
+use data_encoding::BASE64URL_NOPAD;
+use derive_more::{Display, From};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{DecodingKey, Validation};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Display, From)]
+pub(crate) enum OidcError {
+    Http(reqwest::Error),
+    Token(jsonwebtoken::errors::Error),
+    /// The provider answered, but not with something we can accept.
+    #[from(ignore)]
+    Provider(String),
+}
+
+/// The parts of the discovery document the code flow needs.
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+}
+
+/// Talks to the configured identity provider. Discovery is fetched once and
+/// kept; the signing keys are fetched again whenever a token names a key we
+/// don't have, which is how providers roll their keys over.
+pub struct OidcProvider {
+    client: reqwest::Client,
+    issuer: String,
+    discovery_url: String,
+    jwks_url: Option<String>,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_uri: String,
+    scopes: String,
+    metadata: RwLock<Option<ProviderMetadata>>,
+    jwks: RwLock<Option<JwkSet>>,
+}
+
+/// A random PKCE verifier; 64 hex characters is within the allowed length.
+pub fn generate_code_verifier() -> String {
+    token::generate_token()
+}
+
+/// The S256 challenge sent with the authorization request.
+pub fn code_challenge(code_verifier: &str) -> String {
+    BASE64URL_NOPAD.encode(&Sha256::digest(code_verifier.as_bytes()))
+}
+
+impl OidcProvider {
+    async fn metadata(&self) -> Result<ProviderMetadata, OidcError> {
+        if let Some(metadata) = self.metadata.read().await.as_ref() {
+            return Ok(metadata.clone());
+        }
+        let metadata: ProviderMetadata = self
+            .client
+            .get(&self.discovery_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if metadata.issuer.trim_end_matches('/') != self.issuer {
+            return Err(OidcError::Provider(format!(
+                "discovery names issuer {} instead of {}",
+                metadata.issuer, self.issuer
+            )));
+        }
+        *self.metadata.write().await = Some(metadata.clone());
+        Ok(metadata)
+    }
+
+    async fn fetch_jwks(&self) -> Result<JwkSet, OidcError> {
+        let jwks_url = match &self.jwks_url {
+            Some(url) => url.clone(),
+            None => self.metadata().await?.jwks_uri,
+        };
+        let jwks: JwkSet = self
+            .client
+            .get(&jwks_url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        *self.jwks.write().await = Some(jwks.clone());
+        Ok(jwks)
+    }
+
+    async fn decoding_key(&self, kid: &str) -> Result<DecodingKey, OidcError> {
+        let cached = self
+            .jwks
+            .read()
+            .await
+            .as_ref()
+            .and_then(|jwks| jwks.find(kid).map(DecodingKey::from_jwk));
+        if let Some(key) = cached {
+            return Ok(key?);
+        }
+        let jwks = self.fetch_jwks().await?;
+        let jwk = jwks
+            .find(kid)
+            .ok_or_else(|| OidcError::Provider(format!("no signing key {}", kid)))?;
+        Ok(DecodingKey::from_jwk(jwk)?)
+    }
+
+    pub(crate) async fn authorization_url(
+        &self,
+        state: &str,
+        nonce: &str,
+        code_verifier: &str,
+    ) -> Result<String, OidcError> {
+        let metadata = self.metadata().await?;
+        let mut url = reqwest::Url::parse(&metadata.authorization_endpoint)
+            .map_err(|e| OidcError::Provider(format!("invalid authorization endpoint: {}", e)))?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_uri)
+            .append_pair("scope", &self.scopes)
+            .append_pair("state", state)
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", &code_challenge(code_verifier))
+            .append_pair("code_challenge_method", "S256");
+        Ok(url.to_string())
+    }
+
+    /// Redeems the authorization code and returns the verified ID token
+    /// claims. The issuer, audience, expiry and nonce are all checked here.
+    pub(crate) async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let metadata = self.metadata().await?;
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &self.redirect_uri),
+            ("client_id", &self.client_id),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(secret) = &self.client_secret {
+            form.push(("client_secret", secret));
+        }
+        let response: TokenResponse = self
+            .client
+            .post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let id_token = response
+            .id_token
+            .ok_or_else(|| OidcError::Provider(String::from("no id_token in token response")))?;
+
+        let header = jsonwebtoken::decode_header(&id_token)?;
+        let kid = header
+            .kid
+            .ok_or_else(|| OidcError::Provider(String::from("ID token names no key")))?;
+        let key = self.decoding_key(&kid).await?;
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        let claims = jsonwebtoken::decode::<IdTokenClaims>(&id_token, &key, &validation)?.claims;
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(OidcError::Provider(String::from("ID token nonce mismatch")));
+        }
+        Ok(claims)
+    }
+}
+
+/// `None` when no issuer or client id is configured.
+pub(crate) fn get_oidc_provider() -> Option<OidcProvider> {
+    let issuer = config::get_oidc_issuer()?;
+    let client_id = config::get_oidc_client_id()?;
+    let discovery_url = config::get_oidc_discovery_url()
+        .unwrap_or_else(|| format!("{}/.well-known/openid-configuration", issuer));
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .expect("Failed to build OIDC HTTP client");
+    Some(OidcProvider {
+        client,
+        issuer,
+        discovery_url,
+        jwks_url: config::get_oidc_jwks_url(),
+        client_id,
+        client_secret: config::get_oidc_client_secret(),
+        redirect_uri: config::get_oidc_redirect_uri(),
+        scopes: config::get_oidc_scopes(),
+        metadata: RwLock::new(None),
+        jwks: RwLock::new(None),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::testing::HttpStub;
+    use jsonwebtoken::{EncodingKey, Header};
+
+    const ISSUER: &str = "https://id.example.com";
+    const CLIENT_ID: &str = "todoer";
+    const SIGNING_SECRET: &[u8] = b"a signing secret for the tests";
+
+    fn provider(stub: &HttpStub) -> OidcProvider {
+        OidcProvider {
+            client: reqwest::Client::new(),
+            issuer: ISSUER.to_string(),
+            discovery_url: stub.url("/.well-known/openid-configuration"),
+            jwks_url: None,
+            client_id: CLIENT_ID.to_string(),
+            client_secret: None,
+            redirect_uri: String::from("https://app.example.com/callback"),
+            scopes: String::from("openid email profile"),
+            metadata: RwLock::new(Some(ProviderMetadata {
+                issuer: ISSUER.to_string(),
+                authorization_endpoint: format!("{}/authorize", ISSUER),
+                token_endpoint: stub.url("/token"),
+                jwks_uri: stub.url("/jwks"),
+            })),
+            jwks: RwLock::new(None),
+        }
+    }
+
+    /// A token endpoint handing out an ID token carrying `nonce`, and the
+    /// keys to check it with.
+    fn identity_provider(nonce: Option<&str>) -> HttpStub {
+        let claims = serde_json::json!({
+            "iss": ISSUER,
+            "aud": CLIENT_ID,
+            "sub": "subject-1",
+            "exp": chrono::Utc::now().timestamp() + 300,
+            "nonce": nonce,
+            "email": "someone@example.com",
+            "email_verified": true,
+        });
+        let mut header = Header::new(jsonwebtoken::Algorithm::HS256);
+        header.kid = Some(String::from("key-1"));
+        let id_token =
+            jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(SIGNING_SECRET))
+                .unwrap();
+        let jwks = serde_json::json!({
+            "keys": [{
+                "kty": "oct",
+                "kid": "key-1",
+                "alg": "HS256",
+                "k": BASE64URL_NOPAD.encode(SIGNING_SECRET),
+            }],
+        });
+        HttpStub::start(vec![
+            (
+                "/token",
+                200,
+                serde_json::json!({ "id_token": id_token }).to_string(),
+            ),
+            ("/jwks", 200, jwks.to_string()),
+        ])
+    }
+
+    #[test]
+    fn code_challenge_is_s256() {
+        // RFC 7636, appendix B
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn code_verifiers_are_fresh_and_within_bounds() {
+        let verifier = generate_code_verifier();
+        assert!((43..=128).contains(&verifier.len()));
+        assert_ne!(verifier, generate_code_verifier());
+    }
+
+    #[actix_web::test]
+    async fn authorization_url_carries_state_nonce_and_challenge() {
+        let stub = HttpStub::start(Vec::new());
+        let url = provider(&stub)
+            .authorization_url("the-state", "the-nonce", "the-verifier")
+            .await
+            .unwrap();
+        let url = reqwest::Url::parse(&url).unwrap();
+        let query: std::collections::HashMap<String, String> =
+            url.query_pairs().into_owned().collect();
+        assert_eq!(query["state"], "the-state");
+        assert_eq!(query["nonce"], "the-nonce");
+        assert_eq!(query["code_challenge"], code_challenge("the-verifier"));
+        assert_eq!(query["code_challenge_method"], "S256");
+        assert_eq!(query["client_id"], CLIENT_ID);
+        assert!(!query.values().any(|value| value == "the-verifier"));
+    }
+
+    #[actix_web::test]
+    async fn code_exchange_sends_the_verifier_and_checks_the_nonce() {
+        let stub = identity_provider(Some("the-nonce"));
+        let claims = provider(&stub)
+            .exchange_code("the-code", "the-verifier", "the-nonce")
+            .await
+            .unwrap();
+        assert_eq!(claims.sub, "subject-1");
+        assert!(claims.email_verified);
+
+        let token_request = stub
+            .received()
+            .into_iter()
+            .find(|request| request.path == "/token")
+            .unwrap();
+        assert_eq!(token_request.method, "POST");
+        assert_eq!(
+            token_request.header("content-type"),
+            Some("application/x-www-form-urlencoded")
+        );
+        assert!(token_request.body.contains("code=the-code"));
+        assert!(token_request.body.contains("code_verifier=the-verifier"));
+    }
+
+    #[actix_web::test]
+    async fn code_exchange_refuses_another_nonce() {
+        let stub = identity_provider(Some("another-nonce"));
+        let result = provider(&stub)
+            .exchange_code("the-code", "the-verifier", "the-nonce")
+            .await;
+        assert!(matches!(result, Err(OidcError::Provider(_))));
+    }
+
+    #[actix_web::test]
+    async fn code_exchange_refuses_a_missing_nonce() {
+        let stub = identity_provider(None);
+        let result = provider(&stub)
+            .exchange_code("the-code", "the-verifier", "the-nonce")
+            .await;
+        assert!(matches!(result, Err(OidcError::Provider(_))));
+    }
+}
@@ -0,0 +1,523 @@
+use crate::models::reminder_preference::{ReminderPreference, MAX_WINDOW_HOURS};
+use crate::models::sent_reminder::{NewSentReminder, SentReminder};
+use crate::models::task_reminder::TaskReminder;
+use crate::models::todo_task::TodoTask;
+use crate::schema::{
+    list_members, reminder_preferences, sent_reminders, task_assignees, task_reminders, todolists,
+    todotasks, users,
+};
+use crate::utils::config;
+use crate::utils::database::access::{db_check_task_participant, AccessError};
+use crate::utils::database::connection::Pool;
+use crate::utils::mailer::Mailer;
+use crate::utils::notifier::{Notice, Notifiers, NotifyError};
+use actix_web::web;
+use chrono::{Days, NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Timestamp};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The widest reminder window users may pick, in days.
+const MAX_WINDOW_DAYS: u64 = MAX_WINDOW_HOURS as u64 / 24;
+
+/// Task reminders are set to the minute, so they are polled far more often
+/// than due-date digests.
+const TASK_REMINDER_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+struct DueTask {
+    id: i32,
+    name: String,
+    list_name: String,
+    due_date: NaiveDate,
+}
+
+struct Recipient {
+    email: String,
+    digest: bool,
+    tasks: Vec<DueTask>,
+}
+
+/// A task counts as due at the start of its due date.
+fn hours_until_due(now: NaiveDateTime, due_date: NaiveDate) -> i64 {
+    (due_date.and_time(chrono::NaiveTime::MIN) - now).num_hours()
+}
+
+/// Every (task, user) pair whose reminder window has opened, skipping users
+/// who opted out and those no longer on the task's list.
+fn db_find_due_reminders(
+    conn: &mut PgConnection,
+    now: NaiveDateTime,
+) -> Result<Vec<(DueTask, i32)>, diesel::result::Error> {
+    let today = now.date();
+    let tasks = todotasks::table
+        .inner_join(todolists::table.on(todolists::id.eq(todotasks::todolist_id)))
+        .filter(todotasks::completed_at.is_null())
+        .filter(todotasks::due_date.ge(today))
+        .filter(todotasks::due_date.le(today + Days::new(MAX_WINDOW_DAYS)))
+        .select((
+            todotasks::id,
+            todotasks::user_id,
+            todotasks::todolist_id,
+            todotasks::name,
+            todolists::name,
+            todotasks::due_date.assume_not_null(),
+        ))
+        .load::<(i32, i32, i32, String, String, NaiveDate)>(conn)?;
+    let task_ids: Vec<i32> = tasks.iter().map(|task| task.0).collect();
+
+    let mut assignees: HashMap<i32, Vec<i32>> = HashMap::new();
+    for (task_id, user_id) in task_assignees::table
+        .filter(task_assignees::task_id.eq_any(&task_ids))
+        .select((task_assignees::task_id, task_assignees::user_id))
+        .load::<(i32, i32)>(conn)?
+    {
+        assignees.entry(task_id).or_default().push(user_id);
+    }
+    let list_ids: Vec<i32> = tasks.iter().map(|task| task.2).collect();
+    let members: Vec<(i32, i32)> = list_members::table
+        .filter(list_members::list_id.eq_any(&list_ids))
+        .filter(list_members::accepted_at.is_not_null())
+        .select((list_members::list_id, list_members::user_id))
+        .load(conn)?;
+    let preferences: HashMap<i32, ReminderPreference> = reminder_preferences::table
+        .load::<ReminderPreference>(conn)?
+        .into_iter()
+        .map(|preference| (preference.user_id, preference))
+        .collect();
+
+    let mut due = Vec::new();
+    for (task_id, creator_id, list_id, name, list_name, due_date) in tasks {
+        let recipients = assignees
+            .remove(&task_id)
+            .unwrap_or_else(|| vec![creator_id]);
+        for user_id in recipients {
+            if !members.contains(&(list_id, user_id)) {
+                continue;
+            }
+            let preference = preferences
+                .get(&user_id)
+                .map_or_else(|| ReminderPreference::default_for(user_id), |p| p.clone());
+            if !preference.email_enabled
+                || hours_until_due(now, due_date) > i64::from(preference.window_hours)
+            {
+                continue;
+            }
+            let task = DueTask {
+                id: task_id,
+                name: name.clone(),
+                list_name: list_name.clone(),
+                due_date,
+            };
+            due.push((task, user_id));
+        }
+    }
+    Ok(due)
+}
+
+/// Records the reminders about to go out and drops any already recorded, so
+/// that overlapping runs never send the same reminder twice.
+fn db_claim_reminders(
+    conn: &mut PgConnection,
+    now: NaiveDateTime,
+    due: Vec<(DueTask, i32)>,
+) -> Result<BTreeMap<i32, Recipient>, diesel::result::Error> {
+    let rows: Vec<NewSentReminder> = due
+        .iter()
+        .map(|(task, user_id)| NewSentReminder {
+            task_id: task.id,
+            user_id: *user_id,
+            due_date: task.due_date,
+            sent_at: now,
+        })
+        .collect();
+    let claimed: Vec<SentReminder> = diesel::insert_into(sent_reminders::table)
+        .values(&rows)
+        .on_conflict_do_nothing()
+        .get_results(conn)?;
+
+    let recipient_ids: Vec<i32> = claimed.iter().map(|row| row.user_id).collect();
+    let emails: HashMap<i32, String> = users::table
+        .filter(users::id.eq_any(&recipient_ids))
+        .select((users::id, users::email))
+        .load::<(i32, String)>(conn)?
+        .into_iter()
+        .collect();
+    let digests: HashMap<i32, bool> = reminder_preferences::table
+        .filter(reminder_preferences::user_id.eq_any(&recipient_ids))
+        .select((reminder_preferences::user_id, reminder_preferences::digest))
+        .load::<(i32, bool)>(conn)?
+        .into_iter()
+        .collect();
+
+    let mut recipients: BTreeMap<i32, Recipient> = BTreeMap::new();
+    for (task, user_id) in due {
+        let was_claimed = claimed
+            .iter()
+            .any(|row| row.task_id == task.id && row.user_id == user_id);
+        let Some(email) = emails.get(&user_id).filter(|_| was_claimed) else {
+            continue;
+        };
+        recipients
+            .entry(user_id)
+            .or_insert_with(|| Recipient {
+                email: email.clone(),
+                digest: digests.get(&user_id).copied().unwrap_or(true),
+                tasks: Vec::new(),
+            })
+            .tasks
+            .push(task);
+    }
+    Ok(recipients)
+}
+
+fn describe(task: &DueTask) -> String {
+    format!(
+        "{} (in {}) is due on {}",
+        task.name, task.list_name, task.due_date
+    )
+}
+
+fn send_reminders(mailer: &Mailer, recipient: &Recipient) -> Result<(), String> {
+    if recipient.digest {
+        let subject = match recipient.tasks.len() {
+            1 => String::from("1 task is due soon"),
+            n => format!("{} tasks are due soon", n),
+        };
+        let body: Vec<String> = recipient
+            .tasks
+            .iter()
+            .map(|task| format!("- {}", describe(task)))
+            .collect();
+        return mailer
+            .send(&recipient.email, &subject, body.join("\n"))
+            .map_err(|e| e.to_string());
+    }
+    for task in &recipient.tasks {
+        let subject = format!("Reminder: {}", task.name);
+        mailer
+            .send(&recipient.email, &subject, describe(task))
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// One scheduler tick. A user whose mail could not be sent has their claims
+/// released so the next tick retries them.
+fn db_run_due_reminders(
+    conn: &mut PgConnection,
+    mailer: &Mailer,
+    now: NaiveDateTime,
+) -> Result<usize, diesel::result::Error> {
+    let due = db_find_due_reminders(conn, now)?;
+    if due.is_empty() {
+        return Ok(0);
+    }
+    let recipients = conn.transaction(|conn| db_claim_reminders(conn, now, due))?;
+    let mut sent = 0;
+    for (user_id, recipient) in recipients {
+        match send_reminders(mailer, &recipient) {
+            Ok(()) => sent += recipient.tasks.len(),
+            Err(e) => {
+                eprintln!("Failed to email reminders to user {}: {}", user_id, e);
+                let task_ids: Vec<i32> = recipient.tasks.iter().map(|task| task.id).collect();
+                diesel::delete(
+                    sent_reminders::table
+                        .filter(sent_reminders::user_id.eq(user_id))
+                        .filter(sent_reminders::task_id.eq_any(task_ids))
+                        .filter(sent_reminders::sent_at.eq(now)),
+                )
+                .execute(conn)?;
+            }
+        }
+    }
+    Ok(sent)
+}
+
+fn run_due_reminders(
+    pool: web::Data<Pool>,
+    mailer: &Mailer,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    db_run_due_reminders(&mut conn, mailer, chrono::Local::now().naive_local())
+}
+
+/// Does nothing unless an SMTP relay is configured.
+pub(crate) fn start_reminder_scheduler(pool: Pool, mailer: Option<Arc<Mailer>>) {
+    let Some(mailer) = mailer else {
+        return;
+    };
+    let pool = web::Data::new(pool);
+    let period = Duration::from_secs(config::get_reminder_interval_secs());
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(period);
+        loop {
+            interval.tick().await;
+            let pool = pool.clone();
+            let mailer = mailer.clone();
+            match web::block(move || run_due_reminders(pool, &mailer)).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => eprintln!("Failed to send due reminders: {}", e),
+                Err(e) => eprintln!("Reminder scheduler failed: {}", e),
+            }
+        }
+    });
+}
+
+fn describe_reminder(task: &TodoTask) -> String {
+    match task.due_date {
+        Some(due_date) => format!("{} is due on {}", task.name, due_date),
+        None => task.name.clone(),
+    }
+}
+
+/// Marks the reminder sent and hands it to its notifier in one transaction,
+/// so a notifier failure leaves it pending for the next poll. Reminders of
+/// users who have since lost access to the task are dropped unsent.
+fn db_fire_task_reminder(
+    conn: &mut PgConnection,
+    notifiers: &Notifiers,
+    reminder: &TaskReminder,
+    task: &TodoTask,
+    now: NaiveDateTime,
+) -> Result<bool, NotifyError> {
+    conn.transaction(|conn| {
+        let claimed = diesel::update(task_reminders::table.find(reminder.id))
+            .filter(task_reminders::sent_at.is_null())
+            .set(task_reminders::sent_at.eq(now))
+            .execute(conn)?;
+        if claimed == 0 {
+            return Ok(false);
+        }
+        match db_check_task_participant(conn, task.id, reminder.user_id) {
+            Ok(()) => {}
+            Err(AccessError::Forbidden) => return Ok(false),
+            Err(AccessError::Database(e)) => return Err(e.into()),
+        }
+        let notice = Notice {
+            user_id: reminder.user_id,
+            kind: "task.reminder",
+            task,
+            title: format!("Reminder: {}", task.name),
+            body: describe_reminder(task),
+        };
+        notifiers
+            .for_channel(reminder.channel)
+            .notify(conn, &notice)?;
+        Ok(true)
+    })
+}
+
+/// Unsent reminders on open tasks whose time has come. A reminder fires at
+/// `snoozed_until` once snoozed, else at `remind_at` or `minutes_before_due`
+/// ahead of the start of its task's due date; relative reminders on tasks
+/// without a due date never fire.
+fn db_pending_task_reminders(
+    conn: &mut PgConnection,
+    now: NaiveDateTime,
+) -> Result<Vec<(TaskReminder, TodoTask)>, diesel::result::Error> {
+    let set_time_passed = diesel::dsl::sql::<Bool>(
+        "COALESCE(task_reminders.snoozed_until, task_reminders.remind_at) <= ",
+    )
+    .bind::<Timestamp, _>(now);
+    let due_time_passed = diesel::dsl::sql::<Bool>(
+        "task_reminders.snoozed_until IS NULL AND todotasks.due_date \
+         - make_interval(mins => task_reminders.minutes_before_due) <= ",
+    )
+    .bind::<Timestamp, _>(now);
+    task_reminders::table
+        .inner_join(todotasks::table)
+        .filter(task_reminders::sent_at.is_null())
+        .filter(todotasks::completed_at.is_null())
+        .filter(set_time_passed.or(due_time_passed))
+        .select((task_reminders::all_columns, todotasks::all_columns))
+        .load::<(TaskReminder, TodoTask)>(conn)
+}
+
+fn run_task_reminders(
+    pool: web::Data<Pool>,
+    notifiers: &Notifiers,
+) -> Result<usize, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    let now = chrono::Local::now().naive_local();
+    let mut fired = 0;
+    for (reminder, task) in db_pending_task_reminders(&mut conn, now)? {
+        match db_fire_task_reminder(&mut conn, notifiers, &reminder, &task, now) {
+            Ok(true) => fired += 1,
+            Ok(false) => {}
+            Err(e) => eprintln!("Failed to deliver task reminder {}: {}", reminder.id, e),
+        }
+    }
+    Ok(fired)
+}
+
+pub(crate) fn start_task_reminder_scheduler(pool: Pool, notifiers: Arc<Notifiers>) {
+    let pool = web::Data::new(pool);
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(TASK_REMINDER_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let pool = pool.clone();
+            let notifiers = notifiers.clone();
+            match web::block(move || run_task_reminders(pool, &notifiers)).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => eprintln!("Failed to send task reminders: {}", e),
+                Err(e) => eprintln!("Task reminder scheduler failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::todo_task::NewTodoTask;
+    use crate::utils::testing::{db_insert_list, db_insert_user, test_connection, SmtpSink};
+
+    /// Far enough ahead that no other task in the database is due.
+    fn noon() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2100, 1, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    }
+
+    fn db_insert_task(
+        conn: &mut PgConnection,
+        user_id: i32,
+        list_id: i32,
+        name: &str,
+        due_date: NaiveDate,
+        completed_at: Option<NaiveDateTime>,
+    ) -> i32 {
+        diesel::insert_into(todotasks::table)
+            .values(&NewTodoTask {
+                user_id,
+                todolist_id: list_id,
+                name: name.to_string(),
+                description: None,
+                parent_task_id: None,
+                due_date: Some(due_date),
+                created_at: noon(),
+                modified_at: noon(),
+                priority: None,
+                completed_at,
+            })
+            .returning(todotasks::id)
+            .get_result(conn)
+            .unwrap()
+    }
+
+    fn due_task(name: &str) -> DueTask {
+        DueTask {
+            id: 0,
+            name: name.to_string(),
+            list_name: String::from("Chores"),
+            due_date: noon().date(),
+        }
+    }
+
+    #[test]
+    fn digests_send_one_mail() {
+        let sink = SmtpSink::start(false);
+        let recipient = Recipient {
+            email: String::from("someone@example.com"),
+            digest: true,
+            tasks: vec![due_task("Water plants"), due_task("Pay rent")],
+        };
+        send_reminders(&Mailer::local(sink.port), &recipient).unwrap();
+        let received = sink.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].recipients, vec![recipient.email]);
+        assert!(received[0].data.contains("Subject: 2 tasks are due soon"));
+        assert!(received[0]
+            .data
+            .contains("- Water plants (in Chores) is due on 2100-01-01"));
+        assert!(received[0]
+            .data
+            .contains("- Pay rent (in Chores) is due on 2100-01-01"));
+    }
+
+    #[test]
+    fn single_reminders_send_a_mail_per_task() {
+        let sink = SmtpSink::start(false);
+        let recipient = Recipient {
+            email: String::from("someone@example.com"),
+            digest: false,
+            tasks: vec![due_task("Water plants"), due_task("Pay rent")],
+        };
+        send_reminders(&Mailer::local(sink.port), &recipient).unwrap();
+        let subjects: Vec<bool> = sink
+            .received()
+            .iter()
+            .map(|mail| mail.data.contains("Subject: Reminder: "))
+            .collect();
+        assert_eq!(subjects, vec![true, true]);
+    }
+
+    #[test]
+    fn refused_mail_is_an_error() {
+        let sink = SmtpSink::start(true);
+        let recipient = Recipient {
+            email: String::from("someone@example.com"),
+            digest: true,
+            tasks: vec![due_task("Water plants")],
+        };
+        assert!(send_reminders(&Mailer::local(sink.port), &recipient).is_err());
+        assert!(sink.received().is_empty());
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn due_tasks_are_mailed_once_and_completed_ones_never() {
+        let mut conn = test_connection();
+        let sink = SmtpSink::start(false);
+        let mailer = Mailer::local(sink.port);
+        let user = db_insert_user(&mut conn, true);
+        let list = db_insert_list(&mut conn, user.id);
+        let tomorrow = noon().date() + Days::new(1);
+        db_insert_task(&mut conn, user.id, list.id, "Open", tomorrow, None);
+        db_insert_task(&mut conn, user.id, list.id, "Done", tomorrow, Some(noon()));
+        db_insert_task(
+            &mut conn,
+            user.id,
+            list.id,
+            "Later",
+            tomorrow + Days::new(7),
+            None,
+        );
+
+        assert_eq!(db_run_due_reminders(&mut conn, &mailer, noon()).unwrap(), 1);
+        assert_eq!(db_run_due_reminders(&mut conn, &mailer, noon()).unwrap(), 0);
+
+        let received = sink.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].recipients, vec![user.email]);
+        assert!(received[0].data.contains("Open (in Test)"));
+        assert!(!received[0].data.contains("Done"));
+        assert!(!received[0].data.contains("Later"));
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn reminders_that_failed_to_send_are_retried() {
+        let mut conn = test_connection();
+        let user = db_insert_user(&mut conn, true);
+        let list = db_insert_list(&mut conn, user.id);
+        let tomorrow = noon().date() + Days::new(1);
+        db_insert_task(&mut conn, user.id, list.id, "Open", tomorrow, None);
+
+        let refusing = SmtpSink::start(true);
+        assert_eq!(
+            db_run_due_reminders(&mut conn, &Mailer::local(refusing.port), noon()).unwrap(),
+            0
+        );
+        let sink = SmtpSink::start(false);
+        assert_eq!(
+            db_run_due_reminders(&mut conn, &Mailer::local(sink.port), noon()).unwrap(),
+            1
+        );
+        assert_eq!(sink.received().len(), 1);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::models::session::*;
+use crate::schema::{refresh_tokens, sessions};
+use crate::utils::{config, token};
+use actix_web::HttpRequest;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use std::net::SocketAddr;
+
+const MAX_USER_AGENT_CHARS: usize = 512;
+
+/// Recording every request would turn each read into a write, so the
+/// last-seen details are only refreshed this often unless they changed.
+const LAST_SEEN_GRANULARITY_SECS: i64 = 60;
+
+/// The device details shown in the session list.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl ClientInfo {
+    /// Forwarded addresses are only taken from a trusted proxy; anyone else
+    /// could put whatever they like in those headers.
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let user_agent = req
+            .headers()
+            .get(actix_web::http::header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(MAX_USER_AGENT_CHARS).collect());
+        let peer = req.peer_addr().map(|socket| socket.ip());
+        let ip_address = match peer {
+            Some(proxy) if config::get_trusted_proxies().contains(&proxy) => {
+                req.connection_info().realip_remote_addr().map(|addr| {
+                    addr.parse::<SocketAddr>()
+                        .map(|socket| socket.ip().to_string())
+                        .unwrap_or_else(|_| addr.to_string())
+                })
+            }
+            _ => peer.map(|ip| ip.to_string()),
+        };
+        ClientInfo {
+            user_agent,
+            ip_address,
+        }
+    }
+}
+
+fn db_issue_refresh_token(
+    conn: &mut PgConnection,
+    session_id: i32,
+    now: NaiveDateTime,
+) -> Result<String, diesel::result::Error> {
+    let plain_token = token::generate_token();
+    diesel::insert_into(refresh_tokens::table)
+        .values(NewRefreshToken {
+            session_id,
+            token_hash: token::hash_token(&plain_token),
+            created_at: now,
+        })
+        .execute(conn)?;
+    Ok(plain_token)
+}
+
+pub(crate) fn db_start_session(
+    conn: &mut PgConnection,
+    user_id: i32,
+    client: &ClientInfo,
+) -> Result<SessionTokens, diesel::result::Error> {
+    let now = chrono::Local::now().naive_local();
+    let access_token = token::generate_token();
+    conn.transaction(|conn| {
+        let session = diesel::insert_into(sessions::table)
+            .values(NewSession {
+                user_id,
+                access_token_hash: token::hash_token(&access_token),
+                access_expires_at: now
+                    + chrono::Duration::minutes(config::get_access_token_minutes()),
+                expires_at: now + chrono::Duration::days(config::get_refresh_token_days()),
+                user_agent: client.user_agent.clone(),
+                ip_address: client.ip_address.clone(),
+                created_at: now,
+                last_seen_at: now,
+            })
+            .returning(Session::as_returning())
+            .get_result(conn)?;
+        let refresh_token = db_issue_refresh_token(conn, session.id, now)?;
+        Ok(SessionTokens {
+            session_id: session.id,
+            access_token,
+            access_expires_at: session.access_expires_at,
+            refresh_token,
+            expires_at: session.expires_at,
+        })
+    })
+}
+
+/// Swaps a refresh token for a new pair of tokens, extending the session.
+/// A token that was already swapped revokes the whole session, since either
+/// the client or whoever copied it is replaying it. `None` when the refresh
+/// is refused.
+pub(crate) fn db_refresh_session(
+    conn: &mut PgConnection,
+    plain_refresh_token: &str,
+    client: &ClientInfo,
+) -> Result<Option<SessionTokens>, diesel::result::Error> {
+    let now = chrono::Local::now().naive_local();
+    conn.transaction(|conn| {
+        let Some((refresh_token_id, used_at, session)) = refresh_tokens::table
+            .inner_join(sessions::table)
+            .filter(refresh_tokens::token_hash.eq(token::hash_token(plain_refresh_token)))
+            .for_update()
+            .select((
+                refresh_tokens::id,
+                refresh_tokens::used_at,
+                Session::as_select(),
+            ))
+            .first::<(i32, Option<NaiveDateTime>, Session)>(conn)
+            .optional()?
+        else {
+            return Ok(None);
+        };
+        if session.revoked_at.is_some() || session.expires_at <= now {
+            return Ok(None);
+        }
+        if used_at.is_some() {
+            db_revoke_sessions(
+                conn,
+                session.user_id,
+                Some(session.id),
+                RevokedReason::TokenReuse,
+            )?;
+            return Ok(None);
+        }
+        diesel::update(refresh_tokens::table.find(refresh_token_id))
+            .set(refresh_tokens::used_at.eq(now))
+            .execute(conn)?;
+        let access_token = token::generate_token();
+        let session = diesel::update(sessions::table.find(session.id))
+            .set((
+                sessions::access_token_hash.eq(token::hash_token(&access_token)),
+                sessions::access_expires_at
+                    .eq(now + chrono::Duration::minutes(config::get_access_token_minutes())),
+                sessions::expires_at
+                    .eq(now + chrono::Duration::days(config::get_refresh_token_days())),
+                sessions::user_agent.eq(&client.user_agent),
+                sessions::ip_address.eq(&client.ip_address),
+                sessions::last_seen_at.eq(now),
+            ))
+            .returning(Session::as_returning())
+            .get_result(conn)?;
+        let refresh_token = db_issue_refresh_token(conn, session.id, now)?;
+        Ok(Some(SessionTokens {
+            session_id: session.id,
+            access_token,
+            access_expires_at: session.access_expires_at,
+            refresh_token,
+            expires_at: session.expires_at,
+        }))
+    })
+}
+
+/// The session an unexpired access token belongs to, noting where it was
+/// last used from.
+pub(crate) fn db_authenticate_access_token(
+    conn: &mut PgConnection,
+    plain_access_token: &str,
+    client: &ClientInfo,
+) -> Result<Option<Session>, diesel::result::Error> {
+    let now = chrono::Local::now().naive_local();
+    let Some(session) = sessions::table
+        .filter(sessions::access_token_hash.eq(token::hash_token(plain_access_token)))
+        .filter(sessions::revoked_at.is_null())
+        .filter(sessions::access_expires_at.gt(now))
+        .select(Session::as_select())
+        .first(conn)
+        .optional()?
+    else {
+        return Ok(None);
+    };
+    let stale = now - session.last_seen_at >= chrono::Duration::seconds(LAST_SEEN_GRANULARITY_SECS);
+    let moved = session.user_agent != client.user_agent || session.ip_address != client.ip_address;
+    if stale || moved {
+        diesel::update(sessions::table.find(session.id))
+            .set((
+                sessions::user_agent.eq(&client.user_agent),
+                sessions::ip_address.eq(&client.ip_address),
+                sessions::last_seen_at.eq(now),
+            ))
+            .execute(conn)?;
+    }
+    Ok(Some(session))
+}
+
+/// Revokes one of the user's sessions, or all of them when `session_id` is
+/// `None`. Returns how many were still active.
+pub(crate) fn db_revoke_sessions(
+    conn: &mut PgConnection,
+    user_id: i32,
+    session_id: Option<i32>,
+    reason: RevokedReason,
+) -> Result<usize, diesel::result::Error> {
+    let mut query = diesel::update(sessions::table)
+        .filter(sessions::user_id.eq(user_id))
+        .filter(sessions::revoked_at.is_null())
+        .into_boxed();
+    if let Some(session_id) = session_id {
+        query = query.filter(sessions::id.eq(session_id));
+    }
+    query
+        .set((
+            sessions::revoked_at.eq(chrono::Local::now().naive_local()),
+            sessions::revoked_reason.eq(reason),
+        ))
+        .execute(conn)
+}
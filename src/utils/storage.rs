@@ -0,0 +1,69 @@
+use crate::utils::config;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Blob store for attachment contents. Blobs are addressed by the hex SHA-256
+/// of their bytes, so identical uploads share a single stored copy.
+pub trait AttachmentStorage: Send + Sync {
+    fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()>;
+    fn get(&self, key: &str) -> io::Result<Vec<u8>>;
+    fn delete(&self, key: &str) -> io::Result<()>;
+    fn exists(&self, key: &str) -> io::Result<bool>;
+}
+
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+        Ok(LocalStorage { root })
+    }
+
+    fn path_for(&self, key: &str) -> io::Result<PathBuf> {
+        if key.len() < 3 || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "storage key must be a hex digest",
+            ));
+        }
+        Ok(self.root.join(&key[..2]).join(key))
+    }
+}
+
+impl AttachmentStorage for LocalStorage {
+    fn put(&self, key: &str, bytes: &[u8]) -> io::Result<()> {
+        let path = self.path_for(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // write to a temporary file first so readers never observe a partial blob
+        let tmp_path = path.with_extension("tmp");
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(tmp_path, path)
+    }
+
+    fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.path_for(key)?)
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        match fs::remove_file(self.path_for(key)?) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            other => other,
+        }
+    }
+
+    fn exists(&self, key: &str) -> io::Result<bool> {
+        self.path_for(key)?.try_exists()
+    }
+}
+
+pub(crate) fn get_attachment_storage() -> LocalStorage {
+    LocalStorage::new(config::get_attachments_dir()).expect("Failed to create attachment storage")
+}
@@ -0,0 +1,285 @@
+//! Helpers for unit tests that need a database, a mail server or an HTTP
+//! endpoint.
+
+use crate::models::list_member::{ListRole, NewListMember};
+use crate::models::todo_list::{NewTodoList, TodoList};
+use crate::models::user::{NewUser, User};
+use crate::models::workspace::{NewWorkspace, Workspace};
+use crate::models::workspace_member::{NewWorkspaceMember, WorkspaceRole};
+use crate::schema::{list_members, todolists, users, workspace_members, workspaces};
+use diesel::prelude::*;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+
+/// A connection to `DATABASE_URL` inside a transaction that is never
+/// committed, so tests leave nothing behind. Tests using it are marked
+/// `#[ignore]` and run with `cargo test -- --include-ignored`.
+pub(crate) fn test_connection() -> PgConnection {
+    let database_url =
+        dotenv::var("DATABASE_URL").expect("DATABASE_URL must be set to run database tests");
+    let mut conn =
+        PgConnection::establish(&database_url).expect("Failed to connect to the test database");
+    conn.begin_test_transaction()
+        .expect("Failed to begin test transaction");
+    conn
+}
+
+/// A user with a unique address, verified or not.
+pub(crate) fn db_insert_user(conn: &mut PgConnection, verified: bool) -> User {
+    let now = chrono::Local::now().naive_local();
+    let email = format!(
+        "test-{}@example.com",
+        &crate::utils::token::generate_token()[..16]
+    );
+    let user: User = diesel::insert_into(users::table)
+        .values(&NewUser {
+            first_name: "Test",
+            last_name: "User",
+            email: &email,
+            created_at: now,
+            modified_at: now,
+        })
+        .get_result(conn)
+        .expect("Failed to insert test user");
+    if !verified {
+        return user;
+    }
+    diesel::update(users::table.find(user.id))
+        .set(users::email_verified_at.eq(now))
+        .get_result(conn)
+        .expect("Failed to verify test user")
+}
+
+/// A list owned by `user_id`, alone in a workspace they administer.
+pub(crate) fn db_insert_list(conn: &mut PgConnection, user_id: i32) -> TodoList {
+    let now = chrono::Local::now().naive_local();
+    let workspace: Workspace = diesel::insert_into(workspaces::table)
+        .values(&NewWorkspace {
+            name: String::from("Test"),
+            created_at: now,
+            modified_at: now,
+        })
+        .get_result(conn)
+        .expect("Failed to insert test workspace");
+    diesel::insert_into(workspace_members::table)
+        .values(&NewWorkspaceMember {
+            workspace_id: workspace.id,
+            user_id,
+            role: WorkspaceRole::Admin,
+            created_at: now,
+        })
+        .execute(conn)
+        .expect("Failed to insert test workspace member");
+    let list: TodoList = diesel::insert_into(todolists::table)
+        .values(&NewTodoList {
+            user_id,
+            name: String::from("Test"),
+            description: String::new(),
+            created_at: now,
+            modified_at: now,
+            workspace_id: workspace.id,
+        })
+        .get_result(conn)
+        .expect("Failed to insert test list");
+    diesel::insert_into(list_members::table)
+        .values(&NewListMember {
+            list_id: list.id,
+            user_id,
+            role: ListRole::Owner,
+            invited_by: None,
+            created_at: now,
+            accepted_at: Some(now),
+        })
+        .execute(conn)
+        .expect("Failed to insert test list member");
+    list
+}
+
+/// A message as an SMTP server received it.
+#[derive(Debug, Clone)]
+pub(crate) struct ReceivedMail {
+    pub recipients: Vec<String>,
+    pub data: String,
+}
+
+/// An SMTP server on a free local port that keeps what it is sent.
+pub(crate) struct SmtpSink {
+    pub port: u16,
+    received: Arc<Mutex<Vec<ReceivedMail>>>,
+}
+
+impl SmtpSink {
+    /// With `refuse_recipients`, every recipient is rejected and nothing is
+    /// delivered.
+    pub(crate) fn start(refuse_recipients: bool) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind SMTP sink");
+        let port = listener.local_addr().unwrap().port();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = serve_smtp(stream, refuse_recipients, &sink);
+            }
+        });
+        SmtpSink { port, received }
+    }
+
+    pub(crate) fn received(&self) -> Vec<ReceivedMail> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+fn serve_smtp(
+    stream: TcpStream,
+    refuse_recipients: bool,
+    received: &Mutex<Vec<ReceivedMail>>,
+) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    writer.write_all(b"220 sink ESMTP\r\n")?;
+    let mut recipients = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let command = line.get(..4).unwrap_or_default().to_ascii_uppercase();
+        match command.as_str() {
+            "EHLO" | "HELO" => writer.write_all(b"250 sink\r\n")?,
+            "MAIL" => {
+                recipients.clear();
+                writer.write_all(b"250 OK\r\n")?;
+            }
+            "RCPT" if refuse_recipients => writer.write_all(b"550 No such user\r\n")?,
+            "RCPT" => {
+                let address = line
+                    .split_once('<')
+                    .and_then(|(_, rest)| rest.split_once('>'))
+                    .map_or_else(String::new, |(address, _)| address.to_string());
+                recipients.push(address);
+                writer.write_all(b"250 OK\r\n")?;
+            }
+            "DATA" => {
+                writer.write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")?;
+                let mut data = String::new();
+                loop {
+                    line.clear();
+                    if reader.read_line(&mut line)? == 0 || line == ".\r\n" {
+                        break;
+                    }
+                    data.push_str(&line);
+                }
+                received.lock().unwrap().push(ReceivedMail {
+                    recipients: std::mem::take(&mut recipients),
+                    data,
+                });
+                writer.write_all(b"250 OK\r\n")?;
+            }
+            "QUIT" => {
+                writer.write_all(b"221 Bye\r\n")?;
+                return Ok(());
+            }
+            _ => writer.write_all(b"250 OK\r\n")?,
+        }
+    }
+}
+
+/// A request as an HTTP stub received it. Header names are lowercased.
+#[derive(Debug, Clone)]
+pub(crate) struct ReceivedRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl ReceivedRequest {
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// An HTTP server on a free local port answering each path with a canned
+/// status and JSON body, and 404 for anything else.
+pub(crate) struct HttpStub {
+    pub port: u16,
+    received: Arc<Mutex<Vec<ReceivedRequest>>>,
+}
+
+impl HttpStub {
+    pub(crate) fn start(routes: Vec<(&'static str, u16, String)>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind HTTP stub");
+        let port = listener.local_addr().unwrap().port();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = serve_http(stream, &routes, &log);
+            }
+        });
+        HttpStub { port, received }
+    }
+
+    pub(crate) fn url(&self, path: &str) -> String {
+        format!("http://127.0.0.1:{}{}", self.port, path)
+    }
+
+    pub(crate) fn received(&self) -> Vec<ReceivedRequest> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+fn serve_http(
+    stream: TcpStream,
+    routes: &[(&'static str, u16, String)],
+    received: &Mutex<Vec<ReceivedRequest>>,
+) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line == "\r\n" {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    let length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse::<usize>().ok())
+        .unwrap_or(0);
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body)?;
+    let (status, response) = routes
+        .iter()
+        .find(|(route, _, _)| path.split('?').next() == Some(*route))
+        .map_or((404, String::new()), |(_, status, body)| {
+            (*status, body.clone())
+        });
+    received.lock().unwrap().push(ReceivedRequest {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    });
+    write!(
+        writer,
+        "HTTP/1.1 {} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        response.len(),
+        response
+    )
+}
@@ -0,0 +1,127 @@
+use crate::models::todo_task::is_valid_priority;
+use chrono::NaiveDate;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// One todo.txt line, split into the parts tasks can hold. Anything else,
+/// such as extra projects or unknown `key:value` pairs, stays in the name.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct TodoTxtItem {
+    pub completed: bool,
+    pub completed_on: Option<NaiveDate>,
+    pub priority: Option<String>,
+    pub created_on: Option<NaiveDate>,
+    pub name: String,
+    pub project: Option<String>,
+    pub contexts: Vec<String>,
+    pub due_date: Option<NaiveDate>,
+}
+
+fn parse_date(token: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(token, DATE_FORMAT).ok()
+}
+
+fn parse_priority(token: &str) -> Option<String> {
+    let letter = token.strip_prefix('(')?.strip_suffix(')')?;
+    is_valid_priority(letter).then(|| letter.to_string())
+}
+
+/// `@word` is a context, but `@someone@example.com` is a mention and is left
+/// in the name.
+fn parse_context(token: &str) -> Option<&str> {
+    token
+        .strip_prefix('@')
+        .filter(|context| !context.is_empty() && !context.contains('@'))
+}
+
+/// The project a list is exported as; todo.txt projects can't hold spaces.
+pub(crate) fn project_name(list_name: &str) -> String {
+    list_name.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+/// `None` for blank lines.
+pub(crate) fn parse_line(line: &str) -> Option<TodoTxtItem> {
+    let mut tokens = line.split_whitespace().peekable();
+    tokens.peek()?;
+    let mut item = TodoTxtItem::default();
+
+    if tokens.next_if_eq(&"x").is_some() {
+        item.completed = true;
+        item.completed_on = tokens
+            .next_if(|t| parse_date(t).is_some())
+            .and_then(parse_date);
+        if item.completed_on.is_some() {
+            item.created_on = tokens
+                .next_if(|t| parse_date(t).is_some())
+                .and_then(parse_date);
+        }
+    } else {
+        item.priority = tokens
+            .next_if(|t| parse_priority(t).is_some())
+            .and_then(parse_priority);
+        item.created_on = tokens
+            .next_if(|t| parse_date(t).is_some())
+            .and_then(parse_date);
+    }
+
+    let rest: Vec<&str> = tokens.collect();
+    // the last project picks the list; earlier ones are kept as text
+    let project_at = rest
+        .iter()
+        .rposition(|token| token.len() > 1 && token.starts_with('+'));
+    let mut words = Vec::new();
+    for (index, token) in rest.iter().enumerate() {
+        if Some(index) == project_at {
+            item.project = Some(token[1..].to_string());
+        } else if let Some(context) = parse_context(token) {
+            if !item.contexts.iter().any(|c| c == context) {
+                item.contexts.push(context.to_string());
+            }
+        } else if let Some(due_date) = token.strip_prefix("due:").and_then(parse_date) {
+            item.due_date = Some(due_date);
+        } else if let Some(letter) = token.strip_prefix("pri:").filter(|p| is_valid_priority(p)) {
+            item.priority.get_or_insert_with(|| letter.to_string());
+        } else {
+            words.push(*token);
+        }
+    }
+    item.name = words.join(" ");
+    Some(item)
+}
+
+/// Completed tasks keep their priority as `pri:`, since the `(A)` prefix is
+/// only allowed on open ones.
+pub(crate) fn format_line(item: &TodoTxtItem) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    if item.completed {
+        parts.push(String::from("x"));
+        if let Some(completed_on) = item.completed_on {
+            parts.push(completed_on.format(DATE_FORMAT).to_string());
+            // a creation date is only recognisable after a completion date
+            if let Some(created_on) = item.created_on {
+                parts.push(created_on.format(DATE_FORMAT).to_string());
+            }
+        }
+    } else {
+        if let Some(priority) = &item.priority {
+            parts.push(format!("({})", priority));
+        }
+        if let Some(created_on) = item.created_on {
+            parts.push(created_on.format(DATE_FORMAT).to_string());
+        }
+    }
+    parts.extend(item.name.split_whitespace().map(str::to_string));
+    if let Some(project) = &item.project {
+        parts.push(format!("+{}", project));
+    }
+    parts.extend(item.contexts.iter().map(|context| format!("@{}", context)));
+    if let Some(due_date) = item.due_date {
+        parts.push(format!("due:{}", due_date.format(DATE_FORMAT)));
+    }
+    if item.completed {
+        if let Some(priority) = &item.priority {
+            parts.push(format!("pri:{}", priority));
+        }
+    }
+    parts.join(" ")
+}
@@ -0,0 +1,16 @@
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+/// A fresh 256-bit secret, hex encoded. Bearer secrets are persisted only as
+/// their hash; signing keys, which the server itself needs, are kept as is.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+/// Tokens carry full entropy, so a plain SHA-256 is enough to keep a leaked
+/// database from yielding usable secrets while still allowing lookup by hash.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
@@ -0,0 +1,198 @@
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, RngCore};
+use sha1::Sha1;
+
+/// RFC 6238 defaults, which is all that most authenticator apps support.
+const DIGITS: usize = 6;
+const STEP_SECS: i64 = 30;
+
+/// Codes from one step either side of the current one are accepted too, to
+/// allow for clock drift and slow typing.
+const SKEW_STEPS: i64 = 1;
+
+/// A fresh 160-bit secret, base32 encoded as authenticator apps expect.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 20];
+    OsRng.fill_bytes(&mut bytes);
+    BASE32_NOPAD.encode(&bytes)
+}
+
+/// An 80-bit recovery code, grouped as `xxxx-xxxx-xxxx-xxxx` for reading out.
+pub fn generate_recovery_code() -> String {
+    let mut bytes = [0u8; 10];
+    OsRng.fill_bytes(&mut bytes);
+    let code = BASE32_NOPAD.encode(&bytes).to_lowercase();
+    code.as_bytes()
+        .chunks(4)
+        .map(|group| String::from_utf8_lossy(group).into_owned())
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
+/// Recovery codes are matched ignoring case, spaces and dashes.
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// The RFC 4226 one-time password for `counter`.
+fn hotp(key: &[u8], counter: u64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = usize::from(digest[digest.len() - 1] & 0x0f);
+    let truncated = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    format!(
+        "{:0width$}",
+        truncated % 10u32.pow(DIGITS as u32),
+        width = DIGITS
+    )
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// The time step `code` is valid for at `unix_time`, if any. Steps at or
+/// before `last_step` are refused so that an accepted code can't be replayed.
+pub fn verify_code(
+    secret: &str,
+    code: &str,
+    unix_time: i64,
+    last_step: Option<i64>,
+) -> Option<i64> {
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+    if code.len() != DIGITS || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let current = unix_time.div_euclid(STEP_SECS);
+    (current - SKEW_STEPS..=current + SKEW_STEPS)
+        .filter(|step| *step >= 0 && last_step.is_none_or(|last| *step > last))
+        .find(|step| constant_time_eq(hotp(&key, *step as u64).as_bytes(), code.as_bytes()))
+}
+
+/// The `otpauth://` URI authenticator apps scan from a QR code.
+pub fn provisioning_uri(secret: &str, account: &str, issuer: &str) -> String {
+    let mut uri = reqwest::Url::parse("otpauth://totp/").expect("Invalid otpauth base URI");
+    uri.path_segments_mut()
+        .expect("otpauth URIs have a path")
+        .pop_if_empty()
+        .push(&format!("{}:{}", issuer, account));
+    uri.query_pairs_mut()
+        .append_pair("secret", secret)
+        .append_pair("issuer", issuer)
+        .append_pair("algorithm", "SHA1")
+        .append_pair("digits", &DIGITS.to_string())
+        .append_pair("period", &STEP_SECS.to_string());
+    uri.to_string()
+}
+
+/// The code an authenticator shows at `unix_time`.
+#[cfg(test)]
+pub(crate) fn code_at(secret: &str, unix_time: i64) -> String {
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).unwrap();
+    hotp(&key, unix_time.div_euclid(STEP_SECS) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The RFC 6238 SHA-1 test key, "12345678901234567890".
+    const RFC_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn codes_match_the_rfc_vectors() {
+        for (unix_time, code) in [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ] {
+            assert_eq!(code_at(RFC_SECRET, unix_time), code);
+            assert_eq!(
+                verify_code(RFC_SECRET, code, unix_time, None),
+                Some(unix_time / STEP_SECS)
+            );
+        }
+    }
+
+    #[test]
+    fn codes_are_accepted_one_step_either_side() {
+        let now = 1234567890;
+        let step = now / STEP_SECS;
+        let code = code_at(RFC_SECRET, now);
+        for offset in [-1, 0, 1] {
+            let at = now + offset * STEP_SECS;
+            assert_eq!(verify_code(RFC_SECRET, &code, at, None), Some(step));
+        }
+        for offset in [-2, 2] {
+            let at = now + offset * STEP_SECS;
+            assert_eq!(verify_code(RFC_SECRET, &code, at, None), None);
+        }
+    }
+
+    #[test]
+    fn used_steps_are_refused() {
+        let now = 1234567890;
+        let step = now / STEP_SECS;
+        let code = code_at(RFC_SECRET, now);
+        assert_eq!(verify_code(RFC_SECRET, &code, now, Some(step)), None);
+        assert_eq!(verify_code(RFC_SECRET, &code, now, Some(step + 1)), None);
+        assert_eq!(
+            verify_code(RFC_SECRET, &code, now, Some(step - 1)),
+            Some(step)
+        );
+    }
+
+    #[test]
+    fn malformed_codes_are_refused() {
+        assert_eq!(
+            verify_code(RFC_SECRET, "005 924", 1234567890, None),
+            Some(1234567890 / STEP_SECS)
+        );
+        for code in ["", "05924", "0005924", "00592a", "00-5924"] {
+            assert_eq!(verify_code(RFC_SECRET, code, 1234567890, None), None);
+        }
+        assert_eq!(verify_code("not base32!", "005924", 1234567890, None), None);
+    }
+
+    #[test]
+    fn secrets_are_160_bits() {
+        let secret = generate_secret();
+        assert_eq!(BASE32_NOPAD.decode(secret.as_bytes()).unwrap().len(), 20);
+        assert_ne!(secret, generate_secret());
+    }
+
+    #[test]
+    fn recovery_codes_normalize_to_themselves() {
+        let code = generate_recovery_code();
+        assert_eq!(code.len(), 19);
+        assert_eq!(code.matches('-').count(), 3);
+        let normalized = normalize_recovery_code(&code);
+        assert_eq!(
+            normalize_recovery_code(&code.to_uppercase().replace('-', " ")),
+            normalized
+        );
+    }
+
+    #[test]
+    fn provisioning_uris_name_the_issuer_and_account() {
+        let uri = provisioning_uri(RFC_SECRET, "someone@example.com", "Todoer");
+        assert!(uri.starts_with("otpauth://totp/Todoer:someone@example.com?"));
+        assert!(uri.contains(&format!("secret={}", RFC_SECRET)));
+        assert!(uri.contains("issuer=Todoer"));
+        assert!(uri.contains("digits=6"));
+        assert!(uri.contains("period=30"));
+    }
+}
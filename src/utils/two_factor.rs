@@ -0,0 +1,175 @@
+use crate::models::two_factor::{NewRecoveryCode, SecondFactor, UserTotp, RECOVERY_CODE_COUNT};
+use crate::schema::{recovery_codes, user_totp, workspace_members, workspaces};
+use crate::utils::{token, totp};
+use diesel::prelude::*;
+
+/// Enrollments only count once a code from the authenticator confirmed them.
+pub(crate) fn db_get_confirmed_totp(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> Result<Option<UserTotp>, diesel::result::Error> {
+    user_totp::table
+        .find(user_id)
+        .filter(user_totp::confirmed_at.is_not_null())
+        .select(UserTotp::as_select())
+        .first(conn)
+        .optional()
+}
+
+/// Accepts a TOTP code at most once, recording its time step so it can't be
+/// used again even within its validity window.
+pub(crate) fn db_accept_totp_code(
+    conn: &mut PgConnection,
+    enrollment: &UserTotp,
+    code: &str,
+) -> Result<bool, diesel::result::Error> {
+    let now = chrono::Utc::now().timestamp();
+    let Some(step) = totp::verify_code(&enrollment.secret, code, now, enrollment.last_step) else {
+        return Ok(false);
+    };
+    let claimed = diesel::update(user_totp::table.find(enrollment.user_id))
+        .filter(
+            user_totp::last_step
+                .is_null()
+                .or(user_totp::last_step.lt(step)),
+        )
+        .set(user_totp::last_step.eq(step))
+        .execute(conn)?;
+    Ok(claimed == 1)
+}
+
+/// Checks a second factor of a user with two-factor enabled, using up the
+/// recovery code if that is what was given.
+pub(crate) fn db_check_second_factor(
+    conn: &mut PgConnection,
+    user_id: i32,
+    factor: &SecondFactor,
+) -> Result<bool, diesel::result::Error> {
+    let Some(enrollment) = db_get_confirmed_totp(conn, user_id)? else {
+        return Ok(false);
+    };
+    if let Some(code) = &factor.code {
+        return db_accept_totp_code(conn, &enrollment, code);
+    }
+    let Some(recovery_code) = &factor.recovery_code else {
+        return Ok(false);
+    };
+    let code_hash = token::hash_token(&totp::normalize_recovery_code(recovery_code));
+    let used = diesel::update(recovery_codes::table)
+        .filter(recovery_codes::user_id.eq(user_id))
+        .filter(recovery_codes::code_hash.eq(code_hash))
+        .filter(recovery_codes::used_at.is_null())
+        .set(recovery_codes::used_at.eq(chrono::Local::now().naive_local()))
+        .execute(conn)?;
+    Ok(used == 1)
+}
+
+/// Replaces all of the user's recovery codes, returning the new ones in plain.
+pub(crate) fn db_replace_recovery_codes(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> Result<Vec<String>, diesel::result::Error> {
+    diesel::delete(recovery_codes::table.filter(recovery_codes::user_id.eq(user_id)))
+        .execute(conn)?;
+    let now = chrono::Local::now().naive_local();
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| totp::generate_recovery_code())
+        .collect();
+    let rows: Vec<NewRecoveryCode> = codes
+        .iter()
+        .map(|code| NewRecoveryCode {
+            user_id,
+            code_hash: token::hash_token(&totp::normalize_recovery_code(code)),
+            created_at: now,
+        })
+        .collect();
+    diesel::insert_into(recovery_codes::table)
+        .values(&rows)
+        .execute(conn)?;
+    Ok(codes)
+}
+
+/// Whether any workspace the user belongs to requires two-factor.
+pub(crate) fn db_two_factor_required(
+    conn: &mut PgConnection,
+    user_id: i32,
+) -> Result<bool, diesel::result::Error> {
+    diesel::select(diesel::dsl::exists(
+        workspace_members::table
+            .inner_join(workspaces::table)
+            .filter(workspace_members::user_id.eq(user_id))
+            .filter(workspaces::require_two_factor.eq(true)),
+    ))
+    .get_result(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::two_factor::NewUserTotp;
+    use crate::utils::testing::{db_insert_user, test_connection};
+
+    fn db_enroll(conn: &mut PgConnection, user_id: i32) -> UserTotp {
+        let now = chrono::Local::now().naive_local();
+        diesel::insert_into(user_totp::table)
+            .values(&NewUserTotp {
+                user_id,
+                secret: totp::generate_secret(),
+                confirmed_at: Some(now),
+                last_step: None,
+                created_at: now,
+            })
+            .execute(conn)
+            .unwrap();
+        db_get_confirmed_totp(conn, user_id).unwrap().unwrap()
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn totp_codes_are_accepted_once() {
+        let mut conn = test_connection();
+        let user = db_insert_user(&mut conn, true);
+        let enrollment = db_enroll(&mut conn, user.id);
+        let code = totp::code_at(&enrollment.secret, chrono::Utc::now().timestamp());
+
+        assert!(db_accept_totp_code(&mut conn, &enrollment, &code).unwrap());
+        // a request that loaded the enrollment before the code was used
+        assert!(!db_accept_totp_code(&mut conn, &enrollment, &code).unwrap());
+        let reloaded = db_get_confirmed_totp(&mut conn, user.id).unwrap().unwrap();
+        assert!(reloaded.last_step.is_some());
+        assert!(!db_accept_totp_code(&mut conn, &reloaded, &code).unwrap());
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn wrong_totp_codes_are_refused() {
+        let mut conn = test_connection();
+        let user = db_insert_user(&mut conn, true);
+        let enrollment = db_enroll(&mut conn, user.id);
+        let now = chrono::Utc::now().timestamp();
+        let stale = totp::code_at(&enrollment.secret, now - 5 * 30);
+        let code = totp::code_at(&enrollment.secret, now);
+        if stale != code {
+            assert!(!db_accept_totp_code(&mut conn, &enrollment, &stale).unwrap());
+        }
+        assert!(!db_accept_totp_code(&mut conn, &enrollment, "abcdef").unwrap());
+        let reloaded = db_get_confirmed_totp(&mut conn, user.id).unwrap().unwrap();
+        assert_eq!(reloaded.last_step, None);
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn recovery_codes_work_once() {
+        let mut conn = test_connection();
+        let user = db_insert_user(&mut conn, true);
+        db_enroll(&mut conn, user.id);
+        let codes = db_replace_recovery_codes(&mut conn, user.id).unwrap();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        let factor = SecondFactor {
+            code: None,
+            recovery_code: Some(codes[0].to_uppercase()),
+        };
+        assert!(db_check_second_factor(&mut conn, user.id, &factor).unwrap());
+        assert!(!db_check_second_factor(&mut conn, user.id, &factor).unwrap());
+    }
+}
@@ -0,0 +1,219 @@
+use std::fmt::Write;
+
+pub const DAV_NS: &str = "DAV:";
+pub const CALDAV_NS: &str = "urn:ietf:params:xml:ns:caldav";
+pub const CALSERVER_NS: &str = "http://calendarserver.org/ns/";
+
+/// A property name, qualified by its XML namespace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropName {
+    pub ns: String,
+    pub name: String,
+}
+
+impl PropName {
+    pub fn is(&self, ns: &str, name: &str) -> bool {
+        self.ns == ns && self.name == name
+    }
+}
+
+/// What a PROPFIND asks for.
+pub enum PropRequest {
+    AllProp,
+    PropName,
+    Props(Vec<PropName>),
+}
+
+pub enum ReportRequest {
+    /// Carries the components named by the query's `comp-filter`s.
+    CalendarQuery(PropRequest, Vec<String>),
+    CalendarMultiget(PropRequest, Vec<String>),
+    SyncCollection(PropRequest, Option<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Depth {
+    Zero,
+    One,
+}
+
+impl Depth {
+    /// `infinity`, the default, is served as `1`: there is nothing deeper.
+    pub fn from_header(value: Option<&str>) -> Depth {
+        match value.map(str::trim) {
+            Some("0") => Depth::Zero,
+            _ => Depth::One,
+        }
+    }
+}
+
+pub fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn element_props(node: roxmltree::Node) -> PropRequest {
+    for child in node.children().filter(|child| child.is_element()) {
+        match child.tag_name().name() {
+            "allprop" => return PropRequest::AllProp,
+            "propname" => return PropRequest::PropName,
+            "prop" => {
+                let names = child
+                    .children()
+                    .filter(|prop| prop.is_element())
+                    .map(|prop| PropName {
+                        ns: prop.tag_name().namespace().unwrap_or_default().to_string(),
+                        name: prop.tag_name().name().to_string(),
+                    })
+                    .collect();
+                return PropRequest::Props(names);
+            }
+            _ => {}
+        }
+    }
+    PropRequest::AllProp
+}
+
+/// An empty body means `allprop`. `None` for malformed XML.
+pub fn parse_propfind(body: &str) -> Option<PropRequest> {
+    if body.trim().is_empty() {
+        return Some(PropRequest::AllProp);
+    }
+    let document = roxmltree::Document::parse(body).ok()?;
+    Some(element_props(document.root_element()))
+}
+
+pub fn parse_report(body: &str) -> Option<ReportRequest> {
+    let document = roxmltree::Document::parse(body).ok()?;
+    let root = document.root_element();
+    let props = element_props(root);
+    let text_of = |name: &str| {
+        root.descendants()
+            .filter(|node| node.is_element() && node.tag_name().name() == name)
+            .map(|node| node.text().unwrap_or_default().trim().to_string())
+            .collect::<Vec<String>>()
+    };
+    match root.tag_name().name() {
+        "calendar-query" => {
+            let components = root
+                .descendants()
+                .filter(|node| node.is_element() && node.tag_name().name() == "comp-filter")
+                .filter_map(|node| node.attribute("name"))
+                .filter(|name| !name.eq_ignore_ascii_case("VCALENDAR"))
+                .map(str::to_ascii_uppercase)
+                .collect();
+            Some(ReportRequest::CalendarQuery(props, components))
+        }
+        "calendar-multiget" => Some(ReportRequest::CalendarMultiget(props, text_of("href"))),
+        "sync-collection" => {
+            let token = text_of("sync-token")
+                .into_iter()
+                .next()
+                .filter(|token| !token.is_empty());
+            Some(ReportRequest::SyncCollection(props, token))
+        }
+        _ => None,
+    }
+}
+
+fn prefix_for(ns: &str) -> Option<&'static str> {
+    match ns {
+        DAV_NS => Some("d"),
+        CALDAV_NS => Some("c"),
+        CALSERVER_NS => Some("cs"),
+        _ => None,
+    }
+}
+
+/// Renders `<prefix:name>value</prefix:name>`, declaring foreign namespaces
+/// inline.
+fn push_prop(out: &mut String, prop: &PropName, value: Option<&str>) {
+    let (open, close) = match prefix_for(&prop.ns) {
+        Some(prefix) => (
+            format!("{}:{}", prefix, prop.name),
+            format!("{}:{}", prefix, prop.name),
+        ),
+        None => (
+            format!("x:{} xmlns:x=\"{}\"", prop.name, escape_xml(&prop.ns)),
+            format!("x:{}", prop.name),
+        ),
+    };
+    match value {
+        Some(value) if !value.is_empty() => {
+            let _ = write!(out, "<{}>{}</{}>", open, value, close);
+        }
+        _ => {
+            let _ = write!(out, "<{}/>", open);
+        }
+    }
+}
+
+/// Builds a `207 Multi-Status` body one response at a time.
+pub struct MultiStatus {
+    body: String,
+}
+
+impl Default for MultiStatus {
+    fn default() -> Self {
+        MultiStatus {
+            body: format!(
+                "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                 <d:multistatus xmlns:d=\"{}\" xmlns:c=\"{}\" xmlns:cs=\"{}\">",
+                DAV_NS, CALDAV_NS, CALSERVER_NS
+            ),
+        }
+    }
+}
+
+impl MultiStatus {
+    /// `found` values are already XML; `missing` are reported as 404.
+    pub fn push_props(&mut self, href: &str, found: &[(PropName, String)], missing: &[PropName]) {
+        let _ = write!(
+            self.body,
+            "<d:response><d:href>{}</d:href>",
+            escape_xml(href)
+        );
+        if !found.is_empty() {
+            self.body.push_str("<d:propstat><d:prop>");
+            for (prop, value) in found {
+                push_prop(&mut self.body, prop, Some(value));
+            }
+            self.body
+                .push_str("</d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat>");
+        }
+        if !missing.is_empty() {
+            self.body.push_str("<d:propstat><d:prop>");
+            for prop in missing {
+                push_prop(&mut self.body, prop, None);
+            }
+            self.body
+                .push_str("</d:prop><d:status>HTTP/1.1 404 Not Found</d:status></d:propstat>");
+        }
+        self.body.push_str("</d:response>");
+    }
+
+    pub fn push_status(&mut self, href: &str, status: &str) {
+        let _ = write!(
+            self.body,
+            "<d:response><d:href>{}</d:href><d:status>HTTP/1.1 {}</d:status></d:response>",
+            escape_xml(href),
+            status
+        );
+    }
+
+    pub fn push_sync_token(&mut self, token: &str) {
+        let _ = write!(
+            self.body,
+            "<d:sync-token>{}</d:sync-token>",
+            escape_xml(token)
+        );
+    }
+
+    pub fn finish(mut self) -> String {
+        self.body.push_str("</d:multistatus>");
+        self.body
+    }
+}
@@ -0,0 +1,634 @@
+use crate::models::event::Event;
+use crate::models::webhook::Webhook;
+use crate::models::webhook_delivery::{DeliveryStatus, NewWebhookDelivery, WebhookDelivery};
+use crate::schema::{events, webhook_deliveries, webhooks};
+use crate::utils::config;
+use crate::utils::database::connection::Pool;
+use crate::utils::events::EventHub;
+use actix_web::web;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use hmac::{Hmac, Mac};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const SIGNATURE_HEADER: &str = "X-Todoer-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Todoer-Timestamp";
+pub const EVENT_HEADER: &str = "X-Todoer-Event";
+pub const DELIVERY_HEADER: &str = "X-Todoer-Delivery";
+
+const BATCH_SIZE: i64 = 50;
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Claimed deliveries are hidden from other workers for this long, which
+/// must comfortably exceed `REQUEST_TIMEOUT`.
+const CLAIM_LEASE_SECS: i64 = 300;
+const MAX_ATTEMPTS: i32 = 8;
+const BACKOFF_BASE_SECS: i64 = 30;
+const BACKOFF_MAX_SECS: i64 = 6 * 60 * 60;
+/// Failed attempts in a row, across deliveries, after which the endpoint is
+/// considered dead and the webhook is switched off.
+const DISABLE_AFTER_FAILURES: i32 = 20;
+
+type DeliveryJob = (WebhookDelivery, Webhook, Event);
+
+/// Whether a webhook may be delivered to `ip`. Loopback, private, link-local,
+/// shared and unspecified addresses belong to this server's own network, so
+/// a webhook pointed at them could reach services never meant to be exposed.
+fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || first == 0
+                || (first == 100 && (64..128).contains(&second)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_address(IpAddr::V4(mapped)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+/// Resolves webhook hosts, dropping addresses that aren't public. Doing it
+/// in the resolver means the address checked is the one connected to, so a
+/// host can't pass the check and then re-resolve somewhere internal.
+struct PublicOnlyResolver;
+
+impl Resolve for PublicOnlyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_address(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", host).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Hosts written as an address are never looked up, so they are checked
+/// here instead. `None` when the URL may be delivered to.
+pub(crate) fn refused_target(url: &str, allow_private: bool) -> Option<String> {
+    if allow_private {
+        return None;
+    }
+    let parsed = match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(e) => return Some(format!("invalid URL: {}", e)),
+    };
+    let ip = parsed
+        .host_str()?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+        .ok()?;
+    (!is_public_address(ip)).then(|| format!("{} is not a public address", ip))
+}
+
+/// Queues a delivery of the event to every enabled webhook whose owner is in
+/// the event's audience and whose list and event filter match. Runs inside
+/// the transaction that records the event.
+pub(crate) fn enqueue_deliveries(
+    conn: &mut PgConnection,
+    event_id: i64,
+    kind: &str,
+    list_ids: &[i32],
+    audience: &[i32],
+) -> Result<(), diesel::result::Error> {
+    if audience.is_empty() {
+        return Ok(());
+    }
+    let candidates = webhooks::table
+        .filter(webhooks::enabled.eq(true))
+        .filter(webhooks::user_id.eq_any(audience))
+        .filter(
+            webhooks::list_id
+                .is_null()
+                .or(webhooks::list_id.eq_any(list_ids)),
+        )
+        .load::<Webhook>(conn)?;
+    let now = chrono::Local::now().naive_local();
+    let deliveries: Vec<NewWebhookDelivery> = candidates
+        .iter()
+        .filter(|webhook| webhook.matches(kind))
+        .map(|webhook| NewWebhookDelivery {
+            webhook_id: webhook.id,
+            event_id,
+            next_attempt_at: now,
+            created_at: now,
+        })
+        .collect();
+    if !deliveries.is_empty() {
+        diesel::insert_into(webhook_deliveries::table)
+            .values(&deliveries)
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Hex HMAC-SHA256 of `"{timestamp}.{body}"`. Binding the timestamp lets
+/// receivers reject replays of old deliveries.
+pub fn sign_payload(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn backoff(attempts: i32) -> chrono::Duration {
+    let exponent = (attempts - 1).clamp(0, 20) as u32;
+    let secs = BACKOFF_BASE_SECS.saturating_mul(1 << exponent);
+    chrono::Duration::seconds(secs.min(BACKOFF_MAX_SECS))
+}
+
+/// Claims due deliveries so that concurrent workers, in this or another
+/// instance, skip them.
+fn claim_due_deliveries(pool: web::Data<Pool>) -> Result<Vec<DeliveryJob>, diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    conn.transaction(|conn| {
+        let now = chrono::Local::now().naive_local();
+        let due: Vec<i64> = webhook_deliveries::table
+            .filter(webhook_deliveries::status.eq(DeliveryStatus::Pending))
+            .filter(webhook_deliveries::next_attempt_at.le(now))
+            .order(webhook_deliveries::next_attempt_at.asc())
+            .limit(BATCH_SIZE)
+            .select(webhook_deliveries::id)
+            .for_update()
+            .skip_locked()
+            .load(conn)?;
+        if due.is_empty() {
+            return Ok(Vec::new());
+        }
+        diesel::update(webhook_deliveries::table.filter(webhook_deliveries::id.eq_any(&due)))
+            .set(
+                webhook_deliveries::next_attempt_at
+                    .eq(now + chrono::Duration::seconds(CLAIM_LEASE_SECS)),
+            )
+            .execute(conn)?;
+        webhook_deliveries::table
+            .inner_join(webhooks::table)
+            .inner_join(events::table)
+            .filter(webhook_deliveries::id.eq_any(&due))
+            .load::<DeliveryJob>(conn)
+    })
+}
+
+struct AttemptOutcome {
+    succeeded: bool,
+    response_status: Option<i32>,
+    error: Option<String>,
+}
+
+async fn send_delivery(
+    client: &reqwest::Client,
+    allow_private: bool,
+    job: &DeliveryJob,
+) -> AttemptOutcome {
+    let (delivery, webhook, event) = job;
+    if let Some(reason) = refused_target(&webhook.url, allow_private) {
+        return AttemptOutcome {
+            succeeded: false,
+            response_status: None,
+            error: Some(reason),
+        };
+    }
+    let body = serde_json::json!({
+        "id": event.id,
+        "kind": event.kind,
+        "created_at": event.created_at,
+        "data": event.payload,
+    })
+    .to_string();
+    let timestamp = chrono::Utc::now().timestamp();
+    let signature = sign_payload(&webhook.secret, timestamp, &body);
+    let response = client
+        .post(&webhook.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &event.kind)
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+        .body(body)
+        .send()
+        .await;
+    match response {
+        Ok(response) => {
+            let status = response.status();
+            AttemptOutcome {
+                succeeded: status.is_success(),
+                response_status: Some(status.as_u16() as i32),
+                error: (!status.is_success()).then(|| format!("HTTP {}", status)),
+            }
+        }
+        Err(e) => AttemptOutcome {
+            succeeded: false,
+            response_status: None,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+fn db_record_attempt(
+    conn: &mut PgConnection,
+    delivery: &WebhookDelivery,
+    outcome: AttemptOutcome,
+) -> Result<(), diesel::result::Error> {
+    conn.transaction(|conn| {
+        let now = chrono::Local::now().naive_local();
+        let attempts = delivery.attempts + 1;
+        let target = webhook_deliveries::table.find(delivery.id);
+        if outcome.succeeded {
+            diesel::update(target)
+                .set((
+                    webhook_deliveries::status.eq(DeliveryStatus::Succeeded),
+                    webhook_deliveries::attempts.eq(attempts),
+                    webhook_deliveries::response_status.eq(outcome.response_status),
+                    webhook_deliveries::last_error.eq(None::<String>),
+                    webhook_deliveries::delivered_at.eq(Some(now)),
+                ))
+                .execute(conn)?;
+            diesel::update(webhooks::table.find(delivery.webhook_id))
+                .set(webhooks::consecutive_failures.eq(0))
+                .execute(conn)?;
+            return Ok(());
+        }
+
+        let (status, next_attempt_at) = if attempts >= MAX_ATTEMPTS {
+            (DeliveryStatus::Failed, now)
+        } else {
+            (DeliveryStatus::Pending, now + backoff(attempts))
+        };
+        diesel::update(target)
+            .set((
+                webhook_deliveries::status.eq(status),
+                webhook_deliveries::attempts.eq(attempts),
+                webhook_deliveries::next_attempt_at.eq(next_attempt_at),
+                webhook_deliveries::response_status.eq(outcome.response_status),
+                webhook_deliveries::last_error.eq(outcome.error),
+            ))
+            .execute(conn)?;
+        let failures: i32 = diesel::update(webhooks::table.find(delivery.webhook_id))
+            .set(webhooks::consecutive_failures.eq(webhooks::consecutive_failures + 1))
+            .returning(webhooks::consecutive_failures)
+            .get_result(conn)?;
+        if failures >= DISABLE_AFTER_FAILURES {
+            disable_webhook(conn, delivery.webhook_id, now)?;
+        }
+        Ok(())
+    })
+}
+
+fn record_attempt(
+    pool: web::Data<Pool>,
+    delivery: &WebhookDelivery,
+    outcome: AttemptOutcome,
+) -> Result<(), diesel::result::Error> {
+    let mut conn = pool.get().unwrap();
+    db_record_attempt(&mut conn, delivery, outcome)
+}
+
+/// Switches a webhook off and fails whatever it still had queued, so that
+/// re-enabling it later doesn't flood the endpoint with stale events.
+pub(crate) fn disable_webhook(
+    conn: &mut PgConnection,
+    webhook_id: i32,
+    now: NaiveDateTime,
+) -> Result<(), diesel::result::Error> {
+    diesel::update(webhooks::table.find(webhook_id))
+        .filter(webhooks::enabled.eq(true))
+        .set((
+            webhooks::enabled.eq(false),
+            webhooks::disabled_at.eq(Some(now)),
+        ))
+        .execute(conn)?;
+    diesel::update(
+        webhook_deliveries::table
+            .filter(webhook_deliveries::webhook_id.eq(webhook_id))
+            .filter(webhook_deliveries::status.eq(DeliveryStatus::Pending)),
+    )
+    .set((
+        webhook_deliveries::status.eq(DeliveryStatus::Failed),
+        webhook_deliveries::last_error.eq(Some("webhook disabled")),
+    ))
+    .execute(conn)?;
+    Ok(())
+}
+
+async fn run_delivery_batch(
+    pool: &web::Data<Pool>,
+    client: &reqwest::Client,
+    allow_private: bool,
+) -> usize {
+    let claim_pool = pool.clone();
+    let jobs = match web::block(move || claim_due_deliveries(claim_pool)).await {
+        Ok(Ok(jobs)) => jobs,
+        Ok(Err(e)) => {
+            eprintln!("Failed to claim webhook deliveries: {}", e);
+            return 0;
+        }
+        Err(_) => return 0,
+    };
+    let claimed = jobs.len();
+    let attempts = jobs.into_iter().map(|job| async move {
+        let outcome = send_delivery(client, allow_private, &job).await;
+        let record_pool = pool.clone();
+        let (delivery, _, _) = job;
+        match web::block(move || record_attempt(record_pool, &delivery, outcome)).await {
+            Ok(Err(e)) => eprintln!("Failed to record webhook delivery: {}", e),
+            Err(e) => eprintln!("Failed to record webhook delivery: {}", e),
+            Ok(Ok(())) => {}
+        }
+    });
+    futures_util::future::join_all(attempts).await;
+    claimed
+}
+
+/// Delivers queued webhooks in the background. New events wake the worker
+/// straight away; retries are picked up by polling.
+pub(crate) fn start_delivery_worker(pool: Pool, hub: EventHub) {
+    let pool = web::Data::new(pool);
+    let mut log = hub.subscribe_log();
+    let allow_private = config::get_webhooks_allow_private();
+    let mut builder = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none());
+    if !allow_private {
+        // a proxy would resolve the host itself, past the check
+        builder = builder
+            .dns_resolver(Arc::new(PublicOnlyResolver))
+            .no_proxy();
+    }
+    let client = builder
+        .build()
+        .expect("Failed to build webhook HTTP client");
+    actix_web::rt::spawn(async move {
+        loop {
+            // a full batch suggests more are due right away
+            if run_delivery_batch(&pool, &client, allow_private).await as i64 == BATCH_SIZE {
+                continue;
+            }
+            tokio::select! {
+                _ = log.changed() => {}
+                _ = actix_web::rt::time::sleep(POLL_INTERVAL) => {}
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::webhook::NewWebhook;
+    use crate::utils::events::record_event;
+    use crate::utils::testing::{db_insert_user, test_connection, HttpStub};
+
+    const SECRET: &str = "the webhook secret";
+
+    fn job(url: String) -> DeliveryJob {
+        let now = chrono::Local::now().naive_local();
+        let delivery = WebhookDelivery {
+            id: 7,
+            webhook_id: 3,
+            event_id: 42,
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            next_attempt_at: now,
+            response_status: None,
+            last_error: None,
+            created_at: now,
+            delivered_at: None,
+        };
+        let webhook = Webhook {
+            id: 3,
+            user_id: 1,
+            list_id: None,
+            url,
+            secret: SECRET.to_string(),
+            event_filter: String::from("*"),
+            enabled: true,
+            consecutive_failures: 0,
+            disabled_at: None,
+            created_at: now,
+            modified_at: now,
+        };
+        let event = Event {
+            id: 42,
+            kind: String::from("task.created"),
+            payload: serde_json::json!({ "name": "Water plants" }),
+            created_at: now,
+        };
+        (delivery, webhook, event)
+    }
+
+    fn failure() -> AttemptOutcome {
+        AttemptOutcome {
+            succeeded: false,
+            response_status: Some(500),
+            error: Some(String::from("HTTP 500")),
+        }
+    }
+
+    #[test]
+    fn signatures_are_hex_hmac_of_timestamp_and_body() {
+        let signature = sign_payload(SECRET, 1700000000, r#"{"id":1}"#);
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(br#"1700000000.{"id":1}"#);
+        assert_eq!(signature, hex::encode(mac.finalize().into_bytes()));
+        assert_eq!(signature.len(), 64);
+        assert!(signature
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c)));
+        assert_ne!(signature, sign_payload(SECRET, 1700000001, r#"{"id":1}"#));
+        assert_ne!(
+            signature,
+            sign_payload("another secret", 1700000000, r#"{"id":1}"#)
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        assert_eq!(backoff(1), chrono::Duration::seconds(BACKOFF_BASE_SECS));
+        assert_eq!(backoff(2), chrono::Duration::seconds(2 * BACKOFF_BASE_SECS));
+        assert_eq!(backoff(3), chrono::Duration::seconds(4 * BACKOFF_BASE_SECS));
+        assert_eq!(backoff(30), chrono::Duration::seconds(BACKOFF_MAX_SECS));
+    }
+
+    #[test]
+    fn internal_targets_are_refused() {
+        for url in [
+            "http://127.0.0.1/hook",
+            "http://10.0.0.5/hook",
+            "http://192.168.1.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://100.64.0.1/hook",
+            "http://0.0.0.0/hook",
+            "http://[::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[fe80::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+        ] {
+            assert!(refused_target(url, false).is_some(), "{} was allowed", url);
+            assert!(refused_target(url, true).is_none());
+        }
+        assert!(refused_target("https://93.184.216.34/hook", false).is_none());
+        assert!(refused_target("https://hooks.example.com/hook", false).is_none());
+    }
+
+    #[actix_web::test]
+    async fn deliveries_are_signed() {
+        let stub = HttpStub::start(vec![("/hook", 204, String::new())]);
+        let outcome = send_delivery(&reqwest::Client::new(), true, &job(stub.url("/hook"))).await;
+        assert!(outcome.succeeded);
+        assert_eq!(outcome.response_status, Some(204));
+
+        let request = stub.received().pop().unwrap();
+        assert_eq!(request.header("x-todoer-event"), Some("task.created"));
+        assert_eq!(request.header("x-todoer-delivery"), Some("7"));
+        let timestamp: i64 = request
+            .header("x-todoer-timestamp")
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(
+            request.header("x-todoer-signature"),
+            Some(format!("sha256={}", sign_payload(SECRET, timestamp, &request.body)).as_str())
+        );
+        let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(body["id"], 42);
+        assert_eq!(body["data"]["name"], "Water plants");
+    }
+
+    #[actix_web::test]
+    async fn error_responses_are_failures() {
+        let stub = HttpStub::start(vec![("/hook", 500, String::new())]);
+        let outcome = send_delivery(&reqwest::Client::new(), true, &job(stub.url("/hook"))).await;
+        assert!(!outcome.succeeded);
+        assert_eq!(outcome.response_status, Some(500));
+    }
+
+    #[actix_web::test]
+    async fn private_targets_are_not_contacted() {
+        let stub = HttpStub::start(vec![("/hook", 204, String::new())]);
+        let outcome = send_delivery(&reqwest::Client::new(), false, &job(stub.url("/hook"))).await;
+        assert!(!outcome.succeeded);
+        assert!(outcome.error.is_some());
+        assert!(stub.received().is_empty());
+    }
+
+    fn db_insert_webhook_with_delivery(conn: &mut PgConnection) -> (i32, WebhookDelivery) {
+        let user = db_insert_user(conn, true);
+        let now = chrono::Local::now().naive_local();
+        let webhook_id: i32 = diesel::insert_into(webhooks::table)
+            .values(&NewWebhook {
+                user_id: user.id,
+                list_id: None,
+                url: String::from("https://hooks.example.com/hook"),
+                secret: SECRET.to_string(),
+                event_filter: String::from("*"),
+                created_at: now,
+                modified_at: now,
+            })
+            .returning(webhooks::id)
+            .get_result(conn)
+            .unwrap();
+        record_event(conn, "task.created", &serde_json::json!({}), &[user.id]).unwrap();
+        let delivery = webhook_deliveries::table
+            .filter(webhook_deliveries::webhook_id.eq(webhook_id))
+            .first::<WebhookDelivery>(conn)
+            .unwrap();
+        (webhook_id, delivery)
+    }
+
+    fn db_webhook(conn: &mut PgConnection, webhook_id: i32) -> Webhook {
+        webhooks::table.find(webhook_id).first(conn).unwrap()
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn webhooks_are_disabled_after_repeated_failures() {
+        let mut conn = test_connection();
+        let (webhook_id, delivery) = db_insert_webhook_with_delivery(&mut conn);
+        for _ in 1..DISABLE_AFTER_FAILURES {
+            db_record_attempt(&mut conn, &delivery, failure()).unwrap();
+        }
+        let webhook = db_webhook(&mut conn, webhook_id);
+        assert!(webhook.enabled);
+        assert_eq!(webhook.consecutive_failures, DISABLE_AFTER_FAILURES - 1);
+
+        db_record_attempt(&mut conn, &delivery, failure()).unwrap();
+        let webhook = db_webhook(&mut conn, webhook_id);
+        assert!(!webhook.enabled);
+        assert!(webhook.disabled_at.is_some());
+        let delivery: WebhookDelivery = webhook_deliveries::table
+            .find(delivery.id)
+            .first(&mut conn)
+            .unwrap();
+        assert_eq!(delivery.status, DeliveryStatus::Failed);
+        assert_eq!(delivery.last_error.as_deref(), Some("webhook disabled"));
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn a_success_resets_the_failure_count() {
+        let mut conn = test_connection();
+        let (webhook_id, delivery) = db_insert_webhook_with_delivery(&mut conn);
+        for _ in 1..DISABLE_AFTER_FAILURES {
+            db_record_attempt(&mut conn, &delivery, failure()).unwrap();
+        }
+        let success = AttemptOutcome {
+            succeeded: true,
+            response_status: Some(204),
+            error: None,
+        };
+        db_record_attempt(&mut conn, &delivery, success).unwrap();
+        db_record_attempt(&mut conn, &delivery, failure()).unwrap();
+        let webhook = db_webhook(&mut conn, webhook_id);
+        assert!(webhook.enabled);
+        assert_eq!(webhook.consecutive_failures, 1);
+    }
+
+    #[test]
+    #[ignore = "needs DATABASE_URL"]
+    fn failed_attempts_back_off_until_the_last() {
+        let mut conn = test_connection();
+        let (_, mut delivery) = db_insert_webhook_with_delivery(&mut conn);
+        db_record_attempt(&mut conn, &delivery, failure()).unwrap();
+        let retried: WebhookDelivery = webhook_deliveries::table
+            .find(delivery.id)
+            .first(&mut conn)
+            .unwrap();
+        assert_eq!(retried.status, DeliveryStatus::Pending);
+        assert_eq!(retried.attempts, 1);
+        assert!(retried.next_attempt_at > delivery.next_attempt_at);
+
+        delivery.attempts = MAX_ATTEMPTS - 1;
+        db_record_attempt(&mut conn, &delivery, failure()).unwrap();
+        let given_up: WebhookDelivery = webhook_deliveries::table
+            .find(delivery.id)
+            .first(&mut conn)
+            .unwrap();
+        assert_eq!(given_up.status, DeliveryStatus::Failed);
+        assert_eq!(given_up.attempts, MAX_ATTEMPTS);
+    }
+}